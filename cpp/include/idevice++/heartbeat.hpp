@@ -19,7 +19,7 @@ class Heartbeat {
 
     // Ops
     Result<void, FfiError>             send_polo();
-    Result<u_int64_t, FfiError>        get_marco(u_int64_t interval);
+    Result<u_int64_t, FfiError>        get_marco(u_int64_t interval, u_int64_t timeout_ms = 0);
 
     // RAII / moves
     ~Heartbeat() noexcept                              = default;
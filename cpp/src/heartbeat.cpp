@@ -35,9 +35,9 @@ Result<void, FfiError> Heartbeat::send_polo() {
     return Ok();
 }
 
-Result<u_int64_t, FfiError> Heartbeat::get_marco(u_int64_t interval) {
+Result<u_int64_t, FfiError> Heartbeat::get_marco(u_int64_t interval, u_int64_t timeout_ms) {
     u_int64_t new_interval = 0;
-    FfiError  e(::heartbeat_get_marco(handle_.get(), interval, &new_interval));
+    FfiError  e(::heartbeat_get_marco(handle_.get(), interval, timeout_ms, &new_interval));
     if (e) {
         return Err(e);
     }
@@ -28,7 +28,7 @@ Result<Lockdown, FfiError> Lockdown::from_socket(Idevice&& socket) {
 }
 
 Result<void, FfiError> Lockdown::start_session(const PairingFile& pf) {
-    FfiError e(::lockdownd_start_session(handle_.get(), pf.raw()));
+    FfiError e(::lockdownd_start_session(handle_.get(), pf.raw(), nullptr));
     if (e) {
         return Err(e);
     }
@@ -94,17 +94,6 @@ void print_device_info(const AfcDeviceInfo *info) {
   printf("  Block Size: %zu bytes\n", info->block_size);
 }
 
-void free_directory_listing(char **entries, size_t count) {
-  if (!entries)
-    return;
-  for (size_t i = 0; i < count; i++) {
-    if (entries[i]) {
-      free(entries[i]);
-    }
-  }
-  free(entries);
-}
-
 int main(int argc, char **argv) {
   // Initialize logger
   idevice_init_logger(Debug, Disabled, NULL);
@@ -212,7 +201,7 @@ int main(int argc, char **argv) {
         for (size_t i = 0; i < count; i++) {
           printf("- %s\n", entries[i]);
         }
-        free_directory_listing(entries, count);
+        afc_list_directory_free(entries, count);
       } else {
         fprintf(stderr, "Failed to list directory: [%d] %s\n", err->code,
                 err->message);
@@ -56,7 +56,7 @@ int main() {
   while (1) {
     // Get the new interval
     u_int64_t new_interval = 0;
-    err = heartbeat_get_marco(client, current_interval, &new_interval);
+    err = heartbeat_get_marco(client, current_interval, 0, &new_interval);
     if (err != NULL) {
       fprintf(stderr, "Failed to get marco: [%d] %s", err->code, err->message);
       heartbeat_client_free(client);
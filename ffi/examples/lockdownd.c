@@ -63,7 +63,7 @@ int main() {
   }
 
   // Start session
-  err = lockdownd_start_session(client, pairing_file_2);
+  err = lockdownd_start_session(client, pairing_file_2, NULL);
   if (err != NULL) {
     fprintf(stderr, "Failed to start session: [%d] %s", err->code,
             err->message);
@@ -0,0 +1,192 @@
+// Jackson Coxson
+
+#include "idevice.h"
+#include <arpa/inet.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+void print_usage() {
+  printf("Usage: misagent [options] [command] [args]\n\n");
+  printf("Options:\n");
+  printf("  --ip IP_ADDRESS     Device IP address (default: 10.7.0.2)\n");
+  printf("  --pairing FILE      Pairing file path (default: "
+         "pairing_file.plist)\n\n");
+  printf("Commands:\n");
+  printf("  list                List installed provisioning profiles\n");
+  printf("  install FILE        Install a provisioning profile\n");
+  printf("  remove UUID         Remove a provisioning profile\n");
+}
+
+int read_file(const char *path, uint8_t **data, size_t *len) {
+  FILE *file = fopen(path, "rb");
+  if (!file) {
+    perror("Failed to open file");
+    return 0;
+  }
+
+  fseek(file, 0, SEEK_END);
+  *len = ftell(file);
+  fseek(file, 0, SEEK_SET);
+
+  *data = malloc(*len);
+  if (!*data || fread(*data, 1, *len, file) != *len) {
+    perror("Failed to read file");
+    free(*data);
+    fclose(file);
+    return 0;
+  }
+
+  fclose(file);
+  return 1;
+}
+
+int main(int argc, char **argv) {
+  // Initialize logger
+  idevice_init_logger(Debug, Disabled, NULL);
+
+  // Default values
+  char *ip = "10.7.0.2";
+  char *pairing_file_path = "pairing_file.plist";
+  int command_index = 0;
+
+  // Parse arguments
+  for (int i = 1; i < argc; i++) {
+    if (strcmp(argv[i], "--ip") == 0) {
+      if (i + 1 >= argc) {
+        printf("Error: Missing IP address argument\n");
+        return 1;
+      }
+      ip = argv[++i];
+    } else if (strcmp(argv[i], "--pairing") == 0) {
+      if (i + 1 >= argc) {
+        printf("Error: Missing pairing file argument\n");
+        return 1;
+      }
+      pairing_file_path = argv[++i];
+    } else if (strcmp(argv[i], "help") == 0) {
+      print_usage();
+      return 0;
+    } else {
+      command_index = i;
+      break;
+    }
+  }
+
+  if (!command_index) {
+    print_usage();
+    return 1;
+  }
+  char *command = argv[command_index];
+  char *command_arg = command_index + 1 < argc ? argv[command_index + 1] : NULL;
+
+  // Create the socket address
+  struct sockaddr_in addr;
+  memset(&addr, 0, sizeof(addr));
+  addr.sin_family = AF_INET;
+  addr.sin_port = htons(LOCKDOWN_PORT);
+  if (inet_pton(AF_INET, ip, &addr.sin_addr) != 1) {
+    fprintf(stderr, "Invalid IP address\n");
+    return 1;
+  }
+
+  // Read pairing file
+  IdevicePairingFile *pairing_file = NULL;
+  IdeviceFfiError *err =
+      idevice_pairing_file_read(pairing_file_path, &pairing_file);
+  if (err != NULL) {
+    fprintf(stderr, "Failed to read pairing file: [%d] %s\n", err->code,
+            err->message);
+    idevice_error_free(err);
+    return 1;
+  }
+
+  // Create TCP provider
+  IdeviceProviderHandle *provider = NULL;
+  err = idevice_tcp_provider_new((struct sockaddr *)&addr, pairing_file,
+                                 "MisagentTest", &provider);
+  if (err != NULL) {
+    fprintf(stderr, "Failed to create TCP provider: [%d] %s\n", err->code,
+            err->message);
+    idevice_error_free(err);
+    idevice_pairing_file_free(pairing_file);
+    return 1;
+  }
+
+  // Connect to misagent
+  MisagentClientHandle *client = NULL;
+  err = misagent_connect(provider, &client);
+  if (err != NULL) {
+    fprintf(stderr, "Failed to connect to misagent: [%d] %s\n", err->code,
+            err->message);
+    idevice_error_free(err);
+    idevice_provider_free(provider);
+    return 1;
+  }
+  idevice_provider_free(provider);
+
+  int success = 1;
+
+  if (strcmp(command, "list") == 0) {
+    uint8_t **profiles = NULL;
+    size_t *lens = NULL;
+    size_t count = 0;
+
+    err = misagent_copy_all(client, &profiles, &lens, &count);
+    if (err == NULL) {
+      printf("Found %zu provisioning profiles\n", count);
+      for (size_t i = 0; i < count; i++) {
+        printf("- Profile %zu: %zu bytes\n", i, lens[i]);
+      }
+      misagent_free_profiles(profiles, lens, count);
+    } else {
+      fprintf(stderr, "Failed to copy profiles: [%d] %s\n", err->code,
+              err->message);
+      idevice_error_free(err);
+      success = 0;
+    }
+  } else if (strcmp(command, "install") == 0) {
+    uint8_t *profile = NULL;
+    size_t profile_len = 0;
+
+    if (!command_arg) {
+      printf("Error: Missing profile path argument\n");
+      success = 0;
+    } else if (!read_file(command_arg, &profile, &profile_len)) {
+      success = 0;
+    } else {
+      err = misagent_install(client, profile, profile_len);
+      free(profile);
+      if (err == NULL) {
+        printf("Profile installed successfully\n");
+      } else {
+        fprintf(stderr, "Failed to install profile: [%d] %s\n", err->code,
+                err->message);
+        idevice_error_free(err);
+        success = 0;
+      }
+    }
+  } else if (strcmp(command, "remove") == 0) {
+    if (!command_arg) {
+      printf("Error: Missing profile UUID argument\n");
+      success = 0;
+    } else {
+      err = misagent_remove(client, command_arg);
+      if (err == NULL) {
+        printf("Profile removed successfully\n");
+      } else {
+        fprintf(stderr, "Failed to remove profile: [%d] %s\n", err->code,
+                err->message);
+        idevice_error_free(err);
+        success = 0;
+      }
+    }
+  } else {
+    printf("Unknown command: %s\n", command);
+    print_usage();
+    success = 0;
+  }
+
+  misagent_client_free(client);
+  return success ? 0 : 1;
+}
@@ -143,7 +143,7 @@ int main(int argc, char **argv) {
   }
 
   // Start session
-  err = lockdownd_start_session(lockdown_client, pairing_file_2);
+  err = lockdownd_start_session(lockdown_client, pairing_file_2, NULL);
   if (err != NULL) {
     fprintf(stderr, "Failed to start session: [%d] %s", err->code,
             err->message);
@@ -1,6 +1,6 @@
 // Jackson Coxson
 
-use std::{io::SeekFrom, ptr::null_mut};
+use std::{ffi::c_void, io::SeekFrom, ptr::null_mut};
 
 use idevice::{
     IdeviceError, IdeviceService,
@@ -11,7 +11,7 @@ use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
 use crate::{
     IdeviceFfiError, IdeviceHandle, LOCAL_RUNTIME, ffi_err, provider::IdeviceProviderHandle,
-    run_sync, run_sync_local,
+    run_sync, run_sync_local, spawn_connect,
 };
 
 pub struct AfcClientHandle(pub AfcClient);
@@ -54,6 +54,52 @@ pub unsafe extern "C" fn afc_client_connect(
     }
 }
 
+/// Connects to the AFC service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_client_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut AfcClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            AfcClient::connect,
+            AfcClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Creates a new AfcClient from an existing Idevice connection
 ///
 /// # Arguments
@@ -83,6 +129,8 @@ pub unsafe extern "C" fn afc_client_new(
 
 /// Frees an AfcClient handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -93,7 +141,8 @@ pub unsafe extern "C" fn afc_client_new(
 pub unsafe extern "C" fn afc_client_free(handle: *mut AfcClientHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing afc_client");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
     }
 }
 
@@ -111,6 +160,7 @@ pub unsafe extern "C" fn afc_client_free(handle: *mut AfcClientHandle) {
 /// # Safety
 /// All pointers must be valid and non-null
 /// `path` must be a valid null-terminated C string
+/// The entries must be freed with `afc_list_directory_free`
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn afc_list_directory(
     client: *mut AfcClientHandle,
@@ -118,7 +168,7 @@ pub unsafe extern "C" fn afc_list_directory(
     entries: *mut *mut *mut libc::c_char,
     count: *mut libc::size_t,
 ) -> *mut IdeviceFfiError {
-    if path.is_null() || entries.is_null() || count.is_null() {
+    if client.is_null() || path.is_null() || entries.is_null() || count.is_null() {
         return ffi_err!(IdeviceError::FfiInvalidArg);
     }
 
@@ -171,6 +221,33 @@ pub unsafe extern "C" fn afc_list_directory(
     }
 }
 
+/// Frees a directory listing returned by `afc_list_directory`
+///
+/// # Arguments
+/// * [`entries`] - The array of entries to free
+/// * [`count`] - The number of entries, as returned by `afc_list_directory`
+///
+/// # Safety
+/// `entries` must be a pointer returned by `afc_list_directory` with its matching `count`,
+/// or NULL (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_list_directory_free(
+    entries: *mut *mut libc::c_char,
+    count: libc::size_t,
+) {
+    if entries.is_null() {
+        return;
+    }
+    for i in 0..count {
+        let entry = unsafe { *entries.add(i) };
+        if !entry.is_null() {
+            let _ = unsafe { std::ffi::CString::from_raw(entry) };
+        }
+    }
+    let layout = std::alloc::Layout::array::<*mut libc::c_char>(count + 1).unwrap();
+    unsafe { std::alloc::dealloc(entries as *mut u8, layout) };
+}
+
 /// Creates a new directory on the device
 ///
 /// # Arguments
@@ -306,6 +383,91 @@ pub unsafe extern "C" fn afc_file_info_free(info: *mut AfcFileInfo) {
     }
 }
 
+/// Sets the modification time of a file or directory
+///
+/// # Arguments
+/// * [`client`] - A valid AfcClient handle
+/// * [`path`] - Path to the file or directory (UTF-8 null-terminated)
+/// * [`mtime`] - New modification time, in seconds since the Unix epoch
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `path` must be a valid null-terminated C string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_set_file_time(
+    client: *mut AfcClientHandle,
+    path: *const libc::c_char,
+    mtime: i64,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || path.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let path_cstr = unsafe { std::ffi::CStr::from_ptr(path) };
+    let path = match path_cstr.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+
+    let mtime = match chrono::DateTime::from_timestamp(mtime, 0) {
+        Some(t) => t.naive_utc(),
+        None => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+
+    let res: Result<(), IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.set_file_time(path, mtime).await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Sets the length of a file, padding it with zeros or cutting it short
+///
+/// # Arguments
+/// * [`client`] - A valid AfcClient handle
+/// * [`path`] - Path to the file to resize (UTF-8 null-terminated)
+/// * [`size`] - New length of the file in bytes
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `path` must be a valid null-terminated C string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_truncate(
+    client: *mut AfcClientHandle,
+    path: *const libc::c_char,
+    size: u64,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || path.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let path_cstr = unsafe { std::ffi::CStr::from_ptr(path) };
+    let path = match path_cstr.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+
+    let res: Result<(), IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.truncate(path, size).await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Device information structure for C bindings
 #[repr(C)]
 pub struct AfcDeviceInfo {
@@ -732,6 +894,319 @@ pub unsafe extern "C" fn afc_file_write(
     }
 }
 
+/// Reads the entire contents of a file on the device
+///
+/// # Arguments
+/// * [`client`] - A valid AfcClient handle
+/// * [`path`] - Path to the file to read (UTF-8 null-terminated)
+/// * [`data`] - Will be set to point to the file's contents
+/// * [`length`] - Will be set to the length of the file's contents
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// All pointers must be valid and non-null
+/// `path` must be a valid null-terminated C string
+/// The data must be freed with `afc_file_read_data_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_read_file(
+    client: *mut AfcClientHandle,
+    path: *const libc::c_char,
+    data: *mut *mut u8,
+    length: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || path.is_null() || data.is_null() || length.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let path_cstr = unsafe { std::ffi::CStr::from_ptr(path) };
+    let path = match path_cstr.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+
+    let res: Result<Vec<u8>, IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        let mut fd = client_ref
+            .open(path, idevice::afc::opcode::AfcFopenMode::RdOnly)
+            .await?;
+        let bytes = fd.read_entire().await?;
+        fd.close().await?;
+        Ok(bytes)
+    });
+
+    match res {
+        Ok(bytes) => {
+            let mut boxed = bytes.into_boxed_slice();
+            unsafe {
+                *data = boxed.as_mut_ptr();
+                *length = boxed.len();
+            }
+            std::mem::forget(boxed);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Writes a buffer to a file on the device, creating or truncating it
+///
+/// # Arguments
+/// * [`client`] - A valid AfcClient handle
+/// * [`path`] - Path to the file to write (UTF-8 null-terminated)
+/// * [`data`] - Data to write
+/// * [`length`] - Length of data to write
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// All pointers must be valid and non-null
+/// `path` must be a valid null-terminated C string
+/// `data` must point to at least `length` bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_write_file(
+    client: *mut AfcClientHandle,
+    path: *const libc::c_char,
+    data: *const u8,
+    length: libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || path.is_null() || data.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let path_cstr = unsafe { std::ffi::CStr::from_ptr(path) };
+    let path = match path_cstr.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+    let data_slice = unsafe { std::slice::from_raw_parts(data, length) };
+
+    let res: Result<(), IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        let mut fd = client_ref
+            .open(path, idevice::afc::opcode::AfcFopenMode::WrOnly)
+            .await?;
+        fd.write_entire(data_slice).await?;
+        fd.close().await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Downloads a file from the device to a local path, reporting progress
+///
+/// # Arguments
+/// * [`client`] - A valid AfcClient handle
+/// * [`remote_path`] - Path of the file on the device (UTF-8 null-terminated)
+/// * [`local_path`] - Local path to write to (UTF-8 null-terminated), created or truncated
+/// * [`on_progress`] - Called on the calling thread with the bytes transferred so far and the
+///   file's size, before the first chunk and after each one
+/// * [`context`] - User context to pass to callback
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `remote_path` and `local_path` must be valid null-terminated C strings
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_download_with_progress(
+    client: *mut AfcClientHandle,
+    remote_path: *const libc::c_char,
+    local_path: *const libc::c_char,
+    on_progress: extern "C" fn(transferred: u64, total: u64, context: *mut c_void),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || remote_path.is_null() || local_path.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let (remote_path, local_path) = match (
+        unsafe { std::ffi::CStr::from_ptr(remote_path) }.to_str(),
+        unsafe { std::ffi::CStr::from_ptr(local_path) }.to_str(),
+    ) {
+        (Ok(r), Ok(l)) => (r, std::path::Path::new(l)),
+        _ => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+
+    let res: Result<(), IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref
+            .download_with_progress(remote_path, local_path, |transferred, total| {
+                on_progress(transferred, total, context)
+            })
+            .await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Uploads a local file to the device, reporting progress
+///
+/// # Arguments
+/// * [`client`] - A valid AfcClient handle
+/// * [`local_path`] - Local path of the file to upload (UTF-8 null-terminated)
+/// * [`remote_path`] - Path on the device to write to (UTF-8 null-terminated), created or
+///   truncated
+/// * [`on_progress`] - Called on the calling thread with the bytes transferred so far and the
+///   file's size, before the first chunk and after each one
+/// * [`context`] - User context to pass to callback
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `local_path` and `remote_path` must be valid null-terminated C strings
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_upload_with_progress(
+    client: *mut AfcClientHandle,
+    local_path: *const libc::c_char,
+    remote_path: *const libc::c_char,
+    on_progress: extern "C" fn(transferred: u64, total: u64, context: *mut c_void),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || local_path.is_null() || remote_path.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let (local_path, remote_path) = match (
+        unsafe { std::ffi::CStr::from_ptr(local_path) }.to_str(),
+        unsafe { std::ffi::CStr::from_ptr(remote_path) }.to_str(),
+    ) {
+        (Ok(l), Ok(r)) => (std::path::Path::new(l), r),
+        _ => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+
+    let res: Result<(), IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref
+            .upload_with_progress(local_path, remote_path, |transferred, total| {
+                on_progress(transferred, total, context)
+            })
+            .await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Reads part of a file on the device, starting at a byte offset
+///
+/// The file is opened and closed again by every call, so no handle is kept between calls.
+///
+/// # Arguments
+/// * [`client`] - A valid AfcClient handle
+/// * [`path`] - Path to the file to read (UTF-8 null-terminated)
+/// * [`offset`] - Byte offset to start reading at
+/// * [`len`] - Maximum number of bytes to read
+/// * [`data`] - Will be set to point to the bytes read
+/// * [`length`] - Will be set to the number of bytes read
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// All pointers must be valid and non-null
+/// `path` must be a valid null-terminated C string
+/// The data must be freed with `afc_file_read_data_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_read_at(
+    client: *mut AfcClientHandle,
+    path: *const libc::c_char,
+    offset: u64,
+    len: u64,
+    data: *mut *mut u8,
+    length: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || path.is_null() || data.is_null() || length.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let path_cstr = unsafe { std::ffi::CStr::from_ptr(path) };
+    let path = match path_cstr.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+
+    let res: Result<Vec<u8>, IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.read_at(path, offset, len).await
+    });
+
+    match res {
+        Ok(bytes) => {
+            let mut boxed = bytes.into_boxed_slice();
+            unsafe {
+                *data = boxed.as_mut_ptr();
+                *length = boxed.len();
+            }
+            std::mem::forget(boxed);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Writes a buffer into a file on the device, starting at a byte offset
+///
+/// The file is created if needed and never truncated. It is opened and closed again by
+/// every call, so no handle is kept between calls.
+///
+/// # Arguments
+/// * [`client`] - A valid AfcClient handle
+/// * [`path`] - Path to the file to write (UTF-8 null-terminated)
+/// * [`offset`] - Byte offset to start writing at
+/// * [`data`] - Data to write
+/// * [`length`] - Length of data to write
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// All pointers must be valid and non-null
+/// `path` must be a valid null-terminated C string
+/// `data` must point to at least `length` bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn afc_write_at(
+    client: *mut AfcClientHandle,
+    path: *const libc::c_char,
+    offset: u64,
+    data: *const u8,
+    length: libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || path.is_null() || data.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let path_cstr = unsafe { std::ffi::CStr::from_ptr(path) };
+    let path = match path_cstr.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+    let data_slice = unsafe { std::slice::from_raw_parts(data, length) };
+
+    let res: Result<(), IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.write_at(path, offset, data_slice).await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Link type for creating hard or symbolic links
 #[repr(C)]
 pub enum AfcLinkType {
@@ -777,13 +1252,13 @@ pub unsafe extern "C" fn afc_make_link(
     };
 
     let link_type = match link_type {
-        AfcLinkType::Hard => idevice::afc::opcode::LinkType::Hardlink,
-        AfcLinkType::Symbolic => idevice::afc::opcode::LinkType::Symlink,
+        AfcLinkType::Hard => idevice::afc::AfcLinkType::Hard,
+        AfcLinkType::Symbolic => idevice::afc::AfcLinkType::Symbolic,
     };
 
     let res: Result<(), IdeviceError> = run_sync_local(async move {
         let client_ref = unsafe { &mut (*client).0 };
-        client_ref.link(target, source, link_type).await
+        client_ref.make_link(link_type, target, source).await
     });
 
     match res {
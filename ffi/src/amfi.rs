@@ -1,15 +1,32 @@
 // Jackson Coxson
 
-use std::ptr::null_mut;
+use std::{ffi::c_void, ptr::null_mut};
 
-use idevice::{IdeviceError, IdeviceService, amfi::AmfiClient, provider::IdeviceProvider};
+use idevice::{
+    IdeviceError, IdeviceService,
+    amfi::{AmfiClient, DevModePreflight},
+    provider::IdeviceProvider,
+};
 
 use crate::{
-    IdeviceFfiError, IdeviceHandle, ffi_err, provider::IdeviceProviderHandle, run_sync_local,
+    IdeviceFfiError, IdeviceHandle, ffi_err, lockdown::LockdowndClientHandle,
+    provider::IdeviceProviderHandle, run_sync_local, spawn_connect,
 };
 
 pub struct AmfiClientHandle(pub AmfiClient);
 
+/// What enabling developer mode would run into
+#[repr(C)]
+pub struct AmfiDevModePreflight {
+    /// Developer mode is already enabled, so there is nothing to do
+    pub enabled: bool,
+    /// The device is locked with a passcode, which blocks enabling developer mode
+    pub passcode_locked: bool,
+    /// Enabling developer mode reboots the device. Not reported by the device, but derived
+    /// as the opposite of `enabled`.
+    pub reboot_required: bool,
+}
+
 /// Automatically creates and connects to AMFI service, returning a client handle
 ///
 /// # Arguments
@@ -49,6 +66,52 @@ pub unsafe extern "C" fn amfi_connect(
     }
 }
 
+/// Connects to the AMFI service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn amfi_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut AmfiClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            AmfiClient::connect,
+            AmfiClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Automatically creates and connects to AMFI service, returning a client handle
 ///
 /// # Arguments
@@ -162,8 +225,90 @@ pub unsafe extern "C" fn amfi_accept_developer_mode(
     }
 }
 
+/// Queries whether developer mode is currently enabled on the device
+///
+/// # Arguments
+/// * `client` - A valid AmfiClient handle
+/// * `out_enabled` - On success, will be set to whether developer mode is enabled
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `out_enabled` must be a valid, non-null pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn amfi_get_developer_mode_status(
+    client: *mut AmfiClientHandle,
+    out_enabled: *mut bool,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || out_enabled.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res: Result<bool, IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.developer_mode_status().await
+    });
+    match res {
+        Ok(enabled) => {
+            unsafe { *out_enabled = enabled };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Checks what enabling developer mode would run into, without changing anything
+///
+/// Lockdown only reports whether the device is locked, so an unlocked device with a passcode
+/// isn't caught here, and enabling then fails with the device's error message.
+///
+/// # Arguments
+/// * `client` - A valid AmfiClient handle
+/// * `lockdown` - A lockdown client handle for the same device, with a session started
+/// * `out_preflight` - On success, will be filled in with the results
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` and `lockdown` must be valid pointers to handles allocated by this library
+/// `out_preflight` must be a valid, non-null pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn amfi_developer_mode_preflight(
+    client: *mut AmfiClientHandle,
+    lockdown: *mut LockdowndClientHandle,
+    out_preflight: *mut AmfiDevModePreflight,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || lockdown.is_null() || out_preflight.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res: Result<DevModePreflight, IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        let lockdown_ref = unsafe { &mut (*lockdown).0 };
+        client_ref.developer_mode_preflight(lockdown_ref).await
+    });
+    match res {
+        Ok(p) => {
+            unsafe {
+                *out_preflight = AmfiDevModePreflight {
+                    enabled: p.enabled,
+                    passcode_locked: p.passcode_locked,
+                    reboot_required: p.reboot_required,
+                }
+            };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Frees a handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -174,6 +319,7 @@ pub unsafe extern "C" fn amfi_accept_developer_mode(
 pub unsafe extern "C" fn amfi_client_free(handle: *mut AmfiClientHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing AmfiClient handle");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
     }
 }
@@ -1,7 +1,7 @@
 // Jackson Coxson
 
 use std::{
-    ffi::{CString, c_char},
+    ffi::{CString, c_char, c_void},
     ptr::null_mut,
 };
 
@@ -11,7 +11,7 @@ use idevice::{
 
 use crate::{
     IdeviceFfiError, IdeviceHandle, ffi_err, provider::IdeviceProviderHandle, run_sync,
-    run_sync_local,
+    run_sync_local, spawn_connect,
 };
 
 pub struct CoreDeviceProxyHandle(pub CoreDeviceProxy);
@@ -56,6 +56,52 @@ pub unsafe extern "C" fn core_device_proxy_connect(
     }
 }
 
+/// Connects to the core device proxy service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn core_device_proxy_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut CoreDeviceProxyHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            CoreDeviceProxy::connect,
+            CoreDeviceProxyHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Automatically creates and connects to Core Device Proxy, returning a client handle
 ///
 /// # Arguments
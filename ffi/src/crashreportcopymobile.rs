@@ -0,0 +1,432 @@
+//! iOS Crash Logs Client Bindings
+//!
+//! Provides C-compatible bindings for listing, pulling and removing crash logs
+//! through the crashreportcopymobile service, and for pulling sysdiagnose archives.
+
+use std::{ffi::c_void, ptr::null_mut, time::Duration};
+
+use idevice::{
+    IdeviceService,
+    crashreportcopymobile::{
+        CrashReportCopyMobileClient, SysdiagnoseProgress, flush_reports, wait_for_sysdiagnose,
+    },
+    provider::IdeviceProvider,
+};
+
+use crate::{
+    IdeviceFfiError, ffi_err, provider::IdeviceProviderHandle, run_sync_local, spawn_connect,
+};
+
+pub struct CrashReportCopyMobileHandle(pub CrashReportCopyMobileClient);
+
+/// Automatically creates and connects to the crash report copy service, returning a client handle
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`client`] - On success, will be set to point to a newly allocated client handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library
+/// `client` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crash_report_client_connect(
+    provider: *mut IdeviceProviderHandle,
+    client: *mut *mut CrashReportCopyMobileHandle,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() || client.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async {
+        let provider_ref: &dyn IdeviceProvider = unsafe { &*(*provider).0 };
+        CrashReportCopyMobileClient::connect(provider_ref).await
+    });
+
+    match res {
+        Ok(r) => {
+            let boxed = Box::new(CrashReportCopyMobileHandle(r));
+            unsafe { *client = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Connects to the crash report copy mobile service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crash_report_client_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut CrashReportCopyMobileHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            CrashReportCopyMobileClient::connect,
+            CrashReportCopyMobileHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
+/// Frees a crash report client handle
+///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
+/// # Arguments
+/// * [`handle`] - The handle to free
+///
+/// # Safety
+/// `handle` must be a valid pointer to the handle that was allocated by this library,
+/// or NULL (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crash_report_client_free(handle: *mut CrashReportCopyMobileHandle) {
+    if !handle.is_null() {
+        tracing::debug!("Freeing crash report client");
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.afc_client.idevice.shutdown());
+    }
+}
+
+/// Lists crash logs in a directory of the crash logs directory
+///
+/// # Arguments
+/// * [`client`] - A valid crash report client handle
+/// * [`dir_path`] - The directory to list (UTF-8 null-terminated), or NULL for the root
+/// * [`entries`] - Will be set to point to an array of file names
+/// * [`count`] - Will be set to the number of entries
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client`, `entries` and `count` must be valid and non-null
+/// `dir_path` must be a valid null-terminated C string or NULL
+/// The entries must be freed with `afc_list_directory_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crash_report_client_ls(
+    client: *mut CrashReportCopyMobileHandle,
+    dir_path: *const libc::c_char,
+    entries: *mut *mut *mut libc::c_char,
+    count: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || entries.is_null() || count.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let dir_path = if dir_path.is_null() {
+        None
+    } else {
+        Some(
+            unsafe { std::ffi::CStr::from_ptr(dir_path) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    };
+
+    let res = run_sync_local(async { unsafe { &mut *client }.0.ls(dir_path.as_deref()).await });
+
+    match res {
+        Ok(items) => {
+            let c_strings = items
+                .into_iter()
+                .filter_map(|s| std::ffi::CString::new(s).ok())
+                .collect::<Vec<_>>();
+            let string_count = c_strings.len();
+
+            // Allocated the same way as afc_list_directory, so it can be freed the same way
+            let layout = std::alloc::Layout::array::<*mut libc::c_char>(string_count + 1).unwrap();
+            let ptr = unsafe { std::alloc::alloc(layout) as *mut *mut libc::c_char };
+            if ptr.is_null() {
+                return ffi_err!(IdeviceError::FfiInvalidArg);
+            }
+
+            for (i, cstring) in c_strings.into_iter().enumerate() {
+                unsafe { *ptr.add(i) = cstring.into_raw() };
+            }
+            unsafe { *ptr.add(string_count) = null_mut() };
+
+            unsafe {
+                *entries = ptr;
+                *count = string_count;
+            }
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Reads the contents of a crash log
+///
+/// # Arguments
+/// * [`client`] - A valid crash report client handle
+/// * [`log`] - Name of the log to read (UTF-8 null-terminated)
+/// * [`data`] - Will be set to point to the log contents
+/// * [`length`] - Will be set to the length of the contents
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// All pointers must be valid and non-null
+/// `log` must be a valid null-terminated C string
+/// The data must be freed with `idevice_data_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crash_report_client_pull(
+    client: *mut CrashReportCopyMobileHandle,
+    log: *const libc::c_char,
+    data: *mut *mut u8,
+    length: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || log.is_null() || data.is_null() || length.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let log = unsafe { std::ffi::CStr::from_ptr(log) }
+        .to_string_lossy()
+        .into_owned();
+
+    let res = run_sync_local(async { unsafe { &mut *client }.0.pull(log).await });
+
+    match res {
+        Ok(r) => {
+            let mut r = r.into_boxed_slice();
+            unsafe {
+                *data = r.as_mut_ptr();
+                *length = r.len();
+            }
+            std::mem::forget(r);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Removes a crash log from the device
+///
+/// # Arguments
+/// * [`client`] - A valid crash report client handle
+/// * [`log`] - Name of the log to remove (UTF-8 null-terminated)
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `log` must be a valid null-terminated C string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crash_report_client_remove(
+    client: *mut CrashReportCopyMobileHandle,
+    log: *const libc::c_char,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || log.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let log = unsafe { std::ffi::CStr::from_ptr(log) }
+        .to_string_lossy()
+        .into_owned();
+
+    let res = run_sync_local(async { unsafe { &mut *client }.0.remove(log).await });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Moves freshly generated crash logs into the crash logs directory
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crash_report_flush(
+    provider: *mut IdeviceProviderHandle,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async {
+        let provider_ref: &dyn IdeviceProvider = unsafe { &*(*provider).0 };
+        flush_reports(provider_ref).await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Waits for a new sysdiagnose archive and pulls it from the crash logs directory
+///
+/// This only waits, generation must be started on the device by pressing both volume buttons
+/// and the side button together. Archives that existed when this was called are ignored. This
+/// blocks until the archive is pulled or the timeout passes. Use
+/// `crash_report_capture_sysdiagnose` for devices reachable over RSD.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`timeout_secs`] - How long to wait for the archive to be finished, in seconds
+/// * [`on_progress`] - Called on the calling thread with the stage: 0 while waiting for the
+///   device to start writing an archive, 1 while it is written, and 2 after each chunk is
+///   pulled, with the bytes received and the archive size. Sizes are 0 for the other stages.
+/// * [`context`] - User context to pass to callback
+/// * [`data`] - Will be set to point to the archive contents
+/// * [`length`] - Will be set to the length of the archive
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library
+/// `data` and `length` must be valid and non-null
+/// The data must be freed with `idevice_data_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crash_report_wait_for_sysdiagnose(
+    provider: *mut IdeviceProviderHandle,
+    timeout_secs: u64,
+    on_progress: extern "C" fn(stage: u32, received: u64, total: u64, context: *mut c_void),
+    context: *mut c_void,
+    data: *mut *mut u8,
+    length: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() || data.is_null() || length.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async {
+        let provider_ref: &dyn IdeviceProvider = unsafe { &*(*provider).0 };
+        wait_for_sysdiagnose(
+            provider_ref,
+            Duration::from_secs(timeout_secs),
+            |p| match p {
+                SysdiagnoseProgress::Waiting => on_progress(0, 0, 0, context),
+                SysdiagnoseProgress::Generating => on_progress(1, 0, 0, context),
+                SysdiagnoseProgress::Downloading { received, total } => {
+                    on_progress(2, received, total, context)
+                }
+            },
+        )
+        .await
+    });
+
+    match res {
+        Ok(r) => {
+            let mut r = r.into_boxed_slice();
+            unsafe {
+                *data = r.as_mut_ptr();
+                *length = r.len();
+            }
+            std::mem::forget(r);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Starts a sysdiagnose over RSD and pulls the archive
+///
+/// Unlike `crash_report_wait_for_sysdiagnose`, generation is started through the CoreDevice
+/// diagnostics service, so nothing has to be pressed on the device. This blocks until the
+/// archive is pulled, which usually takes a few minutes.
+///
+/// # Arguments
+/// * [`provider`] - An adapter connected to the device's tunnel
+/// * [`handshake`] - An RSD handshake from the same provider
+/// * [`on_progress`] - Called on the calling thread with the stage: 1 once generation is
+///   requested, and 2 after each chunk is pulled, with the bytes received and the archive
+///   size. Sizes are 0 for stage 1.
+/// * [`context`] - User context to pass to callback
+/// * [`data`] - Will be set to point to the archive contents
+/// * [`length`] - Will be set to the length of the archive
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `provider` and `handshake` must be valid pointers to handles allocated by this library
+/// `data` and `length` must be valid and non-null
+/// The data must be freed with `idevice_data_free`
+#[cfg(all(feature = "core_device", feature = "rsd"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn crash_report_capture_sysdiagnose(
+    provider: *mut crate::core_device_proxy::AdapterHandle,
+    handshake: *mut crate::rsd::RsdHandshakeHandle,
+    on_progress: extern "C" fn(stage: u32, received: u64, total: u64, context: *mut c_void),
+    context: *mut c_void,
+    data: *mut *mut u8,
+    length: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() || handshake.is_null() || data.is_null() || length.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res =
+        run_sync_local(async {
+            let provider_ref = unsafe { &mut (*provider).0 };
+            let handshake_ref = unsafe { &mut (*handshake).0 };
+            idevice::crashreportcopymobile::capture_sysdiagnose(provider_ref, handshake_ref, |p| {
+                match p {
+                    SysdiagnoseProgress::Waiting => on_progress(0, 0, 0, context),
+                    SysdiagnoseProgress::Generating => on_progress(1, 0, 0, context),
+                    SysdiagnoseProgress::Downloading { received, total } => {
+                        on_progress(2, received, total, context)
+                    }
+                }
+            })
+            .await
+        });
+
+    match res {
+        Ok(r) => {
+            let mut r = r.into_boxed_slice();
+            unsafe {
+                *data = r.as_mut_ptr();
+                *length = r.len();
+            }
+            std::mem::forget(r);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
@@ -4,7 +4,8 @@ use std::ffi::{CStr, CString, c_char};
 use std::os::raw::c_int;
 use std::ptr::{self, null_mut};
 
-use idevice::debug_proxy::{DebugProxyClient, DebugserverCommand};
+use futures::StreamExt;
+use idevice::debug_proxy::{DebugProxyClient, DebugserverCommand, LaunchOptions};
 use idevice::{IdeviceError, ReadWrite, RsdService};
 
 use crate::core_device_proxy::AdapterHandle;
@@ -187,6 +188,8 @@ pub unsafe extern "C" fn debug_proxy_new(
 
 /// Frees a DebugProxyClient handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -195,7 +198,14 @@ pub unsafe extern "C" fn debug_proxy_new(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn debug_proxy_free(handle: *mut DebugProxyHandle) {
     if !handle.is_null() {
-        let _ = unsafe { Box::from_raw(handle) };
+        tracing::debug!("Freeing debug proxy client");
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(async move {
+            use tokio::io::AsyncWriteExt;
+
+            handle.0.socket.flush().await?;
+            handle.0.socket.shutdown().await
+        });
     }
 }
 
@@ -431,6 +441,254 @@ pub unsafe extern "C" fn debug_proxy_set_argv(
     }
 }
 
+/// Launches a process under debugserver, leaving it stopped at its entry point
+///
+/// # Arguments
+/// * [`handle`] - The DebugProxyClient handle
+/// * [`bundle_path`] - Path on the device of the app bundle or executable to launch
+/// * [`argv`] - Array of arguments passed after the path, or NULL
+/// * [`argv_count`] - Number of arguments
+/// * [`pid`] - Pointer to store the process ID of the launched process
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `handle` must be a valid pointer
+/// `bundle_path` must be a valid C string
+/// `argv` must be a valid pointer to `argv_count` C strings or NULL
+/// `pid` must be a valid pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn debug_proxy_launch(
+    handle: *mut DebugProxyHandle,
+    bundle_path: *const c_char,
+    argv: *const *const c_char,
+    argv_count: usize,
+    pid: *mut u32,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() || bundle_path.is_null() || pid.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { &mut (*handle).0 };
+    let bundle_path = match unsafe { CStr::from_ptr(bundle_path) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+    let mut args = Vec::new();
+    if !argv.is_null() && argv_count > 0 {
+        let argv_slice = unsafe { std::slice::from_raw_parts(argv, argv_count) };
+        for &arg in argv_slice {
+            if arg.is_null() {
+                return ffi_err!(IdeviceError::FfiInvalidArg);
+            }
+            match unsafe { CStr::from_ptr(arg) }.to_str() {
+                Ok(s) => args.push(s),
+                Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+            }
+        }
+    }
+
+    let res = run_sync_local(async move { client.launch(bundle_path, &args).await });
+
+    match res {
+        Ok(p) => {
+            unsafe { *pid = p };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Launches a process under debugserver with environment variables, optionally resuming it
+///
+/// # Arguments
+/// * [`handle`] - The DebugProxyClient handle
+/// * [`bundle_path`] - Path on the device of the app bundle or executable to launch
+/// * [`argv`] - Array of arguments passed after the path, or NULL
+/// * [`argv_count`] - Number of arguments
+/// * [`env`] - Array of `KEY=VALUE` environment variables, or NULL
+/// * [`env_count`] - Number of environment variables
+/// * [`start_suspended`] - Whether to leave the process stopped at its entry point
+/// * [`pid`] - Pointer to store the process ID of the launched process
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `handle` must be a valid pointer
+/// `bundle_path` must be a valid C string
+/// `argv` must be a valid pointer to `argv_count` C strings or NULL
+/// `env` must be a valid pointer to `env_count` C strings or NULL
+/// `pid` must be a valid pointer
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn debug_proxy_launch_with_options(
+    handle: *mut DebugProxyHandle,
+    bundle_path: *const c_char,
+    argv: *const *const c_char,
+    argv_count: usize,
+    env: *const *const c_char,
+    env_count: usize,
+    start_suspended: bool,
+    pid: *mut u32,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() || bundle_path.is_null() || pid.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { &mut (*handle).0 };
+    let bundle_path = match unsafe { CStr::from_ptr(bundle_path) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+    let mut args = Vec::new();
+    if !argv.is_null() && argv_count > 0 {
+        let argv_slice = unsafe { std::slice::from_raw_parts(argv, argv_count) };
+        for &arg in argv_slice {
+            if arg.is_null() {
+                return ffi_err!(IdeviceError::FfiInvalidArg);
+            }
+            match unsafe { CStr::from_ptr(arg) }.to_str() {
+                Ok(s) => args.push(s),
+                Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+            }
+        }
+    }
+    let mut options = LaunchOptions {
+        start_suspended,
+        ..Default::default()
+    };
+    if !env.is_null() && env_count > 0 {
+        let env_slice = unsafe { std::slice::from_raw_parts(env, env_count) };
+        for &var in env_slice {
+            if var.is_null() {
+                return ffi_err!(IdeviceError::FfiInvalidArg);
+            }
+            let var = match unsafe { CStr::from_ptr(var) }.to_str() {
+                Ok(s) => s,
+                Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+            };
+            let Some((key, value)) = var.split_once('=') else {
+                return ffi_err!(IdeviceError::FfiInvalidArg);
+            };
+            options.env.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let res = run_sync_local(async move {
+        client
+            .launch_with_options(bundle_path, &args, &options)
+            .await
+    });
+
+    match res {
+        Ok(p) => {
+            unsafe { *pid = p };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Attaches to a running process, which stops it
+///
+/// # Arguments
+/// * [`handle`] - The DebugProxyClient handle
+/// * [`pid`] - The process ID to attach to
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `handle` must be a valid pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn debug_proxy_attach(
+    handle: *mut DebugProxyHandle,
+    pid: u32,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { &mut (*handle).0 };
+    let res = run_sync_local(async move { client.attach(pid).await });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Resumes the stopped process. Read its output with ``debug_proxy_read_console``.
+///
+/// # Arguments
+/// * [`handle`] - The DebugProxyClient handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `handle` must be a valid pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn debug_proxy_continue(
+    handle: *mut DebugProxyHandle,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { &mut (*handle).0 };
+    let res = run_sync_local(async move { client.continue_execution().await });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Waits for the next chunk of console output from the running process
+///
+/// # Arguments
+/// * [`handle`] - The DebugProxyClient handle
+/// * [`output`] - Pointer to store the output (caller must free), or NULL once the process
+///   has exited or stopped
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `handle` must be a valid pointer
+/// `output` must be a valid pointer to a location where the string will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn debug_proxy_read_console(
+    handle: *mut DebugProxyHandle,
+    output: *mut *mut c_char,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() || output.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { &mut (*handle).0 };
+    let res = run_sync_local(async move { client.read_console().next().await });
+
+    match res {
+        Some(Ok(r)) => {
+            let cstr = match CString::new(r) {
+                Ok(c) => c,
+                Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+            };
+            unsafe { *output = cstr.into_raw() };
+            null_mut()
+        }
+        Some(Err(e)) => ffi_err!(e),
+        None => {
+            unsafe { *output = null_mut() };
+            null_mut()
+        }
+    }
+}
+
 /// Sends an ACK to the debug proxy
 ///
 /// # Arguments
@@ -1,22 +1,80 @@
 // Jackson Coxson
 
 use std::{
-    ffi::{CStr, c_char},
+    ffi::{CStr, c_char, c_void},
     ptr::null_mut,
 };
 
 use idevice::{
-    IdeviceError, IdeviceService, diagnostics_relay::DiagnosticsRelayClient,
+    IdeviceError, IdeviceService,
+    diagnostics_relay::{BatteryInfo, DiagnosticsRelayClient, ThermalState},
     provider::IdeviceProvider,
 };
 use plist_ffi::plist_t;
 
 use crate::{
     IdeviceFfiError, IdeviceHandle, ffi_err, provider::IdeviceProviderHandle, run_sync_local,
+    spawn_connect,
 };
 
 pub struct DiagnosticsRelayClientHandle(pub DiagnosticsRelayClient);
 
+/// Battery state. Capacities and cycle count are -1, and temperature is NaN, when the
+/// device doesn't report them.
+#[repr(C)]
+pub struct DiagnosticsRelayBatteryInfo {
+    /// Charge level as a percentage
+    pub current_capacity: u64,
+    /// Charge level in mAh
+    pub raw_current_capacity: i64,
+    /// Capacity of the battery in its current condition, in mAh
+    pub raw_max_capacity: i64,
+    /// Capacity of the battery when new, in mAh
+    pub design_capacity: i64,
+    pub cycle_count: i64,
+    pub is_charging: bool,
+    pub external_connected: bool,
+    pub fully_charged: bool,
+    /// Degrees Celsius, or NaN if not reported
+    pub temperature: f64,
+}
+
+impl From<BatteryInfo> for DiagnosticsRelayBatteryInfo {
+    fn from(value: BatteryInfo) -> Self {
+        let or_unknown = |v: Option<u64>| v.map(|v| v as i64).unwrap_or(-1);
+        Self {
+            current_capacity: value.current_capacity,
+            raw_current_capacity: or_unknown(value.raw_current_capacity),
+            raw_max_capacity: or_unknown(value.raw_max_capacity),
+            design_capacity: or_unknown(value.design_capacity),
+            cycle_count: or_unknown(value.cycle_count),
+            is_charging: value.is_charging,
+            external_connected: value.external_connected,
+            fully_charged: value.fully_charged,
+            temperature: value.temperature.unwrap_or(f64::NAN),
+        }
+    }
+}
+
+/// Temperatures reported by the battery. Temperatures are NaN when the device doesn't
+/// report them.
+#[repr(C)]
+pub struct DiagnosticsRelayThermalState {
+    /// Battery temperature in degrees Celsius
+    pub battery_temperature: f64,
+    /// The battery manager's virtual temperature in degrees Celsius
+    pub virtual_temperature: f64,
+}
+
+impl From<ThermalState> for DiagnosticsRelayThermalState {
+    fn from(value: ThermalState) -> Self {
+        Self {
+            battery_temperature: value.battery_temperature.unwrap_or(f64::NAN),
+            virtual_temperature: value.virtual_temperature.unwrap_or(f64::NAN),
+        }
+    }
+}
+
 /// Automatically creates and connects to Diagnostics Relay, returning a client handle
 ///
 /// # Arguments
@@ -57,6 +115,52 @@ pub unsafe extern "C" fn diagnostics_relay_client_connect(
     }
 }
 
+/// Connects to the diagnostics relay service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn diagnostics_relay_client_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut DiagnosticsRelayClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            DiagnosticsRelayClient::connect,
+            DiagnosticsRelayClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Automatically creates and connects to Diagnostics Relay, returning a client handle
 ///
 /// # Arguments
@@ -357,6 +461,76 @@ pub unsafe extern "C" fn diagnostics_relay_client_all(
     }
 }
 
+/// Reads the battery's charge, health and temperature
+///
+/// # Arguments
+/// * `client` - A valid DiagnosticsRelayClient handle
+/// * `info` - On success, will be filled with the battery state
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success.
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `info` must be a valid, non-null pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn diagnostics_relay_client_query_battery(
+    client: *mut DiagnosticsRelayClientHandle,
+    info: *mut DiagnosticsRelayBatteryInfo,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || info.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let output: Result<BatteryInfo, IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.query_battery().await
+    });
+
+    match output {
+        Ok(b) => {
+            unsafe { *info = b.into() };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Reads the temperatures reported by the battery, without needing developer mode
+///
+/// # Arguments
+/// * `client` - A valid DiagnosticsRelayClient handle
+/// * `state` - On success, will be filled with the temperatures
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success.
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `state` must be a valid, non-null pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn diagnostics_relay_client_thermal_state(
+    client: *mut DiagnosticsRelayClientHandle,
+    state: *mut DiagnosticsRelayThermalState,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || state.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let output: Result<ThermalState, IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.thermal_state().await
+    });
+
+    match output {
+        Ok(t) => {
+            unsafe { *state = t.into() };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Restarts the device
 ///
 /// # Arguments
@@ -518,6 +692,8 @@ pub unsafe extern "C" fn diagnostics_relay_client_goodbye(
 
 /// Frees a handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -528,6 +704,7 @@ pub unsafe extern "C" fn diagnostics_relay_client_goodbye(
 pub unsafe extern "C" fn diagnostics_relay_client_free(handle: *mut DiagnosticsRelayClientHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing DiagnosticsRelayClientHandle");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
     }
 }
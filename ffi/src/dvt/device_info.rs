@@ -0,0 +1,311 @@
+// Jackson Coxson
+
+use std::{
+    ffi::{CString, c_char},
+    ptr::null_mut,
+    time::UNIX_EPOCH,
+};
+
+use idevice::{
+    ReadWrite,
+    dvt::device_info::{DeviceInfoClient, HardwareInformation, ProcessInfo},
+};
+use plist_ffi::{PlistWrapper, plist_t};
+
+use crate::{IdeviceFfiError, dvt::remote_server::RemoteServerHandle, ffi_err, run_sync};
+
+/// Opaque handle to a DeviceInfoClient
+pub struct DeviceInfoHandle<'a>(pub DeviceInfoClient<'a, Box<dyn ReadWrite>>);
+
+/// A process running on the device
+#[repr(C)]
+pub struct DeviceInfoProcess {
+    pub pid: u64,
+    pub name: *mut c_char,
+    /// NULL if the app path wasn't reported
+    pub real_app_name: *mut c_char,
+    /// NULL if the process isn't an app
+    pub bundle_id: *mut c_char,
+    pub is_application: bool,
+    /// Seconds since the Unix epoch, or 0 if the start date wasn't reported
+    pub start_date: f64,
+}
+
+impl From<ProcessInfo> for DeviceInfoProcess {
+    fn from(value: ProcessInfo) -> Self {
+        let opt_string = |s: Option<String>| {
+            s.and_then(|s| CString::new(s).ok())
+                .map(CString::into_raw)
+                .unwrap_or(null_mut())
+        };
+        Self {
+            pid: value.pid,
+            name: CString::new(value.name).unwrap_or_default().into_raw(),
+            real_app_name: opt_string(value.real_app_name),
+            bundle_id: opt_string(value.bundle_id),
+            is_application: value.is_application,
+            start_date: value
+                .start_date
+                .and_then(|d| d.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs_f64())
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+/// CPU details of the device. Values the device didn't report are 0.
+#[repr(C)]
+pub struct DeviceInfoHardware {
+    pub cpu_type: u64,
+    pub cpu_subtype: u64,
+    pub cpu_count: u64,
+    pub physical_cpu_count: u64,
+    pub is_64_bit: bool,
+}
+
+impl From<HardwareInformation> for DeviceInfoHardware {
+    fn from(value: HardwareInformation) -> Self {
+        Self {
+            cpu_type: value.cpu_type.unwrap_or(0),
+            cpu_subtype: value.cpu_subtype.unwrap_or(0),
+            cpu_count: value.cpu_count.unwrap_or(0),
+            physical_cpu_count: value.physical_cpu_count.unwrap_or(0),
+            is_64_bit: value.is_64_bit,
+        }
+    }
+}
+
+/// Creates a new DeviceInfoClient from a RemoteServerClient
+///
+/// # Arguments
+/// * [`server`] - The RemoteServerClient to use
+/// * [`handle`] - Pointer to store the newly created DeviceInfoClient handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `server` must be a valid pointer to a handle allocated by this library
+/// `handle` must be a valid pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn device_info_new(
+    server: *mut RemoteServerHandle,
+    handle: *mut *mut DeviceInfoHandle<'static>,
+) -> *mut IdeviceFfiError {
+    if server.is_null() || handle.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let server = unsafe { &mut (*server).0 };
+    let res = run_sync(async move { DeviceInfoClient::new(server).await });
+
+    match res {
+        Ok(client) => {
+            let boxed = Box::new(DeviceInfoHandle(client));
+            unsafe { *handle = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Frees a DeviceInfoClient handle
+///
+/// # Arguments
+/// * [`handle`] - The handle to free
+///
+/// # Safety
+/// `handle` must be a valid pointer to a handle allocated by this library or NULL
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn device_info_free(handle: *mut DeviceInfoHandle<'static>) {
+    if !handle.is_null() {
+        let _ = unsafe { Box::from_raw(handle) };
+    }
+}
+
+/// Lists the processes running on the device
+///
+/// # Arguments
+/// * [`handle`] - The DeviceInfoClient handle
+/// * [`processes`] - Will be set to point to an array of processes on success
+/// * [`processes_len`] - Will be set to the number of processes
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `handle` must be a valid pointer to a handle allocated by this library
+/// `processes` and `processes_len` must be valid, non-null pointers
+/// The processes must be freed with `device_info_processes_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn device_info_running_processes(
+    handle: *mut DeviceInfoHandle<'static>,
+    processes: *mut *mut DeviceInfoProcess,
+    processes_len: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() || processes.is_null() || processes_len.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { &mut (*handle).0 };
+    let res = run_sync(async move { client.running_processes().await });
+
+    match res {
+        Ok(p) => {
+            let p = p
+                .into_iter()
+                .map(DeviceInfoProcess::from)
+                .collect::<Vec<_>>()
+                .into_boxed_slice();
+            let len = p.len();
+            let ptr = Box::leak(p).as_mut_ptr();
+
+            unsafe {
+                *processes = ptr;
+                *processes_len = len;
+            }
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Frees an array of processes returned by `device_info_running_processes`
+///
+/// # Arguments
+/// * [`processes`] - The array to free
+/// * [`processes_len`] - The number of processes in the array
+///
+/// # Safety
+/// `processes` must be a pointer returned by `device_info_running_processes` with its matching
+/// length, or NULL (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn device_info_processes_free(
+    processes: *mut DeviceInfoProcess,
+    processes_len: libc::size_t,
+) {
+    if processes.is_null() {
+        return;
+    }
+    let processes =
+        unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(processes, processes_len)) };
+    for process in processes.iter() {
+        unsafe {
+            let _ = CString::from_raw(process.name);
+            if !process.real_app_name.is_null() {
+                let _ = CString::from_raw(process.real_app_name);
+            }
+            if !process.bundle_id.is_null() {
+                let _ = CString::from_raw(process.bundle_id);
+            }
+        }
+    }
+}
+
+/// Reads the CPU details of the device
+///
+/// # Arguments
+/// * [`handle`] - The DeviceInfoClient handle
+/// * [`hardware`] - Will be filled with the CPU details on success
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `handle` must be a valid pointer to a handle allocated by this library
+/// `hardware` must be a valid, non-null pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn device_info_hardware_information(
+    handle: *mut DeviceInfoHandle<'static>,
+    hardware: *mut DeviceInfoHardware,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() || hardware.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { &mut (*handle).0 };
+    let res = run_sync(async move { client.hardware_information().await });
+
+    match res {
+        Ok(h) => {
+            unsafe { *hardware = h.into() };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Reads the network interfaces of the device
+///
+/// # Arguments
+/// * [`handle`] - The DeviceInfoClient handle
+/// * [`interfaces`] - Will be set to a plist dictionary of interface names to descriptions
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `handle` must be a valid pointer to a handle allocated by this library
+/// `interfaces` must be a valid, non-null pointer. The plist must be freed with `plist_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn device_info_network_information(
+    handle: *mut DeviceInfoHandle<'static>,
+    interfaces: *mut plist_t,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() || interfaces.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { &mut (*handle).0 };
+    let res = run_sync(async move { client.network_information().await });
+
+    match res {
+        Ok(n) => {
+            let dict = n
+                .interfaces
+                .into_iter()
+                .map(|(k, v)| (k, plist::Value::String(v)))
+                .collect::<plist::Dictionary>();
+            unsafe {
+                *interfaces = PlistWrapper::new_node(plist::Value::Dictionary(dict)).into_ptr()
+            };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Reads the OS details of the device
+///
+/// # Arguments
+/// * [`handle`] - The DeviceInfoClient handle
+/// * [`values`] - Will be set to a plist dictionary of the values the device reported
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `handle` must be a valid pointer to a handle allocated by this library
+/// `values` must be a valid, non-null pointer. The plist must be freed with `plist_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn device_info_system_information(
+    handle: *mut DeviceInfoHandle<'static>,
+    values: *mut plist_t,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() || values.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { &mut (*handle).0 };
+    let res = run_sync(async move { client.system_information().await });
+
+    match res {
+        Ok(s) => {
+            unsafe {
+                *values = PlistWrapper::new_node(plist::Value::Dictionary(s.values)).into_ptr()
+            };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
@@ -3,6 +3,7 @@
 #[cfg(feature = "location_simulation")]
 pub mod location_simulation;
 
+pub mod device_info;
 pub mod process_control;
 pub mod remote_server;
 pub mod screenshot;
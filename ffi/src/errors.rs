@@ -1,6 +1,13 @@
 // Jackson Coxson
 
-use std::ffi::{CString, c_char};
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString, c_char},
+    sync::Mutex,
+};
+
+use idevice::IdeviceError;
+use once_cell::sync::Lazy;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -34,6 +41,7 @@ macro_rules! ffi_err {
         use $crate::IdeviceFfiError;
 
         let err: IdeviceError = $err.into();
+        $crate::errors::intern_error_message(&err);
         let code = err.code();
         let msg = CString::new(format!("{:?}", err))
             .unwrap_or_else(|_| CString::new("invalid error").unwrap());
@@ -45,3 +53,298 @@ macro_rules! ffi_err {
         }))
     }};
 }
+
+/// Payload-free messages by error code, formatted once from each error's `Display`
+///
+/// Starts with the representative errors, and learns the codes of errors that can't be
+/// constructed here as they're returned. Entries are never replaced or removed, so pointers
+/// to their strings stay valid.
+static ERROR_MESSAGES: Lazy<Mutex<HashMap<i32, CString>>> = Lazy::new(|| {
+    Mutex::new(
+        representative_errors()
+            .iter()
+            .map(|err| (err.code(), payload_free_message(err)))
+            .collect(),
+    )
+});
+
+/// Records the message of an error's code, if it isn't known yet
+pub(crate) fn intern_error_message(err: &IdeviceError) {
+    ERROR_MESSAGES
+        .lock()
+        .unwrap()
+        .entry(err.code())
+        .or_insert_with(|| payload_free_message(err));
+}
+
+/// Formats an error's message without its payload
+///
+/// Payloads either follow a `: `, `, ` or ` - `, which the message is cut at, or sit in the
+/// middle of the message, like the bundle id in "no app with bundle id {0} is installed". The
+/// representative errors leave those empty or zero, and the empty renderings are dropped.
+fn payload_free_message(err: &IdeviceError) -> CString {
+    let msg = err.to_string();
+    let end = [": ", ", ", " - "]
+        .iter()
+        .filter_map(|sep| msg.find(sep))
+        .min()
+        .unwrap_or(msg.len());
+    let msg = msg[..end]
+        .split_whitespace()
+        .filter(|word| !["0", "0x0", "[]", "``"].contains(word))
+        .collect::<Vec<_>>()
+        .join(" ");
+    CString::new(msg).unwrap_or_else(|_| UNKNOWN_ERROR_MESSAGE.to_owned())
+}
+
+static UNKNOWN_ERROR_MESSAGE: &CStr = c"unknown error";
+
+/// Category of an error code, as returned by `idevice_error_category`
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorCategory {
+    /// Retrying won't help
+    Fatal = 0,
+    /// Retrying the operation may succeed
+    Transient = 1,
+    /// The connection was lost, so the client must be recreated
+    Connection = 2,
+}
+
+static ERROR_CATEGORIES: Lazy<HashMap<i32, ErrorCategory>> = Lazy::new(|| {
+    representative_errors()
+        .iter()
+        .map(|err| {
+            let category = if err.is_transient() {
+                ErrorCategory::Transient
+            } else if err.is_connection_error() {
+                ErrorCategory::Connection
+            } else {
+                ErrorCategory::Fatal
+            };
+            (err.code(), category)
+        })
+        .collect()
+});
+
+/// One instance of every error variant that can be constructed here
+///
+/// Variants with a payload in the middle of their message must be listed, so their message
+/// is formatted with an empty payload.
+fn representative_errors() -> Vec<IdeviceError> {
+    let mut errors = vec![
+        IdeviceError::Socket(std::io::Error::other("")),
+        IdeviceError::UnexpectedResponse,
+        IdeviceError::GetProhibited,
+        IdeviceError::SessionInactive,
+        IdeviceError::InvalidHostID,
+        IdeviceError::NoEstablishedConnection,
+        IdeviceError::HeartbeatSleepyTime,
+        IdeviceError::HeartbeatTimeout,
+        IdeviceError::NotFound,
+        IdeviceError::ServiceNotFound,
+        IdeviceError::CdtunnelPacketTooShort,
+        IdeviceError::CdtunnelPacketInvalidMagic,
+        IdeviceError::PacketSizeMismatch,
+        IdeviceError::DeviceNotFound,
+        IdeviceError::DeviceLocked,
+        IdeviceError::UsbConnectionRefused,
+        IdeviceError::UsbBadCommand,
+        IdeviceError::UsbBadDevice,
+        IdeviceError::UsbBadVersion,
+        IdeviceError::BadBuildManifest,
+        IdeviceError::ImageNotMounted,
+        IdeviceError::InternalError(String::new()),
+        IdeviceError::Utf8Error,
+        IdeviceError::FfiInvalidArg,
+        IdeviceError::FfiInvalidString,
+        IdeviceError::UnsupportedWatchKey,
+        IdeviceError::MalformedCommand,
+        IdeviceError::IntegerOverflow,
+        IdeviceError::CanceledByUser,
+        IdeviceError::Canceled,
+        IdeviceError::DeveloperModeNotEnabled,
+        IdeviceError::Timeout,
+        IdeviceError::FfiRuntimeAlreadyInitialized,
+        IdeviceError::MissingKey { key: String::new() },
+        IdeviceError::Unsupported(String::new()),
+        IdeviceError::InvalidPairRecord(String::new()),
+        IdeviceError::MessageTooLarge(0),
+        IdeviceError::NotEnoughBytes(0, 0),
+        IdeviceError::UnknownErrorType(String::new()),
+        IdeviceError::FfiBufferTooSmall(0, 0),
+        IdeviceError::DebugserverError(String::new()),
+    ];
+
+    if let Err(e) = plist::from_bytes::<plist::Value>(&[]) {
+        errors.push(IdeviceError::Plist(e));
+    }
+    if let Err(e) = String::from_utf8(vec![0xff]) {
+        errors.push(IdeviceError::Utf8(e));
+    }
+    if let Err(e) = "".parse::<std::net::IpAddr>() {
+        errors.push(IdeviceError::AddrParseError(e));
+    }
+
+    #[cfg(feature = "pair")]
+    errors.extend([
+        IdeviceError::PairingDialogResponsePending,
+        IdeviceError::UserDeniedPairing,
+    ]);
+    errors.push(IdeviceError::PasswordProtected);
+    #[cfg(feature = "misagent")]
+    errors.push(IdeviceError::MisagentFailure);
+    #[cfg(feature = "installation_proxy")]
+    errors.extend([
+        IdeviceError::InstallationProxyOperationFailed(String::new()),
+        IdeviceError::AppStillInstalled(String::new()),
+    ]);
+    #[cfg(feature = "afc")]
+    errors.extend([
+        IdeviceError::Afc(idevice::afc::errors::AfcError::OpNotSupported),
+        IdeviceError::UnknownAfcOpcode,
+        IdeviceError::InvalidAfcMagic,
+        IdeviceError::AfcMissingAttribute,
+        IdeviceError::AfcLinkFailed(idevice::afc::errors::AfcError::OpNotSupported),
+        IdeviceError::NotEnoughSpace {
+            needed: 0,
+            available: 0,
+        },
+    ]);
+    #[cfg(feature = "crashreportcopymobile")]
+    errors.push(IdeviceError::CrashReportMoverBadResponse(Vec::new()));
+    #[cfg(feature = "house_arrest")]
+    errors.extend([
+        IdeviceError::HouseArrestAppNotFound(String::new()),
+        IdeviceError::HouseArrestDocumentsNotShared(String::new()),
+    ]);
+    #[cfg(feature = "xpc")]
+    errors.extend([
+        IdeviceError::UnknownFrame(0),
+        IdeviceError::UnknownHttpSetting(0),
+        IdeviceError::UninitializedStreamId,
+        IdeviceError::UnknownXpcType(0),
+        IdeviceError::MalformedXpc,
+        IdeviceError::InvalidXpcMagic,
+        IdeviceError::UnexpectedXpcVersion,
+        IdeviceError::InvalidCString,
+        IdeviceError::HttpStreamReset,
+        IdeviceError::HttpGoAway(String::new()),
+    ]);
+    #[cfg(feature = "dvt")]
+    errors.extend([
+        IdeviceError::UnknownAuxValueType(0),
+        IdeviceError::UnknownChannel(0),
+        IdeviceError::DisableMemoryLimitFailed,
+    ]);
+    #[cfg(any(feature = "debug_proxy", feature = "afc"))]
+    errors.push(IdeviceError::InvalidArgument);
+
+    errors
+}
+
+/// Returns a human-readable description of an error code
+///
+/// # Arguments
+/// * [`code`] - The `code` field of an IdeviceFfiError
+///
+/// # Returns
+/// A static, null-terminated string describing the error. Unknown codes return a generic message.
+/// The string must not be freed.
+///
+/// A few errors, such as TLS errors, can only be described once one has been returned by
+/// this library. Their codes are unknown until then.
+#[unsafe(no_mangle)]
+pub extern "C" fn idevice_error_message(code: i32) -> *const c_char {
+    ERROR_MESSAGES
+        .lock()
+        .unwrap()
+        .get(&code)
+        .map(|m| m.as_ptr())
+        .unwrap_or(UNKNOWN_ERROR_MESSAGE.as_ptr())
+}
+
+/// Classifies an error code for retry and alerting logic
+///
+/// # Arguments
+/// * [`code`] - The `code` field of an IdeviceFfiError
+///
+/// # Returns
+/// * `0` - Fatal, retrying won't help
+/// * `1` - Transient, retrying the operation may succeed
+/// * `2` - Connection, the connection was lost and the client must be recreated
+///
+/// Unknown codes are fatal. Socket errors are reported as connection errors, since the
+/// code alone doesn't say whether the socket failure was transient.
+#[unsafe(no_mangle)]
+pub extern "C" fn idevice_error_category(code: i32) -> u8 {
+    ERROR_CATEGORIES
+        .get(&code)
+        .copied()
+        .unwrap_or(ErrorCategory::Fatal) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_message_matches_display() {
+        let msg = unsafe { CStr::from_ptr(idevice_error_message(IdeviceError::NotFound.code())) };
+        assert_eq!(msg.to_str().unwrap(), IdeviceError::NotFound.to_string());
+    }
+
+    #[test]
+    fn payload_is_left_out_of_message() {
+        let msg = unsafe {
+            CStr::from_ptr(idevice_error_message(
+                IdeviceError::InternalError(String::new()).code(),
+            ))
+        };
+        assert_eq!(msg, c"internal error");
+        let msg = unsafe {
+            CStr::from_ptr(idevice_error_message(
+                IdeviceError::Socket(std::io::Error::other("")).code(),
+            ))
+        };
+        assert_eq!(msg, c"device socket io failed");
+    }
+
+    #[test]
+    fn payload_in_middle_of_message_is_dropped() {
+        let msg = unsafe {
+            CStr::from_ptr(idevice_error_message(
+                IdeviceError::UnknownErrorType(String::new()).code(),
+            ))
+        };
+        assert_eq!(msg, c"unknown error returned from device");
+        let msg = unsafe {
+            CStr::from_ptr(idevice_error_message(
+                IdeviceError::FfiBufferTooSmall(0, 0).code(),
+            ))
+        };
+        assert_eq!(msg, c"buffer passed is too small");
+    }
+
+    #[test]
+    fn unknown_code_has_message() {
+        let msg = unsafe { CStr::from_ptr(idevice_error_message(1)) };
+        assert_eq!(msg, UNKNOWN_ERROR_MESSAGE);
+    }
+
+    #[test]
+    fn error_categories() {
+        assert_eq!(
+            idevice_error_category(IdeviceError::Timeout.code()),
+            ErrorCategory::Transient as u8
+        );
+        assert_eq!(
+            idevice_error_category(IdeviceError::NoEstablishedConnection.code()),
+            ErrorCategory::Connection as u8
+        );
+        assert_eq!(
+            idevice_error_category(IdeviceError::UnexpectedResponse.code()),
+            ErrorCategory::Fatal as u8
+        );
+    }
+}
@@ -1,28 +1,39 @@
 // Jackson Coxson
 
-use std::ptr::null_mut;
+use std::{ffi::c_void, ptr::null_mut, time::Duration};
 
 use idevice::{
     IdeviceError, IdeviceService, heartbeat::HeartbeatClient, provider::IdeviceProvider,
 };
+use tokio::{sync::oneshot, task::JoinHandle};
 
 use crate::{
-    IdeviceFfiError, IdeviceHandle, ffi_err, provider::IdeviceProviderHandle, run_sync_local,
+    IdeviceFfiError, IdeviceHandle, LOCAL_RUNTIME, ffi_err, provider::IdeviceProviderHandle,
+    run_sync_local, spawn_connect,
 };
 
 pub struct HeartbeatClientHandle(pub HeartbeatClient);
 
-/// Automatically creates and connects to Installation Proxy, returning a client handle
+pub struct HeartbeatLoopHandle {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+struct HeartbeatContext(*mut c_void);
+unsafe impl Send for HeartbeatContext {}
+
+/// Automatically creates and connects to the heartbeat service, returning a client handle
 ///
 /// # Arguments
 /// * [`provider`] - An IdeviceProvider
-/// * [`client`] - On success, will be set to point to a newly allocated InstallationProxyClient handle
+/// * [`client`] - On success, will be set to point to a newly allocated HeartbeatClient handle
 ///
 /// # Returns
 /// An IdeviceFfiError on error, null on success
 ///
 /// # Safety
-/// `provider` must be a valid pointer to a handle allocated by this library
+/// `provider` must be a valid pointer to a handle allocated by this library. It is only borrowed
+/// for the duration of the call, and remains owned by the caller whether or not it succeeds.
 /// `client` must be a valid, non-null pointer to a location where the handle will be stored
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn heartbeat_connect(
@@ -46,12 +57,56 @@ pub unsafe extern "C" fn heartbeat_connect(
             unsafe { *client = Box::into_raw(boxed) };
             null_mut()
         }
-        Err(e) => {
-            ffi_err!(e)
-        }
+        Err(e) => ffi_err!(e),
     }
 }
 
+/// Connects to the heartbeat service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn heartbeat_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut HeartbeatClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            HeartbeatClient::connect,
+            HeartbeatClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Automatically creates and connects to Installation Proxy, returning a client handle
 ///
 /// # Arguments
@@ -107,15 +162,17 @@ pub unsafe extern "C" fn heartbeat_send_polo(
     }
 }
 
-/// Sends a polo to the device
+/// Waits for a marco from the device
 ///
 /// # Arguments
 /// * `client` - A valid HeartbeatClient handle
 /// * `interval` - The time to wait for a marco
+/// * `timeout_ms` - Client-side deadline in milliseconds, or 0 to only wait for `interval`
 /// * `new_interval` - A pointer to set the requested marco
 ///
 /// # Returns
-/// An IdeviceFfiError on error, null on success.
+/// An IdeviceFfiError on error, null on success. If `timeout_ms` elapses first, the error code
+/// is that of `IdeviceError::Timeout`.
 ///
 /// # Safety
 /// `client` must be a valid pointer to a handle allocated by this library
@@ -123,14 +180,16 @@ pub unsafe extern "C" fn heartbeat_send_polo(
 pub unsafe extern "C" fn heartbeat_get_marco(
     client: *mut HeartbeatClientHandle,
     interval: u64,
+    timeout_ms: u64,
     new_interval: *mut u64,
 ) -> *mut IdeviceFfiError {
     if client.is_null() || new_interval.is_null() {
         return ffi_err!(IdeviceError::FfiInvalidArg);
     }
+    let timeout = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms));
     let res: Result<u64, IdeviceError> = run_sync_local(async move {
         let client_ref = unsafe { &mut (*client).0 };
-        client_ref.get_marco(interval).await
+        client_ref.get_marco_with_timeout(interval, timeout).await
     });
     match res {
         Ok(n) => {
@@ -141,8 +200,77 @@ pub unsafe extern "C" fn heartbeat_get_marco(
     }
 }
 
+/// Starts a background loop that answers every marco from the device with a polo
+///
+/// # Arguments
+/// * `client` - A valid HeartbeatClient handle. It is consumed, and should not be used again.
+/// * `on_error` - Called with the error code once if the loop stops because of an error
+/// * `context` - Passed to `on_error` untouched
+/// * `handle` - On success, will be set to point to a newly allocated loop handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `context` must remain valid until the loop is stopped with `heartbeat_stop_loop`
+/// `handle` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn heartbeat_start_loop(
+    client: *mut HeartbeatClientHandle,
+    on_error: extern "C" fn(code: i32, context: *mut c_void),
+    context: *mut c_void,
+    handle: *mut *mut HeartbeatLoopHandle,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || handle.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let client = unsafe { Box::from_raw(client) }.0;
+    let context = HeartbeatContext(context);
+    let (stop, mut stop_rx) = oneshot::channel();
+
+    let task = LOCAL_RUNTIME.spawn(async move {
+        let context = context;
+        let mut heartbeat = client.run();
+        tokio::select! {
+            err = heartbeat.error() => {
+                if let Some(e) = err {
+                    on_error(e.code(), context.0);
+                }
+            }
+            _ = &mut stop_rx => heartbeat.shutdown().await,
+        }
+    });
+
+    let boxed = Box::new(HeartbeatLoopHandle { stop, task });
+    unsafe { *handle = Box::into_raw(boxed) };
+    null_mut()
+}
+
+/// Stops a heartbeat loop, waits for it to exit, and frees its handle
+///
+/// # Arguments
+/// * [`handle`] - The loop handle to stop
+///
+/// # Safety
+/// `handle` must be a valid pointer to the handle that was allocated by this library,
+/// or NULL (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn heartbeat_stop_loop(handle: *mut HeartbeatLoopHandle) {
+    if handle.is_null() {
+        return;
+    }
+    tracing::debug!("Stopping heartbeat loop");
+    let handle = unsafe { Box::from_raw(handle) };
+    let _ = handle.stop.send(());
+    let _ = run_sync_local(handle.task);
+}
+
 /// Frees a handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -153,6 +281,38 @@ pub unsafe extern "C" fn heartbeat_get_marco(
 pub unsafe extern "C" fn heartbeat_client_free(handle: *mut HeartbeatClientHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing installation_proxy_client");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        errors::idevice_error_free,
+        lockdown::{lockdownd_client_free, lockdownd_connect},
+        provider::{idevice_provider_free, tests::flaky_provider},
+    };
+
+    #[test]
+    fn failed_connect_leaves_provider_usable() {
+        let provider = flaky_provider(1);
+
+        let mut client = null_mut();
+        let err = unsafe { heartbeat_connect(provider, &mut client) };
+        assert!(!err.is_null());
+        assert!(client.is_null());
+        unsafe { idevice_error_free(err) };
+
+        let mut lockdown = null_mut();
+        let err = unsafe { lockdownd_connect(provider, &mut lockdown) };
+        assert!(err.is_null());
+        assert!(!lockdown.is_null());
+
+        unsafe {
+            lockdownd_client_free(lockdown);
+            idevice_provider_free(provider);
+        }
     }
 }
@@ -0,0 +1,69 @@
+//! App Container Access Bindings
+//!
+//! Provides C-compatible bindings for exposing an app's container or Documents directory
+//! over AFC through the HouseArrest service.
+
+use std::ptr::null_mut;
+
+use idevice::{
+    house_arrest::{ContainerType, HouseArrestClient},
+    provider::IdeviceProvider,
+};
+
+use crate::{
+    IdeviceFfiError, afc::AfcClientHandle, ffi_err, provider::IdeviceProviderHandle, run_sync_local,
+};
+
+/// Connects to an app's container, returning an AFC client rooted at it
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`bundle_id`] - The bundle identifier of the app (UTF-8 null-terminated)
+/// * [`container_type`] - 0 for the full data container, 1 for the Documents directory
+/// * [`client`] - On success, will be set to point to a newly allocated AfcClient handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success. The error code is that of
+/// `HouseArrestAppNotFound` if the app isn't installed, and `HouseArrestDocumentsNotShared` if
+/// the Documents directory was requested and the app doesn't enable file sharing.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library
+/// `bundle_id` must be a valid null-terminated C string
+/// `client` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn house_arrest_connect_container(
+    provider: *mut IdeviceProviderHandle,
+    bundle_id: *const libc::c_char,
+    container_type: u8,
+    client: *mut *mut AfcClientHandle,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() || bundle_id.is_null() || client.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let container_type = match container_type {
+        0 => ContainerType::App,
+        1 => ContainerType::Documents,
+        _ => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+    let bundle_id = match unsafe { std::ffi::CStr::from_ptr(bundle_id) }.to_str() {
+        Ok(b) => b.to_string(),
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+
+    let res = run_sync_local(async {
+        let provider_ref: &dyn IdeviceProvider = unsafe { &*(*provider).0 };
+        HouseArrestClient::connect_container(provider_ref, bundle_id, container_type).await
+    });
+
+    match res {
+        Ok(r) => {
+            let boxed = Box::new(AfcClientHandle(r));
+            unsafe { *client = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
@@ -1,6 +1,9 @@
 // Jackson Coxson
 
-use std::{ffi::c_void, ptr::null_mut};
+use std::{
+    ffi::{CString, c_void},
+    ptr::null_mut,
+};
 
 use idevice::{
     IdeviceError, IdeviceService, installation_proxy::InstallationProxyClient,
@@ -10,6 +13,7 @@ use plist_ffi::{PlistWrapper, plist_t};
 
 use crate::{
     IdeviceFfiError, IdeviceHandle, ffi_err, provider::IdeviceProviderHandle, run_sync_local,
+    spawn_connect,
 };
 
 pub struct InstallationProxyClientHandle(pub InstallationProxyClient);
@@ -51,6 +55,52 @@ pub unsafe extern "C" fn installation_proxy_connect(
     }
 }
 
+/// Connects to the installation proxy service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn installation_proxy_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut InstallationProxyClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            InstallationProxyClient::connect,
+            InstallationProxyClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Automatically creates and connects to Installation Proxy, returning a client handle
 ///
 /// # Arguments
@@ -161,8 +211,91 @@ pub unsafe extern "C" fn installation_proxy_get_apps(
     }
 }
 
+/// Looks up specific applications by bundle identifier
+///
+/// # Arguments
+/// * [`client`] - A valid InstallationProxyClient handle
+/// * [`bundle_ids`] - Array of bundle identifiers to look up
+/// * [`bundle_ids_len`] - Length of the bundle identifier array
+/// * [`return_attributes`] - Array of attributes to return for each app, or NULL for all
+/// * [`return_attributes_len`] - Length of the attribute array
+/// * [`out_result`] - On success, set to a plist dictionary of bundle identifiers to app info.
+///   Apps that aren't installed are left out.
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `bundle_ids` must be a valid array of `bundle_ids_len` C strings
+/// `return_attributes` must be a valid array of `return_attributes_len` C strings or NULL
+/// A NULL element in either array is rejected with an invalid argument error
+/// `out_result` must be a valid, non-null pointer. The plist must be freed with `plist_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn installation_proxy_lookup(
+    client: *mut InstallationProxyClientHandle,
+    bundle_ids: *const *const libc::c_char,
+    bundle_ids_len: libc::size_t,
+    return_attributes: *const *const libc::c_char,
+    return_attributes_len: libc::size_t,
+    out_result: *mut plist_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || bundle_ids.is_null() || out_result.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    // None if any element is NULL
+    let to_strings = |ptr: *const *const libc::c_char, len: libc::size_t| {
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+            .iter()
+            .map(|&s| {
+                (!s.is_null()).then(|| {
+                    unsafe { std::ffi::CStr::from_ptr(s) }
+                        .to_string_lossy()
+                        .into_owned()
+                })
+            })
+            .collect::<Option<Vec<String>>>()
+    };
+    let Some(bundle_ids) = to_strings(bundle_ids, bundle_ids_len) else {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    };
+    let return_attributes = if return_attributes.is_null() {
+        None
+    } else {
+        match to_strings(return_attributes, return_attributes_len) {
+            Some(a) => Some(a),
+            None => return ffi_err!(IdeviceError::FfiInvalidArg),
+        }
+    };
+
+    let res = run_sync_local(async {
+        let bundle_ids = bundle_ids.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let return_attributes = return_attributes
+            .as_ref()
+            .map(|a| a.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+        unsafe { &mut *client }
+            .0
+            .lookup(&bundle_ids, return_attributes.as_deref())
+            .await
+    });
+
+    match res {
+        Ok(apps) => {
+            let dict = apps.into_iter().collect::<plist::Dictionary>();
+            unsafe {
+                *out_result = PlistWrapper::new_node(plist::Value::Dictionary(dict)).into_ptr();
+            }
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Frees a handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -175,7 +308,8 @@ pub unsafe extern "C" fn installation_proxy_client_free(
 ) {
     if !handle.is_null() {
         tracing::debug!("Freeing installation_proxy_client");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
     }
 }
 
@@ -281,6 +415,142 @@ pub unsafe extern "C" fn installation_proxy_install_with_callback(
     }
 }
 
+/// Installs an application package on the device, reporting each progress status
+///
+/// # Arguments
+/// * [`client`] - A valid InstallationProxyClient handle
+/// * [`package_path`] - Path to the .ipa package in the AFC jail
+/// * [`options`] - Optional installation options as a plist dictionary (can be NULL)
+/// * [`on_progress`] - Called for each progress message with the percentage and status. The
+///   final call is at 100% before this function returns success.
+/// * [`context`] - User context to pass to callback
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `package_path` must be a valid C string
+/// `options` must be a valid plist dictionary or NULL
+/// The status string passed to `on_progress` is only valid for the duration of the call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn installation_proxy_install_with_status_callback(
+    client: *mut InstallationProxyClientHandle,
+    package_path: *const libc::c_char,
+    options: plist_t,
+    on_progress: extern "C" fn(percent: u32, status: *const libc::c_char, context: *mut c_void),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || package_path.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let package_path = unsafe { std::ffi::CStr::from_ptr(package_path) }
+        .to_string_lossy()
+        .into_owned();
+    let options = if options.is_null() {
+        None
+    } else {
+        Some(unsafe { &mut *options })
+    }
+    .map(|x| x.borrow_self().clone());
+
+    let res = run_sync_local(async {
+        unsafe { &mut *client }
+            .0
+            .install_with_status_callback(
+                package_path,
+                options,
+                |(percent, status, context)| async move {
+                    report_status(on_progress, percent, status, context)
+                },
+                context,
+            )
+            .await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Installs a local package, staging it on the device first
+///
+/// The package is uploaded over AFC to `PublicStaging` and installed from there. The staged
+/// copy is removed afterwards, whether the install succeeded or not.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider, used to connect to AFC and Installation Proxy
+/// * [`package_path`] - Local path of the .ipa file or app directory (UTF-8 null-terminated)
+/// * [`options`] - Optional installation options as a plist dictionary (can be NULL)
+/// * [`on_progress`] - Called with the stage: 0 while uploading, with the bytes sent and the
+///   package size, and 1 while installing, with the percentage done out of 100
+/// * [`context`] - User context to pass to callback
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library
+/// `package_path` must be a valid C string
+/// `options` must be a valid plist dictionary or NULL
+#[cfg(feature = "afc")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn installation_proxy_install_package(
+    provider: *mut IdeviceProviderHandle,
+    package_path: *const libc::c_char,
+    options: plist_t,
+    on_progress: extern "C" fn(stage: u32, done: u64, total: u64, context: *mut c_void),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() || package_path.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let package_path = match unsafe { std::ffi::CStr::from_ptr(package_path) }.to_str() {
+        Ok(p) => std::path::PathBuf::from(p),
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+    let options = if options.is_null() {
+        None
+    } else {
+        Some(unsafe { &mut *options })
+    }
+    .map(|x| x.borrow_self().clone());
+
+    let res = run_sync_local(async {
+        let provider_ref: &dyn IdeviceProvider = unsafe { &*(*provider).0 };
+        idevice::utils::installation::install_package_with_progress(
+            provider_ref,
+            &package_path,
+            options,
+            |sent, total| on_progress(0, sent, total, context),
+            |(percent, ())| {
+                on_progress(1, percent.min(100), 100, context);
+                std::future::ready(())
+            },
+            (),
+        )
+        .await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+fn report_status(
+    on_progress: extern "C" fn(u32, *const libc::c_char, *mut c_void),
+    percent: u64,
+    status: String,
+    context: *mut c_void,
+) {
+    let status = CString::new(status).unwrap_or_default();
+    on_progress(percent.min(100) as u32, status.as_ptr(), context);
+}
+
 /// Upgrades an existing application on the device
 ///
 /// # Arguments
@@ -383,6 +653,58 @@ pub unsafe extern "C" fn installation_proxy_upgrade_with_callback(
     }
 }
 
+/// Uninstalls an application, then checks that it is gone
+///
+/// The device can report a successful uninstall while the app is still registered, so the
+/// app is looked up afterwards.
+///
+/// # Arguments
+/// * [`client`] - A valid InstallationProxyClient handle
+/// * [`bundle_id`] - Bundle identifier of the application to uninstall
+/// * [`options`] - Optional uninstall options as a plist dictionary (can be NULL)
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success. The error code is that of
+/// `AppStillInstalled` if the app can still be looked up.
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `bundle_id` must be a valid UTF-8 C string
+/// `options` must be a valid plist dictionary or NULL
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn installation_proxy_uninstall_and_verify(
+    client: *mut InstallationProxyClientHandle,
+    bundle_id: *const libc::c_char,
+    options: plist_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || bundle_id.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let bundle_id = match unsafe { std::ffi::CStr::from_ptr(bundle_id) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+    let options = if options.is_null() {
+        None
+    } else {
+        Some(unsafe { &mut *options })
+    }
+    .map(|x| x.borrow_self().clone());
+
+    let res = run_sync_local(async {
+        unsafe { &mut *client }
+            .0
+            .uninstall_and_verify(bundle_id, options)
+            .await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Uninstalls an application from the device
 ///
 /// # Arguments
@@ -485,6 +807,66 @@ pub unsafe extern "C" fn installation_proxy_uninstall_with_callback(
     }
 }
 
+/// Uninstalls an application from the device, reporting each progress status
+///
+/// # Arguments
+/// * [`client`] - A valid InstallationProxyClient handle
+/// * [`bundle_id`] - Bundle identifier of the application to uninstall
+/// * [`options`] - Optional uninstall options as a plist dictionary (can be NULL)
+/// * [`on_progress`] - Called for each progress message with the percentage and status. The
+///   final call is at 100% before this function returns success.
+/// * [`context`] - User context to pass to callback
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `bundle_id` must be a valid C string
+/// `options` must be a valid plist dictionary or NULL
+/// The status string passed to `on_progress` is only valid for the duration of the call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn installation_proxy_uninstall_with_status_callback(
+    client: *mut InstallationProxyClientHandle,
+    bundle_id: *const libc::c_char,
+    options: plist_t,
+    on_progress: extern "C" fn(percent: u32, status: *const libc::c_char, context: *mut c_void),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || bundle_id.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let bundle_id = unsafe { std::ffi::CStr::from_ptr(bundle_id) }
+        .to_string_lossy()
+        .into_owned();
+    let options = if options.is_null() {
+        None
+    } else {
+        Some(unsafe { &mut *options })
+    }
+    .map(|x| x.borrow_self().clone());
+
+    let res = run_sync_local(async {
+        unsafe { &mut *client }
+            .0
+            .uninstall_with_status_callback(
+                bundle_id,
+                options,
+                |(percent, status, context)| async move {
+                    report_status(on_progress, percent, status, context)
+                },
+                context,
+            )
+            .await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Checks if the device capabilities match the required capabilities
 ///
 /// # Arguments
@@ -581,11 +963,15 @@ pub unsafe extern "C" fn installation_proxy_browse(
     .map(|x| x.borrow_self().clone());
 
     let res: Result<Vec<plist_t>, IdeviceError> = run_sync_local(async {
-        unsafe { &mut *client }.0.browse(options).await.map(|apps| {
-            apps.into_iter()
-                .map(|v| PlistWrapper::new_node(v).into_ptr())
-                .collect()
-        })
+        unsafe { &mut *client }
+            .0
+            .browse_raw(options)
+            .await
+            .map(|apps| {
+                apps.into_iter()
+                    .map(|v| PlistWrapper::new_node(v).into_ptr())
+                    .collect()
+            })
     });
 
     match res {
@@ -10,6 +10,8 @@ pub mod amfi;
 pub mod core_device;
 #[cfg(feature = "core_device_proxy")]
 pub mod core_device_proxy;
+#[cfg(feature = "crashreportcopymobile")]
+pub mod crashreportcopymobile;
 #[cfg(feature = "debug_proxy")]
 pub mod debug_proxy;
 #[cfg(feature = "diagnostics_relay")]
@@ -19,6 +21,8 @@ pub mod dvt;
 mod errors;
 #[cfg(feature = "heartbeat")]
 pub mod heartbeat;
+#[cfg(feature = "house_arrest")]
+pub mod house_arrest;
 #[cfg(feature = "installation_proxy")]
 pub mod installation_proxy;
 pub mod lockdown;
@@ -27,12 +31,18 @@ pub mod logging;
 pub mod misagent;
 #[cfg(feature = "mobile_image_mounter")]
 pub mod mobile_image_mounter;
+#[cfg(feature = "notification_proxy")]
+pub mod notification_proxy;
 #[cfg(feature = "syslog_relay")]
 pub mod os_trace_relay;
 mod pairing_file;
+#[cfg(feature = "power_assertion")]
+pub mod power_assertion;
 pub mod provider;
 #[cfg(feature = "xpc")]
 pub mod rsd;
+#[cfg(feature = "screenshotr")]
+pub mod screenshotr;
 #[cfg(feature = "springboardservices")]
 pub mod springboardservices;
 #[cfg(feature = "syslog_relay")]
@@ -46,32 +56,62 @@ pub mod util;
 pub use errors::*;
 pub use pairing_file::*;
 
-use idevice::{Idevice, IdeviceSocket, ReadWrite};
-use once_cell::sync::Lazy;
+use idevice::{Idevice, IdeviceError, IdeviceSocket, ReadWrite};
+use once_cell::sync::{Lazy, OnceCell};
 use plist_ffi::PlistWrapper;
 use std::{
     ffi::{CStr, CString, c_char, c_void},
     ptr::null_mut,
+    time::Duration,
 };
 use tokio::runtime::{self, Runtime};
 
 #[cfg(unix)]
 use crate::util::{idevice_sockaddr, idevice_socklen_t};
 
+/// Worker thread count for the runtimes, fixed the first time either runtime is used
+static WORKER_THREADS: OnceCell<Option<usize>> = OnceCell::new();
+
+fn multi_thread_builder() -> runtime::Builder {
+    let mut builder = runtime::Builder::new_multi_thread();
+    if let Some(threads) = WORKER_THREADS.get_or_init(|| None) {
+        builder.worker_threads(*threads);
+    }
+    builder
+}
+
 static GLOBAL_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
-    runtime::Builder::new_multi_thread()
+    multi_thread_builder()
         .enable_io()
         .enable_time()
         .build()
         .unwrap()
 });
 
-static LOCAL_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
-    runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap()
-});
+static LOCAL_RUNTIME: Lazy<Runtime> =
+    Lazy::new(|| multi_thread_builder().enable_all().build().unwrap());
+
+/// Sets the number of worker threads used by the library's async runtimes
+///
+/// This is a one-shot setting, and must be called before any other function in this library
+/// that talks to a device.
+///
+/// # Arguments
+/// * [`worker_threads`] - The number of worker threads, must be greater than zero
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success. If the runtimes have already been configured or
+/// used, the error code is that of `FfiRuntimeAlreadyInitialized`.
+#[unsafe(no_mangle)]
+pub extern "C" fn idevice_runtime_configure(worker_threads: usize) -> *mut IdeviceFfiError {
+    if worker_threads == 0 {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+    match WORKER_THREADS.set(Some(worker_threads)) {
+        Ok(()) => null_mut(),
+        Err(_) => ffi_err!(IdeviceError::FfiRuntimeAlreadyInitialized),
+    }
+}
 
 /// Spawn the future on the global runtime and block current (FFI) thread until result.
 /// F and R must be Send + 'static.
@@ -99,6 +139,42 @@ where
     LOCAL_RUNTIME.block_on(fut)
 }
 
+/// A context pointer passed back to a C callback from a runtime thread
+struct CallbackContext(*mut c_void);
+unsafe impl Send for CallbackContext {}
+
+/// Connects to a service without blocking, passing the result to a C callback
+///
+/// The connection runs on the local runtime. `on_done` is called exactly once, from a runtime
+/// worker thread, with either a null error and a new handle owned by the callback, or an error
+/// and a null handle.
+///
+/// # Safety
+/// `provider` must be a valid, non-null provider handle that stays valid until `on_done` is called
+pub(crate) unsafe fn spawn_connect<S, H, F, Fut>(
+    provider: *mut provider::IdeviceProviderHandle,
+    connect: F,
+    wrap: fn(S) -> H,
+    on_done: extern "C" fn(error: *mut IdeviceFfiError, client: *mut H, context: *mut c_void),
+    context: *mut c_void,
+) where
+    F: FnOnce(&'static dyn idevice::provider::IdeviceProvider) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = Result<S, IdeviceError>> + Send + 'static,
+    S: 'static,
+    H: 'static,
+{
+    let provider_ref: &'static dyn idevice::provider::IdeviceProvider = unsafe { &*(*provider).0 };
+    let context = CallbackContext(context);
+
+    LOCAL_RUNTIME.spawn(async move {
+        let context = context;
+        match connect(provider_ref).await {
+            Ok(c) => on_done(null_mut(), Box::into_raw(Box::new(wrap(c))), context.0),
+            Err(e) => on_done(ffi_err!(e), null_mut(), context.0),
+        }
+    });
+}
+
 pub const LOCKDOWN_PORT: u16 = 62078;
 
 #[repr(C)]
@@ -297,6 +373,29 @@ pub unsafe extern "C" fn idevice_get_type(
     }
 }
 
+/// Checks whether the connection is still open, without sending anything
+///
+/// A connection lost without being closed by the device is still reported open. Use a
+/// request such as `lockdownd_ping` to catch those.
+///
+/// # Arguments
+/// * [`idevice`] - The Idevice handle
+///
+/// # Returns
+/// Whether the connection is open. False for a null handle.
+///
+/// # Safety
+/// `idevice` must be a valid pointer to an Idevice handle or NULL
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_is_alive(idevice: *mut IdeviceHandle) -> bool {
+    if idevice.is_null() {
+        return false;
+    }
+
+    let dev = unsafe { &mut (*idevice).0 };
+    run_sync_local(async { dev.is_alive() })
+}
+
 /// Performs RSD checkin
 ///
 /// # Arguments
@@ -362,8 +461,103 @@ pub unsafe extern "C" fn idevice_start_session(
     }
 }
 
+/// Closes an Idevice connection gracefully
+///
+/// Pending writes are flushed and the socket is shut down, so the device sees the connection
+/// end rather than reset. A peer that stalls the shutdown for more than two seconds fails it
+/// with a timeout. The handle must still be freed, and any further use of it fails.
+///
+/// # Arguments
+/// * [`idevice`] - The Idevice handle to close
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `idevice` must be a valid pointer to an Idevice handle that was allocated by this library
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_shutdown(idevice: *mut IdeviceHandle) -> *mut IdeviceFfiError {
+    if idevice.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(bounded_shutdown(unsafe { &mut (*idevice).0 }.shutdown()));
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Closes a socket gracefully and frees its handle
+///
+/// For sockets that won't be passed on to `idevice_new` or a service constructor.
+///
+/// # Arguments
+/// * [`socket`] - The socket handle to close
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success. The handle is freed either way.
+///
+/// # Safety
+/// `socket` must be a valid pointer to a socket handle that was allocated by this library. It
+/// must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_socket_shutdown(
+    socket: *mut IdeviceSocketHandle,
+) -> *mut IdeviceFfiError {
+    if socket.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let mut socket = unsafe { Box::from_raw(socket) }.0;
+    let res = run_sync_local(bounded_shutdown(shutdown_socket(&mut socket)));
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Flushes and shuts down a socket before it's dropped
+async fn shutdown_socket(socket: &mut IdeviceSocket) -> std::io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    socket.flush().await?;
+    socket.shutdown().await
+}
+
+/// How long a graceful shutdown may take before the connection is dropped anyway
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Runs a graceful shutdown, giving up with `IdeviceError::Timeout` if the peer stalls it
+async fn bounded_shutdown<E: Into<IdeviceError>>(
+    fut: impl std::future::Future<Output = Result<(), E>>,
+) -> Result<(), IdeviceError> {
+    match tokio::time::timeout(SHUTDOWN_TIMEOUT, fut).await {
+        Ok(res) => res.map_err(Into::into),
+        Err(_) => Err(IdeviceError::Timeout),
+    }
+}
+
+/// Runs a graceful shutdown for a free function
+///
+/// Free functions may be called on a runtime thread, such as from a callback, where blocking
+/// on the runtime would panic. The handle is dropped without shutting down there instead.
+pub(crate) fn shutdown_on_free<E: Into<IdeviceError>>(
+    fut: impl std::future::Future<Output = Result<(), E>>,
+) {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        tracing::debug!("Freed on a runtime thread, dropping without shutting down");
+        return;
+    }
+    if let Err(e) = run_sync_local(bounded_shutdown(fut)) {
+        tracing::debug!("Failed to shut down connection: {e:?}");
+    }
+}
+
 /// Frees an Idevice handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`idevice`] - The Idevice handle to free
 ///
@@ -373,18 +567,24 @@ pub unsafe extern "C" fn idevice_start_session(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn idevice_free(idevice: *mut IdeviceHandle) {
     if !idevice.is_null() {
-        let _ = unsafe { Box::from_raw(idevice) };
+        let mut idevice = unsafe { Box::from_raw(idevice) };
+        shutdown_on_free(idevice.0.shutdown());
     }
 }
 
 /// Frees a stream handle
 ///
+/// The stream is flushed and shut down before it's dropped.
+///
 /// # Safety
 /// Pass a valid handle allocated by this library
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn idevice_stream_free(stream_handle: *mut ReadWriteOpaque) {
     if !stream_handle.is_null() {
-        let _ = unsafe { Box::from_raw(stream_handle) };
+        let stream = unsafe { Box::from_raw(stream_handle) };
+        if let Some(mut inner) = stream.inner {
+            shutdown_on_free(shutdown_socket(&mut inner));
+        }
     }
 }
 
@@ -447,3 +647,23 @@ pub unsafe extern "C" fn idevice_outer_slice_free(slice: *mut c_void, len: usize
         let _ = unsafe { Vec::from_raw_parts(slice, len, len) };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use idevice::IdeviceError;
+
+    use super::*;
+
+    #[test]
+    fn runtime_configure_rejected_after_first_use() {
+        run_sync_local(async {});
+
+        let err = idevice_runtime_configure(4);
+        assert!(!err.is_null());
+        assert_eq!(
+            unsafe { (*err).code },
+            IdeviceError::FfiRuntimeAlreadyInitialized.code()
+        );
+        unsafe { idevice_error_free(err) };
+    }
+}
@@ -1,13 +1,16 @@
 // Jackson Coxson
 
-use std::ptr::null_mut;
+use std::{ffi::c_void, ptr::null_mut};
 
-use idevice::{IdeviceError, IdeviceService, lockdown::LockdownClient, provider::IdeviceProvider};
+use idevice::{
+    IdeviceError, IdeviceService, lockdown::LockdownClient, pairing_file::PairingFile,
+    provider::IdeviceProvider,
+};
 use plist_ffi::plist_t;
 
 use crate::{
     IdeviceFfiError, IdeviceHandle, IdevicePairingFile, ffi_err, provider::IdeviceProviderHandle,
-    run_sync_local,
+    run_sync_local, spawn_connect,
 };
 
 pub struct LockdowndClientHandle(pub LockdownClient);
@@ -52,6 +55,52 @@ pub unsafe extern "C" fn lockdownd_connect(
     }
 }
 
+/// Connects to the lockdownd service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lockdownd_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut LockdowndClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            LockdownClient::connect,
+            LockdowndClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Creates a new LockdowndClient from an existing Idevice connection
 ///
 /// # Arguments
@@ -85,6 +134,7 @@ pub unsafe extern "C" fn lockdownd_new(
 /// # Arguments
 /// * `client` - A valid LockdowndClient handle
 /// * `pairing_file` - An IdevicePairingFile alocated by this library
+/// * `ssl_enabled` - On success, set to whether the device enabled SSL for the session, or NULL
 ///
 /// # Returns
 /// An IdeviceFfiError on error, null on success
@@ -92,18 +142,58 @@ pub unsafe extern "C" fn lockdownd_new(
 /// # Safety
 /// `client` must be a valid pointer to a handle allocated by this library
 /// `pairing_file` must be a valid plist_t containing a pairing file
+/// `ssl_enabled` must be a valid pointer or NULL
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn lockdownd_start_session(
     client: *mut LockdowndClientHandle,
     pairing_file: *mut IdevicePairingFile,
+    ssl_enabled: *mut bool,
 ) -> *mut IdeviceFfiError {
-    let res: Result<(), IdeviceError> = run_sync_local(async move {
+    if client.is_null() || pairing_file.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res: Result<bool, IdeviceError> = run_sync_local(async move {
         let client_ref = unsafe { &mut (*client).0 };
         let pairing_file_ref = unsafe { &(*pairing_file).0 };
 
         client_ref.start_session(pairing_file_ref).await
     });
 
+    match res {
+        Ok(ssl) => {
+            if !ssl_enabled.is_null() {
+                unsafe { *ssl_enabled = ssl };
+            }
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Stops the session started with `lockdownd_start_session`
+///
+/// # Arguments
+/// * `client` - A valid LockdowndClient handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lockdownd_stop_session(
+    client: *mut LockdowndClientHandle,
+) -> *mut IdeviceFfiError {
+    if client.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.stop_session().await
+    });
+
     match res {
         Ok(_) => null_mut(),
         Err(e) => ffi_err!(e),
@@ -221,8 +311,249 @@ pub unsafe extern "C" fn lockdownd_get_value(
     }
 }
 
+/// Checks that lockdown still answers, with the cheapest request it supports
+///
+/// # Arguments
+/// * `client` - A valid LockdowndClient handle
+///
+/// # Returns
+/// An IdeviceFfiError if the connection is no longer usable, null otherwise
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lockdownd_ping(
+    client: *mut LockdowndClientHandle,
+) -> *mut IdeviceFfiError {
+    if client.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async move { unsafe { &mut (*client).0 }.ping().await });
+
+    match res {
+        Ok(()) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Checks whether the device is currently locked with a passcode
+///
+/// Services that touch user data refuse to start while the device is locked, failing with the
+/// code of `PasswordProtected`.
+///
+/// # Arguments
+/// * `client` - A valid LockdowndClient handle
+/// * `locked` - On success, will be set to whether the device is locked
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `locked` must be a valid, non-null pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lockdownd_is_passcode_locked(
+    client: *mut LockdowndClientHandle,
+    locked: *mut bool,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || locked.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.is_passcode_locked().await
+    });
+
+    match res {
+        Ok(l) => {
+            unsafe { *locked = l };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Generates a pairing record and asks the device to trust it
+///
+/// This blocks until the user accepts or denies the trust dialog on the device.
+///
+/// # Arguments
+/// * `client` - A valid LockdowndClient handle
+/// * `host_id` - The host ID, in the form of a UUID (null-terminated string)
+/// * `system_buid` - The SystemBUID from usbmuxd (null-terminated string)
+/// * `data` - On success, will point to the serialized pairing record plist
+/// * `length` - On success, will be set to the length of the pairing record
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `host_id` and `system_buid` must be valid null-terminated strings
+/// `data` and `length` must be valid pointers. The data must be freed with `idevice_data_free`
+#[cfg(feature = "pair")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lockdownd_pair(
+    client: *mut LockdowndClientHandle,
+    host_id: *const libc::c_char,
+    system_buid: *const libc::c_char,
+    data: *mut *mut u8,
+    length: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null()
+        || host_id.is_null()
+        || system_buid.is_null()
+        || data.is_null()
+        || length.is_null()
+    {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let host_id = match unsafe { std::ffi::CStr::from_ptr(host_id) }.to_str() {
+        Ok(v) => v,
+        Err(_) => return ffi_err!(IdeviceError::InvalidCString),
+    };
+    let system_buid = match unsafe { std::ffi::CStr::from_ptr(system_buid) }.to_str() {
+        Ok(v) => v,
+        Err(_) => return ffi_err!(IdeviceError::InvalidCString),
+    };
+
+    let res: Result<Vec<u8>, IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.pair(host_id, system_buid).await?.serialize()
+    });
+
+    match res {
+        Ok(r) => {
+            let mut r = r.into_boxed_slice();
+            unsafe {
+                *data = r.as_mut_ptr();
+                *length = r.len();
+            }
+            std::mem::forget(r);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Checks that the device still trusts a pairing record
+///
+/// # Arguments
+/// * `client` - A valid LockdowndClient handle
+/// * `data` - The serialized pairing record plist
+/// * `length` - The length of the pairing record
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `data` must be a valid pointer to `length` bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lockdownd_validate_pair(
+    client: *mut LockdowndClientHandle,
+    data: *const u8,
+    length: libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || data.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let bytes = unsafe { std::slice::from_raw_parts(data, length) };
+    let pairing_file = match PairingFile::from_bytes(bytes) {
+        Ok(p) => p,
+        Err(e) => return ffi_err!(e),
+    };
+
+    let res = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.validate_pair(&pairing_file).await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Removes a pairing record from the device
+///
+/// # Arguments
+/// * `client` - A valid LockdowndClient handle
+/// * `data` - The serialized pairing record plist
+/// * `length` - The length of the pairing record
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `data` must be a valid pointer to `length` bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lockdownd_unpair(
+    client: *mut LockdowndClientHandle,
+    data: *const u8,
+    length: libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || data.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let bytes = unsafe { std::slice::from_raw_parts(data, length) };
+    let pairing_file = match PairingFile::from_bytes(bytes) {
+        Ok(p) => p,
+        Err(e) => return ffi_err!(e),
+    };
+
+    let res = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.unpair(&pairing_file).await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Reboots the device into recovery mode
+///
+/// The device drops the connection afterwards, so the client should be freed.
+///
+/// # Arguments
+/// * `client` - A valid LockdowndClient handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lockdownd_enter_recovery(
+    client: *mut LockdowndClientHandle,
+) -> *mut IdeviceFfiError {
+    if client.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.enter_recovery().await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Frees a LockdowndClient handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -233,6 +564,7 @@ pub unsafe extern "C" fn lockdownd_get_value(
 pub unsafe extern "C" fn lockdownd_client_free(handle: *mut LockdowndClientHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing lockdownd_client");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
     }
 }
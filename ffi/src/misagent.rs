@@ -2,11 +2,13 @@
 //!
 //! Provides C-compatible bindings for interacting with the misagent service on iOS devices.
 
-use std::ptr::null_mut;
+use std::{ffi::c_void, ptr::null_mut};
 
 use idevice::{IdeviceError, IdeviceService, misagent::MisagentClient, provider::IdeviceProvider};
 
-use crate::{IdeviceFfiError, ffi_err, provider::IdeviceProviderHandle, run_sync_local};
+use crate::{
+    IdeviceFfiError, ffi_err, provider::IdeviceProviderHandle, run_sync_local, spawn_connect,
+};
 
 pub struct MisagentClientHandle(pub MisagentClient);
 
@@ -47,6 +49,52 @@ pub unsafe extern "C" fn misagent_connect(
     }
 }
 
+/// Connects to the misagent service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn misagent_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut MisagentClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            MisagentClient::connect,
+            MisagentClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Installs a provisioning profile on the device
 ///
 /// # Arguments
@@ -209,6 +257,8 @@ pub unsafe extern "C" fn misagent_free_profiles(
 
 /// Frees a misagent client handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -219,6 +269,7 @@ pub unsafe extern "C" fn misagent_free_profiles(
 pub unsafe extern "C" fn misagent_client_free(handle: *mut MisagentClientHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing misagent_client");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
     }
 }
@@ -1,15 +1,21 @@
 // Jackson Coxson
 
-use std::{ffi::c_void, ptr::null_mut};
+use std::{
+    ffi::{CString, c_char, c_void},
+    ptr::null_mut,
+};
 
 use idevice::{
-    IdeviceError, IdeviceService, mobile_image_mounter::ImageMounter, provider::IdeviceProvider,
+    IdeviceError, IdeviceService,
+    mobile_image_mounter::{ImageMounter, MountedImage},
+    provider::IdeviceProvider,
 };
 use plist::Value;
 use plist_ffi::{PlistWrapper, plist_t};
 
 use crate::{
     IdeviceFfiError, IdeviceHandle, ffi_err, provider::IdeviceProviderHandle, run_sync_local,
+    spawn_connect,
 };
 
 pub struct ImageMounterHandle(pub ImageMounter);
@@ -54,6 +60,52 @@ pub unsafe extern "C" fn image_mounter_connect(
     }
 }
 
+/// Connects to the image mounter service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn image_mounter_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut ImageMounterHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            ImageMounter::connect,
+            ImageMounterHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Creates a new ImageMounter client from an existing Idevice connection
 ///
 /// # Arguments
@@ -83,6 +135,8 @@ pub unsafe extern "C" fn image_mounter_new(
 
 /// Frees an ImageMounter handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -93,7 +147,8 @@ pub unsafe extern "C" fn image_mounter_new(
 pub unsafe extern "C" fn image_mounter_free(handle: *mut ImageMounterHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing image_mounter_client");
-        let _ = unsafe { Box::from_raw(handle) };
+        let handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.into_inner().shutdown());
     }
 }
 
@@ -141,6 +196,110 @@ pub unsafe extern "C" fn image_mounter_copy_devices(
     }
 }
 
+/// An image currently mounted on the device
+#[repr(C)]
+pub struct ImageMounterMountedImage {
+    pub image_type: *mut c_char,
+    pub signature: *mut u8,
+    pub signature_len: libc::size_t,
+    pub mount_path: *mut c_char,
+}
+
+impl From<MountedImage> for ImageMounterMountedImage {
+    fn from(value: MountedImage) -> Self {
+        let signature = Box::leak(value.signature.into_boxed_slice());
+        Self {
+            image_type: CString::new(value.image_type)
+                .unwrap_or_default()
+                .into_raw(),
+            signature_len: signature.len(),
+            signature: signature.as_mut_ptr(),
+            mount_path: CString::new(value.mount_path)
+                .unwrap_or_default()
+                .into_raw(),
+        }
+    }
+}
+
+/// Gets the images currently mounted on the device
+///
+/// # Arguments
+/// * [`client`] - A valid ImageMounter handle
+/// * [`images`] - Will be set to point to an array of mounted images on success
+/// * [`images_len`] - Will be set to the number of mounted images
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `images` and `images_len` must be valid, non-null pointers
+/// The images must be freed with `image_mounter_mounted_images_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn image_mounter_query_mounted(
+    client: *mut ImageMounterHandle,
+    images: *mut *mut ImageMounterMountedImage,
+    images_len: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || images.is_null() || images_len.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res: Result<Vec<MountedImage>, IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.query_mounted().await
+    });
+
+    match res {
+        Ok(mounted) => {
+            let mounted = mounted
+                .into_iter()
+                .map(ImageMounterMountedImage::from)
+                .collect::<Vec<_>>()
+                .into_boxed_slice();
+            let len = mounted.len();
+            let ptr = Box::leak(mounted).as_mut_ptr();
+
+            unsafe {
+                *images = ptr;
+                *images_len = len;
+            }
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Frees an array of mounted images returned by `image_mounter_query_mounted`
+///
+/// # Arguments
+/// * [`images`] - The array to free
+/// * [`images_len`] - The number of images in the array
+///
+/// # Safety
+/// `images` must be a pointer returned by `image_mounter_query_mounted` with its matching length,
+/// or NULL (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn image_mounter_mounted_images_free(
+    images: *mut ImageMounterMountedImage,
+    images_len: libc::size_t,
+) {
+    if images.is_null() {
+        return;
+    }
+    let images = unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(images, images_len)) };
+    for image in images.iter() {
+        unsafe {
+            let _ = CString::from_raw(image.image_type);
+            let _ = CString::from_raw(image.mount_path);
+            let _ = Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                image.signature,
+                image.signature_len,
+            ));
+        }
+    }
+}
+
 /// Looks up an image and returns its signature
 ///
 /// # Arguments
@@ -192,6 +351,50 @@ pub unsafe extern "C" fn image_mounter_lookup_image(
     }
 }
 
+/// Checks whether an image of a type is mounted
+///
+/// # Arguments
+/// * [`client`] - A valid ImageMounter handle
+/// * [`image_type`] - The type of image to check
+/// * [`mounted`] - Will be set to whether an image of that type is mounted
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `image_type` must be a valid null-terminated C string
+/// `mounted` must be a valid pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn image_mounter_is_mounted(
+    client: *mut ImageMounterHandle,
+    image_type: *const libc::c_char,
+    mounted: *mut bool,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || image_type.is_null() || mounted.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let image_type_cstr = unsafe { std::ffi::CStr::from_ptr(image_type) };
+    let image_type = match image_type_cstr.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+
+    let res = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.is_mounted(image_type).await
+    });
+
+    match res {
+        Ok(m) => {
+            unsafe { *mounted = m };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Uploads an image to the device
 ///
 /// # Arguments
@@ -430,6 +633,60 @@ pub unsafe extern "C" fn image_mounter_mount_developer(
     }
 }
 
+/// Uploads and mounts an image of any type that is mounted with only a signature
+///
+/// # Arguments
+/// * [`client`] - A valid ImageMounter handle
+/// * [`image`] - Pointer to the image data
+/// * [`image_len`] - Length of the image data
+/// * [`signature`] - Pointer to the signature data
+/// * [`signature_len`] - Length of the signature data
+/// * [`image_type`] - The type of image, such as `Developer`
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// All pointers must be valid and non-null
+/// `image_type` must be a valid null-terminated C string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn image_mounter_mount(
+    client: *mut ImageMounterHandle,
+    image: *const u8,
+    image_len: libc::size_t,
+    signature: *const u8,
+    signature_len: libc::size_t,
+    image_type: *const libc::c_char,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || image.is_null() || signature.is_null() || image_type.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let image_type_cstr = unsafe { std::ffi::CStr::from_ptr(image_type) };
+    let image_type = match image_type_cstr.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+
+    let image_slice = unsafe { std::slice::from_raw_parts(image, image_len) };
+    let signature_slice = unsafe { std::slice::from_raw_parts(signature, signature_len) };
+
+    let res: Result<(), IdeviceError> = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref
+            .upload_image(image_type, image_slice, signature_slice.to_vec())
+            .await?;
+        client_ref
+            .mount_image(image_type, signature_slice.to_vec(), None, None)
+            .await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Queries the personalization manifest from the device
 ///
 /// # Arguments
@@ -0,0 +1,286 @@
+// Jackson Coxson
+
+use std::{
+    ffi::{CStr, CString, c_char, c_void},
+    ptr::null_mut,
+};
+
+use idevice::{
+    IdeviceError, IdeviceService, notification_proxy::NotificationProxyClient,
+    provider::IdeviceProvider,
+};
+use tokio::{sync::oneshot, task::JoinHandle};
+
+use crate::{
+    IdeviceFfiError, LOCAL_RUNTIME, ffi_err, provider::IdeviceProviderHandle, run_sync_local,
+    spawn_connect,
+};
+
+pub struct NotificationProxyClientHandle(pub NotificationProxyClient);
+
+/// A running notification observer, created by ``notification_proxy_observe``
+pub struct NotificationProxyObserverHandle {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+struct NotificationProxyContext(*mut c_void);
+unsafe impl Send for NotificationProxyContext {}
+
+/// Automatically creates and connects to the notification proxy, returning a client handle
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`client`] - On success, will be set to point to a newly allocated NotificationProxyClient handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library. It is borrowed,
+/// and remains owned by the caller whether or not this function succeeds.
+/// `client` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn notification_proxy_connect(
+    provider: *mut IdeviceProviderHandle,
+    client: *mut *mut NotificationProxyClientHandle,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() || client.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res: Result<NotificationProxyClient, IdeviceError> = run_sync_local(async move {
+        let provider_ref: &dyn IdeviceProvider = unsafe { &*(*provider).0 };
+        NotificationProxyClient::connect(provider_ref).await
+    });
+
+    match res {
+        Ok(r) => {
+            let boxed = Box::new(NotificationProxyClientHandle(r));
+            unsafe { *client = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Connects to the notification proxy service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn notification_proxy_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut NotificationProxyClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            NotificationProxyClient::connect,
+            NotificationProxyClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
+/// Posts a notification on the device
+///
+/// # Arguments
+/// * [`client`] - A valid NotificationProxyClient handle
+/// * [`name`] - The notification to post
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `name` must be a valid C string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn notification_proxy_post(
+    client: *mut NotificationProxyClientHandle,
+    name: *const c_char,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || name.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+    let name = match unsafe { CStr::from_ptr(name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+
+    let res = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.post(name).await
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Observes notifications, invoking a callback for each one
+///
+/// The callback is called from a runtime worker thread with the notification's name, which
+/// is only valid for the duration of the call. Observing stops on its own if the device
+/// closes the proxy.
+///
+/// # Arguments
+/// * [`client`] - A valid NotificationProxyClient handle, which is consumed by this function
+/// * [`names`] - Array of notification names to observe
+/// * [`names_count`] - Number of names
+/// * [`on_notification`] - Called for every observed notification
+/// * [`context`] - Passed back to `on_notification` unchanged
+/// * [`handle`] - On success, will be set to the observer handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library. It is consumed and
+/// must not be used again, even if this function returns an error.
+/// `names` must be a valid pointer to `names_count` C strings
+/// `context` must remain valid until the observer is stopped with
+/// ``notification_proxy_stop_observing``.
+/// `handle` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn notification_proxy_observe(
+    client: *mut NotificationProxyClientHandle,
+    names: *const *const c_char,
+    names_count: usize,
+    on_notification: extern "C" fn(name: *const c_char, context: *mut c_void),
+    context: *mut c_void,
+    handle: *mut *mut NotificationProxyObserverHandle,
+) -> *mut IdeviceFfiError {
+    if client.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+    let mut client = unsafe { Box::from_raw(client) }.0;
+    if handle.is_null() || (names.is_null() && names_count > 0) {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let mut names_vec = Vec::with_capacity(names_count);
+    if names_count > 0 {
+        for &name in unsafe { std::slice::from_raw_parts(names, names_count) } {
+            if name.is_null() {
+                return ffi_err!(IdeviceError::FfiInvalidArg);
+            }
+            match unsafe { CStr::from_ptr(name) }.to_str() {
+                Ok(s) => names_vec.push(s.to_string()),
+                Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+            }
+        }
+    }
+
+    let names_ref: Vec<&str> = names_vec.iter().map(String::as_str).collect();
+    if let Err(e) = run_sync_local(async { client.observe(&names_ref).await.map(|_| ()) }) {
+        return ffi_err!(e);
+    }
+
+    let context = NotificationProxyContext(context);
+    let (stop, mut stop_rx) = oneshot::channel();
+    let task = LOCAL_RUNTIME.spawn(async move {
+        let context = context;
+        loop {
+            let name = tokio::select! {
+                name = client.next_notification() => name,
+                _ = &mut stop_rx => break,
+            };
+            match name {
+                Ok(Some(name)) => {
+                    if let Ok(name) = CString::new(name) {
+                        on_notification(name.as_ptr(), context.0);
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::error!("Notification observer ended: {e:?}");
+                    break;
+                }
+            }
+        }
+        if let Err(e) = crate::bounded_shutdown(client.idevice.shutdown()).await {
+            tracing::debug!("Failed to shut down notification observer: {e:?}");
+        }
+    });
+
+    let boxed = Box::new(NotificationProxyObserverHandle { stop, task });
+    unsafe { *handle = Box::into_raw(boxed) };
+    null_mut()
+}
+
+/// Stops observing notifications, waits for the observer to exit, and frees its handle
+///
+/// Once this returns, the observer's callback will not be called again. When called from
+/// the observer's own callback, or any other runtime thread, it doesn't wait: the observer
+/// exits once the callback returns.
+///
+/// # Safety
+/// `handle` must be a valid pointer to a handle allocated by this library, or NULL
+/// (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn notification_proxy_stop_observing(
+    handle: *mut NotificationProxyObserverHandle,
+) {
+    if handle.is_null() {
+        return;
+    }
+    let handle = unsafe { Box::from_raw(handle) };
+    let _ = handle.stop.send(());
+    // Blocking on the runtime from one of its threads would panic
+    if tokio::runtime::Handle::try_current().is_ok() {
+        tracing::debug!("Stopped observing on a runtime thread, not waiting for the observer");
+        return;
+    }
+    let _ = run_sync_local(handle.task);
+}
+
+/// Frees a handle
+///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
+/// # Arguments
+/// * [`handle`] - The handle to free
+///
+/// # Safety
+/// `handle` must be a valid pointer to the handle that was allocated by this library,
+/// or NULL (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn notification_proxy_client_free(
+    handle: *mut NotificationProxyClientHandle,
+) {
+    if !handle.is_null() {
+        tracing::debug!("Freeing notification_proxy_client");
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
+    }
+}
@@ -1,26 +1,36 @@
 use std::os::raw::c_char;
-use std::{ffi::CString, ptr::null_mut};
+use std::{
+    ffi::{CString, c_void},
+    ptr::null_mut,
+};
 
 use idevice::{
     IdeviceError, IdeviceService, os_trace_relay::OsTraceRelayClient, provider::IdeviceProvider,
 };
 
-use crate::run_sync_local;
 use crate::{IdeviceFfiError, ffi_err, provider::IdeviceProviderHandle};
+use crate::{run_sync_local, spawn_connect};
 
 pub struct OsTraceRelayClientHandle(pub OsTraceRelayClient);
-pub struct OsTraceRelayReceiverHandle(pub idevice::os_trace_relay::OsTraceRelayReceiver);
+/// The token is kept outside the receiver so it can be reached while another thread reads
+pub struct OsTraceRelayReceiverHandle(
+    pub idevice::os_trace_relay::OsTraceRelayReceiver,
+    idevice::CancellationToken,
+);
 
 #[repr(C)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OsTraceLog {
     pub pid: u32,
+    /// Seconds since the Unix epoch, truncated
     pub timestamp: i64,
     pub level: u8,
     pub image_name: *const c_char,
     pub filename: *const c_char,
     pub message: *const c_char,
     pub label: *const SyslogLabel,
+    /// Nanoseconds since the Unix epoch, keeping the sub-second precision of the device
+    pub timestamp_ns: i64,
 }
 
 #[repr(C)]
@@ -30,6 +40,41 @@ pub struct SyslogLabel {
     pub category: *const c_char,
 }
 
+impl From<idevice::os_trace_relay::OsTraceLog> for OsTraceLog {
+    fn from(r: idevice::os_trace_relay::OsTraceLog) -> Self {
+        Self {
+            pid: r.pid,
+            timestamp: r.timestamp.and_utc().timestamp(),
+            level: r.level as u8,
+            image_name: log_c_string(r.image_name),
+            filename: log_c_string(r.filename),
+            message: log_c_string(r.message),
+            label: if let Some(label) = r.label {
+                Box::into_raw(Box::new(SyslogLabel {
+                    subsystem: log_c_string(label.subsystem),
+                    category: log_c_string(label.category),
+                }))
+            } else {
+                std::ptr::null()
+            },
+            timestamp_ns: r
+                .timestamp
+                .and_utc()
+                .timestamp_nanos_opt()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Converts a log field to a C string.
+/// Device payloads aren't guaranteed to be NUL-free, so interior NULs are replaced with spaces.
+fn log_c_string(s: String) -> *const c_char {
+    let s = s.replace('\0', " ");
+    CString::new(s)
+        .expect("interior NULs were replaced")
+        .into_raw()
+}
+
 /// Connects to the relay with the given provider
 ///
 /// # Arguments
@@ -41,12 +86,14 @@ pub struct SyslogLabel {
 ///
 /// # Safety
 /// None of the arguments can be null. Provider must be allocated by this library.
+/// The provider is only borrowed, and remains owned by the caller whether or not the connection
+/// succeeds.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn os_trace_relay_connect(
     provider: *mut IdeviceProviderHandle,
     client: *mut *mut OsTraceRelayClientHandle,
 ) -> *mut IdeviceFfiError {
-    if provider.is_null() {
+    if provider.is_null() || client.is_null() {
         tracing::error!("Null pointer provided");
         return ffi_err!(IdeviceError::FfiInvalidArg);
     }
@@ -62,15 +109,60 @@ pub unsafe extern "C" fn os_trace_relay_connect(
             unsafe { *client = Box::into_raw(boxed) };
             null_mut()
         }
-        Err(e) => {
-            let _ = unsafe { Box::from_raw(provider) };
-            ffi_err!(e)
-        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Connects to the relay without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - A provider created by this library
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn os_trace_relay_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut OsTraceRelayClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
     }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            OsTraceRelayClient::connect,
+            OsTraceRelayClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
 }
 
 /// Frees the relay client
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The relay client handle
 ///
@@ -80,7 +172,8 @@ pub unsafe extern "C" fn os_trace_relay_connect(
 pub unsafe extern "C" fn os_trace_relay_free(handle: *mut OsTraceRelayClientHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing os trace relay client");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
     }
 }
 
@@ -119,7 +212,8 @@ pub unsafe extern "C" fn os_trace_relay_start_trace(
 
     match res {
         Ok(relay) => {
-            let boxed = Box::new(OsTraceRelayReceiverHandle(relay));
+            let token = relay.cancellation_token();
+            let boxed = Box::new(OsTraceRelayReceiverHandle(relay, token));
             unsafe { *receiver = Box::into_raw(boxed) };
 
             null_mut()
@@ -130,6 +224,8 @@ pub unsafe extern "C" fn os_trace_relay_start_trace(
 
 /// Frees the receiver handle
 ///
+/// The relay is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The relay receiver client handle
 ///
@@ -138,8 +234,28 @@ pub unsafe extern "C" fn os_trace_relay_start_trace(
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn os_trace_relay_receiver_free(handle: *mut OsTraceRelayReceiverHandle) {
     if !handle.is_null() {
-        tracing::debug!("Freeing syslog relay client");
-        let _ = unsafe { Box::from_raw(handle) };
+        tracing::debug!("Freeing os trace relay receiver");
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.shutdown());
+    }
+}
+
+/// Cancels the receiver, aborting a pending `os_trace_relay_next` and closing the relay
+///
+/// Later reads from the receiver fail. The handle must still be freed with
+/// `os_trace_relay_receiver_free`.
+///
+/// # Arguments
+/// * [`handle`] - The relay receiver client handle
+///
+/// # Safety
+/// The handle must be allocated by this library.
+/// This may be called from any thread, including while another thread is reading from the
+/// receiver, but not after the handle is freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn os_trace_relay_cancel(handle: *mut OsTraceRelayReceiverHandle) {
+    if !handle.is_null() {
+        unsafe { &(*handle).1 }.cancel();
     }
 }
 
@@ -191,26 +307,11 @@ pub unsafe extern "C" fn os_trace_relay_next(
         return ffi_err!(IdeviceError::FfiInvalidArg);
     }
 
-    let res = run_sync_local(async { unsafe { &mut *client }.0.next().await });
+    let res = run_sync_local(async { unsafe { &mut (*client).0 }.next().await });
 
     match res {
         Ok(r) => {
-            let log_entry = Box::new(OsTraceLog {
-                pid: r.pid,
-                timestamp: r.timestamp.and_utc().timestamp(),
-                level: r.level as u8,
-                image_name: CString::new(r.image_name).unwrap().into_raw(),
-                filename: CString::new(r.filename).unwrap().into_raw(),
-                message: CString::new(r.message).unwrap().into_raw(),
-                label: if let Some(label) = r.label {
-                    Box::into_raw(Box::new(SyslogLabel {
-                        subsystem: CString::new(label.subsystem).unwrap().into_raw(),
-                        category: CString::new(label.category).unwrap().into_raw(),
-                    }))
-                } else {
-                    std::ptr::null()
-                },
-            });
+            let log_entry = Box::new(OsTraceLog::from(r));
 
             unsafe { *log = Box::into_raw(log_entry) };
             null_mut()
@@ -219,6 +320,42 @@ pub unsafe extern "C" fn os_trace_relay_next(
     }
 }
 
+/// Gets the next log from the relay if one is already buffered, without waiting for the device
+///
+/// # Arguments
+/// * [`client`] - The relay receiver client handle
+/// * [`log`] - A pointer to allocate the new log. Set to null if no log is ready.
+///
+/// # Returns
+/// 0 for success, an *mut IdeviceFfiError otherwise
+///
+/// # Safety
+/// The handle must be allocated by this library.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn os_trace_relay_try_next(
+    client: *mut OsTraceRelayReceiverHandle,
+    log: *mut *mut OsTraceLog,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || log.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async { unsafe { &mut (*client).0 }.try_next().await });
+
+    match res {
+        Ok(Some(r)) => {
+            unsafe { *log = Box::into_raw(Box::new(OsTraceLog::from(r))) };
+            null_mut()
+        }
+        Ok(None) => {
+            unsafe { *log = null_mut() };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Frees a log received from the relay
 ///
 /// # Arguments
@@ -233,30 +370,196 @@ pub unsafe extern "C" fn os_trace_relay_next(
 pub unsafe extern "C" fn os_trace_relay_free_log(log: *mut OsTraceLog) {
     if !log.is_null() {
         unsafe {
-            if !(*log).image_name.is_null() {
-                let _ = CString::from_raw((*log).image_name as *mut c_char);
+            free_log_fields(&*log);
+            let _ = Box::from_raw(log);
+        }
+    }
+}
+
+/// Gets up to `max` logs from the relay in one call
+///
+/// Waits up to `timeout_ms` for the first log, then takes any further logs that are already
+/// buffered without waiting again.
+///
+/// # Arguments
+/// * [`client`] - The relay receiver client handle
+/// * [`logs`] - Will be set to point to an array of logs, or null if none arrived
+/// * [`max`] - The maximum number of logs to return
+/// * [`count`] - Will be set to the number of logs returned
+/// * [`timeout_ms`] - How long to wait for the first log, in milliseconds
+///
+/// # Returns
+/// 0 for success, an *mut IdeviceFfiError otherwise
+///
+/// # Safety
+/// The handle must be allocated by this library.
+/// `logs` and `count` must be valid, non-null pointers.
+/// The logs must be freed with `os_trace_relay_free_logs`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn os_trace_relay_next_batch(
+    client: *mut OsTraceRelayReceiverHandle,
+    logs: *mut *mut OsTraceLog,
+    max: libc::size_t,
+    count: *mut libc::size_t,
+    timeout_ms: u64,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || logs.is_null() || count.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async {
+        unsafe { &mut (*client).0 }
+            .next_batch(max, std::time::Duration::from_millis(timeout_ms))
+            .await
+    });
+
+    match res {
+        Ok(batch) => {
+            unsafe { *count = batch.len() };
+            if batch.is_empty() {
+                unsafe { *logs = null_mut() };
+                return null_mut();
             }
-            if !(*log).filename.is_null() {
-                let _ = CString::from_raw((*log).filename as *mut c_char);
+            let mut batch = batch
+                .into_iter()
+                .map(OsTraceLog::from)
+                .collect::<Vec<_>>()
+                .into_boxed_slice();
+            unsafe { *logs = batch.as_mut_ptr() };
+            std::mem::forget(batch);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Frees logs returned by `os_trace_relay_next_batch`
+///
+/// # Arguments
+/// * [`logs`] - The array of logs to free
+/// * [`count`] - The number of logs in the array
+///
+/// # Safety
+/// `logs` must be an array of `count` logs returned by `os_trace_relay_next_batch`, or null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn os_trace_relay_free_logs(logs: *mut OsTraceLog, count: libc::size_t) {
+    if logs.is_null() {
+        return;
+    }
+    let logs = unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(logs, count)) };
+    for log in logs.iter() {
+        unsafe { free_log_fields(log) };
+    }
+}
+
+/// Frees the strings and label owned by a log, but not the log itself
+///
+/// # Safety
+/// The log's pointers must have been allocated by this library and not freed yet.
+unsafe fn free_log_fields(log: &OsTraceLog) {
+    unsafe {
+        if !log.image_name.is_null() {
+            let _ = CString::from_raw(log.image_name as *mut c_char);
+        }
+        if !log.filename.is_null() {
+            let _ = CString::from_raw(log.filename as *mut c_char);
+        }
+        if !log.message.is_null() {
+            let _ = CString::from_raw(log.message as *mut c_char);
+        }
+        if !log.label.is_null() {
+            let label = &*log.label;
+
+            if !label.subsystem.is_null() {
+                let _ = CString::from_raw(label.subsystem as *mut c_char);
             }
-            if !(*log).message.is_null() {
-                let _ = CString::from_raw((*log).message as *mut c_char);
+
+            if !label.category.is_null() {
+                let _ = CString::from_raw(label.category as *mut c_char);
             }
-            if !(*log).label.is_null() {
-                let label = &*(*log).label;
 
-                if !label.subsystem.is_null() {
-                    let _ = CString::from_raw(label.subsystem as *mut c_char);
-                }
+            let _ = Box::from_raw(log.label as *mut SyslogLabel);
+        }
+    }
+}
 
-                if !label.category.is_null() {
-                    let _ = CString::from_raw(label.category as *mut c_char);
-                }
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
 
-                let _ = Box::from_raw((*log).label as *mut SyslogLabel);
-            }
+    use super::*;
 
-            let _ = Box::from_raw(log);
+    #[test]
+    fn failed_connect_leaves_provider_usable() {
+        let provider = crate::provider::tests::refusing_provider();
+        let mut client = null_mut();
+
+        for _ in 0..2 {
+            let err = unsafe { os_trace_relay_connect(provider, &mut client) };
+            assert!(!err.is_null());
+            assert!(client.is_null());
+            unsafe { crate::errors::idevice_error_free(err) };
+        }
+
+        unsafe { crate::provider::idevice_provider_free(provider) };
+    }
+
+    #[test]
+    fn log_with_nul_bytes_converts() {
+        let log = idevice::os_trace_relay::OsTraceLog {
+            pid: 1,
+            timestamp: chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc(),
+            level: idevice::os_trace_relay::LogLevel::Notice,
+            image_name: "/usr/libexec/back\0boardd".to_string(),
+            filename: "\0".to_string(),
+            message: "hello\0world".to_string(),
+            label: Some(idevice::os_trace_relay::SyslogLabel {
+                subsystem: "com.apple\0".to_string(),
+                category: "default".to_string(),
+            }),
+        };
+
+        let c_log = Box::into_raw(Box::new(OsTraceLog::from(log)));
+        unsafe {
+            assert_eq!(
+                CStr::from_ptr((*c_log).message).to_str().unwrap(),
+                "hello world"
+            );
+            assert_eq!(
+                CStr::from_ptr((*c_log).image_name).to_str().unwrap(),
+                "/usr/libexec/back boardd"
+            );
+            assert_eq!(CStr::from_ptr((*c_log).filename).to_str().unwrap(), " ");
+            assert_eq!(
+                CStr::from_ptr((*(*c_log).label).subsystem)
+                    .to_str()
+                    .unwrap(),
+                "com.apple "
+            );
+            os_trace_relay_free_log(c_log);
+        }
+    }
+
+    #[test]
+    fn timestamp_keeps_sub_second_precision() {
+        let log = idevice::os_trace_relay::OsTraceLog {
+            pid: 1,
+            timestamp: chrono::DateTime::from_timestamp(1, 500_000)
+                .unwrap()
+                .naive_utc(),
+            level: idevice::os_trace_relay::LogLevel::Info,
+            image_name: String::new(),
+            filename: String::new(),
+            message: String::new(),
+            label: None,
+        };
+
+        let c_log = Box::into_raw(Box::new(OsTraceLog::from(log)));
+        unsafe {
+            assert_eq!((*c_log).timestamp, 1);
+            assert_eq!((*c_log).timestamp_ns, 1_000_500_000);
+            os_trace_relay_free_log(c_log);
         }
     }
 }
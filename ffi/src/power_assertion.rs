@@ -0,0 +1,172 @@
+//! iOS Power Assertion Client Bindings
+//!
+//! Provides C-compatible bindings for keeping the device awake through the assertion_agent
+//! service.
+
+use std::{ffi::c_void, ptr::null_mut, time::Duration};
+
+use idevice::{IdeviceService, power_assertion::PowerAssertionClient, provider::IdeviceProvider};
+
+use crate::{
+    IdeviceFfiError, ffi_err, provider::IdeviceProviderHandle, run_sync_local, spawn_connect,
+};
+
+pub struct PowerAssertionClientHandle(pub PowerAssertionClient);
+
+/// Automatically creates and connects to the power assertion service, returning a client handle
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`client`] - On success, will be set to point to a newly allocated client handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library
+/// `client` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn power_assertion_connect(
+    provider: *mut IdeviceProviderHandle,
+    client: *mut *mut PowerAssertionClientHandle,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() || client.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async {
+        let provider_ref: &dyn IdeviceProvider = unsafe { &*(*provider).0 };
+        PowerAssertionClient::connect(provider_ref).await
+    });
+
+    match res {
+        Ok(r) => {
+            let boxed = Box::new(PowerAssertionClientHandle(r));
+            unsafe { *client = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Connects to the power assertion service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn power_assertion_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut PowerAssertionClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            PowerAssertionClient::connect,
+            PowerAssertionClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
+/// Keeps the device from idle sleeping until the client is freed
+///
+/// The device releases the assertion when the client's connection closes, so each client
+/// holds at most one assertion.
+///
+/// # Arguments
+/// * [`client`] - A valid power assertion client handle
+/// * [`name`] - Name of the assertion (UTF-8 null-terminated)
+/// * [`details`] - Description of why the assertion is held (UTF-8 null-terminated)
+/// * [`timeout_secs`] - How long the device keeps the assertion at most, or 0 for no limit
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `name` and `details` must be valid null-terminated C strings
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn power_assertion_create(
+    client: *mut PowerAssertionClientHandle,
+    name: *const libc::c_char,
+    details: *const libc::c_char,
+    timeout_secs: f64,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || name.is_null() || details.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let (name, details) = match (
+        unsafe { std::ffi::CStr::from_ptr(name) }.to_str(),
+        unsafe { std::ffi::CStr::from_ptr(details) }.to_str(),
+    ) {
+        (Ok(n), Ok(d)) => (n, d),
+        _ => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+    let timeout = match Duration::try_from_secs_f64(timeout_secs) {
+        Ok(t) if !t.is_zero() => Some(t),
+        Ok(_) => None,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+
+    let res = run_sync_local(async {
+        let client_ref = unsafe { &mut (*client).0 };
+        // Keep the connection open, freeing the client is what releases the assertion
+        client_ref
+            .create(name, details, timeout)
+            .await
+            .map(std::mem::forget)
+    });
+
+    match res {
+        Ok(_) => null_mut(),
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Frees a power assertion client handle, releasing its assertion
+///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
+/// # Arguments
+/// * [`handle`] - The handle to free
+///
+/// # Safety
+/// `handle` must be a valid pointer to the handle that was allocated by this library,
+/// or NULL (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn power_assertion_client_free(handle: *mut PowerAssertionClientHandle) {
+    if !handle.is_null() {
+        tracing::debug!("Freeing power assertion client");
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
+    }
+}
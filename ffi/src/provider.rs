@@ -1,11 +1,11 @@
 // Jackson Coxson
 
-use idevice::provider::{IdeviceProvider, TcpProvider, UsbmuxdProvider};
+use idevice::provider::{IdeviceProvider, RemoteUsbmuxdProvider, TcpProvider, UsbmuxdProvider};
 use std::net::IpAddr;
 use std::os::raw::c_char;
 use std::{ffi::CStr, ptr::null_mut};
 
-use crate::util::{SockAddr, idevice_sockaddr};
+use crate::util::{SockAddr, idevice_sockaddr, idevice_socklen_t};
 use crate::{IdeviceFfiError, ffi_err, usbmuxd::UsbmuxdAddrHandle, util};
 use crate::{IdevicePairingFile, run_sync};
 
@@ -14,7 +14,7 @@ pub struct IdeviceProviderHandle(pub Box<dyn IdeviceProvider>);
 /// Creates a TCP provider for idevice
 ///
 /// # Arguments
-/// * [`ip`] - The sockaddr IP to connect to
+/// * [`ip`] - The sockaddr IP to connect to, including the scope id of link-local IPv6 addresses
 /// * [`pairing_file`] - The pairing file handle to use
 /// * [`label`] - The label to use with the connection
 /// * [`provider`] - A pointer to a newly allocated provider
@@ -35,7 +35,7 @@ pub unsafe extern "C" fn idevice_tcp_provider_new(
     provider: *mut *mut IdeviceProviderHandle,
 ) -> *mut IdeviceFfiError {
     let ip = ip as *const SockAddr;
-    let addr: IpAddr = match util::c_addr_to_rust(ip) {
+    let (addr, scope_id): (IpAddr, u32) = match util::c_addr_to_rust(ip) {
         Ok(i) => i,
         Err(e) => return ffi_err!(e),
     };
@@ -48,11 +48,7 @@ pub unsafe extern "C" fn idevice_tcp_provider_new(
     // consume the pairing file on success
     let pairing_file = unsafe { Box::from_raw(pairing_file) };
 
-    let t = TcpProvider {
-        addr,
-        pairing_file: pairing_file.0,
-        label,
-    };
+    let t = TcpProvider::new(addr, pairing_file.0, label).with_scope_id(scope_id);
 
     let boxed = Box::new(IdeviceProviderHandle(Box::new(t)));
     unsafe { *provider = Box::into_raw(boxed) };
@@ -124,20 +120,241 @@ pub unsafe extern "C" fn usbmuxd_provider_new(
 
     let addr = unsafe { Box::from_raw(addr) }.0;
 
-    let p = UsbmuxdProvider {
-        addr,
-        tag,
-        udid,
-        device_id,
-        label,
+    let p = UsbmuxdProvider::new(addr, tag, udid, device_id, label);
+
+    let boxed = Box::new(IdeviceProviderHandle(Box::new(p)));
+    unsafe { *provider = Box::into_raw(boxed) };
+
+    null_mut()
+}
+
+/// Creates a usbmuxd provider for the attached device with the given UDID
+///
+/// usbmuxd is located the same way as `idevice_usbmuxd_new_default_connection`, and a
+/// device attached over USB is preferred to the same device over the network. Fails if the
+/// device isn't attached or has no pair record.
+///
+/// # Arguments
+/// * [`udid`] - The UDID of the device to connect to
+/// * [`provider`] - A pointer to a newly allocated provider
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `udid` must be a valid CStr
+/// `provider` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_provider_from_udid(
+    udid: *const c_char,
+    provider: *mut *mut IdeviceProviderHandle,
+) -> *mut IdeviceFfiError {
+    if udid.is_null() || provider.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let udid = match unsafe { CStr::from_ptr(udid) }.to_str() {
+        Ok(u) => u.to_string(),
+        Err(e) => {
+            tracing::error!("Invalid UDID string: {e:?}");
+            return ffi_err!(IdeviceError::FfiInvalidString);
+        }
+    };
+
+    match run_sync(async move { idevice::connect_usbmuxd(&udid).await }) {
+        Ok(p) => {
+            let boxed = Box::new(IdeviceProviderHandle(Box::new(p)));
+            unsafe { *provider = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Creates a usbmuxd provider that uses a pair record file instead of usbmuxd's pair records
+///
+/// usbmuxd is still used to reach the device, and is located the same way as
+/// `idevice_provider_from_udid`.
+///
+/// # Arguments
+/// * [`udid`] - The UDID of the device to connect to
+/// * [`path`] - Path to the pair record
+/// * [`provider`] - A pointer to a newly allocated provider
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success. The error code is that of
+/// `InvalidPairRecord` if the record can't be used for the device.
+///
+/// # Safety
+/// `udid` and `path` must be valid CStrs
+/// `provider` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_provider_from_pair_record(
+    udid: *const c_char,
+    path: *const c_char,
+    provider: *mut *mut IdeviceProviderHandle,
+) -> *mut IdeviceFfiError {
+    if udid.is_null() || path.is_null() || provider.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let (udid, path) = match (
+        unsafe { CStr::from_ptr(udid) }.to_str(),
+        unsafe { CStr::from_ptr(path) }.to_str(),
+    ) {
+        (Ok(u), Ok(p)) => (u.to_string(), p.to_string()),
+        _ => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+
+    match run_sync(async move {
+        UsbmuxdProvider::from_pair_record_path(&udid, std::path::Path::new(&path)).await
+    }) {
+        Ok(p) => {
+            let boxed = Box::new(IdeviceProviderHandle(Box::new(p)));
+            unsafe { *provider = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Waits for the device with the given UDID to attach and become ready
+///
+/// usbmuxd is located the same way as `idevice_usbmuxd_new_default_connection`. A device that
+/// is already attached returns right away. This blocks until the device's lockdown service
+/// answers or the timeout passes.
+///
+/// # Arguments
+/// * [`udid`] - The UDID of the device to wait for
+/// * [`timeout_ms`] - How long to wait in total, in milliseconds
+/// * [`provider`] - A pointer to a newly allocated provider
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success. The error code is that of `Timeout` if the
+/// device wasn't ready in time.
+///
+/// # Safety
+/// `udid` must be a valid CStr
+/// `provider` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_wait_for_device(
+    udid: *const c_char,
+    timeout_ms: u64,
+    provider: *mut *mut IdeviceProviderHandle,
+) -> *mut IdeviceFfiError {
+    if udid.is_null() || provider.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let udid = match unsafe { CStr::from_ptr(udid) }.to_str() {
+        Ok(u) => u.to_string(),
+        Err(e) => {
+            tracing::error!("Invalid UDID string: {e:?}");
+            return ffi_err!(IdeviceError::FfiInvalidString);
+        }
+    };
+
+    let timeout = std::time::Duration::from_millis(timeout_ms);
+    match run_sync(async move { idevice::wait_for_device(&udid, timeout).await }) {
+        Ok(p) => {
+            let boxed = Box::new(IdeviceProviderHandle(Box::new(p)));
+            unsafe { *provider = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Creates a provider for a device attached to usbmuxd on another host
+///
+/// The device is looked up by UDID on every connection.
+///
+/// # Arguments
+/// * [`addr`] - The sockaddr of the remote usbmuxd, including its port
+/// * [`addr_len`] - The length of the sockaddr
+/// * [`udid`] - The UDID of the device to connect to
+/// * [`pairing_file`] - The pairing file handle to use
+/// * [`label`] - The label to use with the connection
+/// * [`provider`] - A pointer to a newly allocated provider
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `addr` must be a valid sockaddr of `addr_len` bytes
+/// `udid` and `label` must be valid CStrs
+/// `pairing_file` is consumed on success and must never be used again
+/// `provider` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn remote_usbmuxd_provider_new(
+    addr: *const idevice_sockaddr,
+    addr_len: idevice_socklen_t,
+    udid: *const c_char,
+    pairing_file: *mut crate::pairing_file::IdevicePairingFile,
+    label: *const c_char,
+    provider: *mut *mut IdeviceProviderHandle,
+) -> *mut IdeviceFfiError {
+    if addr.is_null()
+        || udid.is_null()
+        || pairing_file.is_null()
+        || label.is_null()
+        || provider.is_null()
+    {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let addr = match util::c_socket_to_rust(addr as *const SockAddr, addr_len as _) {
+        Ok(a) => a,
+        Err(e) => return ffi_err!(e),
+    };
+
+    let udid = match unsafe { CStr::from_ptr(udid) }.to_str() {
+        Ok(u) => u.to_string(),
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
     };
 
+    let label = match unsafe { CStr::from_ptr(label) }.to_str() {
+        Ok(l) => l.to_string(),
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+
+    // consume the pairing file on success
+    let pairing_file = unsafe { Box::from_raw(pairing_file) };
+
+    let mut p = RemoteUsbmuxdProvider::new(addr, udid, pairing_file.0);
+    p.label = label;
+
     let boxed = Box::new(IdeviceProviderHandle(Box::new(p)));
     unsafe { *provider = Box::into_raw(boxed) };
 
     null_mut()
 }
 
+/// Sets how long the provider may take to connect before failing
+///
+/// # Arguments
+/// * [`provider`] - A pointer to the provider
+/// * [`timeout_ms`] - The timeout in milliseconds, or 0 to wait indefinitely
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `provider` must be a valid, non-null pointer to the provider
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_provider_set_timeout(
+    provider: *mut IdeviceProviderHandle,
+    timeout_ms: u64,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let timeout = (timeout_ms != 0).then(|| std::time::Duration::from_millis(timeout_ms));
+    unsafe { &mut *provider }.0.set_connect_timeout(timeout);
+    null_mut()
+}
+
 /// Gets the pairing file for the device
 ///
 /// # Arguments
@@ -166,3 +383,85 @@ pub unsafe extern "C" fn idevice_provider_get_pairing_file(
         Err(e) => ffi_err!(e),
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use idevice::{Idevice, IdeviceError, pairing_file::PairingFile};
+
+    use super::*;
+
+    /// A provider that refuses every connection, used to exercise connect failure paths
+    #[derive(Debug)]
+    pub(crate) struct RefusingProvider;
+
+    impl IdeviceProvider for RefusingProvider {
+        fn connect(
+            &self,
+            _port: u16,
+        ) -> Pin<Box<dyn Future<Output = Result<Idevice, IdeviceError>> + Send>> {
+            Box::pin(async { Err(IdeviceError::NoEstablishedConnection) })
+        }
+
+        fn label(&self) -> &str {
+            "refusing"
+        }
+
+        fn get_pairing_file(
+            &self,
+        ) -> Pin<Box<dyn Future<Output = Result<PairingFile, IdeviceError>> + Send>> {
+            Box::pin(async { Err(IdeviceError::NoEstablishedConnection) })
+        }
+    }
+
+    pub(crate) fn refusing_provider() -> *mut IdeviceProviderHandle {
+        Box::into_raw(Box::new(IdeviceProviderHandle(Box::new(RefusingProvider))))
+    }
+
+    /// A provider that refuses a number of connections, then hands out in-memory connections
+    #[derive(Debug)]
+    pub(crate) struct FlakyProvider {
+        failures: AtomicUsize,
+    }
+
+    impl IdeviceProvider for FlakyProvider {
+        fn connect(
+            &self,
+            _port: u16,
+        ) -> Pin<Box<dyn Future<Output = Result<Idevice, IdeviceError>> + Send>> {
+            let fail = self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |f| f.checked_sub(1))
+                .is_ok();
+            Box::pin(async move {
+                if fail {
+                    return Err(IdeviceError::NoEstablishedConnection);
+                }
+                let (socket, _) = tokio::io::duplex(1024);
+                Ok(Idevice::new(Box::new(socket), "flaky"))
+            })
+        }
+
+        fn label(&self) -> &str {
+            "flaky"
+        }
+
+        fn get_pairing_file(
+            &self,
+        ) -> Pin<Box<dyn Future<Output = Result<PairingFile, IdeviceError>> + Send>> {
+            Box::pin(async { Err(IdeviceError::NoEstablishedConnection) })
+        }
+    }
+
+    pub(crate) fn flaky_provider(failures: usize) -> *mut IdeviceProviderHandle {
+        let provider = FlakyProvider {
+            failures: AtomicUsize::new(failures),
+        };
+        Box::into_raw(Box::new(IdeviceProviderHandle(Box::new(provider))))
+    }
+}
@@ -273,6 +273,46 @@ pub unsafe extern "C" fn rsd_service_available(
     null_mut()
 }
 
+/// Gets the port a specific service is advertised on
+///
+/// # Arguments
+/// * [`handle`] - A valid RsdHandshake handle
+/// * [`service_name`] - Name of the service to look up
+/// * [`port`] - Pointer to store the port
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success.
+/// Returns ServiceNotFound if the device doesn't advertise the service.
+///
+/// # Safety
+/// `handle` must be a valid pointer to a handle allocated by this library
+/// `service_name` must be a valid C string
+/// `port` must be a valid pointer to store the port
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rsd_get_service_port(
+    handle: *mut RsdHandshakeHandle,
+    service_name: *const libc::c_char,
+    port: *mut u16,
+) -> *mut IdeviceFfiError {
+    if handle.is_null() || service_name.is_null() || port.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let name = match unsafe { CStr::from_ptr(service_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+    };
+
+    let handshake = unsafe { &*handle };
+    match handshake.0.service_port(name) {
+        Some(p) => {
+            unsafe { *port = p };
+            null_mut()
+        }
+        None => ffi_err!(IdeviceError::ServiceNotFound),
+    }
+}
+
 /// Gets information about a specific service
 ///
 /// # Arguments
@@ -0,0 +1,161 @@
+// Jackson Coxson
+
+use std::{ffi::c_void, ptr::null_mut};
+
+use idevice::{
+    IdeviceError, IdeviceService, provider::IdeviceProvider, screenshotr::ScreenshotService,
+};
+
+use crate::{
+    IdeviceFfiError, ffi_err, provider::IdeviceProviderHandle, run_sync_local, spawn_connect,
+};
+
+pub struct ScreenshotrClientHandle(pub ScreenshotService);
+
+/// Automatically creates and connects to the screenshotr service, returning a client handle.
+/// This service is only available below iOS 17; newer versions take screenshots through DVT.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`client`] - On success, will be set to point to a newly allocated ScreenshotrClient handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library. It is borrowed,
+/// and remains owned by the caller whether or not this function succeeds.
+/// `client` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn screenshotr_connect(
+    provider: *mut IdeviceProviderHandle,
+    client: *mut *mut ScreenshotrClientHandle,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() || client.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res: Result<ScreenshotService, IdeviceError> = run_sync_local(async move {
+        let provider_ref: &dyn IdeviceProvider = unsafe { &*(*provider).0 };
+        ScreenshotService::connect(provider_ref).await
+    });
+
+    match res {
+        Ok(r) => {
+            let boxed = Box::new(ScreenshotrClientHandle(r));
+            unsafe { *client = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Connects to the screenshotr service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn screenshotr_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut ScreenshotrClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            ScreenshotService::connect,
+            ScreenshotrClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
+/// Takes a screenshot of the device's screen
+///
+/// # Arguments
+/// * [`client`] - A valid ScreenshotrClient handle
+/// * [`data`] - On success, will be set to point to the image data (PNG or TIFF, depending on
+///   the device)
+/// * [`len`] - On success, will be set to the length of the image data
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `data` and `len` must be valid, non-null pointers
+/// The image data must be freed with `idevice_data_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn screenshotr_take_screenshot(
+    client: *mut ScreenshotrClientHandle,
+    data: *mut *mut u8,
+    len: *mut usize,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || data.is_null() || len.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync_local(async move {
+        let client_ref = unsafe { &mut (*client).0 };
+        client_ref.take_screenshot().await
+    });
+
+    match res {
+        Ok(r) => {
+            let mut r = r.into_boxed_slice();
+            unsafe {
+                *data = r.as_mut_ptr();
+                *len = r.len();
+            }
+            std::mem::forget(r);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Frees a handle
+///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
+/// # Arguments
+/// * [`handle`] - The handle to free
+///
+/// # Safety
+/// `handle` must be a valid pointer to the handle that was allocated by this library,
+/// or NULL (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn screenshotr_client_free(handle: *mut ScreenshotrClientHandle) {
+    if !handle.is_null() {
+        tracing::debug!("Freeing screenshotr_client");
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
+    }
+}
@@ -4,13 +4,14 @@ use std::{
 };
 
 use idevice::{
-    IdeviceError, IdeviceService, provider::IdeviceProvider,
-    springboardservices::SpringBoardServicesClient,
+    IdeviceError, IdeviceService,
+    provider::IdeviceProvider,
+    springboardservices::{InterfaceOrientation, SpringBoardServicesClient},
 };
 
 use crate::{
     IdeviceFfiError, IdeviceHandle, ffi_err, provider::IdeviceProviderHandle, run_sync,
-    run_sync_local,
+    run_sync_local, spawn_connect,
 };
 
 pub struct SpringBoardServicesClientHandle(pub SpringBoardServicesClient);
@@ -57,6 +58,52 @@ pub unsafe extern "C" fn springboard_services_connect(
     }
 }
 
+/// Connects to the SpringBoard services service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn springboard_services_connect_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut SpringBoardServicesClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            SpringBoardServicesClient::connect,
+            SpringBoardServicesClientHandle,
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Creates a new SpringBoardServices client from an existing Idevice connection
 ///
 /// # Arguments
@@ -91,6 +138,7 @@ pub unsafe extern "C" fn springboard_services_new(
 /// * `client` - A valid SpringBoardServicesClient handle
 /// * `bundle_identifier` - The identifiers of the app to get icon
 /// * `out_result` - On success, will be set to point to a newly allocated png data
+/// * `out_result_len` - On success, will be set to the length of the png data
 ///
 /// # Returns
 /// An IdeviceFfiError on error, null on success
@@ -98,6 +146,7 @@ pub unsafe extern "C" fn springboard_services_new(
 /// # Safety
 /// `client` must be a valid pointer to a handle allocated by this library
 /// `out_result` must be a valid, non-null pointer to a location where the result will be stored
+/// The png data must be freed with `idevice_data_free`
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn springboard_services_get_icon(
     client: *mut SpringBoardServicesClientHandle,
@@ -105,7 +154,11 @@ pub unsafe extern "C" fn springboard_services_get_icon(
     out_result: *mut *mut c_void,
     out_result_len: *mut libc::size_t,
 ) -> *mut IdeviceFfiError {
-    if client.is_null() || out_result.is_null() || out_result_len.is_null() {
+    if client.is_null()
+        || bundle_identifier.is_null()
+        || out_result.is_null()
+        || out_result_len.is_null()
+    {
         tracing::error!("Invalid arguments: {client:?}, {out_result:?}");
         return ffi_err!(IdeviceError::FfiInvalidArg);
     }
@@ -137,8 +190,97 @@ pub unsafe extern "C" fn springboard_services_get_icon(
     }
 }
 
+/// Gets the home screen wallpaper as PNG data
+///
+/// The service has no command to set the wallpaper, only to read it.
+///
+/// # Arguments
+/// * `client` - A valid SpringBoardServicesClient handle
+/// * `out_result` - On success, will be set to point to a newly allocated png data
+/// * `out_result_len` - On success, will be set to the length of the png data
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `out_result` and `out_result_len` must be valid, non-null pointers
+/// The png data must be freed with `idevice_data_free`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn springboard_services_get_wallpaper(
+    client: *mut SpringBoardServicesClientHandle,
+    out_result: *mut *mut c_void,
+    out_result_len: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || out_result.is_null() || out_result_len.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+    let client = unsafe { &mut *client };
+
+    let res = run_sync_local(async { client.0.get_wallpaper_pngdata().await });
+
+    match res {
+        Ok(r) => {
+            let len = r.len();
+            let boxed_slice = r.into_boxed_slice();
+            let ptr = boxed_slice.as_ptr();
+            std::mem::forget(boxed_slice);
+
+            unsafe {
+                *out_result = ptr as *mut c_void;
+                *out_result_len = len;
+            }
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Gets the current orientation of the device's user interface
+///
+/// # Arguments
+/// * `client` - A valid SpringBoardServicesClient handle
+/// * `orientation` - On success, will be set to the UIInterfaceOrientation value: 1 for portrait,
+///   2 for portrait upside down, 3 for landscape right and 4 for landscape left
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library
+/// `orientation` must be a valid, non-null pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn springboard_services_get_interface_orientation(
+    client: *mut SpringBoardServicesClientHandle,
+    orientation: *mut u64,
+) -> *mut IdeviceFfiError {
+    if client.is_null() || orientation.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+    let client = unsafe { &mut *client };
+
+    let res = run_sync_local(async { client.0.get_interface_orientation().await });
+
+    match res {
+        Ok(o) => {
+            let raw = match o {
+                InterfaceOrientation::Portrait => 1,
+                InterfaceOrientation::PortraitUpsideDown => 2,
+                InterfaceOrientation::LandscapeRight => 3,
+                InterfaceOrientation::LandscapeLeft => 4,
+                InterfaceOrientation::Unknown(v) => v,
+            };
+            unsafe { *orientation = raw };
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Frees an SpringBoardServicesClient handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -149,6 +291,7 @@ pub unsafe extern "C" fn springboard_services_get_icon(
 pub unsafe extern "C" fn springboard_services_free(handle: *mut SpringBoardServicesClientHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing springboard_services_client");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
     }
 }
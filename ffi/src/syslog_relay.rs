@@ -1,12 +1,16 @@
-use std::{os::raw::c_char, ptr::null_mut};
+use std::{ffi::c_void, os::raw::c_char, ptr::null_mut};
 
 use idevice::{
-    IdeviceError, IdeviceService, provider::IdeviceProvider, syslog_relay::SyslogRelayClient,
+    CancellationToken, IdeviceError, IdeviceService, provider::IdeviceProvider,
+    syslog_relay::SyslogRelayClient,
 };
 
-use crate::{IdeviceFfiError, ffi_err, provider::IdeviceProviderHandle, run_sync_local};
+use crate::{
+    IdeviceFfiError, ffi_err, provider::IdeviceProviderHandle, run_sync_local, spawn_connect,
+};
 
-pub struct SyslogRelayClientHandle(pub SyslogRelayClient);
+/// The token is kept outside the client so it can be reached while another thread reads
+pub struct SyslogRelayClientHandle(pub SyslogRelayClient, CancellationToken);
 
 /// Automatically creates and connects to syslog relay, returning a client handle
 ///
@@ -22,7 +26,7 @@ pub unsafe extern "C" fn syslog_relay_connect_tcp(
     provider: *mut IdeviceProviderHandle,
     client: *mut *mut SyslogRelayClientHandle,
 ) -> *mut IdeviceFfiError {
-    if provider.is_null() {
+    if provider.is_null() || client.is_null() {
         tracing::error!("Null pointer provided");
         return ffi_err!(IdeviceError::FfiInvalidArg);
     }
@@ -34,7 +38,8 @@ pub unsafe extern "C" fn syslog_relay_connect_tcp(
 
     match res {
         Ok(c) => {
-            let boxed = Box::new(SyslogRelayClientHandle(c));
+            let token = c.cancellation_token();
+            let boxed = Box::new(SyslogRelayClientHandle(c, token));
 
             unsafe { *client = Box::into_raw(boxed) };
 
@@ -47,8 +52,59 @@ pub unsafe extern "C" fn syslog_relay_connect_tcp(
     }
 }
 
+/// Connects to the syslog relay service without blocking, passing the client handle to a callback
+///
+/// `on_done` is called exactly once, from a runtime worker thread rather than the calling
+/// thread. On success `error` is null and `client` is a newly allocated handle owned by the
+/// callback. On failure `client` is null and `error` must be freed with `idevice_error_free`.
+///
+/// # Arguments
+/// * [`provider`] - An IdeviceProvider
+/// * [`on_done`] - Called with the result of the connection
+/// * [`context`] - Passed back to `on_done` unchanged
+///
+/// # Returns
+/// An IdeviceFfiError if the arguments are invalid, in which case `on_done` is never called.
+/// Null otherwise.
+///
+/// # Safety
+/// `provider` must be a valid pointer to a handle allocated by this library, and must not be
+/// freed until `on_done` has been called
+/// `context` must be safe to use from the thread `on_done` is called on
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn syslog_relay_connect_tcp_async(
+    provider: *mut IdeviceProviderHandle,
+    on_done: extern "C" fn(
+        error: *mut IdeviceFfiError,
+        client: *mut SyslogRelayClientHandle,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+) -> *mut IdeviceFfiError {
+    if provider.is_null() {
+        tracing::error!("Null pointer provided");
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    unsafe {
+        spawn_connect(
+            provider,
+            SyslogRelayClient::connect,
+            |c| {
+                let token = c.cancellation_token();
+                SyslogRelayClientHandle(c, token)
+            },
+            on_done,
+            context,
+        )
+    };
+    null_mut()
+}
+
 /// Frees a handle
 ///
+/// The connection is closed gracefully first, as with `idevice_shutdown`.
+///
 /// # Arguments
 /// * [`handle`] - The handle to free
 ///
@@ -59,12 +115,15 @@ pub unsafe extern "C" fn syslog_relay_connect_tcp(
 pub unsafe extern "C" fn syslog_relay_client_free(handle: *mut SyslogRelayClientHandle) {
     if !handle.is_null() {
         tracing::debug!("Freeing syslog relay client");
-        let _ = unsafe { Box::from_raw(handle) };
+        let mut handle = unsafe { Box::from_raw(handle) };
+        crate::shutdown_on_free(handle.0.idevice.shutdown());
     }
 }
 
 /// Gets the next log message from the relay
 ///
+/// Lines are split internally, so each call returns exactly one log line.
+///
 /// # Arguments
 /// * [`client`] - The SyslogRelayClient handle
 /// * [`log_message`] - On success a newly allocated cstring will be set to point to the log message
@@ -81,7 +140,7 @@ pub unsafe extern "C" fn syslog_relay_next(
         return ffi_err!(IdeviceError::FfiInvalidArg);
     }
 
-    let res = run_sync_local(async { unsafe { &mut *client }.0.next().await });
+    let res = run_sync_local(async { unsafe { &mut (*client).0 }.next().await });
 
     match res {
         Ok(log) => {
@@ -104,3 +163,22 @@ pub unsafe extern "C" fn syslog_relay_next(
         Err(e) => ffi_err!(e),
     }
 }
+
+/// Cancels the client, aborting a pending `syslog_relay_next` and closing the relay
+///
+/// Later calls to `syslog_relay_next` fail. The handle must still be freed with
+/// `syslog_relay_client_free`.
+///
+/// # Arguments
+/// * [`client`] - The SyslogRelayClient handle
+///
+/// # Safety
+/// `client` must be a valid pointer to a handle allocated by this library.
+/// This may be called from any thread, including while another thread is in `syslog_relay_next`,
+/// but not after the handle is freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn syslog_relay_cancel(client: *mut SyslogRelayClientHandle) {
+    if !client.is_null() {
+        unsafe { &(*client).1 }.cancel();
+    }
+}
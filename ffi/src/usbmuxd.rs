@@ -1,13 +1,14 @@
 // Jackson Coxson
 
 use std::{
-    ffi::{CStr, CString, c_char},
+    ffi::{CStr, CString, c_char, c_void},
     pin::Pin,
     ptr::null_mut,
 };
 
 use crate::{
-    IdeviceFfiError, IdeviceHandle, IdevicePairingFile, ffi_err, run_sync, run_sync_local,
+    IdeviceFfiError, IdeviceHandle, IdevicePairingFile, LOCAL_RUNTIME, ffi_err, run_sync,
+    run_sync_local,
     util::{SockAddr, c_socket_to_rust, idevice_sockaddr, idevice_socklen_t},
 };
 use futures::{Stream, StreamExt};
@@ -15,15 +16,25 @@ use idevice::{
     IdeviceError,
     usbmuxd::{UsbmuxdAddr, UsbmuxdConnection, UsbmuxdDevice, UsbmuxdListenEvent},
 };
+use tokio::{sync::oneshot, task::JoinHandle};
 use tracing::error;
 
 pub struct UsbmuxdConnectionHandle(pub UsbmuxdConnection);
 pub struct UsbmuxdAddrHandle(pub UsbmuxdAddr);
 pub struct UsbmuxdDeviceHandle(pub UsbmuxdDevice);
 pub struct UsbmuxdListenerHandle<'a>(
-    Pin<Box<dyn Stream<Item = Result<UsbmuxdListenEvent, IdeviceError>> + 'a>>,
+    Pin<Box<dyn Stream<Item = Result<UsbmuxdListenEvent, IdeviceError>> + Send + 'a>>,
 );
 
+/// A running device event subscription, created by ``idevice_usbmuxd_subscribe``
+pub struct UsbmuxdSubscriptionHandle {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+struct UsbmuxdSubscriptionContext(*mut c_void);
+unsafe impl Send for UsbmuxdSubscriptionContext {}
+
 /// Connects to a usbmuxd instance over TCP
 ///
 /// # Arguments
@@ -306,6 +317,58 @@ pub unsafe extern "C" fn idevice_usbmuxd_get_pair_record(
     }
 }
 
+/// Reads the pairing record for a given device UDID as raw plist bytes.
+///
+/// The returned buffer must be freed with `idevice_data_free`.
+///
+/// # Arguments
+/// * `usbmuxd_conn` - A valid connection to usbmuxd.
+/// * `udid` - The UDID of the device.
+/// * `data` - On success, points to the plist bytes of the pairing record.
+/// * `length` - On success, set to the length of `data`.
+///
+/// # Returns
+/// An `IdeviceFfiError` on error, `null` on success.
+///
+/// # Safety
+/// * `usbmuxd_conn` must be a valid pointer.
+/// * `udid` must be a valid, null-terminated C string.
+/// * `data` and `length` must be valid, non-null pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_usbmuxd_get_pair_record_bytes(
+    usbmuxd_conn: *mut UsbmuxdConnectionHandle,
+    udid: *const c_char,
+    data: *mut *mut u8,
+    length: *mut usize,
+) -> *mut IdeviceFfiError {
+    if usbmuxd_conn.is_null() || udid.is_null() || data.is_null() || length.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+    let conn = unsafe { &mut (*usbmuxd_conn).0 };
+
+    let udid_str = unsafe {
+        match CStr::from_ptr(udid).to_str() {
+            Ok(s) => s,
+            Err(_) => return ffi_err!(IdeviceError::FfiInvalidArg),
+        }
+    };
+
+    let res = run_sync_local(async { conn.get_pair_record_bytes(udid_str).await });
+
+    match res {
+        Ok(raw) => {
+            let mut raw = raw.into_boxed_slice();
+            unsafe {
+                *length = raw.len();
+                *data = raw.as_mut_ptr();
+            }
+            std::mem::forget(raw);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
 /// Saves the pairing record for a given device UDID.
 ///
 /// # Arguments
@@ -449,6 +512,116 @@ pub unsafe extern "C" fn idevice_usbmuxd_listener_next(
     }
 }
 
+/// Subscribes to device attach and detach events, invoking a callback for each one
+///
+/// The callback is called from a runtime worker thread. On an attach event, `connected`
+/// is true and `device` points to a newly allocated device handle that the callback takes
+/// ownership of, and must free with ``idevice_usbmuxd_device_free``. On a detach event,
+/// `connected` is false, `device` is NULL and `device_id` holds the mux ID of the device.
+/// The subscription ends on its own if the usbmuxd connection fails.
+///
+/// # Arguments
+/// * [`usbmuxd_conn`] - The usbmuxd connection, which is consumed by this function
+/// * [`on_event`] - Called for every attach or detach event
+/// * [`context`] - Passed back to `on_event` unchanged
+/// * [`handle`] - On success, will be set to the subscription handle
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `usbmuxd_conn` must be a valid pointer to a handle allocated by this library. It is
+/// consumed and must not be used again, even if this function returns an error.
+/// `context` must remain valid until the subscription is cancelled with
+/// ``idevice_usbmuxd_unsubscribe``.
+/// `handle` must be a valid, non-null pointer to a location where the handle will be stored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_usbmuxd_subscribe(
+    usbmuxd_conn: *mut UsbmuxdConnectionHandle,
+    on_event: extern "C" fn(
+        connected: bool,
+        device: *mut UsbmuxdDeviceHandle,
+        device_id: u32,
+        context: *mut c_void,
+    ),
+    context: *mut c_void,
+    handle: *mut *mut UsbmuxdSubscriptionHandle,
+) -> *mut IdeviceFfiError {
+    if usbmuxd_conn.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+    let mut conn = unsafe { Box::from_raw(usbmuxd_conn) }.0;
+    if handle.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    // Wait for usbmuxd to accept the request, so a rejection is reported to the caller
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let (stop, mut stop_rx) = oneshot::channel();
+    let context = UsbmuxdSubscriptionContext(context);
+
+    let task = LOCAL_RUNTIME.spawn(async move {
+        let context = context;
+        let mut stream = match conn.listen().await {
+            Ok(s) => {
+                let _ = ready_tx.send(Ok(()));
+                s
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+                return;
+            }
+        };
+        loop {
+            let event = tokio::select! {
+                event = stream.next() => event,
+                _ = &mut stop_rx => break,
+            };
+            match event {
+                Some(Ok(UsbmuxdListenEvent::Connected(dev))) => {
+                    let dev = Box::into_raw(Box::new(UsbmuxdDeviceHandle(dev)));
+                    on_event(true, dev, 0, context.0);
+                }
+                Some(Ok(UsbmuxdListenEvent::Disconnected(id))) => {
+                    on_event(false, null_mut(), id, context.0);
+                }
+                Some(Err(e)) => {
+                    error!("usbmuxd subscription ended: {e:?}");
+                    break;
+                }
+                None => break,
+            }
+        }
+    });
+
+    match run_sync_local(ready_rx) {
+        Ok(Ok(())) => {
+            let boxed = Box::new(UsbmuxdSubscriptionHandle { stop, task });
+            unsafe { *handle = Box::into_raw(boxed) };
+            null_mut()
+        }
+        Ok(Err(e)) => ffi_err!(e),
+        Err(_) => ffi_err!(IdeviceError::UnexpectedResponse),
+    }
+}
+
+/// Cancels a device event subscription, waits for it to exit, and frees its handle
+///
+/// Once this returns, the subscription's callback will not be called again.
+///
+/// # Safety
+/// `handle` must be a valid pointer to a handle allocated by this library, or NULL
+/// (in which case this function does nothing)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_usbmuxd_unsubscribe(handle: *mut UsbmuxdSubscriptionHandle) {
+    if handle.is_null() {
+        return;
+    }
+    let handle = unsafe { Box::from_raw(handle) };
+    let _ = handle.stop.send(());
+    let _ = run_sync_local(handle.task);
+}
+
 /// Reads the BUID (Boot-Unique ID) from usbmuxd.
 ///
 /// The returned string must be freed with `idevice_string_free`.
@@ -715,3 +888,185 @@ pub unsafe extern "C" fn idevice_usbmuxd_device_get_connection_type(
     };
     ct as u8
 }
+
+/// Gets the address of a device attached over the network.
+/// Link-local IPv6 addresses include their zone index, such as `fe80::1%4`.
+/// The returned string must be freed by the caller using `idevice_string_free`.
+///
+/// # Returns
+/// The address, or NULL if the device isn't attached over the network
+///
+/// # Safety
+/// `device` must be a valid pointer to a `UsbmuxdDeviceHandle`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_usbmuxd_device_get_network_address(
+    device: *const UsbmuxdDeviceHandle,
+) -> *mut c_char {
+    if device.is_null() {
+        return null_mut();
+    }
+    let device = unsafe { &(*device).0 };
+    let addr = match device.connection_type {
+        idevice::usbmuxd::Connection::Network(addr) if device.scope_id != 0 => {
+            format!("{addr}%{}", device.scope_id)
+        }
+        idevice::usbmuxd::Connection::Network(addr) => addr.to_string(),
+        _ => return null_mut(),
+    };
+    match CString::new(addr) {
+        Ok(s) => s.into_raw(),
+        Err(_) => null_mut(),
+    }
+}
+
+/// Creates a provider connecting straight to a device attached over the network,
+/// bypassing usbmuxd
+///
+/// # Arguments
+/// * [`device`] - A device handle
+/// * [`pairing_file`] - The pairing file handle to use
+/// * [`label`] - The label to use with the connection
+/// * [`provider`] - A pointer to a newly allocated provider
+///
+/// # Returns
+/// An IdeviceFfiError on error, null on success
+///
+/// # Safety
+/// `device` must be a valid pointer to a `UsbmuxdDeviceHandle`
+/// `pairing_file` is consumed on success and must never be used again
+/// `label` must be a valid Cstr
+/// `provider` must be a valid, non-null pointer to a location where the handle will be stored
+#[cfg(feature = "tcp")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_usbmuxd_device_to_network_provider(
+    device: *const UsbmuxdDeviceHandle,
+    pairing_file: *mut IdevicePairingFile,
+    label: *const c_char,
+    provider: *mut *mut crate::provider::IdeviceProviderHandle,
+) -> *mut IdeviceFfiError {
+    if device.is_null() || pairing_file.is_null() || label.is_null() || provider.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+    let label = match unsafe { CStr::from_ptr(label) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ffi_err!(IdeviceError::FfiInvalidString),
+    };
+    let device = unsafe { &(*device).0 };
+    if !matches!(
+        device.connection_type,
+        idevice::usbmuxd::Connection::Network(_)
+    ) {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    // consume the pairing file on success
+    let pairing_file = unsafe { Box::from_raw(pairing_file) };
+    let Some(p) = device.to_network_provider(pairing_file.0, label) else {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    };
+
+    let boxed = Box::new(crate::provider::IdeviceProviderHandle(Box::new(p)));
+    unsafe { *provider = Box::into_raw(boxed) };
+    null_mut()
+}
+
+/// A device attached to usbmuxd, as returned by `idevice_list_devices`
+#[repr(C)]
+pub struct IdeviceDeviceEntry {
+    /// The UDID of the device
+    pub udid: *mut c_char,
+    /// The connection type, as a UsbmuxdConnectionType value
+    pub connection_type: u8,
+    /// The muxer ID of the device
+    pub device_id: u32,
+    /// The user-assigned name of the device, or NULL if it couldn't be read
+    pub device_name: *mut c_char,
+}
+
+/// Lists the devices attached to the local usbmuxd, along with their names
+///
+/// Devices that don't report their name within a second are listed with a NULL name.
+///
+/// # Arguments
+/// * `entries` - On success, points to an array of device entries
+/// * `count` - On success, set to the number of entries
+///
+/// # Returns
+/// An `IdeviceFfiError` on error, `null` on success.
+///
+/// # Safety
+/// `entries` and `count` must be valid, non-null pointers.
+/// The entries must be freed with `idevice_device_entries_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_list_devices(
+    entries: *mut *mut IdeviceDeviceEntry,
+    count: *mut libc::size_t,
+) -> *mut IdeviceFfiError {
+    if entries.is_null() || count.is_null() {
+        return ffi_err!(IdeviceError::FfiInvalidArg);
+    }
+
+    let res = run_sync(idevice::list_devices());
+
+    match res {
+        Ok(devices) => {
+            let mut c_arr = devices
+                .into_iter()
+                .map(|d| {
+                    let connection_type = match d.connection_type {
+                        idevice::usbmuxd::Connection::Usb => UsbmuxdConnectionType::Usb,
+                        idevice::usbmuxd::Connection::Network(_) => UsbmuxdConnectionType::Network,
+                        idevice::usbmuxd::Connection::Unknown(_) => UsbmuxdConnectionType::Unknown,
+                    };
+                    IdeviceDeviceEntry {
+                        udid: CString::new(d.udid)
+                            .map(CString::into_raw)
+                            .unwrap_or(null_mut()),
+                        connection_type: connection_type as u8,
+                        device_id: d.device_id,
+                        device_name: d
+                            .device_name
+                            .and_then(|n| CString::new(n).ok())
+                            .map(CString::into_raw)
+                            .unwrap_or(null_mut()),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .into_boxed_slice();
+            unsafe {
+                *count = c_arr.len();
+                *entries = c_arr.as_mut_ptr();
+            }
+            std::mem::forget(c_arr);
+            null_mut()
+        }
+        Err(e) => ffi_err!(e),
+    }
+}
+
+/// Frees device entries returned by `idevice_list_devices`
+///
+/// # Arguments
+/// * `entries` - The array of entries to free
+/// * `count` - The number of entries in the array
+///
+/// # Safety
+/// `entries` must be an array of `count` entries returned by `idevice_list_devices`, or NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn idevice_device_entries_free(
+    entries: *mut IdeviceDeviceEntry,
+    count: libc::size_t,
+) {
+    if entries.is_null() {
+        return;
+    }
+    let entries = unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(entries, count)) };
+    for entry in entries.iter() {
+        if !entry.udid.is_null() {
+            let _ = unsafe { CString::from_raw(entry.udid) };
+        }
+        if !entry.device_name.is_null() {
+            let _ = unsafe { CString::from_raw(entry.device_name) };
+        }
+    }
+}
@@ -131,7 +131,8 @@ pub(crate) fn c_socket_to_rust(
     }
 }
 
-pub(crate) fn c_addr_to_rust(addr: *const SockAddr) -> Result<IpAddr, IdeviceError> {
+/// Reads the IP address of a sockaddr, with the zone index of IPv6 addresses (0 for IPv4)
+pub(crate) fn c_addr_to_rust(addr: *const SockAddr) -> Result<(IpAddr, u32), IdeviceError> {
     if addr.is_null() {
         tracing::error!("null sockaddr");
         return invalid_arg();
@@ -148,13 +149,17 @@ pub(crate) fn c_addr_to_rust(addr: *const SockAddr) -> Result<IpAddr, IdeviceErr
             libc::AF_INET => {
                 let a = &*(addr as *const sockaddr_in);
                 let octets = u32::from_be(a.sin_addr.s_addr).to_be_bytes();
-                Ok(IpAddr::V4(Ipv4Addr::new(
-                    octets[0], octets[1], octets[2], octets[3],
-                )))
+                Ok((
+                    IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])),
+                    0,
+                ))
             }
             libc::AF_INET6 => {
                 let a = &*(addr as *const sockaddr_in6);
-                Ok(IpAddr::V6(Ipv6Addr::from(a.sin6_addr.s6_addr)))
+                Ok((
+                    IpAddr::V6(Ipv6Addr::from(a.sin6_addr.s6_addr)),
+                    a.sin6_scope_id,
+                ))
             }
             _ => {
                 tracing::error!(
@@ -170,12 +175,12 @@ pub(crate) fn c_addr_to_rust(addr: *const SockAddr) -> Result<IpAddr, IdeviceErr
             AF_INET => {
                 let a = &*(addr as *const sockaddr_in);
                 let ip_be = a.sin_addr.S_un.S_addr;
-                Ok(IpAddr::V4(Ipv4Addr::from(u32::from_be(ip_be))))
+                Ok((IpAddr::V4(Ipv4Addr::from(u32::from_be(ip_be))), 0))
             }
             AF_INET6 => {
                 let a = &*(addr as *const sockaddr_in6);
                 let bytes: [u8; 16] = a.sin6_addr.u.Byte;
-                Ok(IpAddr::V6(Ipv6Addr::from(bytes)))
+                Ok((IpAddr::V6(Ipv6Addr::from(bytes)), a.Anonymous.sin6_scope_id))
             }
             _ => {
                 tracing::error!("Unsupported socket address family: {}", (*addr).sa_family);
@@ -8,7 +8,9 @@ mod ca;
 pub mod cursor;
 mod obfuscation;
 pub mod pairing_file;
+pub mod plist_ext;
 pub mod provider;
+pub mod retry;
 #[cfg(feature = "rustls")]
 mod sni;
 #[cfg(feature = "tunnel_tcp_stack")]
@@ -23,9 +25,18 @@ pub mod utils;
 #[cfg(feature = "xpc")]
 pub mod xpc;
 
+#[cfg(feature = "testing")]
+pub mod testing;
+
 pub mod services;
 pub use services::*;
 
+pub use plist_ext::PlistExt;
+pub use retry::RetryPolicy;
+#[cfg(any(feature = "syslog_relay", feature = "dvt"))]
+pub use tokio_util::sync::CancellationToken;
+#[cfg(feature = "usbmuxd")]
+pub use usbmuxd::{DeviceEntry, connect_usbmuxd, for_each_device, list_devices, wait_for_device};
 #[cfg(feature = "xpc")]
 pub use xpc::RemoteXpcClient;
 
@@ -35,10 +46,13 @@ use provider::{IdeviceProvider, RsdProvider};
 use rustls::{crypto::CryptoProvider, pki_types::ServerName};
 use std::{
     io::{self, BufWriter},
+    pin::Pin,
     sync::Arc,
+    task::{Context, Poll, Waker},
+    time::Duration,
 };
 use thiserror::Error;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tracing::{debug, trace};
 
 use crate::services::lockdown::LockdownClient;
@@ -55,6 +69,57 @@ pub trait ReadWrite: AsyncRead + AsyncWrite + Unpin + Send + Sync + std::fmt::De
 // Blanket implementation for any compatible type
 impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync + std::fmt::Debug> ReadWrite for T {}
 
+/// Starts a lockdown service by name and connects to it
+///
+/// Used by [`IdeviceService::connect`], and by clients that speak the same protocol on
+/// several service names.
+#[tracing::instrument(level = "debug", skip(provider), fields(label = provider.label()))]
+pub(crate) async fn connect_service_by_name(
+    provider: &dyn IdeviceProvider,
+    service_name: &str,
+) -> Result<Idevice, IdeviceError> {
+    if let Some(idevice) = provider.connect_service(service_name).await? {
+        return Ok(idevice);
+    }
+
+    let mut lockdown = LockdownClient::connect(provider).await?;
+
+    let legacy = lockdown
+        .get_value(Some("ProductVersion"), None)
+        .await
+        .ok()
+        .as_ref()
+        .and_then(|x| x.as_string())
+        .and_then(|x| x.split(".").next())
+        .and_then(|x| x.parse::<u8>().ok())
+        .map(|x| x < 5)
+        .unwrap_or(false);
+
+    lockdown
+        .start_session(&provider.get_pairing_file().await?)
+        .await?;
+    // Best-effort fetch UDID for downstream defaults (e.g., MobileBackup2 Target/Source identifiers)
+    let udid_value = match lockdown.get_value(Some("UniqueDeviceID"), None).await {
+        Ok(v) => v.as_string().map(|s| s.to_string()),
+        Err(_) => None,
+    };
+
+    let (port, ssl) = lockdown.start_service(service_name).await?;
+
+    let mut idevice = provider.connect(port).await?;
+    if ssl {
+        idevice
+            .start_session(&provider.get_pairing_file().await?, legacy)
+            .await?;
+    }
+
+    if let Some(udid) = udid_value {
+        idevice.set_udid(udid);
+    }
+
+    Ok(idevice)
+}
+
 /// Interface for services that can be connected to on an iOS device
 ///
 /// Implement this trait to define new services that can be accessed through the
@@ -74,44 +139,29 @@ pub trait IdeviceService: Sized {
     // │ │ │    `#[warn(async_fn_in_trait)]` on by default rustc (async_fn_in_trait) [66, 5]
     #[allow(async_fn_in_trait)]
     async fn connect(provider: &dyn IdeviceProvider) -> Result<Self, IdeviceError> {
-        let mut lockdown = LockdownClient::connect(provider).await?;
-
-        let legacy = lockdown
-            .get_value(Some("ProductVersion"), None)
-            .await
-            .ok()
-            .as_ref()
-            .and_then(|x| x.as_string())
-            .and_then(|x| x.split(".").next())
-            .and_then(|x| x.parse::<u8>().ok())
-            .map(|x| x < 5)
-            .unwrap_or(false);
-
-        lockdown
-            .start_session(&provider.get_pairing_file().await?)
-            .await?;
-        // Best-effort fetch UDID for downstream defaults (e.g., MobileBackup2 Target/Source identifiers)
-        let udid_value = match lockdown.get_value(Some("UniqueDeviceID"), None).await {
-            Ok(v) => v.as_string().map(|s| s.to_string()),
-            Err(_) => None,
-        };
-
-        let (port, ssl) = lockdown.start_service(Self::service_name()).await?;
-
-        let mut idevice = provider.connect(port).await?;
-        if ssl {
-            idevice
-                .start_session(&provider.get_pairing_file().await?, legacy)
-                .await?;
-        }
-
-        if let Some(udid) = udid_value {
-            idevice.set_udid(udid);
-        }
-
+        let idevice = connect_service_by_name(provider, &Self::service_name()).await?;
         Self::from_stream(idevice).await
     }
 
+    /// Establishes a connection to this service, retrying transient failures
+    ///
+    /// Waits between attempts with an exponential backoff. Errors that the policy doesn't
+    /// consider retryable are returned immediately.
+    ///
+    /// # Arguments
+    /// * `provider` - The device provider that can supply connections
+    /// * `policy` - How many times to retry, how long to wait, and which errors to retry
+    ///
+    /// # Returns
+    /// The connected service, or the last error encountered
+    #[allow(async_fn_in_trait)]
+    async fn connect_with_retry(
+        provider: &dyn IdeviceProvider,
+        policy: RetryPolicy,
+    ) -> Result<Self, IdeviceError> {
+        policy.run(|| Self::connect(provider)).await
+    }
+
     #[allow(async_fn_in_trait)]
     async fn from_stream(idevice: Idevice) -> Result<Self, IdeviceError>;
 }
@@ -139,6 +189,9 @@ pub trait RsdService: Sized {
 /// the required ReadWrite characteristics.
 pub type IdeviceSocket = Box<dyn ReadWrite>;
 
+/// Largest plist a connection accepts by default, see [`Idevice::set_max_message_size`]
+pub const DEFAULT_MAX_MESSAGE_SIZE: u32 = 64 * 1024 * 1024;
+
 /// Main handle for communicating with an iOS device
 ///
 /// Manages the connection socket and provides methods for common device operations
@@ -151,6 +204,12 @@ pub struct Idevice {
     label: String,
     /// Cached device UDID for convenience in higher-level protocols
     udid: Option<String>,
+    /// Largest plist accepted from the device, in bytes
+    max_message_size: u32,
+    /// How long reading a plist may take, if bounded
+    read_timeout: Option<Duration>,
+    /// A byte read ahead by [`Idevice::is_alive`], returned by the next read
+    peeked: Option<u8>,
 }
 
 impl Idevice {
@@ -164,11 +223,90 @@ impl Idevice {
             socket: Some(socket),
             label: label.into(),
             udid: None,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            read_timeout: None,
+            peeked: None,
         }
     }
 
-    pub fn get_socket(self) -> Option<Box<dyn ReadWrite>> {
-        self.socket
+    pub fn get_socket(mut self) -> Option<Box<dyn ReadWrite>> {
+        self.take_socket()
+    }
+
+    /// Takes the socket out, with a byte read ahead by [`Idevice::is_alive`] put back in front
+    fn take_socket(&mut self) -> Option<Box<dyn ReadWrite>> {
+        let socket = self.socket.take()?;
+        Some(match self.peeked.take() {
+            Some(b) => Box::new(PeekedSocket {
+                peeked: Some(b),
+                inner: socket,
+            }),
+            None => socket,
+        })
+    }
+
+    /// Closes the connection by dropping its socket
+    ///
+    /// Later operations on this connection fail with `IdeviceError::NoEstablishedConnection`.
+    pub fn close(&mut self) {
+        self.socket = None;
+        self.peeked = None;
+    }
+
+    /// Closes the connection gracefully
+    ///
+    /// Pending writes are flushed, and the socket is shut down before it's dropped, so the
+    /// device sees a TLS `close_notify` on secure connections and end of stream on TCP,
+    /// rather than a reset. Closing an already closed connection does nothing.
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::Socket` if flushing or shutting down fails. The connection is
+    /// closed either way.
+    pub async fn shutdown(&mut self) -> Result<(), IdeviceError> {
+        self.peeked = None;
+        if let Some(mut socket) = self.socket.take() {
+            socket.flush().await?;
+            socket.shutdown().await?;
+        }
+        Ok(())
+    }
+
+    /// Checks whether the connection is still open, without sending anything
+    ///
+    /// The socket is polled once without waiting. A socket the device closed, or that failed,
+    /// is reported dead and dropped. A socket with nothing to read is reported alive, so a
+    /// connection lost without being closed, such as a network device leaving the network,
+    /// is only noticed by a request. Protocol-level pings like [`LockdownClient::ping`] catch
+    /// those too.
+    pub fn is_alive(&mut self) -> bool {
+        let Some(socket) = &mut self.socket else {
+            return false;
+        };
+        if self.peeked.is_some() {
+            return true;
+        }
+
+        let mut byte = [0u8; 1];
+        let mut buf = ReadBuf::new(&mut byte);
+        let mut cx = Context::from_waker(Waker::noop());
+        match Pin::new(socket).poll_read(&mut cx, &mut buf) {
+            Poll::Pending => true,
+            Poll::Ready(Ok(())) if buf.filled().is_empty() => {
+                debug!("Socket was closed by the device");
+                self.socket = None;
+                false
+            }
+            Poll::Ready(Ok(())) => {
+                // The device sent something unrequested, keep it for the next read
+                self.peeked = Some(byte[0]);
+                true
+            }
+            Poll::Ready(Err(e)) => {
+                debug!("Socket failed: {e:?}");
+                self.socket = None;
+                false
+            }
+        }
     }
 
     /// Sets cached UDID
@@ -181,6 +319,26 @@ impl Idevice {
         self.udid.as_deref()
     }
 
+    /// Sets the largest plist accepted from the device
+    ///
+    /// Plists declaring a larger length fail with `IdeviceError::MessageTooLarge` before
+    /// anything is allocated for them. Defaults to [`DEFAULT_MAX_MESSAGE_SIZE`].
+    pub fn set_max_message_size(&mut self, max: u32) {
+        self.max_message_size = max;
+    }
+
+    /// Bounds how long reading a plist may take
+    ///
+    /// Reads taking longer fail with `IdeviceError::Timeout`. Unbounded by default, since
+    /// some services only answer once something happens on the device, such as an install
+    /// finishing or a notification being posted.
+    ///
+    /// A timed out read may have consumed part of a message, so the connection is closed and
+    /// later operations fail with `IdeviceError::NoEstablishedConnection`.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
     /// Queries the device type
     ///
     /// Sends a QueryType request and parses the response
@@ -248,6 +406,7 @@ impl Idevice {
     ///
     /// # Errors
     /// Returns `IdeviceError` if serialization or transmission fails
+    #[tracing::instrument(level = "debug", skip_all, fields(label = %self.label, udid = self.udid.as_deref()))]
     async fn send_plist(&mut self, message: plist::Value) -> Result<(), IdeviceError> {
         if let Some(socket) = &mut self.socket {
             debug!("Sending plist: {}", pretty_print_plist(&message));
@@ -274,6 +433,7 @@ impl Idevice {
     ///
     /// # Errors
     /// Returns `IdeviceError` if serialization or transmission fails
+    #[tracing::instrument(level = "debug", skip_all, fields(label = %self.label, udid = self.udid.as_deref()))]
     async fn send_bplist(&mut self, message: plist::Value) -> Result<(), IdeviceError> {
         if let Some(socket) = &mut self.socket {
             debug!("Sending plist: {}", pretty_print_plist(&message));
@@ -424,7 +584,7 @@ impl Idevice {
     pub async fn read_raw(&mut self, len: usize) -> Result<Vec<u8>, IdeviceError> {
         if let Some(socket) = &mut self.socket {
             let mut buf = vec![0; len];
-            socket.read_exact(&mut buf).await?;
+            read_exact_peeked(socket, &mut self.peeked, &mut buf).await?;
             Ok(buf)
         } else {
             Err(IdeviceError::NoEstablishedConnection)
@@ -443,6 +603,11 @@ impl Idevice {
     /// Returns `IdeviceError` if reading fails
     pub async fn read_any(&mut self, max_size: u32) -> Result<Vec<u8>, IdeviceError> {
         if let Some(socket) = &mut self.socket {
+            if max_size > 0
+                && let Some(b) = self.peeked.take()
+            {
+                return Ok(vec![b]);
+            }
             let mut buf = vec![0; max_size as usize];
             let len = socket.read(&mut buf).await?;
             Ok(buf[..len].to_vec())
@@ -493,12 +658,32 @@ impl Idevice {
         Ok(res)
     }
 
+    #[tracing::instrument(level = "debug", skip_all, fields(label = %self.label, udid = self.udid.as_deref()))]
     async fn read_plist_value(&mut self) -> Result<plist::Value, IdeviceError> {
+        match self.read_timeout {
+            Some(timeout) => {
+                match tokio::time::timeout(timeout, self.read_plist_value_unbounded()).await {
+                    Ok(res) => res,
+                    Err(_) => {
+                        // The rest of a partly read message would be taken for the next one
+                        self.close();
+                        Err(IdeviceError::Timeout)
+                    }
+                }
+            }
+            None => self.read_plist_value_unbounded().await,
+        }
+    }
+
+    async fn read_plist_value_unbounded(&mut self) -> Result<plist::Value, IdeviceError> {
         if let Some(socket) = &mut self.socket {
             debug!("Reading response size");
             let mut buf = [0u8; 4];
-            socket.read_exact(&mut buf).await?;
+            read_exact_peeked(socket, &mut self.peeked, &mut buf).await?;
             let len = u32::from_be_bytes(buf);
+            if len > self.max_message_size {
+                return Err(IdeviceError::MessageTooLarge(len));
+            }
             let mut buf = vec![0; len as usize];
             socket.read_exact(&mut buf).await?;
             let res: plist::Value = plist::from_bytes(&buf)?;
@@ -508,32 +693,39 @@ impl Idevice {
         }
     }
 
+    /// Reads until the next byte that is any of `delimiters`, returning the data before it
+    ///
+    /// `buffer` carries bytes read past the delimiter over to the next call, so it must be
+    /// kept between calls on the same stream.
     #[cfg(feature = "syslog_relay")]
     async fn read_until_delim(
         &mut self,
-        delimiter: &[u8],
+        delimiters: &[u8],
+        buffer: &mut bytes::BytesMut,
     ) -> Result<Option<bytes::BytesMut>, IdeviceError> {
         if let Some(socket) = &mut self.socket {
-            let mut buffer = bytes::BytesMut::with_capacity(1024);
+            if let Some(b) = self.peeked.take() {
+                buffer.extend_from_slice(&[b]);
+            }
             let mut temp = [0u8; 1024];
 
             loop {
+                if let Some(pos) = buffer.iter().position(|b| delimiters.contains(b)) {
+                    let mut line = buffer.split_to(pos + 1);
+                    line.truncate(pos); // remove delimiter
+                    return Ok(Some(line));
+                }
+
                 let n = socket.read(&mut temp).await?;
                 if n == 0 {
                     if buffer.is_empty() {
                         return Ok(None); // EOF and no data
                     } else {
-                        return Ok(Some(buffer)); // EOF but return partial data
+                        return Ok(Some(buffer.split())); // EOF but return partial data
                     }
                 }
 
                 buffer.extend_from_slice(&temp[..n]);
-
-                if let Some(pos) = buffer.windows(delimiter.len()).position(|w| w == delimiter) {
-                    let mut line = buffer.split_to(pos + delimiter.len());
-                    line.truncate(line.len() - delimiter.len()); // remove delimiter
-                    return Ok(Some(line));
-                }
             }
         } else {
             Err(IdeviceError::NoEstablishedConnection)
@@ -605,7 +797,7 @@ impl Idevice {
             let config = sni::create_client_config(pairing_file)?;
             let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
 
-            let socket = self.socket.take().unwrap();
+            let socket = self.take_socket().unwrap();
             let socket = connector
                 .connect(ServerName::try_from("Device").unwrap(), socket)
                 .await?;
@@ -630,7 +822,7 @@ impl Idevice {
             connector.set_certificate(&pairing_file.host_certificate)?;
             connector.set_private_key(&pairing_file.host_private_key)?;
             connector.set_verify(openssl::ssl::SslVerifyMode::empty());
-            let socket = self.socket.take().unwrap();
+            let socket = self.take_socket().unwrap();
             let mut ssl_stream = tokio_openssl::SslStream::new(connector, socket)?;
             std::pin::Pin::new(&mut ssl_stream).connect().await?;
             self.socket = Some(Box::new(ssl_stream));
@@ -640,6 +832,67 @@ impl Idevice {
     }
 }
 
+/// Fills `buf` from the socket, starting with a byte read ahead by [`Idevice::is_alive`]
+async fn read_exact_peeked(
+    socket: &mut Box<dyn ReadWrite>,
+    peeked: &mut Option<u8>,
+    buf: &mut [u8],
+) -> io::Result<()> {
+    let buf = match (peeked.take(), buf.split_first_mut()) {
+        (Some(b), Some((first, rest))) => {
+            *first = b;
+            rest
+        }
+        (b, _) => {
+            *peeked = b;
+            buf
+        }
+    };
+    socket.read_exact(buf).await?;
+    Ok(())
+}
+
+/// A socket with a byte read ahead by [`Idevice::is_alive`] put back in front of it
+#[derive(Debug)]
+struct PeekedSocket {
+    peeked: Option<u8>,
+    inner: Box<dyn ReadWrite>,
+}
+
+impl AsyncRead for PeekedSocket {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if buf.remaining() > 0
+            && let Some(b) = self.peeked.take()
+        {
+            buf.put_slice(&[b]);
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for PeekedSocket {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
 /// Comprehensive error type for all device communication failures
 #[derive(Error, Debug)]
 #[repr(i32)]
@@ -659,13 +912,13 @@ pub enum IdeviceError {
     Rustls(#[from] openssl::ssl::Error) = -3,
 
     #[cfg(feature = "rustls")]
-    #[error("TLS verifiction build failed")]
+    #[error("TLS verification build failed")]
     TlsBuilderFailed(#[from] rustls::server::VerifierBuilderError) = -4,
     #[cfg(all(feature = "openssl", not(feature = "rustls")))]
-    #[error("TLS verifiction build failed")]
+    #[error("TLS verification build failed")]
     TlsBuilderFailed(#[from] openssl::error::ErrorStack) = -4,
 
-    #[error("io on plist")]
+    #[error("plist error")]
     Plist(#[from] plist::Error) = -5,
     #[error("can't convert bytes to utf8")]
     Utf8(#[from] std::string::FromUtf8Error) = -6,
@@ -701,7 +954,7 @@ pub enum IdeviceError {
     #[error("device not found")]
     DeviceNotFound = -20,
 
-    #[error("device lockded")]
+    #[error("device locked")]
     DeviceLocked = -21,
 
     #[error("device refused connection")]
@@ -726,8 +979,8 @@ pub enum IdeviceError {
     #[error("user denied pairing trust")]
     UserDeniedPairing = -29,
 
-    #[cfg(feature = "pair")]
-    #[error("device is locked")]
+    /// The device is locked with a passcode and refused the request until it is unlocked
+    #[error("device is locked with a passcode")]
     PasswordProtected = -30,
 
     #[cfg(feature = "misagent")]
@@ -735,11 +988,11 @@ pub enum IdeviceError {
     MisagentFailure = -31,
 
     #[cfg(feature = "installation_proxy")]
-    #[error("installation proxy operation failed")]
+    #[error("installation proxy operation failed: {0}")]
     InstallationProxyOperationFailed(String) = -32,
 
     #[cfg(feature = "afc")]
-    #[error("afc error: {0}")]
+    #[error("afc error")]
     Afc(#[from] afc::errors::AfcError) = -33,
 
     #[cfg(feature = "afc")]
@@ -755,22 +1008,22 @@ pub enum IdeviceError {
     AfcMissingAttribute = -36,
 
     #[cfg(feature = "crashreportcopymobile")]
-    #[error("crash report mover sent the wrong response")]
+    #[error("crash report mover sent the wrong response: {0:02X?}")]
     CrashReportMoverBadResponse(Vec<u8>) = -37,
 
     #[cfg(any(feature = "tss", feature = "tunneld"))]
     #[error("http reqwest error")]
     Reqwest(#[from] reqwest::Error) = -38,
 
-    #[error("internal error")]
+    #[error("internal error: {0}")]
     InternalError(String) = -39,
 
     #[cfg(feature = "xpc")]
-    #[error("unknown http frame type")]
+    #[error("unknown http frame type {0}")]
     UnknownFrame(u8) = -40,
 
     #[cfg(feature = "xpc")]
-    #[error("unknown http setting type")]
+    #[error("unknown http setting type {0}")]
     UnknownHttpSetting(u16) = -41,
 
     #[cfg(feature = "xpc")]
@@ -778,7 +1031,7 @@ pub enum IdeviceError {
     UninitializedStreamId = -42,
 
     #[cfg(feature = "xpc")]
-    #[error("unknown XPC type")]
+    #[error("unknown XPC type {0:#x}")]
     UnknownXpcType(u32) = -43,
 
     #[cfg(feature = "xpc")]
@@ -802,7 +1055,7 @@ pub enum IdeviceError {
     HttpStreamReset = -48,
 
     #[cfg(feature = "xpc")]
-    #[error("go away packet received")]
+    #[error("go away packet received: {0}")]
     HttpGoAway(String) = -49,
 
     #[cfg(feature = "dvt")]
@@ -810,11 +1063,11 @@ pub enum IdeviceError {
     NsKeyedArchiveError(#[from] ns_keyed_archive::ConverterError) = -50,
 
     #[cfg(feature = "dvt")]
-    #[error("Unknown aux value type")]
+    #[error("unknown aux value type {0}")]
     UnknownAuxValueType(u32) = -51,
 
     #[cfg(feature = "dvt")]
-    #[error("unknown channel")]
+    #[error("unknown channel {0}")]
     UnknownChannel(u32) = -52,
 
     #[error("cannot parse string as IpAddr")]
@@ -830,10 +1083,7 @@ pub enum IdeviceError {
     #[error("failed to parse bytes as valid utf8")]
     Utf8Error = -56,
 
-    #[cfg(any(
-        feature = "debug_proxy",
-        all(feature = "afc", feature = "installation_proxy")
-    ))]
+    #[cfg(any(feature = "debug_proxy", feature = "afc"))]
     #[error("invalid argument passed")]
     InvalidArgument = -57,
 
@@ -856,11 +1106,59 @@ pub enum IdeviceError {
     CanceledByUser = -66,
 
     #[cfg(feature = "installation_proxy")]
-    #[error("malformed package archive: {0}")]
+    #[error("malformed package archive")]
     MalformedPackageArchive(#[from] async_zip::error::ZipError) = -67,
 
     #[error("Developer mode is not enabled")]
     DeveloperModeNotEnabled = -68,
+
+    #[error("operation timed out")]
+    Timeout = -69,
+
+    #[error("runtime is already initialized")]
+    FfiRuntimeAlreadyInitialized = -70,
+
+    #[error("debugserver returned an error: {0}")]
+    DebugserverError(String) = -71,
+
+    #[cfg(feature = "afc")]
+    #[error("link could not be created")]
+    AfcLinkFailed(#[source] afc::errors::AfcError) = -72,
+
+    #[error("operation was canceled")]
+    Canceled = -73,
+
+    #[cfg(feature = "house_arrest")]
+    #[error("no app with bundle id {0} is installed")]
+    HouseArrestAppNotFound(String) = -74,
+
+    #[cfg(feature = "house_arrest")]
+    #[error("app {0} doesn't share its documents")]
+    HouseArrestDocumentsNotShared(String) = -75,
+
+    #[error("response is missing key {key}")]
+    MissingKey { key: String } = -76,
+
+    #[error("not supported by the service: {0}")]
+    Unsupported(String) = -77,
+
+    #[error("invalid pair record: {0}")]
+    InvalidPairRecord(String) = -78,
+
+    #[cfg(feature = "installation_proxy")]
+    #[error("app {0} is still installed after uninstalling it")]
+    AppStillInstalled(String) = -79,
+
+    #[error("message is larger than allowed: {0} bytes")]
+    MessageTooLarge(u32) = -80,
+
+    #[cfg(feature = "mcinstall")]
+    #[error("configuration profile request failed: {0}")]
+    McInstallFailed(String) = -81,
+
+    #[cfg(feature = "afc")]
+    #[error("not enough free space on the device: {needed} bytes needed, {available} available")]
+    NotEnoughSpace { needed: u64, available: u64 } = -82,
 }
 
 impl IdeviceError {
@@ -887,7 +1185,6 @@ impl IdeviceError {
             "PairingDialogResponsePending" => Some(Self::PairingDialogResponsePending),
             #[cfg(feature = "pair")]
             "UserDeniedPairing" => Some(Self::UserDeniedPairing),
-            #[cfg(feature = "pair")]
             "PasswordProtected" => Some(Self::PasswordProtected),
             "UnsupportedWatchKey" => Some(Self::UnsupportedWatchKey),
             "MalformedCommand" => Some(Self::MalformedCommand),
@@ -908,6 +1205,47 @@ impl IdeviceError {
         }
     }
 
+    /// Whether the error is likely to go away if the operation is retried
+    ///
+    /// This covers the device or service not being ready yet, such as a refused connection
+    /// right after the device unlocks. Malformed data and unsupported requests are never transient.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            IdeviceError::Socket(e) => matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::UnexpectedEof
+            ),
+            IdeviceError::UsbConnectionRefused | IdeviceError::Timeout => true,
+            #[cfg(feature = "pair")]
+            IdeviceError::PairingDialogResponsePending => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the error means the connection to the device was lost or couldn't be made
+    ///
+    /// The client that returned it should be discarded and a new connection made.
+    pub fn is_connection_error(&self) -> bool {
+        match self {
+            IdeviceError::Socket(_)
+            | IdeviceError::NoEstablishedConnection
+            | IdeviceError::DeviceNotFound
+            | IdeviceError::UsbConnectionRefused
+            | IdeviceError::HeartbeatSleepyTime
+            | IdeviceError::HeartbeatTimeout
+            | IdeviceError::Timeout
+            | IdeviceError::Canceled => true,
+            #[cfg(feature = "xpc")]
+            IdeviceError::HttpStreamReset | IdeviceError::HttpGoAway(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn code(&self) -> i32 {
         match self {
             IdeviceError::Socket(_) => -1,
@@ -946,7 +1284,6 @@ impl IdeviceError {
             IdeviceError::PairingDialogResponsePending => -28,
             #[cfg(feature = "pair")]
             IdeviceError::UserDeniedPairing => -29,
-            #[cfg(feature = "pair")]
             IdeviceError::PasswordProtected => -30,
 
             #[cfg(feature = "misagent")]
@@ -1008,10 +1345,7 @@ impl IdeviceError {
             IdeviceError::NotEnoughBytes(_, _) => -55,
             IdeviceError::Utf8Error => -56,
 
-            #[cfg(any(
-                feature = "debug_proxy",
-                all(feature = "afc", feature = "installation_proxy")
-            ))]
+            #[cfg(any(feature = "debug_proxy", feature = "afc"))]
             IdeviceError::InvalidArgument => -57,
 
             IdeviceError::UnknownErrorType(_) => -59,
@@ -1026,6 +1360,297 @@ impl IdeviceError {
             #[cfg(feature = "installation_proxy")]
             IdeviceError::MalformedPackageArchive(_) => -67,
             IdeviceError::DeveloperModeNotEnabled => -68,
+            IdeviceError::Timeout => -69,
+            IdeviceError::FfiRuntimeAlreadyInitialized => -70,
+            IdeviceError::DebugserverError(_) => -71,
+            #[cfg(feature = "afc")]
+            IdeviceError::AfcLinkFailed(_) => -72,
+            IdeviceError::Canceled => -73,
+
+            #[cfg(feature = "house_arrest")]
+            IdeviceError::HouseArrestAppNotFound(_) => -74,
+            #[cfg(feature = "house_arrest")]
+            IdeviceError::HouseArrestDocumentsNotShared(_) => -75,
+            IdeviceError::MissingKey { .. } => -76,
+            IdeviceError::Unsupported(_) => -77,
+            IdeviceError::InvalidPairRecord(_) => -78,
+            #[cfg(feature = "installation_proxy")]
+            IdeviceError::AppStillInstalled(_) => -79,
+            IdeviceError::MessageTooLarge(_) => -80,
+            #[cfg(feature = "mcinstall")]
+            IdeviceError::McInstallFailed(_) => -81,
+            #[cfg(feature = "afc")]
+            IdeviceError::NotEnoughSpace { .. } => -82,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_oversized_plist() {
+        let (host, mut device) = tokio::io::duplex(64);
+        let mut idevice = Idevice::new(Box::new(host), "test");
+        idevice.set_max_message_size(1024);
+
+        device.write_u32(u32::MAX).await.unwrap();
+        assert!(matches!(
+            idevice.read_plist_value().await,
+            Err(IdeviceError::MessageTooLarge(u32::MAX))
+        ));
+
+        idevice.set_read_timeout(Some(Duration::from_millis(10)));
+        device.write_all(&[0, 0]).await.unwrap();
+        assert!(matches!(
+            idevice.read_plist_value().await,
+            Err(IdeviceError::Timeout)
+        ));
+        assert!(matches!(
+            idevice.read_plist_value().await,
+            Err(IdeviceError::NoEstablishedConnection)
+        ));
+    }
+
+    #[tokio::test]
+    async fn shutdown_flushes_and_ends_stream() {
+        let (host, mut device) = tokio::io::duplex(64);
+        let mut idevice = Idevice::new(Box::new(tokio::io::BufWriter::new(host)), "test");
+
+        idevice
+            .socket
+            .as_mut()
+            .unwrap()
+            .write_all(b"bye")
+            .await
+            .unwrap();
+        idevice.shutdown().await.unwrap();
+
+        let mut received = Vec::new();
+        device.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, b"bye");
+        assert!(matches!(
+            idevice.send_raw(b"again").await,
+            Err(IdeviceError::NoEstablishedConnection)
+        ));
+        idevice.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn is_alive_keeps_unread_data() {
+        let (host, mut device) = tokio::io::duplex(64);
+        let mut idevice = Idevice::new(Box::new(host), "test");
+        assert!(idevice.is_alive());
+
+        device.write_all(b"hi").await.unwrap();
+        assert!(idevice.is_alive());
+        assert!(idevice.is_alive());
+        assert_eq!(idevice.read_raw(2).await.unwrap(), b"hi");
+
+        device.write_all(b"again").await.unwrap();
+        assert!(idevice.is_alive());
+        let mut socket = idevice.take_socket().unwrap();
+        let mut buf = [0; 5];
+        socket.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"again");
+        idevice.socket = Some(socket);
+
+        drop(device);
+        assert!(!idevice.is_alive());
+        assert!(!idevice.is_alive());
+    }
+
+    #[test]
+    fn classifies_transient_errors() {
+        let socket = |kind| IdeviceError::Socket(io::Error::from(kind));
+        for e in [
+            socket(io::ErrorKind::ConnectionRefused),
+            socket(io::ErrorKind::ConnectionReset),
+            socket(io::ErrorKind::BrokenPipe),
+            socket(io::ErrorKind::UnexpectedEof),
+            IdeviceError::UsbConnectionRefused,
+            IdeviceError::Timeout,
+        ] {
+            assert!(e.is_transient(), "{e:?} should be transient");
+        }
+        for e in [
+            socket(io::ErrorKind::PermissionDenied),
+            IdeviceError::DeviceNotFound,
+            IdeviceError::InvalidHostID,
+            IdeviceError::Canceled,
+            IdeviceError::UnexpectedResponse,
+        ] {
+            assert!(!e.is_transient(), "{e:?} shouldn't be transient");
+        }
+    }
+
+    #[test]
+    fn classifies_connection_errors() {
+        for e in [
+            IdeviceError::Socket(io::Error::from(io::ErrorKind::PermissionDenied)),
+            IdeviceError::NoEstablishedConnection,
+            IdeviceError::DeviceNotFound,
+            IdeviceError::HeartbeatTimeout,
+            IdeviceError::Timeout,
+            IdeviceError::Canceled,
+        ] {
+            assert!(
+                e.is_connection_error(),
+                "{e:?} should be a connection error"
+            );
+        }
+        for e in [
+            IdeviceError::InvalidHostID,
+            IdeviceError::SessionInactive,
+            IdeviceError::UnexpectedResponse,
+            IdeviceError::ServiceNotFound,
+        ] {
+            assert!(
+                !e.is_connection_error(),
+                "{e:?} shouldn't be a connection error"
+            );
+        }
+    }
+
+    #[test]
+    fn error_messages_are_distinct() {
+        use std::error::Error;
+
+        let mut errors = vec![
+            IdeviceError::Socket(io::Error::from(io::ErrorKind::ConnectionRefused)),
+            IdeviceError::Plist(plist::from_bytes::<plist::Value>(b"x").unwrap_err()),
+            IdeviceError::Utf8(String::from_utf8(vec![0xff]).unwrap_err()),
+            IdeviceError::UnexpectedResponse,
+            IdeviceError::GetProhibited,
+            IdeviceError::SessionInactive,
+            IdeviceError::InvalidHostID,
+            IdeviceError::NoEstablishedConnection,
+            IdeviceError::HeartbeatSleepyTime,
+            IdeviceError::HeartbeatTimeout,
+            IdeviceError::NotFound,
+            IdeviceError::ServiceNotFound,
+            IdeviceError::CdtunnelPacketTooShort,
+            IdeviceError::CdtunnelPacketInvalidMagic,
+            IdeviceError::PacketSizeMismatch,
+            IdeviceError::DeviceNotFound,
+            IdeviceError::DeviceLocked,
+            IdeviceError::UsbConnectionRefused,
+            IdeviceError::UsbBadCommand,
+            IdeviceError::UsbBadDevice,
+            IdeviceError::UsbBadVersion,
+            IdeviceError::BadBuildManifest,
+            IdeviceError::ImageNotMounted,
+            IdeviceError::PasswordProtected,
+            IdeviceError::InternalError("details".into()),
+            IdeviceError::AddrParseError("x".parse::<std::net::IpAddr>().unwrap_err()),
+            IdeviceError::NotEnoughBytes(1, 2),
+            IdeviceError::Utf8Error,
+            IdeviceError::UnknownErrorType("Oops".into()),
+            IdeviceError::FfiInvalidArg,
+            IdeviceError::FfiInvalidString,
+            IdeviceError::FfiBufferTooSmall(2, 1),
+            IdeviceError::UnsupportedWatchKey,
+            IdeviceError::MalformedCommand,
+            IdeviceError::IntegerOverflow,
+            IdeviceError::CanceledByUser,
+            IdeviceError::DeveloperModeNotEnabled,
+            IdeviceError::Timeout,
+            IdeviceError::FfiRuntimeAlreadyInitialized,
+            IdeviceError::DebugserverError("E01".into()),
+            IdeviceError::Canceled,
+            IdeviceError::MissingKey { key: "Key".into() },
+            IdeviceError::Unsupported("chmod".into()),
+            IdeviceError::InvalidPairRecord("missing HostID".into()),
+            IdeviceError::MessageTooLarge(1 << 30),
+        ];
+        #[cfg(feature = "rustls")]
+        errors.extend([
+            IdeviceError::PemParseFailed(rustls::pki_types::pem::Error::NoItemsFound),
+            IdeviceError::Rustls(rustls::Error::General("handshake".into())),
+            IdeviceError::TlsBuilderFailed(rustls::server::VerifierBuilderError::NoRootAnchors),
+        ]);
+        // Reqwest errors can only be created by reqwest, so `Reqwest` isn't covered
+        #[cfg(feature = "core_device_proxy")]
+        errors.push(IdeviceError::Json(
+            serde_json::from_str::<u8>("x").unwrap_err(),
+        ));
+        #[cfg(feature = "pair")]
+        errors.extend([
+            IdeviceError::PairingDialogResponsePending,
+            IdeviceError::UserDeniedPairing,
+        ]);
+        #[cfg(feature = "misagent")]
+        errors.push(IdeviceError::MisagentFailure);
+        #[cfg(feature = "mcinstall")]
+        errors.push(IdeviceError::McInstallFailed(
+            "Removal is not allowed".into(),
+        ));
+        #[cfg(feature = "installation_proxy")]
+        errors.extend([
+            IdeviceError::InstallationProxyOperationFailed("APIInternalError".into()),
+            IdeviceError::AppStillInstalled("com.example.app".into()),
+            IdeviceError::MalformedPackageArchive(async_zip::error::ZipError::FeatureNotSupported(
+                "zip64",
+            )),
+        ]);
+        #[cfg(feature = "afc")]
+        errors.extend([
+            IdeviceError::Afc(afc::errors::AfcError::ObjectNotFound),
+            IdeviceError::UnknownAfcOpcode,
+            IdeviceError::InvalidAfcMagic,
+            IdeviceError::AfcMissingAttribute,
+            IdeviceError::AfcLinkFailed(afc::errors::AfcError::ObjectExists),
+            IdeviceError::NotEnoughSpace {
+                needed: 2048,
+                available: 1024,
+            },
+        ]);
+        #[cfg(any(feature = "debug_proxy", feature = "afc"))]
+        errors.push(IdeviceError::InvalidArgument);
+        #[cfg(feature = "crashreportcopymobile")]
+        errors.push(IdeviceError::CrashReportMoverBadResponse(b"pong".to_vec()));
+        #[cfg(feature = "xpc")]
+        errors.extend([
+            IdeviceError::UnknownFrame(9),
+            IdeviceError::UnknownHttpSetting(9),
+            IdeviceError::UninitializedStreamId,
+            IdeviceError::UnknownXpcType(9),
+            IdeviceError::MalformedXpc,
+            IdeviceError::InvalidXpcMagic,
+            IdeviceError::UnexpectedXpcVersion,
+            IdeviceError::InvalidCString,
+            IdeviceError::HttpStreamReset,
+            IdeviceError::HttpGoAway("NO_ERROR".into()),
+        ]);
+        #[cfg(feature = "dvt")]
+        errors.extend([
+            IdeviceError::NsKeyedArchiveError(
+                ns_keyed_archive::ConverterError::InvalidObjectEncoding(0),
+            ),
+            IdeviceError::UnknownAuxValueType(9),
+            IdeviceError::UnknownChannel(9),
+            IdeviceError::DisableMemoryLimitFailed,
+        ]);
+        #[cfg(feature = "house_arrest")]
+        errors.extend([
+            IdeviceError::HouseArrestAppNotFound("com.example.app".into()),
+            IdeviceError::HouseArrestDocumentsNotShared("com.example.app".into()),
+        ]);
+
+        let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        let distinct = messages.iter().collect::<std::collections::HashSet<_>>();
+        assert!(messages.iter().all(|m| !m.is_empty()));
+        assert_eq!(distinct.len(), messages.len(), "{messages:#?}");
+
+        // Wrapped errors are exposed as the source rather than repeated in the message
+        assert_eq!(messages[0], "device socket io failed");
+        assert!(errors[0].source().is_some());
+        #[cfg(feature = "afc")]
+        assert!(
+            IdeviceError::AfcLinkFailed(afc::errors::AfcError::ObjectExists)
+                .source()
+                .is_some()
+        );
+    }
+}
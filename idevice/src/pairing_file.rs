@@ -80,6 +80,18 @@ struct RawPairingFile {
     udid: Option<String>,
 }
 
+/// Keys a pair record needs to be usable, the escrow bag and UDID are optional
+const REQUIRED_PAIR_RECORD_KEYS: &[&str] = &[
+    "DeviceCertificate",
+    "HostPrivateKey",
+    "HostCertificate",
+    "RootPrivateKey",
+    "RootCertificate",
+    "SystemBUID",
+    "HostID",
+    "WiFiMACAddress",
+];
+
 impl PairingFile {
     /// Reads a pairing file from disk
     ///
@@ -130,6 +142,34 @@ impl PairingFile {
         }
     }
 
+    /// Parses a pair record, describing what is wrong with it if it can't be used
+    ///
+    /// Accepts the same records as [`Self::from_bytes`], which reports every problem as an
+    /// unexpected response.
+    ///
+    /// # Arguments
+    /// * `bytes` - Raw bytes of the pair record, in XML or binary PLIST format
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::InvalidPairRecord` if the record isn't a dictionary, is missing
+    /// a required key, or holds malformed certificates or keys
+    pub fn from_pair_record(bytes: &[u8]) -> Result<Self, crate::IdeviceError> {
+        let invalid = crate::IdeviceError::InvalidPairRecord;
+        let record: plist::Dictionary = plist::from_bytes(bytes)
+            .map_err(|e| invalid(format!("not a plist dictionary: {e}")))?;
+        if let Some(key) = REQUIRED_PAIR_RECORD_KEYS
+            .iter()
+            .find(|k| !record.contains_key(k))
+        {
+            return Err(invalid(format!("missing {key}")));
+        }
+
+        let raw: RawPairingFile = plist::from_value(&plist::Value::Dictionary(record))
+            .map_err(|e| invalid(format!("malformed field: {e}")))?;
+        raw.try_into()
+            .map_err(|e| invalid(format!("malformed certificate or key: {e:?}")))
+    }
+
     /// Creates a pairing file from a plist value
     ///
     /// # Arguments
@@ -361,3 +401,24 @@ fn test_pairing_file_roundtrip() {
 
     assert_eq!(f[..output.len()], output);
 }
+
+#[test]
+fn rejects_incomplete_pair_record() {
+    let mut record = Vec::new();
+    crate::plist!({
+        "DeviceCertificate": b"".to_vec(),
+        "HostCertificate": b"".to_vec(),
+        "HostID": "host",
+    })
+    .to_writer_xml(&mut record)
+    .unwrap();
+
+    assert!(matches!(
+        PairingFile::from_pair_record(&record),
+        Err(crate::IdeviceError::InvalidPairRecord(e)) if e == "missing HostPrivateKey"
+    ));
+    assert!(matches!(
+        PairingFile::from_pair_record(b"not a plist"),
+        Err(crate::IdeviceError::InvalidPairRecord(_))
+    ));
+}
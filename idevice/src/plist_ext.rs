@@ -0,0 +1,106 @@
+//! Typed access to the keys of plist responses
+//!
+//! Services reply with plist dictionaries, and reading a key usually means checking that it
+//! exists and has the expected type. [`PlistExt`] does both, so a missing key is always
+//! reported as `IdeviceError::MissingKey` naming the key.
+
+use plist::{Dictionary, Value};
+
+use crate::IdeviceError;
+
+/// Typed getters for the keys of a plist dictionary
+///
+/// Each getter returns `IdeviceError::MissingKey` if the key is absent, and
+/// `IdeviceError::UnexpectedResponse` if its value has another type.
+pub trait PlistExt {
+    /// Returns the value of a key, whatever its type
+    fn get_required(&self, key: &str) -> Result<&Value, IdeviceError>;
+
+    /// Returns the string value of a key
+    fn get_str(&self, key: &str) -> Result<&str, IdeviceError> {
+        self.get_required(key)?
+            .as_string()
+            .ok_or(IdeviceError::UnexpectedResponse)
+    }
+
+    /// Returns the unsigned integer value of a key
+    fn get_u64(&self, key: &str) -> Result<u64, IdeviceError> {
+        self.get_required(key)?
+            .as_unsigned_integer()
+            .ok_or(IdeviceError::UnexpectedResponse)
+    }
+
+    /// Returns the boolean value of a key
+    fn get_bool(&self, key: &str) -> Result<bool, IdeviceError> {
+        self.get_required(key)?
+            .as_boolean()
+            .ok_or(IdeviceError::UnexpectedResponse)
+    }
+
+    /// Returns the data value of a key
+    fn get_data(&self, key: &str) -> Result<&[u8], IdeviceError> {
+        self.get_required(key)?
+            .as_data()
+            .ok_or(IdeviceError::UnexpectedResponse)
+    }
+
+    /// Returns the dictionary value of a key
+    fn get_dict(&self, key: &str) -> Result<&Dictionary, IdeviceError> {
+        self.get_required(key)?
+            .as_dictionary()
+            .ok_or(IdeviceError::UnexpectedResponse)
+    }
+}
+
+impl PlistExt for Dictionary {
+    fn get_required(&self, key: &str) -> Result<&Value, IdeviceError> {
+        self.get(key).ok_or_else(|| IdeviceError::MissingKey {
+            key: key.to_string(),
+        })
+    }
+}
+
+impl PlistExt for Value {
+    /// Looks the key up if this is a dictionary. Any other value has no keys.
+    fn get_required(&self, key: &str) -> Result<&Value, IdeviceError> {
+        match self.as_dictionary() {
+            Some(dict) => dict.get_required(key),
+            None => Err(IdeviceError::MissingKey {
+                key: key.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_missing_and_mistyped_keys() {
+        let response = crate::plist!({
+            "Status": "Complete",
+            "Count": 3,
+            "Identifiers": { "BoardId": 8 },
+        });
+
+        assert_eq!(response.get_str("Status").unwrap(), "Complete");
+        assert_eq!(response.get_u64("Count").unwrap(), 3);
+        assert_eq!(
+            response
+                .get_dict("Identifiers")
+                .unwrap()
+                .get_u64("BoardId")
+                .unwrap(),
+            8
+        );
+        assert!(matches!(
+            response.get_bool("Enabled"),
+            Err(IdeviceError::MissingKey { key }) if key == "Enabled"
+        ));
+        assert!(matches!(
+            response.get_data("Status"),
+            Err(IdeviceError::UnexpectedResponse)
+        ));
+    }
+}
@@ -3,7 +3,9 @@
 //! Provides abstractions for establishing connections to iOS devices through different
 //! transport mechanisms (TCP, USB, etc.).
 
-use std::{future::Future, pin::Pin};
+#[cfg(feature = "usbmuxd")]
+use std::path::Path;
+use std::{future::Future, pin::Pin, time::Duration};
 
 #[cfg(feature = "tcp")]
 use tokio::net::TcpStream;
@@ -40,6 +42,42 @@ pub trait IdeviceProvider: Unpin + Send + Sync + std::fmt::Debug {
     fn get_pairing_file(
         &self,
     ) -> Pin<Box<dyn Future<Output = Result<PairingFile, IdeviceError>> + Send>>;
+
+    /// Sets how long connecting may take before failing with `IdeviceError::Timeout`
+    ///
+    /// `None` waits indefinitely. Providers that don't support a timeout ignore this.
+    fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        let _ = timeout;
+    }
+
+    /// Connects directly to a service by its lockdown name, bypassing lockdown
+    ///
+    /// [`crate::IdeviceService::connect`] tries this first, and falls back to starting the
+    /// service through lockdown when it resolves to `None`, which is the default.
+    ///
+    /// # Arguments
+    /// * `service_name` - The lockdown service name, such as `com.apple.afc`
+    fn connect_service(
+        &self,
+        service_name: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Idevice>, IdeviceError>> + Send>> {
+        let _ = service_name;
+        Box::pin(async { Ok(None) })
+    }
+}
+
+/// Bounds a connection attempt by an optional timeout
+#[cfg(any(feature = "tcp", feature = "usbmuxd"))]
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    connect: impl Future<Output = Result<T, IdeviceError>>,
+) -> Result<T, IdeviceError> {
+    match timeout {
+        Some(t) => tokio::time::timeout(t, connect)
+            .await
+            .map_err(|_| IdeviceError::Timeout)?,
+        None => connect.await,
+    }
 }
 
 pub trait RsdProvider: Unpin + Send + Sync + std::fmt::Debug {
@@ -59,6 +97,62 @@ pub struct TcpProvider {
     pub pairing_file: PairingFile,
     /// Label identifying this connection
     pub label: String,
+    /// How long connecting may take, or `None` to wait indefinitely
+    pub connect_timeout: Option<Duration>,
+    /// Zone index of the interface the device is reached through, or 0 for none
+    pub scope_id: u32,
+}
+
+#[cfg(feature = "tcp")]
+impl TcpProvider {
+    /// Creates a provider for a device at `addr`
+    ///
+    /// # Arguments
+    /// * `addr` - IP address of the device
+    /// * `pairing_file` - Pairing file for the device
+    /// * `label` - Connection label
+    pub fn new(
+        addr: std::net::IpAddr,
+        pairing_file: PairingFile,
+        label: impl Into<String>,
+    ) -> Self {
+        Self {
+            addr,
+            pairing_file,
+            label: label.into(),
+            connect_timeout: None,
+            scope_id: 0,
+        }
+    }
+
+    /// Sets the zone index of the interface the device is reached through
+    ///
+    /// Link-local IPv6 addresses (`fe80::/10`) are only reachable with the index of the
+    /// interface they belong to, like `fe80::1%en0`. Ignored for IPv4 addresses.
+    pub fn with_scope_id(mut self, scope_id: u32) -> Self {
+        self.scope_id = scope_id;
+        self
+    }
+
+    /// Returns the socket address of a port on the device, including the zone index
+    pub fn socket_addr(&self, port: u16) -> std::net::SocketAddr {
+        scoped_socket_addr(self.addr, self.scope_id, port)
+    }
+
+    /// Fails connections that take longer than `timeout` with `IdeviceError::Timeout`
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+}
+
+/// Forms a socket address, keeping the zone index of IPv6 addresses
+#[cfg(feature = "tcp")]
+fn scoped_socket_addr(addr: std::net::IpAddr, scope_id: u32, port: u16) -> std::net::SocketAddr {
+    match addr {
+        std::net::IpAddr::V4(addr) => std::net::SocketAddrV4::new(addr, port).into(),
+        std::net::IpAddr::V6(addr) => std::net::SocketAddrV6::new(addr, port, 0, scope_id).into(),
+    }
 }
 
 #[cfg(feature = "tcp")]
@@ -74,11 +168,14 @@ impl IdeviceProvider for TcpProvider {
         &self,
         port: u16,
     ) -> Pin<Box<dyn Future<Output = Result<Idevice, IdeviceError>> + Send>> {
-        let addr = self.addr;
+        let socket_addr = self.socket_addr(port);
         let label = self.label.clone();
+        let timeout = self.connect_timeout;
         Box::pin(async move {
-            let socket_addr = std::net::SocketAddr::new(addr, port);
-            let stream = TcpStream::connect(socket_addr).await?;
+            let stream = with_timeout(timeout, async {
+                Ok(TcpStream::connect(socket_addr).await?)
+            })
+            .await?;
             Ok(Idevice::new(Box::new(stream), label))
         })
     }
@@ -88,6 +185,10 @@ impl IdeviceProvider for TcpProvider {
         &self.label
     }
 
+    fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect_timeout = timeout;
+    }
+
     /// Returns the pairing file (cloned from the provider)
     fn get_pairing_file(
         &self,
@@ -111,6 +212,108 @@ pub struct UsbmuxdProvider {
     pub device_id: u32,
     /// Connection label
     pub label: String,
+    /// How long connecting may take, or `None` to wait indefinitely
+    pub connect_timeout: Option<Duration>,
+    /// Pair record to use instead of the one stored by usbmuxd
+    pub pairing_file: Option<PairingFile>,
+}
+
+#[cfg(feature = "usbmuxd")]
+impl UsbmuxdProvider {
+    /// Creates a provider for a device attached to usbmuxd
+    ///
+    /// # Arguments
+    /// * `addr` - usbmuxd address
+    /// * `tag` - Connection tag
+    /// * `udid` - UDID of the device
+    /// * `device_id` - usbmuxd-assigned device ID
+    /// * `label` - Connection label
+    pub fn new(
+        addr: UsbmuxdAddr,
+        tag: u32,
+        udid: impl Into<String>,
+        device_id: u32,
+        label: impl Into<String>,
+    ) -> Self {
+        Self {
+            addr,
+            tag,
+            udid: udid.into(),
+            device_id,
+            label: label.into(),
+            connect_timeout: None,
+            pairing_file: None,
+        }
+    }
+
+    /// Creates a provider that uses a pair record file instead of usbmuxd's pair records
+    ///
+    /// usbmuxd is still used to reach the device, and is located the same way as
+    /// [`crate::usbmuxd::connect_usbmuxd`].
+    ///
+    /// # Arguments
+    /// * `udid` - UDID of the device to connect to
+    /// * `path` - Path to the pair record
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::InvalidPairRecord` if the record can't be read or used for the
+    /// device, or `IdeviceError::DeviceNotFound` if the device isn't attached
+    pub async fn from_pair_record_path(udid: &str, path: &Path) -> Result<Self, IdeviceError> {
+        let bytes = tokio::fs::read(path).await.map_err(|e| {
+            IdeviceError::InvalidPairRecord(format!("can't read {}: {e}", path.display()))
+        })?;
+        Self::from_pair_record_bytes(udid, &bytes).await
+    }
+
+    /// Creates a provider that uses the given pair record instead of usbmuxd's pair records
+    ///
+    /// usbmuxd is still used to reach the device, and is located the same way as
+    /// [`crate::usbmuxd::connect_usbmuxd`].
+    ///
+    /// # Arguments
+    /// * `udid` - UDID of the device to connect to
+    /// * `bytes` - The pair record, in XML or binary PLIST format
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::InvalidPairRecord` if the record can't be used for the device,
+    /// or `IdeviceError::DeviceNotFound` if the device isn't attached
+    pub async fn from_pair_record_bytes(udid: &str, bytes: &[u8]) -> Result<Self, IdeviceError> {
+        let pairing_file = PairingFile::from_pair_record(bytes)?;
+        if let Some(record_udid) = &pairing_file.udid
+            && record_udid != udid
+        {
+            return Err(IdeviceError::InvalidPairRecord(format!(
+                "record is for {record_udid}"
+            )));
+        }
+
+        let addr = UsbmuxdAddr::from_env_var().unwrap_or_default();
+        let device = addr
+            .connect(0)
+            .await?
+            .get_devices()
+            .await?
+            .into_iter()
+            .filter(|d| d.udid == udid)
+            .min_by_key(|d| d.connection_type != crate::usbmuxd::Connection::Usb)
+            .ok_or(IdeviceError::DeviceNotFound)?;
+
+        Ok(device
+            .to_provider(addr, "idevice-rs")
+            .with_pairing_file(pairing_file))
+    }
+
+    /// Uses `pairing_file` instead of the pair record stored by usbmuxd
+    pub fn with_pairing_file(mut self, pairing_file: PairingFile) -> Self {
+        self.pairing_file = Some(pairing_file);
+        self
+    }
+
+    /// Fails connections that take longer than `timeout` with `IdeviceError::Timeout`
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
 }
 
 #[cfg(feature = "usbmuxd")]
@@ -130,11 +333,12 @@ impl IdeviceProvider for UsbmuxdProvider {
         let tag = self.tag;
         let device_id = self.device_id;
         let label = self.label.clone();
+        let timeout = self.connect_timeout;
 
-        Box::pin(async move {
+        Box::pin(with_timeout(timeout, async move {
             let usbmuxd = addr.connect(tag).await?;
             usbmuxd.connect_to_device(device_id, port, &label).await
-        })
+        }))
     }
 
     /// Returns the connection label
@@ -142,10 +346,17 @@ impl IdeviceProvider for UsbmuxdProvider {
         &self.label
     }
 
-    /// Retrieves the pairing record from usbmuxd
+    fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect_timeout = timeout;
+    }
+
+    /// Returns the provider's pair record, or retrieves the one stored by usbmuxd
     fn get_pairing_file(
         &self,
     ) -> Pin<Box<dyn Future<Output = Result<PairingFile, IdeviceError>> + Send>> {
+        if let Some(pairing_file) = self.pairing_file.clone() {
+            return Box::pin(async move { Ok(pairing_file) });
+        }
         let addr = self.addr.clone();
         let tag = self.tag;
         let udid = self.udid.clone();
@@ -157,6 +368,248 @@ impl IdeviceProvider for UsbmuxdProvider {
     }
 }
 
+/// Provider for a device attached to usbmuxd on another host, reached over TCP
+///
+/// Unlike [`UsbmuxdProvider`], the device is looked up by UDID on every connection, so the
+/// provider keeps working when the device reconnects and gets a new device ID. The pairing
+/// file is supplied by the caller instead of being read from the remote usbmuxd.
+#[cfg(feature = "usbmuxd")]
+#[derive(Debug)]
+pub struct RemoteUsbmuxdProvider {
+    /// Address of the remote usbmuxd
+    pub addr: std::net::SocketAddr,
+    /// Device UDID
+    pub udid: String,
+    /// Pairing file for secure communication
+    pub pairing_file: PairingFile,
+    /// Connection tag/identifier
+    pub tag: u32,
+    /// Connection label
+    pub label: String,
+    /// How long connecting may take, or `None` to wait indefinitely
+    pub connect_timeout: Option<Duration>,
+}
+
+#[cfg(feature = "usbmuxd")]
+impl RemoteUsbmuxdProvider {
+    /// Creates a provider for a device on a remote usbmuxd
+    ///
+    /// # Arguments
+    /// * `addr` - Address the remote usbmuxd listens on
+    /// * `udid` - UDID of the device to connect to
+    /// * `pairing_file` - Pairing file for the device
+    pub fn new(
+        addr: std::net::SocketAddr,
+        udid: impl Into<String>,
+        pairing_file: PairingFile,
+    ) -> Self {
+        Self {
+            addr,
+            udid: udid.into(),
+            pairing_file,
+            tag: 0,
+            label: "remote_usbmuxd".to_string(),
+            connect_timeout: None,
+        }
+    }
+
+    /// Fails connections that take longer than `timeout` with `IdeviceError::Timeout`
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+}
+
+#[cfg(feature = "usbmuxd")]
+impl IdeviceProvider for RemoteUsbmuxdProvider {
+    /// Connects to the device through the remote usbmuxd
+    ///
+    /// # Arguments
+    /// * `port` - The port number to connect to on the device
+    ///
+    /// # Returns
+    /// An `Idevice` wrapped in a future
+    fn connect(
+        &self,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = Result<Idevice, IdeviceError>> + Send>> {
+        let addr = UsbmuxdAddr::TcpSocket(self.addr);
+        let tag = self.tag;
+        let udid = self.udid.clone();
+        let label = self.label.clone();
+        let timeout = self.connect_timeout;
+
+        Box::pin(with_timeout(timeout, async move {
+            // The connection becomes the device stream after connecting, so look up on a separate one
+            let device_id = addr.connect(tag).await?.get_device(&udid).await?.device_id;
+            let usbmuxd = addr.connect(tag).await?;
+            usbmuxd.connect_to_device(device_id, port, &label).await
+        }))
+    }
+
+    /// Returns the connection label
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect_timeout = timeout;
+    }
+
+    /// Returns the pairing file (cloned from the provider)
+    fn get_pairing_file(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<PairingFile, IdeviceError>> + Send>> {
+        let pairing_file = self.pairing_file.clone();
+        Box::pin(async move { Ok(pairing_file) })
+    }
+}
+
+/// Lockdown service names whose RSD name isn't the lockdown name with `.shim.remote` appended
+///
+/// | Lockdown name | RSD name |
+/// |---|---|
+/// | `com.apple.mobile.lockdown` | `com.apple.mobile.lockdown.remote.trusted` |
+/// | `com.apple.instruments.remoteserver` | `com.apple.instruments.dtservicehub` |
+/// | `com.apple.instruments.remoteserver.DVTSecureSocketProxy` | `com.apple.instruments.dtservicehub` |
+/// | `com.apple.debugserver.DVTSecureSocketProxy` | `com.apple.internal.dt.remote.debugproxy` |
+#[cfg(feature = "rsd")]
+pub const RSD_SERVICE_NAMES: &[(&str, &str)] = &[
+    (
+        "com.apple.mobile.lockdown",
+        "com.apple.mobile.lockdown.remote.trusted",
+    ),
+    (
+        "com.apple.instruments.remoteserver",
+        "com.apple.instruments.dtservicehub",
+    ),
+    (
+        "com.apple.instruments.remoteserver.DVTSecureSocketProxy",
+        "com.apple.instruments.dtservicehub",
+    ),
+    (
+        "com.apple.debugserver.DVTSecureSocketProxy",
+        "com.apple.internal.dt.remote.debugproxy",
+    ),
+];
+
+/// Provider for iOS 17+ devices, connecting to services over a tunnel advertised by RSD
+///
+/// Services are looked up by name in the RSD handshake, so [`crate::IdeviceService::connect`]
+/// works without lockdown. Lockdown names are translated to RSD names using
+/// [`RSD_SERVICE_NAMES`], or by appending `.shim.remote`.
+#[cfg(feature = "rsd")]
+#[derive(Debug)]
+pub struct RsdServiceProvider<P: RsdProvider + 'static> {
+    /// The tunnel used to open service ports
+    tunnel: std::sync::Arc<tokio::sync::Mutex<P>>,
+    /// The handshake listing the advertised services
+    pub handshake: crate::rsd::RsdHandshake,
+    /// Pairing file, for services that still need a TLS session
+    pub pairing_file: Option<PairingFile>,
+    /// Connection label
+    pub label: String,
+}
+
+#[cfg(feature = "rsd")]
+impl<P: RsdProvider + 'static> RsdServiceProvider<P> {
+    /// Creates a provider from a tunnel and the RSD handshake made over it
+    ///
+    /// # Arguments
+    /// * `tunnel` - A provider that can open ports over the tunnel
+    /// * `handshake` - The handshake made with the device's RSD port
+    /// * `label` - Connection label
+    pub fn new(tunnel: P, handshake: crate::rsd::RsdHandshake, label: impl Into<String>) -> Self {
+        Self {
+            tunnel: std::sync::Arc::new(tokio::sync::Mutex::new(tunnel)),
+            handshake,
+            pairing_file: None,
+            label: label.into(),
+        }
+    }
+
+    /// Sets the pairing file returned by [`IdeviceProvider::get_pairing_file`]
+    pub fn with_pairing_file(mut self, pairing_file: PairingFile) -> Self {
+        self.pairing_file = Some(pairing_file);
+        self
+    }
+
+    /// Translates a lockdown service name to the name RSD advertises it under
+    ///
+    /// # Returns
+    /// The RSD name and whether the service needs an RSD check-in, or `None` if the device
+    /// doesn't advertise the service
+    pub fn rsd_service_name(&self, service_name: &str) -> Option<(String, bool)> {
+        if let Some((_, rsd)) = RSD_SERVICE_NAMES.iter().find(|(l, _)| *l == service_name) {
+            let checkin = rsd.ends_with(".remote.trusted");
+            return self
+                .handshake
+                .services
+                .contains_key(*rsd)
+                .then(|| (rsd.to_string(), checkin));
+        }
+        let shim = format!("{service_name}.shim.remote");
+        if self.handshake.services.contains_key(&shim) {
+            return Some((shim, true));
+        }
+        self.handshake
+            .services
+            .contains_key(service_name)
+            .then(|| (service_name.to_string(), false))
+    }
+}
+
+#[cfg(feature = "rsd")]
+impl<P: RsdProvider + 'static> IdeviceProvider for RsdServiceProvider<P> {
+    /// Connects to a port over the tunnel
+    fn connect(
+        &self,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = Result<Idevice, IdeviceError>> + Send>> {
+        let tunnel = self.tunnel.clone();
+        let label = self.label.clone();
+        Box::pin(async move {
+            let stream = tunnel.lock().await.connect_to_service_port(port).await?;
+            Ok(Idevice::new(stream, label))
+        })
+    }
+
+    /// Returns the connection label
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns the pairing file, if one was set
+    fn get_pairing_file(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<PairingFile, IdeviceError>> + Send>> {
+        let pairing_file = self.pairing_file.clone();
+        Box::pin(async move { pairing_file.ok_or(IdeviceError::InvalidHostID) })
+    }
+
+    /// Connects to the service's advertised port, checking in if it's a lockdown shim
+    fn connect_service(
+        &self,
+        service_name: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Idevice>, IdeviceError>> + Send>> {
+        let resolved = self
+            .rsd_service_name(service_name)
+            .and_then(|(name, checkin)| Some((self.handshake.service_port(&name)?, checkin)));
+        let connect = resolved.map(|(port, checkin)| (self.connect(port), checkin));
+
+        Box::pin(async move {
+            let Some((connect, checkin)) = connect else {
+                return Err(IdeviceError::ServiceNotFound);
+            };
+            let mut idevice = connect.await?;
+            if checkin {
+                idevice.rsd_checkin().await?;
+            }
+            Ok(Some(idevice))
+        })
+    }
+}
+
 #[cfg(feature = "tcp")]
 impl RsdProvider for std::net::IpAddr {
     async fn connect_to_service_port(
@@ -168,3 +621,76 @@ impl RsdProvider for std::net::IpAddr {
         ))
     }
 }
+
+#[cfg(all(test, feature = "rsd", feature = "tcp"))]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::rsd::{RsdHandshake, RsdService};
+
+    #[test]
+    fn keeps_scope_of_link_local_addresses() {
+        let link_local = "fe80::1c2b:3cff:fe4d:5e6f".parse().unwrap();
+        assert_eq!(
+            scoped_socket_addr(link_local, 4, 62078).to_string(),
+            "[fe80::1c2b:3cff:fe4d:5e6f%4]:62078"
+        );
+        assert_eq!(
+            scoped_socket_addr("10.0.0.2".parse().unwrap(), 4, 62078).to_string(),
+            "10.0.0.2:62078"
+        );
+    }
+
+    #[test]
+    fn resolves_lockdown_names_to_rsd_names() {
+        let service = RsdService {
+            entitlement: String::new(),
+            port: 1234,
+            uses_remote_xpc: false,
+            features: None,
+            service_version: None,
+        };
+        let services = [
+            "com.apple.afc.shim.remote",
+            "com.apple.mobile.lockdown.remote.trusted",
+            "com.apple.coredevice.appservice",
+        ]
+        .into_iter()
+        .map(|n| (n.to_string(), service.clone()))
+        .collect::<HashMap<_, _>>();
+        let handshake = RsdHandshake {
+            services,
+            protocol_version: 2,
+            properties: HashMap::new(),
+            uuid: String::new(),
+        };
+        let provider = RsdServiceProvider::new(
+            std::net::IpAddr::V6(std::net::Ipv6Addr::LOCALHOST),
+            handshake,
+            "test",
+        );
+
+        assert_eq!(
+            provider.rsd_service_name("com.apple.afc"),
+            Some(("com.apple.afc.shim.remote".to_string(), true))
+        );
+        assert_eq!(
+            provider.rsd_service_name("com.apple.mobile.lockdown"),
+            Some(("com.apple.mobile.lockdown.remote.trusted".to_string(), true))
+        );
+        assert_eq!(
+            provider.rsd_service_name("com.apple.coredevice.appservice"),
+            Some(("com.apple.coredevice.appservice".to_string(), false))
+        );
+        assert_eq!(provider.rsd_service_name("com.apple.misagent"), None);
+    }
+
+    #[cfg(feature = "usbmuxd")]
+    #[tokio::test]
+    async fn unreadable_pair_record_is_invalid() {
+        let path = std::env::temp_dir().join("idevice-missing-pair-record.plist");
+        let res = UsbmuxdProvider::from_pair_record_path("00008030-001A", &path).await;
+        assert!(matches!(res, Err(IdeviceError::InvalidPairRecord(_))));
+    }
+}
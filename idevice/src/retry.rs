@@ -0,0 +1,139 @@
+//! Retrying of transient connection failures
+//!
+//! Connecting to a service right after the device unlocks or boots often fails because
+//! the service hasn't started yet. [`RetryPolicy`] describes how
+//! [`crate::IdeviceService::connect_with_retry`] retries those failures.
+
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::IdeviceError;
+
+/// Describes how many times and how often a failed connection is retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry. Each following retry waits twice as long as the last.
+    pub base_delay: Duration,
+    /// Upper bound for the delay between attempts
+    pub max_delay: Duration,
+    /// Decides whether an error is worth retrying. Defaults to [`IdeviceError::is_transient`].
+    pub retryable: fn(&IdeviceError) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(5),
+            retryable: IdeviceError::is_transient,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy with the default retryable errors
+    ///
+    /// # Arguments
+    /// * `max_attempts` - Total number of attempts, including the first one
+    /// * `base_delay` - Delay before the first retry
+    pub fn new(max_attempts: u32, base_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the function deciding which errors are retried
+    pub fn with_retryable(mut self, retryable: fn(&IdeviceError) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Runs an operation, retrying its failures as the policy allows
+    ///
+    /// Waits between attempts with an exponential backoff. Errors that the policy doesn't
+    /// consider retryable are returned immediately.
+    ///
+    /// # Arguments
+    /// * `op` - The operation, called once per attempt
+    ///
+    /// # Returns
+    /// The operation's result, or the last error encountered
+    pub async fn run<T, Fut>(&self, mut op: impl FnMut() -> Fut) -> Result<T, IdeviceError>
+    where
+        Fut: std::future::Future<Output = Result<T, IdeviceError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Ok(v) => return Ok(v),
+                Err(e) if attempt < self.max_attempts && (self.retryable)(&e) => {
+                    let delay = self.delay_after(attempt);
+                    debug!("Attempt {attempt} failed, retrying in {delay:?}: {e:?}");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Returns how long to wait after the given failed attempt, starting at 1
+    fn delay_after(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_doubles_up_to_max() {
+        let policy = RetryPolicy::new(10, Duration::from_millis(100));
+        assert_eq!(policy.delay_after(1), Duration::from_millis(100));
+        assert_eq!(policy.delay_after(3), Duration::from_millis(400));
+        assert_eq!(policy.delay_after(40), policy.max_delay);
+    }
+
+    #[tokio::test]
+    async fn run_retries_until_success_or_fatal_error() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1))
+            .with_retryable(|e| matches!(e, IdeviceError::ServiceNotFound));
+
+        let mut attempts = 0;
+        let res = policy
+            .run(|| {
+                attempts += 1;
+                let res = if attempts < 3 {
+                    Err(IdeviceError::ServiceNotFound)
+                } else {
+                    Ok(attempts)
+                };
+                async move { res }
+            })
+            .await;
+        assert_eq!(res.unwrap(), 3);
+
+        attempts = 0;
+        let res: Result<(), _> = policy
+            .run(|| {
+                attempts += 1;
+                async { Err(IdeviceError::UnexpectedResponse) }
+            })
+            .await;
+        assert!(matches!(res, Err(IdeviceError::UnexpectedResponse)));
+        assert_eq!(attempts, 1);
+    }
+}
@@ -97,6 +97,13 @@ crate::impl_to_structs!(FileDescriptor<'_>, OwnedFileDescriptor;  {
         self.inner.as_mut().read().await
     }
 
+    /// Reads up to `n` bytes from the current position
+    ///
+    /// Fewer bytes are returned if the end of the file is reached first.
+    pub async fn read_n(&mut self, n: usize) -> Result<Vec<u8>, IdeviceError> {
+        self.inner.as_mut().read_n(n).await
+    }
+
     /// Writes data to the file
     ///
     /// # Arguments
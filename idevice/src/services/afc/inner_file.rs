@@ -637,6 +637,44 @@ mod tests {
         client.remove(path).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn truncate_grows_and_shrinks() {
+        let mut client = make_client().await;
+        let path = "/tmp/truncate.txt";
+
+        let mut f = client.open(path, AfcFopenMode::WrOnly).await.unwrap();
+        f.write_all(b"hello world").await.unwrap();
+        drop(f);
+
+        client.truncate(path, 4096).await.unwrap();
+        assert_eq!(client.get_file_info(path).await.unwrap().size, 4096);
+
+        client.truncate(path, 5).await.unwrap();
+        assert_eq!(client.get_file_info(path).await.unwrap().size, 5);
+
+        let mut f = client.open(path, AfcFopenMode::RdOnly).await.unwrap();
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+
+        drop(f);
+        client.remove(path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_and_write_at_offset() {
+        let mut client = make_client().await;
+        let path = "/tmp/offset.txt";
+
+        client.write_at(path, 0, b"hello world").await.unwrap();
+        client.write_at(path, 6, b"there").await.unwrap();
+
+        assert_eq!(client.read_at(path, 6, 5).await.unwrap(), b"there");
+        assert_eq!(client.read_at(path, 0, 100).await.unwrap(), b"hello there");
+
+        client.remove(path).await.unwrap();
+    }
+
     #[tokio::test]
     async fn write_then_append() {
         use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -3,17 +3,25 @@
 //! This module provides functionality to interact with the file system of iOS devices
 //! through the AFC protocol.
 
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    pin::Pin,
+};
 
 use errors::AfcError;
+use futures::{Stream, TryStreamExt};
 use opcode::{AfcFopenMode, AfcOpcode};
 use packet::{AfcPacket, AfcPacketHeader};
-use tracing::warn;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::{debug, warn};
 
 use crate::{
     Idevice, IdeviceError, IdeviceService,
     afc::file::{FileDescriptor, OwnedFileDescriptor},
     obf,
+    provider::IdeviceProvider,
 };
 
 pub mod errors;
@@ -26,6 +34,9 @@ pub mod packet;
 /// The magic number used in AFC protocol communications
 pub const MAGIC: u64 = 0x4141504c36414643;
 
+/// Bytes transferred between progress reports of single file transfers
+const PROGRESS_CHUNK_SIZE: u64 = 1024 * 1024;
+
 /// Client for interacting with the AFC service on iOS devices
 #[derive(Debug)]
 pub struct AfcClient {
@@ -49,10 +60,126 @@ pub struct FileInfo {
     pub st_nlink: String,
     /// File type (e.g., "S_IFREG" for regular file)
     pub st_ifmt: String,
+    /// File type parsed from `st_ifmt`
+    pub file_type: AfcFileType,
     /// Target path if this is a symbolic link
     pub st_link_target: Option<String>,
 }
 
+/// Type of a file on the device
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AfcFileType {
+    /// Regular file
+    File,
+    /// Directory
+    Directory,
+    /// Symbolic link
+    Symlink,
+    /// Any other type, such as a device or socket, holding the raw `st_ifmt` value
+    Other(String),
+}
+
+impl From<&str> for AfcFileType {
+    fn from(value: &str) -> Self {
+        match value {
+            "S_IFREG" => Self::File,
+            "S_IFDIR" => Self::Directory,
+            "S_IFLNK" => Self::Symlink,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// A file or directory found by [`AfcClient::walk`]
+#[derive(Clone, Debug)]
+pub struct AfcEntry {
+    /// Full path of the entry on the device
+    pub path: String,
+    /// How many directories below the walked root the entry is, starting at 1
+    pub depth: usize,
+    /// Information about the entry
+    pub info: FileInfo,
+}
+
+/// Type of link created by [`AfcClient::make_link`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AfcLinkType {
+    /// Hard link, sharing the target's contents
+    Hard,
+    /// Symbolic link, pointing at the target's path
+    Symbolic,
+}
+
+impl From<AfcLinkType> for opcode::LinkType {
+    fn from(value: AfcLinkType) -> Self {
+        match value {
+            AfcLinkType::Hard => Self::Hardlink,
+            AfcLinkType::Symbolic => Self::Symlink,
+        }
+    }
+}
+
+impl FileInfo {
+    /// Parses the key/value list returned by `GetFileInfo`
+    fn from_payload(payload: &[u8]) -> Result<Self, IdeviceError> {
+        let strings: Vec<String> = payload
+            .split(|b| *b == 0)
+            .filter(|s| !s.is_empty())
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+            .collect();
+
+        let mut kvs: HashMap<String, String> = strings
+            .chunks_exact(2)
+            .map(|chunk| (chunk[0].clone(), chunk[1].clone()))
+            .collect();
+
+        let size = kvs
+            .remove("st_size")
+            .and_then(|x| x.parse::<usize>().ok())
+            .ok_or(IdeviceError::AfcMissingAttribute)?;
+        let blocks = kvs
+            .remove("st_blocks")
+            .and_then(|x| x.parse::<usize>().ok())
+            .ok_or(IdeviceError::AfcMissingAttribute)?;
+
+        let creation = kvs
+            .remove("st_birthtime")
+            .and_then(|x| x.parse::<i64>().ok())
+            .ok_or(IdeviceError::AfcMissingAttribute)?;
+        let creation = chrono::DateTime::from_timestamp_nanos(creation).naive_local();
+
+        let modified = kvs
+            .remove("st_mtime")
+            .and_then(|x| x.parse::<i64>().ok())
+            .ok_or(IdeviceError::AfcMissingAttribute)?;
+        let modified = chrono::DateTime::from_timestamp_nanos(modified).naive_local();
+
+        let st_nlink = kvs
+            .remove("st_nlink")
+            .ok_or(IdeviceError::AfcMissingAttribute)?;
+        let st_ifmt = kvs
+            .remove("st_ifmt")
+            .ok_or(IdeviceError::AfcMissingAttribute)?;
+        let file_type = AfcFileType::from(st_ifmt.as_str());
+        let st_link_target = kvs.remove("st_link_target");
+
+        if !kvs.is_empty() {
+            warn!("File info kvs not empty: {kvs:?}");
+        }
+
+        Ok(Self {
+            size,
+            blocks,
+            creation,
+            modified,
+            st_nlink,
+            st_ifmt,
+            file_type,
+            st_link_target,
+        })
+    }
+}
+
 /// Information about the device's filesystem
 #[derive(Clone, Debug)]
 pub struct DeviceInfo {
@@ -91,6 +218,27 @@ impl AfcClient {
         }
     }
 
+    /// Connects to an AFC speaking service by its lockdown name
+    ///
+    /// [`IdeviceService::connect`] uses the media service, `com.apple.afc`. This connects to
+    /// any other service that speaks AFC, such as `com.apple.crashreportcopymobile`. House
+    /// arrest isn't one of them, as it only switches to AFC after vending a container, see
+    /// `HouseArrestClient`.
+    ///
+    /// # Arguments
+    /// * `provider` - The device provider that can supply connections
+    /// * `service_name` - The lockdown name of the service
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if the service can't be started or connected to
+    pub async fn connect_service(
+        provider: &dyn IdeviceProvider,
+        service_name: &str,
+    ) -> Result<Self, IdeviceError> {
+        let idevice = crate::connect_service_by_name(provider, service_name).await?;
+        Ok(Self::new(idevice))
+    }
+
     /// Lists the contents of a directory on the device
     ///
     /// # Arguments
@@ -193,60 +341,73 @@ impl AfcClient {
         self.send(packet).await?;
         let res = self.read().await?;
 
-        let strings: Vec<String> = res
-            .payload
-            .split(|b| *b == 0)
-            .filter(|s| !s.is_empty())
-            .map(|s| String::from_utf8_lossy(s).into_owned())
-            .collect();
+        FileInfo::from_payload(&res.payload)
+    }
 
-        let mut kvs: HashMap<String, String> = strings
-            .chunks_exact(2)
-            .map(|chunk| (chunk[0].clone(), chunk[1].clone()))
-            .collect();
+    /// Sets the modification time of a file or directory
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file or directory
+    /// * `mtime` - New modification time, in UTC
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::InvalidArgument` if `mtime` is before the Unix epoch or
+    /// too far in the future to be represented in nanoseconds
+    pub async fn set_file_time(
+        &mut self,
+        path: impl Into<String>,
+        mtime: chrono::NaiveDateTime,
+    ) -> Result<(), IdeviceError> {
+        let path = path.into();
+        let mtime = mtime
+            .and_utc()
+            .timestamp_nanos_opt()
+            .and_then(|t| u64::try_from(t).ok())
+            .ok_or(IdeviceError::InvalidArgument)?;
 
-        let size = kvs
-            .remove("st_size")
-            .and_then(|x| x.parse::<usize>().ok())
-            .ok_or(IdeviceError::AfcMissingAttribute)?;
-        let blocks = kvs
-            .remove("st_blocks")
-            .and_then(|x| x.parse::<usize>().ok())
-            .ok_or(IdeviceError::AfcMissingAttribute)?;
+        let mut header_payload = mtime.to_le_bytes().to_vec();
+        header_payload.extend(path.as_bytes());
+        header_payload.push(0);
+        let header_len = header_payload.len() as u64 + AfcPacketHeader::LEN;
 
-        let creation = kvs
-            .remove("st_birthtime")
-            .and_then(|x| x.parse::<i64>().ok())
-            .ok_or(IdeviceError::AfcMissingAttribute)?;
-        let creation = chrono::DateTime::from_timestamp_nanos(creation).naive_local();
+        let header = AfcPacketHeader {
+            magic: MAGIC,
+            entire_len: header_len, // it's the same since the payload is empty for this
+            header_payload_len: header_len,
+            packet_num: self.package_number,
+            operation: AfcOpcode::SetFileTime,
+        };
+        self.package_number += 1;
 
-        let modified = kvs
-            .remove("st_mtime")
-            .and_then(|x| x.parse::<i64>().ok())
-            .ok_or(IdeviceError::AfcMissingAttribute)?;
-        let modified = chrono::DateTime::from_timestamp_nanos(modified).naive_local();
+        let packet = AfcPacket {
+            header,
+            header_payload,
+            payload: Vec::new(),
+        };
 
-        let st_nlink = kvs
-            .remove("st_nlink")
-            .ok_or(IdeviceError::AfcMissingAttribute)?;
-        let st_ifmt = kvs
-            .remove("st_ifmt")
-            .ok_or(IdeviceError::AfcMissingAttribute)?;
-        let st_link_target = kvs.remove("st_link_target");
+        self.send(packet).await?;
+        self.read().await?; // read a response to check for errors
 
-        if !kvs.is_empty() {
-            warn!("File info kvs not empty: {kvs:?}");
-        }
+        Ok(())
+    }
 
-        Ok(FileInfo {
-            size,
-            blocks,
-            creation,
-            modified,
-            st_nlink,
-            st_ifmt,
-            st_link_target,
-        })
+    /// Sets the POSIX permission bits of a file or directory
+    ///
+    /// AFC has no operation for changing permissions. Neither the jailed
+    /// `com.apple.afc` service, the house_arrest containers, nor the crash logs service
+    /// honor one, and files are created with the mode chosen by the device. This fails
+    /// without contacting the device, so callers can tell the mode was not applied.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file or directory
+    /// * `mode` - Permission bits, such as `0o755`
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::Unsupported` on every AFC service
+    pub async fn set_mode(&mut self, path: &str, mode: u32) -> Result<(), IdeviceError> {
+        Err(IdeviceError::Unsupported(format!(
+            "AFC can't set the mode of {path} to {mode:o}"
+        )))
     }
 
     /// Retrieves information about the device's filesystem
@@ -314,6 +475,25 @@ impl AfcClient {
         })
     }
 
+    /// Checks that the device has room for a number of bytes
+    ///
+    /// Meant to be called before large uploads, so they fail up front rather than partway
+    /// through. The filesystem's free space is shared, so another writer may still use it up
+    /// before the upload finishes.
+    ///
+    /// # Arguments
+    /// * `needed` - How many bytes will be written
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::NotEnoughSpace` if fewer bytes than `needed` are free
+    pub async fn ensure_free_space(&mut self, needed: u64) -> Result<(), IdeviceError> {
+        let available = self.get_device_info().await?.free_bytes as u64;
+        if available < needed {
+            return Err(IdeviceError::NotEnoughSpace { needed, available });
+        }
+        Ok(())
+    }
+
     /// Removes a file or directory
     ///
     /// # Arguments
@@ -346,10 +526,39 @@ impl AfcClient {
 
     /// Recursively removes a directory and all its contents
     ///
+    /// The whole tree is removed with a single request. Services that don't support it get
+    /// the tree removed entry by entry instead, contents before their directory.
+    ///
     /// # Arguments
     /// * `path` - Path to the directory to remove
     pub async fn remove_all(&mut self, path: impl Into<String>) -> Result<(), IdeviceError> {
         let path = path.into();
+        match self.remove_path_and_contents(&path).await {
+            Err(IdeviceError::Afc(AfcError::OpNotSupported)) => {
+                debug!("RemovePathAndContents isn't supported, removing {path} entry by entry");
+                self.remove_tree(&path).await
+            }
+            res => res,
+        }
+    }
+
+    /// Removes a tree one entry at a time, deepest entries first
+    async fn remove_tree(&mut self, path: &str) -> Result<(), IdeviceError> {
+        if self.get_file_info(path).await?.file_type == AfcFileType::Directory {
+            // The walk yields directories before their contents, so reversed it's post-order
+            let entries = self
+                .walk(path, None)
+                .map_ok(|e| e.path)
+                .try_collect::<Vec<_>>()
+                .await?;
+            for entry in entries.into_iter().rev() {
+                self.remove(entry).await?;
+            }
+        }
+        self.remove(path).await
+    }
+
+    async fn remove_path_and_contents(&mut self, path: &str) -> Result<(), IdeviceError> {
         let header_payload = path.as_bytes().to_vec();
         let header_len = header_payload.len() as u64 + AfcPacketHeader::LEN;
 
@@ -374,6 +583,43 @@ impl AfcClient {
         Ok(())
     }
 
+    /// Sets the length of a file, padding it with zeros or cutting it short
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file to resize
+    /// * `size` - New length of the file in bytes
+    pub async fn truncate(
+        &mut self,
+        path: impl Into<String>,
+        size: u64,
+    ) -> Result<(), IdeviceError> {
+        let path = path.into();
+        let mut header_payload = size.to_le_bytes().to_vec();
+        header_payload.extend(path.as_bytes());
+        header_payload.push(0);
+        let header_len = header_payload.len() as u64 + AfcPacketHeader::LEN;
+
+        let header = AfcPacketHeader {
+            magic: MAGIC,
+            entire_len: header_len, // it's the same since the payload is empty for this
+            header_payload_len: header_len,
+            packet_num: self.package_number,
+            operation: AfcOpcode::Truncate,
+        };
+        self.package_number += 1;
+
+        let packet = AfcPacket {
+            header,
+            header_payload,
+            payload: Vec::new(),
+        };
+
+        self.send(packet).await?;
+        self.read().await?; // read a response to check for errors
+
+        Ok(())
+    }
+
     /// Opens a file on the device
     ///
     /// # Arguments
@@ -419,6 +665,94 @@ impl AfcClient {
         Ok(unsafe { FileDescriptor::new(self, fd, path) })
     }
 
+    /// Opens a file on the device for streaming reads
+    ///
+    /// The returned descriptor implements [`tokio::io::AsyncRead`] and fetches data in bounded
+    /// chunks as it is polled, so it can be passed to [`tokio::io::copy`] to transfer large files
+    /// without holding them in memory.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file to open
+    pub async fn open_read<'f>(
+        &'f mut self,
+        path: impl Into<String>,
+    ) -> Result<FileDescriptor<'f>, IdeviceError> {
+        self.open(path, AfcFopenMode::RdOnly).await
+    }
+
+    /// Opens a file on the device for streaming writes, creating or truncating it
+    ///
+    /// The returned descriptor implements [`tokio::io::AsyncWrite`], sending each write to the
+    /// device as it happens.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file to open
+    pub async fn open_write<'f>(
+        &'f mut self,
+        path: impl Into<String>,
+    ) -> Result<FileDescriptor<'f>, IdeviceError> {
+        self.open(path, AfcFopenMode::WrOnly).await
+    }
+
+    /// Reads part of a file, starting at a byte offset
+    ///
+    /// The file is opened, read and closed again on every call, so no handle is kept between
+    /// calls and an interrupted transfer can be resumed from a fresh connection. For many
+    /// small reads of the same file, [`AfcClient::open_read`] and seeking are cheaper.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file to read
+    /// * `offset` - Byte offset to start reading at
+    /// * `len` - Maximum number of bytes to read
+    ///
+    /// # Returns
+    /// The bytes read, which are fewer than `len` if the end of the file is reached
+    pub async fn read_at(
+        &mut self,
+        path: impl Into<String>,
+        offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, IdeviceError> {
+        let len = usize::try_from(len).map_err(|_| IdeviceError::IntegerOverflow)?;
+        let mut fd = self.open(path, AfcFopenMode::RdOnly).await?;
+        let res = async {
+            fd.seek(SeekFrom::Start(offset)).await?;
+            fd.read_n(len).await
+        }
+        .await;
+        let closed = fd.close().await;
+        let data = res?;
+        closed?;
+        Ok(data)
+    }
+
+    /// Writes data into a file, starting at a byte offset
+    ///
+    /// The file is created if it doesn't exist, and is never truncated, so bytes outside the
+    /// written range are kept. Like [`AfcClient::read_at`], the file is opened and closed
+    /// again on every call.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file to write
+    /// * `offset` - Byte offset to start writing at
+    /// * `data` - Data to write
+    pub async fn write_at(
+        &mut self,
+        path: impl Into<String>,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), IdeviceError> {
+        let mut fd = self.open(path, AfcFopenMode::Rw).await?;
+        let res = async {
+            fd.seek(SeekFrom::Start(offset)).await?;
+            fd.write_entire(data).await
+        }
+        .await;
+        let closed = fd.close().await;
+        res?;
+        closed
+    }
+
     /// Opens an owned file on the device
     ///
     /// # Arguments
@@ -508,8 +842,36 @@ impl AfcClient {
         Ok(())
     }
 
+    /// Creates a hard or symbolic link, reporting links the device refuses to create
+    ///
+    /// Unlike [`AfcClient::link`], failures caused by the link itself, such as a hard link
+    /// across filesystems or a link type the device doesn't support, are returned as
+    /// `IdeviceError::AfcLinkFailed` so they can be told apart from other AFC errors.
+    ///
+    /// # Arguments
+    /// * `link_type` - Type of link to create
+    /// * `target` - Path the link points to
+    /// * `link` - Path where the link should be created
+    pub async fn make_link(
+        &mut self,
+        link_type: AfcLinkType,
+        target: impl Into<String>,
+        link: impl Into<String>,
+    ) -> Result<(), IdeviceError> {
+        match self.link(target, link, link_type.into()).await {
+            Err(IdeviceError::Afc(
+                e @ (AfcError::OpNotSupported | AfcError::InvalidArg | AfcError::UnknownError),
+            )) => Err(IdeviceError::AfcLinkFailed(e)),
+            res => res,
+        }
+    }
+
     /// Renames a file or directory
     ///
+    /// The rename is atomic and can move the entry to another directory, so a file can be
+    /// written under a temporary name and then moved into place. An existing file at `target`
+    /// is replaced.
+    ///
     /// # Arguments
     /// * `source` - Current path of the file/directory
     /// * `target` - New path for the file/directory
@@ -549,6 +911,287 @@ impl AfcClient {
         Ok(())
     }
 
+    /// Recursively uploads a local directory to the device
+    ///
+    /// Remote directories are created as needed, so the local tree is reproduced under
+    /// `remote`, including empty directories. A failure on one entry doesn't abort the
+    /// transfer; it is recorded and the rest of the tree is still uploaded.
+    ///
+    /// # Arguments
+    /// * `local` - Path to the local directory to upload
+    /// * `remote` - Path on the device to upload into
+    /// * `follow_symlinks` - Whether to follow symbolic links, or recreate them on the device
+    ///
+    /// # Returns
+    /// The local paths that failed to upload, along with their errors
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if `local` can't be read or `remote` can't be created
+    pub async fn upload_dir(
+        &mut self,
+        local: &Path,
+        remote: &str,
+        follow_symlinks: bool,
+    ) -> Result<Vec<(PathBuf, IdeviceError)>, IdeviceError> {
+        let mut failures = Vec::new();
+        let mut visited = HashSet::new();
+        visited.insert(tokio::fs::canonicalize(local).await?);
+
+        self.mk_dir(remote).await?;
+        let mut pending = vec![(
+            local.to_path_buf(),
+            remote.trim_end_matches('/').to_string(),
+        )];
+
+        while let Some((local_dir, remote_dir)) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&local_dir).await {
+                Ok(e) => e,
+                Err(e) => {
+                    failures.push((local_dir, e.into()));
+                    continue;
+                }
+            };
+
+            loop {
+                let entry = match entries.next_entry().await {
+                    Ok(Some(e)) => e,
+                    Ok(None) => break,
+                    Err(e) => {
+                        failures.push((local_dir.clone(), e.into()));
+                        break;
+                    }
+                };
+                let local_path = entry.path();
+                let remote_path = format!("{remote_dir}/{}", entry.file_name().to_string_lossy());
+
+                let file_type = match entry.file_type().await {
+                    Ok(t) => t,
+                    Err(e) => {
+                        failures.push((local_path, e.into()));
+                        continue;
+                    }
+                };
+                let metadata = if file_type.is_symlink() {
+                    if !follow_symlinks {
+                        let target = match tokio::fs::read_link(&local_path).await {
+                            Ok(t) => t,
+                            Err(e) => {
+                                failures.push((local_path, e.into()));
+                                continue;
+                            }
+                        };
+                        if let Err(e) = self
+                            .make_link(AfcLinkType::Symbolic, target.to_string_lossy(), remote_path)
+                            .await
+                        {
+                            failures.push((local_path, e));
+                        }
+                        continue;
+                    }
+                    match tokio::fs::metadata(&local_path).await {
+                        Ok(m) => m,
+                        Err(e) => {
+                            failures.push((local_path, e.into()));
+                            continue;
+                        }
+                    }
+                } else {
+                    match entry.metadata().await {
+                        Ok(m) => m,
+                        Err(e) => {
+                            failures.push((local_path, e.into()));
+                            continue;
+                        }
+                    }
+                };
+
+                if metadata.is_dir() {
+                    // Guard against symlink cycles
+                    match tokio::fs::canonicalize(&local_path).await {
+                        Ok(c) => {
+                            if !visited.insert(c) {
+                                continue;
+                            }
+                        }
+                        Err(e) => {
+                            failures.push((local_path, e.into()));
+                            continue;
+                        }
+                    }
+                    if let Err(e) = self.mk_dir(remote_path.as_str()).await {
+                        failures.push((local_path, e));
+                        continue;
+                    }
+                    pending.push((local_path, remote_path));
+                } else if let Err(e) = self.upload_file(&local_path, remote_path).await {
+                    failures.push((local_path, e));
+                }
+            }
+        }
+
+        Ok(failures)
+    }
+
+    async fn upload_file(&mut self, local: &Path, remote: String) -> Result<(), IdeviceError> {
+        let mut local = tokio::fs::File::open(local).await?;
+        let mut fd = self.open_write(remote).await?;
+        tokio::io::copy(&mut local, &mut fd).await?;
+        fd.close().await
+    }
+
+    /// Walks the tree under a directory, yielding every file and directory in it
+    ///
+    /// Directories are yielded before their contents. Symbolic links are yielded but never
+    /// followed, so link cycles can't make the walk loop. An entry that fails to be listed or
+    /// read is yielded as an error and the walk goes on with the rest of the tree.
+    ///
+    /// # Arguments
+    /// * `root` - Path of the directory to walk, which isn't yielded itself
+    /// * `max_depth` - How many levels below `root` to descend, or `None` for no limit.
+    ///   Entries of `root` itself are at depth 1, so `Some(0)` yields nothing.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example(client: &mut idevice::afc::AfcClient) {
+    /// use futures::StreamExt;
+    ///
+    /// let mut walk = client.walk("/DCIM", None);
+    /// while let Some(entry) = walk.next().await {
+    ///     println!("{}", entry.unwrap().path);
+    /// }
+    /// # }
+    /// ```
+    pub fn walk(
+        &mut self,
+        root: &str,
+        max_depth: Option<usize>,
+    ) -> Pin<Box<dyn Stream<Item = Result<AfcEntry, IdeviceError>> + Send + '_>> {
+        struct Walk<'a> {
+            client: &'a mut AfcClient,
+            max_depth: Option<usize>,
+            /// Directories left to list, with their depth
+            dirs: Vec<(String, usize)>,
+            /// Entries left to read, with their depth
+            entries: Vec<(String, usize)>,
+        }
+
+        let state = Walk {
+            client: self,
+            max_depth,
+            dirs: match max_depth {
+                Some(0) => Vec::new(),
+                _ => vec![(root.trim_end_matches('/').to_string(), 0)],
+            },
+            entries: Vec::new(),
+        };
+
+        Box::pin(futures::stream::unfold(state, |mut walk| async move {
+            loop {
+                // Descend into the last directory found before reading its siblings
+                if let Some((dir, depth)) = walk.dirs.pop() {
+                    let list_path = if dir.is_empty() { "/" } else { dir.as_str() };
+                    let names = match walk.client.list_dir(list_path).await {
+                        Ok(n) => n,
+                        Err(e) => return Some((Err(e), walk)),
+                    };
+                    walk.entries.extend(
+                        names
+                            .into_iter()
+                            .rev()
+                            .filter(|n| n != "." && n != "..")
+                            .map(|n| (format!("{dir}/{n}"), depth + 1)),
+                    );
+                    continue;
+                }
+
+                let (path, depth) = walk.entries.pop()?;
+                let info = match walk.client.get_file_info(path.as_str()).await {
+                    Ok(i) => i,
+                    Err(e) => return Some((Err(e), walk)),
+                };
+                if info.file_type == AfcFileType::Directory
+                    && walk.max_depth.is_none_or(|max| depth < max)
+                {
+                    walk.dirs.push((path.clone(), depth));
+                }
+                return Some((Ok(AfcEntry { path, depth, info }), walk));
+            }
+        }))
+    }
+
+    /// Downloads a file from the device to a local path, reporting progress
+    ///
+    /// The file is transferred in chunks, so it is never held in memory as a whole. The
+    /// total is the file's size when the download starts.
+    ///
+    /// # Arguments
+    /// * `remote` - Path of the file on the device
+    /// * `local` - Local path to write to, created or truncated
+    /// * `on_progress` - Called with the bytes transferred so far and the total, before the
+    ///   first chunk and after each one
+    pub async fn download_with_progress(
+        &mut self,
+        remote: impl Into<String>,
+        local: &Path,
+        mut on_progress: impl FnMut(u64, u64),
+    ) -> Result<(), IdeviceError> {
+        let remote = remote.into();
+        let total = self.get_file_info(remote.as_str()).await?.size as u64;
+        let mut local = tokio::fs::File::create(local).await?;
+        let mut fd = self.open_read(remote).await?;
+
+        let mut transferred = 0;
+        on_progress(transferred, total);
+        while transferred < total {
+            let chunk = fd
+                .read_n(PROGRESS_CHUNK_SIZE.min(total - transferred) as usize)
+                .await?;
+            if chunk.is_empty() {
+                break;
+            }
+            local.write_all(&chunk).await?;
+            transferred += chunk.len() as u64;
+            on_progress(transferred, total);
+        }
+        fd.close().await?;
+        local.flush().await?;
+        Ok(())
+    }
+
+    /// Uploads a local file to the device, reporting progress
+    ///
+    /// The file is transferred in chunks, so it is never held in memory as a whole.
+    ///
+    /// # Arguments
+    /// * `local` - Local path of the file to upload
+    /// * `remote` - Path on the device to write to, created or truncated
+    /// * `on_progress` - Called with the bytes transferred so far and the local file's size,
+    ///   before the first chunk and after each one
+    pub async fn upload_with_progress(
+        &mut self,
+        local: &Path,
+        remote: impl Into<String>,
+        mut on_progress: impl FnMut(u64, u64),
+    ) -> Result<(), IdeviceError> {
+        let mut local = tokio::fs::File::open(local).await?;
+        let total = local.metadata().await?.len();
+        let mut fd = self.open_write(remote).await?;
+
+        let mut buf = vec![0; PROGRESS_CHUNK_SIZE as usize];
+        let mut transferred = 0;
+        on_progress(transferred, total);
+        loop {
+            let n = local.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            fd.write_entire(&buf[..n]).await?;
+            transferred += n as u64;
+            on_progress(transferred, total);
+        }
+        fd.close().await
+    }
+
     /// Reads a response packet from the device
     ///
     /// # Returns
@@ -581,3 +1224,298 @@ impl AfcClient {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use tokio::io::{AsyncWriteExt, DuplexStream};
+
+    use super::*;
+
+    #[test]
+    fn parses_file_info() {
+        let payload = [
+            "st_size",
+            "123",
+            "st_blocks",
+            "8",
+            "st_nlink",
+            "1",
+            "st_ifmt",
+            "S_IFLNK",
+            "st_mtime",
+            "1700000000000000000",
+            "st_birthtime",
+            "1600000000000000000",
+            "st_link_target",
+            "/var/mobile",
+        ]
+        .join("\0");
+
+        let info = FileInfo::from_payload(payload.as_bytes()).unwrap();
+        assert_eq!(info.size, 123);
+        assert_eq!(info.blocks, 8);
+        assert_eq!(info.file_type, AfcFileType::Symlink);
+        assert_eq!(info.modified.and_utc().timestamp(), 1_700_000_000);
+        assert_eq!(info.creation.and_utc().timestamp(), 1_600_000_000);
+        assert_eq!(info.st_link_target.as_deref(), Some("/var/mobile"));
+    }
+
+    /// Creates a client whose device has already queued the given responses
+    ///
+    /// The device end is returned so the connection stays open while it's held.
+    async fn client_with_responses(responses: &[AfcPacket]) -> (AfcClient, DuplexStream) {
+        let (client, mut device) = tokio::io::duplex(1024);
+        for packet in responses {
+            device.write_all(&packet.serialize()).await.unwrap();
+        }
+        (
+            AfcClient::new(Idevice::new(Box::new(client), "test")),
+            device,
+        )
+    }
+
+    fn status_packet(error: AfcError) -> AfcPacket {
+        AfcPacket {
+            header: AfcPacketHeader {
+                magic: MAGIC,
+                entire_len: AfcPacketHeader::LEN + 8,
+                header_payload_len: AfcPacketHeader::LEN + 8,
+                packet_num: 0,
+                operation: AfcOpcode::Status,
+            },
+            header_payload: (error as u64).to_le_bytes().to_vec(),
+            payload: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn unsupported_link_is_distinct() {
+        let (mut client, _device) = client_with_responses(&[
+            status_packet(AfcError::OpNotSupported),
+            status_packet(AfcError::PermDenied),
+        ])
+        .await;
+
+        assert!(matches!(
+            client.make_link(AfcLinkType::Hard, "/a", "/b").await,
+            Err(IdeviceError::AfcLinkFailed(AfcError::OpNotSupported))
+        ));
+        assert!(matches!(
+            client.make_link(AfcLinkType::Symbolic, "/a", "/b").await,
+            Err(IdeviceError::Afc(AfcError::PermDenied))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rename_moves_across_directories() {
+        use tokio::io::AsyncReadExt;
+
+        let (mut client, mut device) = client_with_responses(&[
+            status_packet(AfcError::Success),
+            status_packet(AfcError::ObjectNotFound),
+        ])
+        .await;
+
+        client
+            .rename("/Downloads/book.tmp", "/Books/book.epub")
+            .await
+            .unwrap();
+        assert!(matches!(
+            client.get_file_info("/Downloads/book.tmp").await,
+            Err(IdeviceError::Afc(AfcError::ObjectNotFound))
+        ));
+
+        let mut header = [0; AfcPacketHeader::LEN as usize];
+        device.read_exact(&mut header).await.unwrap();
+        let field = |i: usize| u64::from_le_bytes(header[i * 8..i * 8 + 8].try_into().unwrap());
+        assert_eq!(field(4), AfcOpcode::RenamePath as u64);
+        let mut paths = vec![0; (field(2) - AfcPacketHeader::LEN) as usize];
+        device.read_exact(&mut paths).await.unwrap();
+        assert_eq!(paths, b"/Downloads/book.tmp\0/Books/book.epub\0");
+    }
+
+    #[tokio::test]
+    async fn remove_all_falls_back_to_removing_entries() {
+        use tokio::io::AsyncReadExt;
+
+        let (mut client, mut device) = client_with_responses(&[
+            status_packet(AfcError::OpNotSupported),
+            file_info_packet("S_IFDIR", 0),
+            data_packet(&[".", "..", "Payload"]),
+            file_info_packet("S_IFDIR", 0),
+            data_packet(&[".", "..", "app"]),
+            file_info_packet("S_IFREG", 3),
+            status_packet(AfcError::Success),
+            status_packet(AfcError::Success),
+            status_packet(AfcError::Success),
+        ])
+        .await;
+
+        client.remove_all("/PublicStaging/app").await.unwrap();
+        drop(client);
+
+        let mut sent = Vec::new();
+        device.read_to_end(&mut sent).await.unwrap();
+        let mut removed = Vec::new();
+        let mut rest = sent.as_slice();
+        while !rest.is_empty() {
+            let field = |i: usize| u64::from_le_bytes(rest[i * 8..i * 8 + 8].try_into().unwrap());
+            let (entire_len, header_len) = (field(1) as usize, field(2) as usize);
+            if field(4) == AfcOpcode::RemovePath as u64 {
+                removed.push(String::from_utf8(rest[40..header_len].to_vec()).unwrap());
+            }
+            rest = &rest[entire_len..];
+        }
+        assert_eq!(
+            removed,
+            [
+                "/PublicStaging/app/Payload/app",
+                "/PublicStaging/app/Payload",
+                "/PublicStaging/app"
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_file_info_attribute() {
+        assert!(matches!(
+            FileInfo::from_payload(b"st_size\x00123\x00"),
+            Err(IdeviceError::AfcMissingAttribute)
+        ));
+    }
+
+    fn data_packet(payload: &[&str]) -> AfcPacket {
+        let payload = payload.join("\0").into_bytes();
+        AfcPacket {
+            header: AfcPacketHeader {
+                magic: MAGIC,
+                entire_len: AfcPacketHeader::LEN + payload.len() as u64,
+                header_payload_len: AfcPacketHeader::LEN,
+                packet_num: 0,
+                operation: AfcOpcode::Data,
+            },
+            header_payload: Vec::new(),
+            payload,
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_insufficient_free_space() {
+        let device_info = || {
+            data_packet(&[
+                "Model",
+                "iPhone14,2",
+                "FSTotalBytes",
+                "128000000000",
+                "FSFreeBytes",
+                "4096000",
+                "FSBlockSize",
+                "4096",
+            ])
+        };
+        let (mut client, _device) = client_with_responses(&[device_info(), device_info()]).await;
+
+        client.ensure_free_space(4_096_000).await.unwrap();
+        assert!(matches!(
+            client.ensure_free_space(4_096_001).await,
+            Err(IdeviceError::NotEnoughSpace {
+                needed: 4_096_001,
+                available: 4_096_000,
+            })
+        ));
+    }
+
+    fn file_info_packet(st_ifmt: &str, size: usize) -> AfcPacket {
+        data_packet(&[
+            "st_size",
+            &size.to_string(),
+            "st_blocks",
+            "0",
+            "st_nlink",
+            "1",
+            "st_ifmt",
+            st_ifmt,
+            "st_mtime",
+            "0",
+            "st_birthtime",
+            "0",
+        ])
+    }
+
+    #[tokio::test]
+    async fn walk_yields_tree_depth_first() {
+        let (mut client, _device) = client_with_responses(&[
+            data_packet(&[".", "..", "DCIM", "link"]),
+            file_info_packet("S_IFDIR", 0),
+            data_packet(&[".", "..", "IMG_0001.HEIC"]),
+            file_info_packet("S_IFREG", 0),
+            file_info_packet("S_IFLNK", 0),
+        ])
+        .await;
+
+        let entries: Vec<_> = client
+            .walk("/", None)
+            .map(|e| {
+                let e = e.unwrap();
+                (e.path, e.depth, e.info.file_type)
+            })
+            .collect()
+            .await;
+        assert_eq!(
+            entries,
+            [
+                ("/DCIM".to_string(), 1, AfcFileType::Directory),
+                ("/DCIM/IMG_0001.HEIC".to_string(), 2, AfcFileType::File),
+                ("/link".to_string(), 1, AfcFileType::Symlink),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn walk_stops_at_max_depth() {
+        let (mut client, _device) =
+            client_with_responses(&[data_packet(&["DCIM"]), file_info_packet("S_IFDIR", 0)]).await;
+
+        let entries: Vec<_> = client.walk("/", Some(1)).collect().await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].as_ref().unwrap().path, "/DCIM");
+    }
+
+    #[tokio::test]
+    async fn walk_with_zero_max_depth_yields_nothing() {
+        let (mut client, _device) = client_with_responses(&[]).await;
+
+        assert!(client.walk("/", Some(0)).next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn download_reports_progress() {
+        let mut open_res = data_packet(&[]);
+        open_res.header.operation = AfcOpcode::FileOpenRes;
+        open_res.header_payload = 3u64.to_le_bytes().to_vec();
+        open_res.header.header_payload_len += 8;
+        open_res.header.entire_len += 8;
+
+        let (mut client, _device) = client_with_responses(&[
+            file_info_packet("S_IFREG", 5),
+            open_res,
+            data_packet(&["hello"]),
+            status_packet(AfcError::Success),
+        ])
+        .await;
+
+        let local =
+            std::env::temp_dir().join(format!("idevice-afc-download-{}", std::process::id()));
+        let mut reports = Vec::new();
+        client
+            .download_with_progress("/file", &local, |done, total| reports.push((done, total)))
+            .await
+            .unwrap();
+        let contents = tokio::fs::read(&local).await.unwrap();
+        tokio::fs::remove_file(&local).await.unwrap();
+
+        assert_eq!(contents, b"hello");
+        assert_eq!(reports, [(0, 5), (5, 5)]);
+    }
+}
@@ -1,6 +1,6 @@
 //! Abstraction for Apple Mobile File Integrity
 
-use crate::{Idevice, IdeviceError, IdeviceService, obf};
+use crate::{Idevice, IdeviceError, IdeviceService, PlistExt, lockdown::LockdownClient, obf};
 
 /// Client for interacting with the AMFI service on the device
 #[derive(Debug)]
@@ -9,6 +9,20 @@ pub struct AmfiClient {
     pub idevice: Idevice,
 }
 
+/// What enabling developer mode would run into, see [`AmfiClient::developer_mode_preflight`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DevModePreflight {
+    /// Developer mode is already enabled, so there is nothing to do
+    pub enabled: bool,
+    /// The device is locked with a passcode, which blocks enabling developer mode
+    pub passcode_locked: bool,
+    /// Enabling developer mode reboots the device
+    ///
+    /// The device doesn't report this. It is derived as `!enabled`, since enabling developer
+    /// mode always reboots the device.
+    pub reboot_required: bool,
+}
+
 impl IdeviceService for AmfiClient {
     /// Returns the amfi service name as registered with lockdownd
     fn service_name() -> std::borrow::Cow<'static, str> {
@@ -75,8 +89,11 @@ impl AmfiClient {
         }
     }
 
-    /// Gets the developer mode status
-    pub async fn get_developer_mode_status(&mut self) -> Result<bool, IdeviceError> {
+    /// Queries whether developer mode is currently enabled on the device
+    ///
+    /// # Returns
+    /// `true` if developer mode is enabled, `false` otherwise
+    pub async fn developer_mode_status(&mut self) -> Result<bool, IdeviceError> {
         let request = crate::plist!({
             "action": 3,
         });
@@ -88,10 +105,36 @@ impl AmfiClient {
             _ => return Err(IdeviceError::UnexpectedResponse),
         }
 
-        match res.get("status").and_then(|x| x.as_boolean()) {
-            Some(b) => Ok(b),
-            _ => Err(IdeviceError::UnexpectedResponse),
-        }
+        res.get_bool("status")
+    }
+
+    /// Checks what enabling developer mode would run into, without changing anything
+    ///
+    /// AMFI has no request for this, so the developer mode status comes from AMFI and the
+    /// passcode state from lockdown. Lockdown only reports whether the device is locked, so an
+    /// unlocked device with a passcode isn't caught here, and enabling then fails with the
+    /// device's error message.
+    ///
+    /// # Arguments
+    /// * `lockdown` - A lockdown client for the same device, with a session started
+    pub async fn developer_mode_preflight(
+        &mut self,
+        lockdown: &mut LockdownClient,
+    ) -> Result<DevModePreflight, IdeviceError> {
+        let enabled = self.developer_mode_status().await?;
+        let passcode_locked = lockdown.is_passcode_locked().await?;
+        Ok(DevModePreflight {
+            enabled,
+            passcode_locked,
+            reboot_required: !enabled,
+        })
+    }
+
+    /// Gets the developer mode status
+    ///
+    /// Alias for [`AmfiClient::developer_mode_status`]
+    pub async fn get_developer_mode_status(&mut self) -> Result<bool, IdeviceError> {
+        self.developer_mode_status().await
     }
 
     /// Trusts an app signer
@@ -112,9 +155,47 @@ impl AmfiClient {
             _ => return Err(IdeviceError::UnexpectedResponse),
         }
 
-        match res.get("status").and_then(|x| x.as_boolean()) {
-            Some(b) => Ok(b),
-            _ => Err(IdeviceError::UnexpectedResponse),
-        }
+        res.get_bool("status")
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::{MockProvider, MockScript};
+
+    #[tokio::test]
+    async fn preflight_combines_amfi_and_lockdown_state() {
+        let provider = MockProvider::new()
+            .with_service(
+                AmfiClient::service_name(),
+                MockScript::new()
+                    .expect(crate::plist!({ "action": 3 }))
+                    .respond(crate::plist!({ "success": true, "status": false })),
+            )
+            .with_port(
+                LockdownClient::LOCKDOWND_PORT,
+                MockScript::new()
+                    .expect(crate::plist!({
+                        "Request": "GetValue",
+                        "Key": "PasswordProtected",
+                    }))
+                    .respond(crate::plist!({ "Key": "PasswordProtected", "Value": true })),
+            );
+
+        let mut amfi = AmfiClient::connect(&provider).await.unwrap();
+        let mut lockdown = LockdownClient::connect(&provider).await.unwrap();
+        assert_eq!(
+            amfi.developer_mode_preflight(&mut lockdown).await.unwrap(),
+            DevModePreflight {
+                enabled: false,
+                passcode_locked: true,
+                reboot_required: true,
+            }
+        );
+        drop(amfi);
+        drop(lockdown);
+
+        assert!(provider.verify().await.is_empty());
     }
 }
@@ -1,14 +1,23 @@
 //! Companion Proxy is Apple's bridge to connect to the Apple Watch
+//!
+//! The phone keeps a registry of its paired watches. This service lists them, reads values
+//! from their registry entries, and forwards ports so services on a watch can be reached
+//! through the phone.
 
 use tracing::warn;
 
 use crate::{Idevice, IdeviceError, IdeviceService, RsdService, obf};
 
+/// Client for the companion proxy service
 #[derive(Debug)]
 pub struct CompanionProxy {
     idevice: Idevice,
 }
 
+/// Name matching the other service clients in this crate
+pub type CompanionProxyClient = CompanionProxy;
+
+/// Stream of device events, created by [`CompanionProxy::listen_for_devices`]
 #[derive(Debug)]
 pub struct CompanionProxyStream {
     proxy: CompanionProxy,
@@ -37,10 +46,12 @@ impl RsdService for CompanionProxy {
 }
 
 impl CompanionProxy {
+    /// Creates a new client from an existing connection
     pub fn new(idevice: Idevice) -> Self {
         Self { idevice }
     }
 
+    /// Lists the UDIDs of the paired companion devices
     pub async fn get_device_registry(&mut self) -> Result<Vec<String>, IdeviceError> {
         let command = crate::plist!({
             "Command": "GetDeviceRegistry"
@@ -66,6 +77,7 @@ impl CompanionProxy {
         Ok(res)
     }
 
+    /// Starts listening for companion devices connecting and disconnecting
     pub async fn listen_for_devices(mut self) -> Result<CompanionProxyStream, IdeviceError> {
         let command = crate::plist!({
             "Command": "StartListeningForDevices"
@@ -75,6 +87,14 @@ impl CompanionProxy {
         Ok(CompanionProxyStream { proxy: self })
     }
 
+    /// Reads a value from a paired device's registry entry
+    ///
+    /// # Arguments
+    /// * `udid` - UDID of the paired device
+    /// * `key` - Registry key to read
+    ///
+    /// # Errors
+    /// `IdeviceError::NotFound` if the device has no value for the key
     pub async fn get_value(
         &mut self,
         udid: impl Into<String>,
@@ -100,6 +120,15 @@ impl CompanionProxy {
         }
     }
 
+    /// Forwards a port on a paired device through the phone
+    ///
+    /// # Arguments
+    /// * `port` - Port on the paired device
+    /// * `service_name` - Name of the service being forwarded, if any
+    /// * `options` - Extra keys merged into the request
+    ///
+    /// # Returns
+    /// The port on the phone that reaches the paired device's port
     pub async fn start_forwarding_service_port(
         &mut self,
         port: u16,
@@ -126,6 +155,7 @@ impl CompanionProxy {
         }
     }
 
+    /// Stops forwarding a port started by [`Self::start_forwarding_service_port`]
     pub async fn stop_forwarding_service_port(&mut self, port: u16) -> Result<(), IdeviceError> {
         let command = crate::plist!({
            "Command": "StopForwardingServicePort",
@@ -146,7 +176,39 @@ impl CompanionProxy {
 }
 
 impl CompanionProxyStream {
+    /// Waits for the next device event
     pub async fn next(&mut self) -> Result<plist::Dictionary, IdeviceError> {
         self.proxy.idevice.read_plist().await
     }
 }
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::{MockProvider, MockScript};
+
+    #[tokio::test]
+    async fn lists_paired_devices() {
+        let provider = MockProvider::new().with_service(
+            CompanionProxyClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({ "Command": "GetDeviceRegistry" }))
+                .respond(crate::plist!({
+                    "PairedDevicesArray": [
+                        "00008006-000A1B2C3D4E5F60",
+                        7,
+                        "00008301-00112233445566AA"
+                    ]
+                })),
+        );
+
+        let mut client = CompanionProxyClient::connect(&provider).await.unwrap();
+        assert_eq!(
+            client.get_device_registry().await.unwrap(),
+            vec!["00008006-000A1B2C3D4E5F60", "00008301-00112233445566AA"]
+        );
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+}
@@ -17,7 +17,10 @@ use crate::{Idevice, IdeviceError, IdeviceService, obf};
 
 use byteorder::{BigEndian, WriteBytesExt};
 use serde::{Deserialize, Serialize};
-use std::io::{self, IoSlice, Write};
+use std::{
+    io::{self, IoSlice, Write},
+    net::IpAddr,
+};
 
 /// A representation of a CDTunnel packet used in the CoreDeviceProxy protocol.
 #[derive(Debug, PartialEq)]
@@ -135,9 +138,41 @@ pub struct HandshakeResponse {
     pub server_rsd_port: u16,
 }
 
+/// Parsed addressing information for an established CoreDeviceProxy tunnel.
+///
+/// Returned by [`CoreDeviceProxy::tunnel_info`]. The tunnel stays up for as long as the
+/// `CoreDeviceProxy` (or the adapter created from it) is alive, and is torn down when it
+/// is dropped or closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TunnelInfo {
+    /// The IP address assigned to the host end of the tunnel.
+    pub client_address: IpAddr,
+    /// The IP address of the device end of the tunnel.
+    pub server_address: IpAddr,
+    /// The port RemoteServiceDiscovery listens on, at `server_address`.
+    pub server_rsd_port: u16,
+    /// The MTU negotiated for the tunnel.
+    pub mtu: u16,
+}
+
 impl CoreDeviceProxy {
     const DEFAULT_MTU: u32 = 16000;
 
+    /// Returns the addressing information negotiated during the handshake.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TunnelInfo)` with the parsed tunnel addresses and RSD port.
+    /// * `Err(IdeviceError)` if the device returned an address that can't be parsed.
+    pub fn tunnel_info(&self) -> Result<TunnelInfo, IdeviceError> {
+        Ok(TunnelInfo {
+            client_address: self.handshake.client_parameters.address.parse()?,
+            server_address: self.handshake.server_address.parse()?,
+            server_rsd_port: self.handshake.server_rsd_port,
+            mtu: self.handshake.client_parameters.mtu,
+        })
+    }
+
     /// Constructs a new `CoreDeviceProxy` by performing a handshake on the given `Idevice`.
     ///
     /// # Arguments
@@ -221,22 +256,19 @@ impl CoreDeviceProxy {
 
     /// Creates a software-based TCP tunnel adapter, if the `tunnel_tcp_stack` feature is enabled.
     ///
+    /// The adapter takes over the tunnel; closing or dropping it tears the tunnel down.
+    ///
     /// # Returns
     ///
     /// * `Ok(Adapter)` for the software TCP stack.
     /// * `Err(IdeviceError)` if IP parsing or socket extraction fails.
     #[cfg(feature = "tunnel_tcp_stack")]
     pub fn create_software_tunnel(self) -> Result<crate::tcp::adapter::Adapter, IdeviceError> {
-        let our_ip = self
-            .handshake
-            .client_parameters
-            .address
-            .parse::<std::net::IpAddr>()?;
-        let their_ip = self.handshake.server_address.parse::<std::net::IpAddr>()?;
+        let info = self.tunnel_info()?;
         Ok(crate::tcp::adapter::Adapter::new(
-            Box::new(self.idevice.socket.unwrap()),
-            our_ip,
-            their_ip,
+            Box::new(self.idevice.get_socket().unwrap()),
+            info.client_address,
+            info.server_address,
         ))
     }
 }
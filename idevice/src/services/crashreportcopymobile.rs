@@ -6,10 +6,23 @@
 //! `CrashReportCopyMobile` service using the AFC protocol. It also includes a
 //! function to trigger a flush of crash logs from system storage into the
 //! crash reports directory by connecting to the `com.apple.crashreportmover` service.
+//! [`wait_for_sysdiagnose`] waits for a sysdiagnose archive to be written and pulls it,
+//! and `capture_sysdiagnose` starts one over RSD and pulls it.
+
+use std::{
+    collections::HashSet,
+    path::Path,
+    time::{Duration, Instant},
+};
 
 use tracing::{debug, warn};
 
-use crate::{Idevice, IdeviceError, IdeviceService, afc::AfcClient, lockdown::LockdownClient, obf};
+use crate::{
+    Idevice, IdeviceError, IdeviceService,
+    afc::{AfcClient, errors::AfcError, opcode::AfcFopenMode},
+    lockdown::LockdownClient,
+    obf,
+};
 
 /// Client for managing crash logs on an iOS device.
 ///
@@ -56,18 +69,18 @@ impl CrashReportCopyMobileClient {
     pub async fn ls(&mut self, dir_path: Option<&str>) -> Result<Vec<String>, IdeviceError> {
         let path = dir_path.unwrap_or("/");
         let mut res = self.afc_client.list_dir(path).await?;
-        if res.len() > 2 {
-            if &res[0] == "." {
-                res.swap_remove(0);
-            }
-            if &res[1] == ".." {
-                res.swap_remove(1);
-            }
-        }
+        res.retain(|x| x != "." && x != "..");
 
         Ok(res)
     }
 
+    /// Lists crash report files in the root of the crash logs directory.
+    ///
+    /// Equivalent to `ls(None)`.
+    pub async fn list(&mut self) -> Result<Vec<String>, IdeviceError> {
+        self.ls(None).await
+    }
+
     /// Retrieves the contents of a specified crash log file.
     ///
     /// # Arguments
@@ -88,6 +101,30 @@ impl CrashReportCopyMobileClient {
         f.read_entire().await
     }
 
+    /// Retrieves a crash log file and writes it to a local path.
+    ///
+    /// # Arguments
+    /// * `remote` - Name of the log file to retrieve.
+    /// * `local` - Path to write the file to. It is created or truncated.
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if the file cannot be read or written.
+    pub async fn pull_to(&mut self, remote: &str, local: &Path) -> Result<(), IdeviceError> {
+        let data = self.pull(remote).await?;
+        tokio::fs::write(local, data).await?;
+        Ok(())
+    }
+
+    /// Triggers a flush of crash logs from system storage.
+    ///
+    /// Call this before listing so freshly generated reports are available.
+    /// See [`flush_reports`].
+    pub async fn flush(
+        provider: &dyn crate::provider::IdeviceProvider,
+    ) -> Result<(), IdeviceError> {
+        flush_reports(provider).await
+    }
+
     /// Removes a specified crash log file from the device.
     ///
     /// # Arguments
@@ -164,3 +201,183 @@ pub async fn flush_reports(
         Err(IdeviceError::CrashReportMoverBadResponse(res))
     }
 }
+
+/// Directory in the crash logs directory that sysdiagnose archives are written to
+const SYSDIAGNOSE_DIR: &str = "/DiagnosticLogs/sysdiagnose";
+/// Prefix of archives that are still being written
+const SYSDIAGNOSE_IN_PROGRESS_PREFIX: &str = "IN_PROGRESS_";
+const SYSDIAGNOSE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Size of each read while pulling the archive, so progress can be reported
+const SYSDIAGNOSE_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Stage of a [`wait_for_sysdiagnose`] or `capture_sysdiagnose` call, reported to its
+/// progress callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SysdiagnoseProgress {
+    /// Waiting for the device to start writing an archive
+    Waiting,
+    /// The device is writing the archive
+    Generating,
+    /// Pulling the finished archive
+    Downloading {
+        /// Bytes pulled so far
+        received: u64,
+        /// Size of the archive
+        total: u64,
+    },
+}
+
+/// Waits for a new sysdiagnose archive and pulls it from the crash logs directory
+///
+/// This only waits, it doesn't start generation. No lockdown service starts a sysdiagnose,
+/// so it must be started on the device, by pressing both volume buttons and the side button
+/// together, before or after this is called. Archives that existed when this was called are
+/// ignored. Use `capture_sysdiagnose` for devices reachable over RSD, which starts generation
+/// itself.
+///
+/// # Arguments
+/// * `provider` - The device provider used for connection and pairing info
+/// * `timeout` - How long to wait for the archive to be finished. Generation usually takes a
+///   few minutes.
+/// * `progress` - Called as the capture moves through each [`SysdiagnoseProgress`] stage,
+///   and after each chunk of the archive is pulled
+///
+/// # Returns
+/// The contents of the `.tar.gz` archive
+///
+/// # Errors
+/// `IdeviceError::Timeout` if no archive is finished within `timeout`
+pub async fn wait_for_sysdiagnose(
+    provider: &dyn crate::provider::IdeviceProvider,
+    timeout: Duration,
+    mut progress: impl FnMut(SysdiagnoseProgress),
+) -> Result<Vec<u8>, IdeviceError> {
+    let mut client = CrashReportCopyMobileClient::connect(provider).await?;
+    let existing = list_sysdiagnoses(&mut client)
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    progress(SysdiagnoseProgress::Waiting);
+    let deadline = Instant::now() + timeout;
+    let mut generating = false;
+    let name = loop {
+        if Instant::now() >= deadline {
+            return Err(IdeviceError::Timeout);
+        }
+        tokio::time::sleep(SYSDIAGNOSE_POLL_INTERVAL).await;
+
+        let new = list_sysdiagnoses(&mut client)
+            .await?
+            .into_iter()
+            .filter(|n| !existing.contains(n))
+            .collect::<Vec<_>>();
+        if let Some(name) = new.iter().find(|n| is_finished_sysdiagnose(n)) {
+            break name.clone();
+        }
+        if !generating
+            && new
+                .iter()
+                .any(|n| n.starts_with(SYSDIAGNOSE_IN_PROGRESS_PREFIX))
+        {
+            generating = true;
+            progress(SysdiagnoseProgress::Generating);
+        }
+    };
+    debug!("Pulling sysdiagnose {name}");
+
+    let path = format!("{SYSDIAGNOSE_DIR}/{name}");
+    let total = client.afc_client.get_file_info(&path).await?.size as u64;
+    let mut file = client.afc_client.open(path, AfcFopenMode::RdOnly).await?;
+    let mut data = Vec::with_capacity(total as usize);
+    while (data.len() as u64) < total {
+        let chunk = file.read_n(SYSDIAGNOSE_CHUNK_SIZE).await?;
+        if chunk.is_empty() {
+            break;
+        }
+        data.extend(chunk);
+        progress(SysdiagnoseProgress::Downloading {
+            received: data.len() as u64,
+            total,
+        });
+    }
+    file.close().await?;
+
+    Ok(data)
+}
+
+/// Starts a sysdiagnose over RSD and pulls the archive
+///
+/// Generation is started through the `com.apple.coredevice.diagnosticsservice` service, which
+/// replies once the archive is written, and the archive is then pulled over the same
+/// connection. This usually takes a few minutes. Devices only reachable over lockdown need
+/// [`wait_for_sysdiagnose`] instead.
+///
+/// # Arguments
+/// * `provider` - The RSD provider, such as a tunnel adapter
+/// * `handshake` - The RSD handshake listing the device's services
+/// * `progress` - Called with [`SysdiagnoseProgress::Generating`] once generation is
+///   requested, and with [`SysdiagnoseProgress::Downloading`] after each chunk is pulled
+///
+/// # Returns
+/// The contents of the `.tar.gz` archive
+///
+/// # Errors
+/// Returns `IdeviceError` if the service can't be reached or the transfer fails
+#[cfg(all(feature = "core_device", feature = "rsd"))]
+pub async fn capture_sysdiagnose(
+    provider: &mut impl crate::provider::RsdProvider,
+    handshake: &mut crate::rsd::RsdHandshake,
+    mut progress: impl FnMut(SysdiagnoseProgress),
+) -> Result<Vec<u8>, IdeviceError> {
+    use futures::StreamExt;
+
+    use crate::{RsdService, core_device::DiagnostisServiceClient};
+
+    let mut client = DiagnostisServiceClient::connect_rsd(provider, handshake).await?;
+    progress(SysdiagnoseProgress::Generating);
+    let mut res = client.capture_sysdiagnose(false).await?;
+    debug!("Pulling sysdiagnose {}", res.preferred_filename);
+
+    let total = res.expected_length as u64;
+    let mut data = Vec::with_capacity(res.expected_length);
+    while let Some(chunk) = res.stream.next().await {
+        data.extend(chunk?);
+        progress(SysdiagnoseProgress::Downloading {
+            received: data.len() as u64,
+            total,
+        });
+    }
+
+    Ok(data)
+}
+
+/// Lists the sysdiagnose directory, which doesn't exist until the first archive is written
+async fn list_sysdiagnoses(
+    client: &mut CrashReportCopyMobileClient,
+) -> Result<Vec<String>, IdeviceError> {
+    match client.ls(Some(SYSDIAGNOSE_DIR)).await {
+        Err(IdeviceError::Afc(AfcError::ObjectNotFound)) => Ok(Vec::new()),
+        res => res,
+    }
+}
+
+fn is_finished_sysdiagnose(name: &str) -> bool {
+    name.starts_with("sysdiagnose_") && name.ends_with(".tar.gz")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_unfinished_sysdiagnoses() {
+        assert!(is_finished_sysdiagnose(
+            "sysdiagnose_2024.05.01_10-20-30-0700_iPhone-OS_iPhone_21E236.tar.gz"
+        ));
+        assert!(!is_finished_sysdiagnose(
+            "IN_PROGRESS_sysdiagnose_2024.05.01_10-20-30-0700_iPhone-OS_iPhone_21E236.tar.gz"
+        ));
+        assert!(!is_finished_sysdiagnose("sysdiagnose.log"));
+    }
+}
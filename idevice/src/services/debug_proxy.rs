@@ -4,7 +4,9 @@
 //! GDB Remote Serial Protocol as documented at:
 //! https://sourceware.org/gdb/current/onlinedocs/gdb.html/Packets.html#Packets
 
-use std::fmt::Write;
+use std::{collections::HashMap, fmt::Write, pin::Pin};
+
+use futures::Stream;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::debug;
 
@@ -47,6 +49,25 @@ pub struct DebugserverCommand {
     pub argv: Vec<String>,
 }
 
+/// How [`DebugProxyClient::launch_with_options`] starts a process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchOptions {
+    /// Environment variables set for the process, such as `OS_ACTIVITY_MODE`
+    pub env: HashMap<String, String>,
+    /// Leaves the process stopped at its entry point, to attach or set breakpoints before
+    /// it runs. Otherwise the process is resumed once launched.
+    pub start_suspended: bool,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            env: HashMap::new(),
+            start_suspended: true,
+        }
+    }
+}
+
 impl DebugserverCommand {
     /// Creates a new debugserver command
     ///
@@ -102,23 +123,149 @@ impl<R: ReadWrite> DebugProxyClient<R> {
 
         // Construct the packet data (command + hex-encoded arguments)
         let packet_data = format!("{}{}", command.name, hex_args);
+        self.send_packet(&packet_data).await?;
 
-        // Calculate the checksum
-        let checksum = calculate_checksum(&packet_data);
+        // Read the response
+        let response = self.read_response().await?;
+        Ok(response)
+    }
 
-        // Construct the full packet
-        let packet = format!("${packet_data}#{checksum}");
+    /// Launches a process and leaves it stopped at its entry point
+    ///
+    /// Sets the program arguments with the `A` packet, checks `qLaunchSuccess`,
+    /// then reads the new process ID from `qProcessInfo`. Call
+    /// [`DebugProxyClient::continue_execution`] to start it running.
+    ///
+    /// # Arguments
+    /// * `bundle_path` - Path on the device of the app bundle or executable to launch
+    /// * `args` - Arguments passed to the process after its path
+    ///
+    /// # Returns
+    /// The process ID of the launched process
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::DebugserverError` if debugserver refuses the launch
+    pub async fn launch(&mut self, bundle_path: &str, args: &[&str]) -> Result<u32, IdeviceError> {
+        self.launch_with_options(bundle_path, args, &LaunchOptions::default())
+            .await
+    }
 
-        // Log the packet for debugging
-        debug!("Sending packet: {packet}");
+    /// Launches a process with environment variables, optionally resuming it
+    ///
+    /// Each variable is set with a `QEnvironmentHexEncoded` packet before launching as
+    /// [`DebugProxyClient::launch`] does. Unless `start_suspended` is set, the process is then
+    /// resumed with [`DebugProxyClient::continue_execution`].
+    ///
+    /// # Arguments
+    /// * `bundle_path` - Path on the device of the app bundle or executable to launch
+    /// * `args` - Arguments passed to the process after its path
+    /// * `options` - Environment and whether to start suspended
+    ///
+    /// # Returns
+    /// The process ID of the launched process
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::DebugserverError` if debugserver refuses a variable or the launch
+    pub async fn launch_with_options(
+        &mut self,
+        bundle_path: &str,
+        args: &[&str],
+        options: &LaunchOptions,
+    ) -> Result<u32, IdeviceError> {
+        for (key, value) in &options.env {
+            let var = hex_encode(format!("{key}={value}").as_bytes());
+            self.expect_ok(&format!("QEnvironmentHexEncoded:{var}"))
+                .await?;
+        }
 
-        // Send the packet
-        self.socket.write_all(packet.as_bytes()).await?;
-        self.socket.flush().await?;
+        let argv = std::iter::once(bundle_path)
+            .chain(args.iter().copied())
+            .enumerate()
+            .map(|(i, arg)| {
+                let hex = hex_encode(arg.as_bytes());
+                format!("{},{i},{hex}", hex.len())
+            })
+            .collect::<Vec<String>>()
+            .join(",");
 
-        // Read the response
-        let response = self.read_response().await?;
-        Ok(response)
+        self.expect_ok(&format!("A{argv}")).await?;
+        self.expect_ok("qLaunchSuccess").await?;
+
+        let info = self.request("qProcessInfo").await?;
+        let pid = info
+            .split(';')
+            .find_map(|field| field.strip_prefix("pid:"))
+            .and_then(|pid| u32::from_str_radix(pid, 16).ok())
+            .ok_or(IdeviceError::UnexpectedResponse)?;
+
+        if !options.start_suspended {
+            self.continue_execution().await?;
+        }
+        Ok(pid)
+    }
+
+    /// Attaches to a running process, which stops it
+    ///
+    /// # Arguments
+    /// * `pid` - The process ID to attach to
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::DebugserverError` if debugserver can't attach
+    pub async fn attach(&mut self, pid: u32) -> Result<(), IdeviceError> {
+        let res = self.request(&format!("vAttach;{pid:x}")).await?;
+        if res.starts_with('T') || res.starts_with('S') {
+            Ok(())
+        } else {
+            Err(IdeviceError::DebugserverError(res))
+        }
+    }
+
+    /// Resumes the stopped process
+    ///
+    /// debugserver doesn't reply until the process stops or exits. Until then, use
+    /// [`DebugProxyClient::read_console`] to read its output.
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails
+    pub async fn continue_execution(&mut self) -> Result<(), IdeviceError> {
+        self.send_packet("c").await?;
+        if !self.noack_mode {
+            let mut ack = [0u8; 1];
+            self.socket.read_exact(&mut ack).await?;
+            if ack[0] != b'+' {
+                return Err(IdeviceError::UnexpectedResponse);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the console output of a running process
+    ///
+    /// Yields the process' stdout and stderr as debugserver forwards it. The stream
+    /// ends when the process exits or stops.
+    ///
+    /// # Returns
+    /// A stream of console output chunks
+    pub fn read_console(
+        &mut self,
+    ) -> Pin<Box<dyn Stream<Item = Result<String, IdeviceError>> + Send + '_>> {
+        Box::pin(futures::stream::try_unfold(self, |client| async move {
+            loop {
+                let packet = client.read_packet().await?;
+                match packet.as_bytes().first() {
+                    Some(b'O') => {
+                        let output = hex_decode(&packet[1..])?;
+                        return Ok(Some((String::from_utf8_lossy(&output).to_string(), client)));
+                    }
+                    // Exited, killed by a signal, or stopped
+                    Some(b'W' | b'X' | b'T' | b'S') => {
+                        debug!("Process stopped: {packet}");
+                        return Ok(None);
+                    }
+                    _ => debug!("Ignoring packet while reading console: {packet}"),
+                }
+            }
+        }))
     }
 
     /// Reads a response packet from debugserver
@@ -168,6 +315,66 @@ impl<R: ReadWrite> DebugProxyClient<R> {
         Ok(Some(response))
     }
 
+    /// Frames and sends a packet without waiting for its response
+    async fn send_packet(&mut self, data: &str) -> Result<(), IdeviceError> {
+        let checksum = calculate_checksum(data);
+        let packet = format!("${data}#{checksum}");
+        debug!("Sending packet: {packet}");
+
+        self.socket.write_all(packet.as_bytes()).await?;
+        self.socket.flush().await?;
+        Ok(())
+    }
+
+    /// Reads the next packet sent by debugserver, skipping any acks before it
+    async fn read_packet(&mut self) -> Result<String, IdeviceError> {
+        let mut received_char = [0u8; 1];
+        loop {
+            self.socket.read_exact(&mut received_char).await?;
+            if received_char[0] == b'$' {
+                break;
+            }
+        }
+
+        let mut buffer = Vec::new();
+        loop {
+            self.socket.read_exact(&mut received_char).await?;
+            if received_char[0] == b'#' {
+                break;
+            }
+            buffer.push(received_char[0]);
+        }
+        // swallow checksum
+        let mut checksum_chars = [0u8; 2];
+        self.socket.read_exact(&mut checksum_chars).await?;
+
+        if !self.noack_mode {
+            self.send_ack().await?;
+        }
+
+        Ok(String::from_utf8(buffer)?)
+    }
+
+    /// Sends a packet and returns its response, mapping `E` replies to errors
+    async fn request(&mut self, data: &str) -> Result<String, IdeviceError> {
+        self.send_packet(data).await?;
+        match self.read_response().await? {
+            Some(res) if res.starts_with('E') => Err(IdeviceError::DebugserverError(res)),
+            Some(res) => Ok(res),
+            None => Err(IdeviceError::UnexpectedResponse),
+        }
+    }
+
+    /// Sends a packet and checks that debugserver replied `OK`
+    async fn expect_ok(&mut self, data: &str) -> Result<(), IdeviceError> {
+        let res = self.request(data).await?;
+        if res == "OK" {
+            Ok(())
+        } else {
+            Err(IdeviceError::DebugserverError(res))
+        }
+    }
+
     /// Sends raw bytes directly to the debugproxy connection
     ///
     /// # Arguments
@@ -301,6 +508,21 @@ fn hex_encode(bytes: &[u8]) -> String {
     })
 }
 
+/// Decodes a hex string, as used by `O` console output packets
+fn hex_decode(hex: &str) -> Result<Vec<u8>, IdeviceError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(IdeviceError::UnexpectedResponse);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or(IdeviceError::UnexpectedResponse)
+        })
+        .collect()
+}
+
 impl From<String> for DebugserverCommand {
     /// Converts a string into a debugserver command by splitting on whitespace
     ///
@@ -319,3 +541,71 @@ impl From<&str> for DebugserverCommand {
         s.to_string().into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn packet(data: &str) -> String {
+        format!("${data}#{}", calculate_checksum(data))
+    }
+
+    #[tokio::test]
+    async fn read_console_decodes_output_until_exit() {
+        let (ours, mut theirs) = tokio::io::duplex(1024);
+        let mut client = DebugProxyClient::new(ours);
+        client.set_ack_mode(false);
+
+        let out = format!(
+            "{}{}{}",
+            packet(&format!("O{}", hex_encode(b"hello\n"))),
+            packet(&format!("O{}", hex_encode(b"world\n"))),
+            packet("W00")
+        );
+        theirs.write_all(out.as_bytes()).await.unwrap();
+
+        let lines: Vec<String> = client.read_console().map(|l| l.unwrap()).collect().await;
+        assert_eq!(lines, vec!["hello\n", "world\n"]);
+    }
+
+    #[tokio::test]
+    async fn launch_sets_environment_and_resumes() {
+        let (ours, mut theirs) = tokio::io::duplex(1024);
+        let mut client = DebugProxyClient::new(ours);
+        client.set_ack_mode(false);
+
+        let replies = [
+            packet("OK"),
+            packet("OK"),
+            packet("OK"),
+            packet("pid:1f;parent-pid:1;"),
+        ]
+        .concat();
+        theirs.write_all(replies.as_bytes()).await.unwrap();
+
+        let options = LaunchOptions {
+            env: HashMap::from([("OS_ACTIVITY_MODE".to_string(), "disable".to_string())]),
+            start_suspended: false,
+        };
+        let pid = client
+            .launch_with_options("/Applications/Test.app", &[], &options)
+            .await
+            .unwrap();
+        assert_eq!(pid, 0x1f);
+        drop(client);
+
+        let mut sent = String::new();
+        theirs.read_to_string(&mut sent).await.unwrap();
+        let var = hex_encode(b"OS_ACTIVITY_MODE=disable");
+        assert!(sent.starts_with(&packet(&format!("QEnvironmentHexEncoded:{var}"))));
+        assert!(sent.ends_with(&packet("c")));
+    }
+
+    #[test]
+    fn hex_decode_rejects_malformed_input() {
+        assert_eq!(hex_decode("48690a").unwrap(), b"Hi\n");
+        assert!(hex_decode("486").is_err());
+        assert!(hex_decode("zz").is_err());
+    }
+}
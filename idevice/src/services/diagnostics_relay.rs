@@ -2,6 +2,81 @@
 
 use crate::{Idevice, IdeviceError, IdeviceService, obf};
 
+/// Battery state, read from the `IOPMPowerSource` IORegistry entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryInfo {
+    /// Charge level as a percentage
+    pub current_capacity: u64,
+    /// Charge level in mAh
+    pub raw_current_capacity: Option<u64>,
+    /// Capacity of the battery in its current condition, in mAh
+    pub raw_max_capacity: Option<u64>,
+    /// Capacity of the battery when new, in mAh
+    pub design_capacity: Option<u64>,
+    /// Number of charge cycles the battery has gone through
+    pub cycle_count: Option<u64>,
+    /// Whether the battery is charging
+    pub is_charging: bool,
+    /// Whether external power is connected
+    pub external_connected: bool,
+    /// Whether the battery is fully charged
+    pub fully_charged: bool,
+    /// Battery temperature in degrees Celsius
+    pub temperature: Option<f64>,
+}
+
+impl BatteryInfo {
+    fn from_ioregistry(entry: &plist::Dictionary) -> Option<Self> {
+        let uint = |key| entry.get(key).and_then(|x| x.as_unsigned_integer());
+        let flag = |key| entry.get(key).and_then(|x| x.as_boolean()).unwrap_or(false);
+        Some(Self {
+            current_capacity: uint("CurrentCapacity")?,
+            raw_current_capacity: uint("AppleRawCurrentCapacity"),
+            raw_max_capacity: uint("AppleRawMaxCapacity"),
+            design_capacity: uint("DesignCapacity"),
+            cycle_count: uint("CycleCount"),
+            is_charging: flag("IsCharging"),
+            external_connected: flag("ExternalConnected"),
+            fully_charged: flag("FullyCharged"),
+            // Reported in hundredths of a degree
+            temperature: entry
+                .get("Temperature")
+                .and_then(|x| x.as_signed_integer())
+                .map(|t| t as f64 / 100.0),
+        })
+    }
+}
+
+/// Temperatures read from the battery's IORegistry entry
+///
+/// This doesn't need developer mode, unlike the DVT services.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThermalState {
+    /// Battery temperature in degrees Celsius, from `Temperature`
+    pub battery_temperature: Option<f64>,
+    /// The battery manager's virtual temperature in degrees Celsius, from `VirtualTemperature`
+    pub virtual_temperature: Option<f64>,
+    /// The whole IORegistry entry, for keys not parsed here
+    pub raw: plist::Dictionary,
+}
+
+impl ThermalState {
+    fn from_ioregistry(entry: plist::Dictionary) -> Self {
+        // Both are reported in hundredths of a degree
+        let celsius = |key| {
+            entry
+                .get(key)
+                .and_then(|x| x.as_signed_integer())
+                .map(|t| t as f64 / 100.0)
+        };
+        Self {
+            battery_temperature: celsius("Temperature"),
+            virtual_temperature: celsius("VirtualTemperature"),
+            raw: entry,
+        }
+    }
+}
+
 /// Client for interacting with the Diagnostics Relay
 #[derive(Debug)]
 pub struct DiagnosticsRelayClient {
@@ -69,6 +144,36 @@ impl DiagnosticsRelayClient {
         Ok(res)
     }
 
+    /// Reads the battery's charge, health and temperature
+    ///
+    /// # Returns
+    /// The battery state
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::NotFound` if the device has no battery
+    pub async fn query_battery(&mut self) -> Result<BatteryInfo, IdeviceError> {
+        let entry = self
+            .ioregistry(None, None, Some("IOPMPowerSource"))
+            .await?
+            .ok_or(IdeviceError::NotFound)?;
+        BatteryInfo::from_ioregistry(&entry).ok_or(IdeviceError::NotFound)
+    }
+
+    /// Reads the temperatures reported by the battery
+    ///
+    /// These come from the same IORegistry entry as [`DiagnosticsRelayClient::query_battery`],
+    /// so they are available on devices without developer mode.
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::NotFound` if the device has no battery
+    pub async fn thermal_state(&mut self) -> Result<ThermalState, IdeviceError> {
+        let entry = self
+            .ioregistry(None, None, Some("IOPMPowerSource"))
+            .await?
+            .ok_or(IdeviceError::NotFound)?;
+        Ok(ThermalState::from_ioregistry(entry))
+    }
+
     /// Requests MobileGestalt information from the device
     ///
     /// # Arguments
@@ -260,3 +365,42 @@ impl DiagnosticsRelayClient {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_info_maps_ioregistry_keys() {
+        let entry = crate::plist!(dict {
+            "CurrentCapacity": 87,
+            "AppleRawCurrentCapacity": 2650,
+            "AppleRawMaxCapacity": 3046,
+            "DesignCapacity": 3227,
+            "CycleCount": 412,
+            "IsCharging": true,
+            "ExternalConnected": true,
+            "Temperature": 3050,
+        });
+        let info = BatteryInfo::from_ioregistry(&entry).unwrap();
+        assert_eq!(info.current_capacity, 87);
+        assert_eq!(info.raw_max_capacity, Some(3046));
+        assert_eq!(info.cycle_count, Some(412));
+        assert!(info.is_charging);
+        assert!(!info.fully_charged);
+        assert_eq!(info.temperature, Some(30.5));
+    }
+
+    #[test]
+    fn thermal_state_converts_temperatures() {
+        let entry = crate::plist!(dict {
+            "Temperature": 3050,
+            "VirtualTemperature": 3125,
+            "AtCriticalLevel": false,
+        });
+        let state = ThermalState::from_ioregistry(entry);
+        assert_eq!(state.battery_temperature, Some(30.5));
+        assert_eq!(state.virtual_temperature, Some(31.25));
+        assert!(state.raw.contains_key("AtCriticalLevel"));
+    }
+}
@@ -0,0 +1,219 @@
+//! Condition inducer service client for iOS instruments protocol.
+//!
+//! This module provides a client for the condition inducer service on iOS devices
+//! through the instruments protocol. It applies the same network and thermal conditions
+//! that Xcode's Devices window offers, such as a slow Edge network or 100% packet loss.
+
+use plist::Value;
+use tracing::warn;
+
+use crate::{
+    IdeviceError, ReadWrite,
+    dvt::{
+        message::AuxValue,
+        remote_server::{Channel, RemoteServerClient},
+    },
+    obf,
+};
+
+/// A condition the device can apply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionProfile {
+    /// Identifier of the condition this profile belongs to, such as `SlowNetworkCondition`
+    pub condition_identifier: String,
+    /// Human-readable name of the condition, such as `Network Link`
+    pub condition_name: String,
+    /// Identifier of the profile, such as `SlowNetwork2GUrban`
+    pub identifier: String,
+    /// Human-readable name of the profile, such as `Edge`
+    pub name: String,
+    /// Longer description of the profile, if reported
+    pub description: Option<String>,
+}
+
+/// Client for inducing device conditions on iOS devices
+///
+/// Each instance maintains its own communication channel. An enabled condition stays active
+/// until [`ConditionInducerClient::disable`] is called or the channel is closed.
+#[derive(Debug)]
+pub struct ConditionInducerClient<'a, R: ReadWrite> {
+    /// The underlying channel for communication
+    channel: Channel<'a, R>,
+}
+
+impl<'a, R: ReadWrite> ConditionInducerClient<'a, R> {
+    /// Creates a new ConditionInducerClient
+    ///
+    /// # Arguments
+    /// * `client` - The base RemoteServerClient to use
+    ///
+    /// # Returns
+    /// * `Ok(ConditionInducerClient)` - Connected client instance
+    /// * `Err(IdeviceError)` - If channel creation fails
+    pub async fn new(client: &'a mut RemoteServerClient<R>) -> Result<Self, IdeviceError> {
+        let channel = client
+            .make_channel(obf!(
+                "com.apple.instruments.server.services.ConditionInducer"
+            ))
+            .await?; // Drop `&mut client` before continuing
+
+        Ok(Self { channel })
+    }
+
+    /// Lists every profile of every condition the device supports
+    ///
+    /// # Errors
+    /// * `IdeviceError::UnexpectedResponse` if server response is invalid
+    pub async fn list_profiles(&mut self) -> Result<Vec<ConditionProfile>, IdeviceError> {
+        self.channel
+            .call_method(
+                Some(Value::String("availableConditionInducers".into())),
+                None,
+                true,
+            )
+            .await?;
+
+        match self.channel.read_message().await?.data {
+            Some(Value::Array(conditions)) => Ok(parse_profiles(conditions)),
+            _ => {
+                warn!("Did not get array response");
+                Err(IdeviceError::UnexpectedResponse)
+            }
+        }
+    }
+
+    /// Enables a condition profile, replacing any active one
+    ///
+    /// # Arguments
+    /// * `condition_identifier` - Identifier of the condition, from [`ConditionProfile`]
+    /// * `profile_identifier` - Identifier of the profile within that condition
+    ///
+    /// # Errors
+    /// * `IdeviceError::NotFound` if the device has no such profile
+    /// * `IdeviceError::UnknownErrorType` if the device refuses to enable it
+    pub async fn enable(
+        &mut self,
+        condition_identifier: &str,
+        profile_identifier: &str,
+    ) -> Result<(), IdeviceError> {
+        // Check the identifiers first so unknown ones fail with a clear error
+        if !self.list_profiles().await?.iter().any(|p| {
+            p.condition_identifier == condition_identifier && p.identifier == profile_identifier
+        }) {
+            return Err(IdeviceError::NotFound);
+        }
+
+        self.channel
+            .call_method(
+                Some(Value::String(
+                    "enableConditionWithIdentifier:profileIdentifier:".into(),
+                )),
+                Some(vec![
+                    AuxValue::archived_value(condition_identifier),
+                    AuxValue::archived_value(profile_identifier),
+                ]),
+                true,
+            )
+            .await?;
+
+        match self.channel.read_message().await?.data {
+            Some(Value::Dictionary(d)) if d.contains_key("NSDomain") => {
+                warn!("Failed to enable condition: {d:?}");
+                Err(IdeviceError::UnknownErrorType(format!(
+                    "{} {}",
+                    d.get("NSDomain")
+                        .and_then(|v| v.as_string())
+                        .unwrap_or_default(),
+                    d.get("NSCode")
+                        .and_then(|v| v.as_signed_integer())
+                        .unwrap_or_default()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Disables the active condition, if any
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the request was sent
+    /// * `Err(IdeviceError)` - If communication fails
+    pub async fn disable(&mut self) -> Result<(), IdeviceError> {
+        self.channel
+            .call_method(
+                Some(Value::String("disableActiveCondition".into())),
+                None,
+                false,
+            )
+            .await
+    }
+}
+
+fn parse_profiles(conditions: Vec<Value>) -> Vec<ConditionProfile> {
+    let string = |d: &plist::Dictionary, key: &str| {
+        d.get(key).and_then(|v| v.as_string()).map(str::to_string)
+    };
+
+    let mut res = Vec::new();
+    for condition in conditions {
+        let Some(condition) = condition.as_dictionary() else {
+            warn!("Non-dictionary condition entry: {condition:?}");
+            continue;
+        };
+        let Some(condition_identifier) = string(condition, "identifier") else {
+            warn!("Condition has no identifier: {condition:?}");
+            continue;
+        };
+        let condition_name = string(condition, "name").unwrap_or_default();
+        let profiles = condition
+            .get("profiles")
+            .and_then(|p| p.as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        for profile in profiles.iter().filter_map(|p| p.as_dictionary()) {
+            let Some(identifier) = string(profile, "identifier") else {
+                continue;
+            };
+            res.push(ConditionProfile {
+                condition_identifier: condition_identifier.clone(),
+                condition_name: condition_name.clone(),
+                identifier,
+                name: string(profile, "name").unwrap_or_default(),
+                description: string(profile, "description"),
+            });
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flattens_condition_profiles() {
+        let conditions = crate::plist!([
+            {
+                "identifier": "SlowNetworkCondition",
+                "name": "Network Link",
+                "profiles": [
+                    { "identifier": "SlowNetwork2GUrban", "name": "Edge", "description": "Edge network" },
+                    { "name": "missing identifier" },
+                ],
+            },
+            { "name": "missing identifier" },
+        ]);
+        let profiles = parse_profiles(conditions.into_array().unwrap());
+        assert_eq!(
+            profiles,
+            vec![ConditionProfile {
+                condition_identifier: "SlowNetworkCondition".into(),
+                condition_name: "Network Link".into(),
+                identifier: "SlowNetwork2GUrban".into(),
+                name: "Edge".into(),
+                description: Some("Edge network".into()),
+            }]
+        );
+    }
+}
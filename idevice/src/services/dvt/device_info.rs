@@ -0,0 +1,332 @@
+//! Device info service client for iOS instruments protocol.
+//!
+//! This module provides a client for the device info service on iOS devices
+//! through the instruments protocol. It allows listing the processes running on the device
+//! and reading hardware, network and OS details that lockdown doesn't expose.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use plist::Value;
+use tracing::warn;
+
+use crate::{
+    IdeviceError, ReadWrite,
+    dvt::remote_server::{Channel, RemoteServerClient},
+    obf,
+};
+
+/// Seconds between the Unix epoch and the Apple (Cocoa) epoch of 2001-01-01
+const APPLE_EPOCH_OFFSET: u64 = 978_307_200;
+
+/// A process running on the device
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    /// Process ID
+    pub pid: u64,
+    /// Executable name of the process
+    pub name: String,
+    /// Path of the executable on the device, if reported
+    pub real_app_name: Option<String>,
+    /// Bundle identifier, if the process is an app
+    pub bundle_id: Option<String>,
+    /// Whether the process is an app
+    pub is_application: bool,
+    /// When the process was started, if reported
+    pub start_date: Option<SystemTime>,
+}
+
+/// CPU details of the device
+#[derive(Debug, Clone, Default)]
+pub struct HardwareInformation {
+    /// Mach CPU type, such as `CPU_TYPE_ARM64`
+    pub cpu_type: Option<u64>,
+    /// Mach CPU subtype
+    pub cpu_subtype: Option<u64>,
+    /// Number of logical CPUs
+    pub cpu_count: Option<u64>,
+    /// Number of physical CPUs
+    pub physical_cpu_count: Option<u64>,
+    /// Whether the CPU is 64-bit capable
+    pub is_64_bit: bool,
+    /// Every value the device reported, including the ones above
+    pub values: plist::Dictionary,
+}
+
+/// Network interfaces of the device
+#[derive(Debug, Clone, Default)]
+pub struct NetworkInformation {
+    /// Interface names, such as `en0`, mapped to their descriptions, such as `Wi-Fi`
+    pub interfaces: HashMap<String, String>,
+}
+
+/// OS details of the device
+///
+/// The reported keys vary between iOS versions, so each typed field is `None` when the
+/// device didn't report it. Everything else is kept in `values`.
+#[derive(Debug, Clone, Default)]
+pub struct SystemInformation {
+    /// Model identifier, such as `iPhone14,5`
+    pub model: Option<String>,
+    /// Board identifier, such as `D17AP`
+    pub hardware_model: Option<String>,
+    /// OS version, such as `17.4`
+    pub os_version: Option<String>,
+    /// OS build, such as `21E219`
+    pub build_version: Option<String>,
+    /// Kernel version string, as reported by `uname -v`
+    pub kernel_version: Option<String>,
+    /// Every value the device reported, including the ones above
+    pub values: plist::Dictionary,
+}
+
+impl SystemInformation {
+    /// Returns a reported value if it is a string
+    pub fn get_string(&self, key: &str) -> Option<&str> {
+        self.values.get(key).and_then(|v| v.as_string())
+    }
+}
+
+/// Client for device info operations on iOS devices
+///
+/// Each instance maintains its own communication channel.
+#[derive(Debug)]
+pub struct DeviceInfoClient<'a, R: ReadWrite> {
+    /// The underlying channel for communication
+    channel: Channel<'a, R>,
+}
+
+impl<'a, R: ReadWrite> DeviceInfoClient<'a, R> {
+    /// Creates a new DeviceInfoClient
+    ///
+    /// # Arguments
+    /// * `client` - The base RemoteServerClient to use
+    ///
+    /// # Returns
+    /// * `Ok(DeviceInfoClient)` - Connected client instance
+    /// * `Err(IdeviceError)` - If channel creation fails
+    pub async fn new(client: &'a mut RemoteServerClient<R>) -> Result<Self, IdeviceError> {
+        let channel = client
+            .make_channel(obf!("com.apple.instruments.server.services.deviceinfo"))
+            .await?; // Drop `&mut client` before continuing
+
+        Ok(Self { channel })
+    }
+
+    /// Lists the processes running on the device
+    ///
+    /// # Returns
+    /// * `Ok(Vec<ProcessInfo>)` - The running processes
+    /// * `Err(IdeviceError)` - If communication fails
+    ///
+    /// # Errors
+    /// * `IdeviceError::UnexpectedResponse` if server response is invalid
+    pub async fn running_processes(&mut self) -> Result<Vec<ProcessInfo>, IdeviceError> {
+        let method = Value::String("runningProcesses".into());
+        self.channel.call_method(Some(method), None, true).await?;
+
+        let msg = self.channel.read_message().await?;
+        match msg.data {
+            Some(Value::Array(processes)) => Ok(processes
+                .into_iter()
+                .filter_map(|p| match p {
+                    Value::Dictionary(d) => parse_process(d),
+                    _ => {
+                        warn!("Non-dictionary process entry: {p:?}");
+                        None
+                    }
+                })
+                .collect()),
+            _ => {
+                warn!("Did not get array response");
+                Err(IdeviceError::UnexpectedResponse)
+            }
+        }
+    }
+
+    /// Reads the CPU details of the device
+    ///
+    /// # Errors
+    /// * `IdeviceError::UnexpectedResponse` if server response is invalid
+    pub async fn hardware_information(&mut self) -> Result<HardwareInformation, IdeviceError> {
+        Ok(HardwareInformation::from_values(
+            self.call_for_dictionary("hardwareInformation").await?,
+        ))
+    }
+
+    /// Reads the network interfaces of the device
+    ///
+    /// # Errors
+    /// * `IdeviceError::UnexpectedResponse` if server response is invalid
+    pub async fn network_information(&mut self) -> Result<NetworkInformation, IdeviceError> {
+        let values = self.call_for_dictionary("networkInformation").await?;
+        Ok(NetworkInformation {
+            interfaces: values
+                .into_iter()
+                .filter_map(|(k, v)| v.into_string().map(|v| (k, v)))
+                .collect(),
+        })
+    }
+
+    /// Reads the OS details of the device
+    ///
+    /// # Errors
+    /// * `IdeviceError::UnexpectedResponse` if server response is invalid
+    pub async fn system_information(&mut self) -> Result<SystemInformation, IdeviceError> {
+        Ok(SystemInformation::from_values(
+            self.call_for_dictionary("systemInformation").await?,
+        ))
+    }
+
+    async fn call_for_dictionary(
+        &mut self,
+        method: &str,
+    ) -> Result<plist::Dictionary, IdeviceError> {
+        self.channel
+            .call_method(Some(Value::String(method.into())), None, true)
+            .await?;
+
+        match self.channel.read_message().await?.data {
+            Some(Value::Dictionary(d)) => Ok(d),
+            _ => {
+                warn!("Did not get dictionary response to {method}");
+                Err(IdeviceError::UnexpectedResponse)
+            }
+        }
+    }
+}
+
+impl HardwareInformation {
+    fn from_values(values: plist::Dictionary) -> Self {
+        let get = |key: &str| values.get(key).and_then(|v| v.as_unsigned_integer());
+        Self {
+            cpu_type: get("hwCPUtype"),
+            cpu_subtype: get("hwCPUsubtype"),
+            cpu_count: get("numberOfCpus"),
+            physical_cpu_count: get("numberOfPhysicalCpus"),
+            is_64_bit: get("hwCPU64BitCapable").is_some_and(|v| v != 0),
+            values,
+        }
+    }
+}
+
+impl SystemInformation {
+    fn from_values(values: plist::Dictionary) -> Self {
+        let get = |key: &str| {
+            values
+                .get(key)
+                .and_then(|v| v.as_string())
+                .map(String::from)
+        };
+        Self {
+            model: get("ProductType"),
+            hardware_model: get("HardwareModel"),
+            os_version: get("ProductVersion"),
+            build_version: get("BuildVersion"),
+            kernel_version: get("KernelVersion"),
+            values,
+        }
+    }
+}
+
+fn parse_process(mut d: plist::Dictionary) -> Option<ProcessInfo> {
+    let pid = match d.get("pid").and_then(|p| p.as_unsigned_integer()) {
+        Some(p) => p,
+        None => {
+            warn!("Process entry has no pid: {d:?}");
+            return None;
+        }
+    };
+    let name = match d.remove("name") {
+        Some(Value::String(n)) => n,
+        _ => String::new(),
+    };
+    let real_app_name = match d.remove("realAppName") {
+        Some(Value::String(n)) => Some(n),
+        _ => None,
+    };
+    let bundle_id = match d.remove("bundleIdentifier") {
+        Some(Value::String(b)) => Some(b),
+        _ => None,
+    };
+    let is_application = d
+        .get("isApplication")
+        .and_then(|v| v.as_boolean())
+        .unwrap_or(false);
+    let start_date = d.get("startDate").and_then(parse_date);
+
+    Some(ProcessInfo {
+        pid,
+        name,
+        real_app_name,
+        bundle_id,
+        is_application,
+        start_date,
+    })
+}
+
+/// Parses an archived `NSDate`, which may arrive decoded or as its raw `NS.time` object
+fn parse_date(v: &Value) -> Option<SystemTime> {
+    let secs = match v {
+        Value::Date(d) => return Some((*d).into()),
+        Value::Real(r) => *r,
+        Value::Dictionary(d) => d.get("NS.time")?.as_real()?,
+        _ => return None,
+    };
+    let since_apple_epoch = Duration::try_from_secs_f64(secs).ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(APPLE_EPOCH_OFFSET) + since_apple_epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hardware_information() {
+        let info = HardwareInformation::from_values(crate::plist!(dict {
+            "hwCPUtype": 16777228,
+            "hwCPUsubtype": 2,
+            "numberOfCpus": 6,
+            "numberOfPhysicalCpus": 6,
+            "hwCPU64BitCapable": 1,
+        }));
+        assert_eq!(info.cpu_type, Some(16777228));
+        assert_eq!(info.cpu_count, Some(6));
+        assert!(info.is_64_bit);
+    }
+
+    #[test]
+    fn parses_system_information() {
+        let info = SystemInformation::from_values(crate::plist!(dict {
+            "ProductType": "iPhone14,5",
+            "ProductVersion": "17.4",
+            "BuildVersion": "21E219",
+            "_deviceDisplayName": "iPhone",
+        }));
+        assert_eq!(info.model.as_deref(), Some("iPhone14,5"));
+        assert_eq!(info.os_version.as_deref(), Some("17.4"));
+        assert_eq!(info.build_version.as_deref(), Some("21E219"));
+        assert_eq!(info.kernel_version, None);
+        assert_eq!(info.get_string("_deviceDisplayName"), Some("iPhone"));
+    }
+
+    #[test]
+    fn parses_archived_process_entry() {
+        let entry = crate::plist!(dict {
+            "pid": 42,
+            "name": "SpringBoard",
+            "isApplication": false,
+            "startDate": { "NS.time": 60.0 },
+        });
+        let process = parse_process(entry).unwrap();
+        assert_eq!(process.pid, 42);
+        assert_eq!(process.name, "SpringBoard");
+        assert_eq!(process.bundle_id, None);
+        assert_eq!(
+            process.start_date,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(APPLE_EPOCH_OFFSET + 60))
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Graphics service client for iOS instruments protocol.
+//!
+//! This module provides a client for the OpenGL/Metal graphics service on iOS devices
+//! through the instruments protocol. It samples the frame rate, GPU utilization and
+//! GPU memory use of the device at a fixed interval.
+
+use std::{pin::Pin, time::Duration};
+
+use futures::Stream;
+use plist::Value;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::{
+    IdeviceError, ReadWrite,
+    dvt::{
+        message::AuxValue,
+        remote_server::{Channel, RemoteServerClient},
+        sampling::{SampleSource, as_f64, sample_stream},
+    },
+    obf,
+};
+
+/// One sample of graphics performance
+#[derive(Debug, Clone, Default)]
+pub struct GraphicsSample {
+    /// Frames per second rendered by Core Animation
+    pub fps: f64,
+    /// GPU utilization, as a percentage
+    pub device_utilization: f64,
+    /// Utilization of the GPU's tiler, as a percentage
+    pub tiler_utilization: f64,
+    /// Utilization of the GPU's renderer, as a percentage
+    pub renderer_utilization: f64,
+    /// System memory allocated by the GPU, in bytes
+    pub alloc_system_memory: u64,
+    /// System memory in use by the GPU, in bytes
+    pub in_use_system_memory: u64,
+    /// Every value the device reported, including the ones above
+    pub values: plist::Dictionary,
+}
+
+/// Client for graphics performance sampling on iOS devices
+///
+/// Each instance maintains its own communication channel.
+#[derive(Debug)]
+pub struct GraphicsClient<'a, R: ReadWrite> {
+    /// The underlying channel for communication
+    channel: Channel<'a, R>,
+    /// Ends the sample stream when cancelled
+    cancel: CancellationToken,
+}
+
+impl<'a, R: ReadWrite> GraphicsClient<'a, R> {
+    /// Creates a new GraphicsClient
+    ///
+    /// # Arguments
+    /// * `client` - The base RemoteServerClient to use
+    ///
+    /// # Returns
+    /// * `Ok(GraphicsClient)` - Connected client instance
+    /// * `Err(IdeviceError)` - If channel creation fails
+    pub async fn new(client: &'a mut RemoteServerClient<R>) -> Result<Self, IdeviceError> {
+        let channel = client
+            .make_channel(obf!(
+                "com.apple.instruments.server.services.graphics.opengl"
+            ))
+            .await?; // Drop `&mut client` before continuing
+
+        Ok(Self {
+            channel,
+            cancel: CancellationToken::new(),
+        })
+    }
+
+    /// Returns a token that ends the sample stream
+    ///
+    /// Behaves like the sysmontap token, see
+    /// [`super::sysmontap::SysmontapClient::cancellation_token`].
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Starts sampling and returns a stream of samples
+    ///
+    /// Drop the stream and call [`GraphicsClient::stop`] to stop sampling.
+    ///
+    /// # Arguments
+    /// * `interval` - Time between samples
+    ///
+    /// # Returns
+    /// * `Ok(Stream)` - A stream yielding one sample per interval
+    /// * `Err(IdeviceError)` - If sampling couldn't be started
+    pub async fn start(
+        &mut self,
+        interval: Duration,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<GraphicsSample, IdeviceError>> + Send + '_>>,
+        IdeviceError,
+    > {
+        self.channel
+            .call_method(
+                Some(Value::String("startSamplingAtTimeInterval:".into())),
+                Some(vec![AuxValue::archived_value(interval.as_secs_f64())]),
+                true,
+            )
+            .await?;
+        self.channel.read_message().await?;
+
+        Ok(sample_stream(self))
+    }
+
+    /// Stops sampling
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the stop request was sent
+    /// * `Err(IdeviceError)` - If communication fails
+    pub async fn stop(&mut self) -> Result<(), IdeviceError> {
+        self.channel
+            .call_method(Some(Value::String("stopSampling".into())), None, false)
+            .await
+    }
+}
+
+impl<R: ReadWrite> SampleSource for GraphicsClient<'_, R> {
+    type Sample = GraphicsSample;
+
+    fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    async fn next_sample(&mut self) -> Result<GraphicsSample, IdeviceError> {
+        loop {
+            let msg = self.channel.read_message().await?;
+            match msg.data {
+                Some(Value::Dictionary(d)) => return Ok(parse_sample(d)),
+                _ => debug!("Skipping graphics message without a sample"),
+            }
+        }
+    }
+}
+
+fn parse_sample(values: plist::Dictionary) -> GraphicsSample {
+    let real = |key: &str| values.get(key).and_then(as_f64).unwrap_or(0.0);
+    let int = |key: &str| {
+        values
+            .get(key)
+            .and_then(|v| v.as_unsigned_integer())
+            .unwrap_or(0)
+    };
+    GraphicsSample {
+        fps: real("CoreAnimationFramesPerSecond"),
+        device_utilization: real("Device Utilization %"),
+        tiler_utilization: real("Tiler Utilization %"),
+        renderer_utilization: real("Renderer Utilization %"),
+        alloc_system_memory: int("Alloc system memory"),
+        in_use_system_memory: int("In use system memory"),
+        values,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_graphics_sample() {
+        let sample = parse_sample(crate::plist!(dict {
+            "CoreAnimationFramesPerSecond": 60,
+            "Device Utilization %": 12.5,
+            "Tiler Utilization %": 3,
+            "Alloc system memory": 50331648,
+            "XRVideoCardRunTimeStamp": 1234,
+        }));
+        assert_eq!(sample.fps, 60.0);
+        assert_eq!(sample.device_utilization, 12.5);
+        assert_eq!(sample.tiler_utilization, 3.0);
+        assert_eq!(sample.renderer_utilization, 0.0);
+        assert_eq!(sample.alloc_system_memory, 50331648);
+        assert!(sample.values.contains_key("XRVideoCardRunTimeStamp"));
+    }
+}
@@ -4,13 +4,18 @@ use crate::provider::IdeviceProvider;
 use crate::services::lockdown::LockdownClient;
 use crate::{Idevice, IdeviceError, ReadWrite, RsdService, obf};
 
+pub mod condition_inducer;
+pub mod device_info;
+pub mod graphics;
 #[cfg(feature = "location_simulation")]
 pub mod location_simulation;
 pub mod message;
 pub mod notifications;
 pub mod process_control;
 pub mod remote_server;
+mod sampling;
 pub mod screenshot;
+pub mod sysmontap;
 
 impl RsdService for remote_server::RemoteServerClient<Box<dyn ReadWrite>> {
     fn rsd_service_name() -> std::borrow::Cow<'static, str> {
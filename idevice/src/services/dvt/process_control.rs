@@ -34,6 +34,8 @@
 //! }
 //! ```
 
+use std::collections::HashMap;
+
 use plist::{Dictionary, Value};
 use tracing::warn;
 
@@ -94,6 +96,55 @@ impl<'a, R: ReadWrite> ProcessControlClient<'a, R> {
         arguments: Option<Dictionary>,
         start_suspended: bool,
         kill_existing: bool,
+    ) -> Result<u64, IdeviceError> {
+        self.launch_with(
+            bundle_id.into(),
+            env_vars.unwrap_or_default(),
+            Value::Dictionary(arguments.unwrap_or_default()),
+            start_suspended,
+            kill_existing,
+        )
+        .await
+    }
+
+    /// Launches an application on the device with command line arguments
+    ///
+    /// # Arguments
+    /// * `bundle_id` - The bundle identifier of the app to launch
+    /// * `args` - Arguments passed to the app, in order
+    /// * `env` - Environment variables set for the app
+    /// * `kill_existing` - Whether to kill existing instances of the app
+    ///
+    /// # Returns
+    /// * `Ok(u64)` - PID of the launched process
+    /// * `Err(IdeviceError)` - If launch fails
+    ///
+    /// # Errors
+    /// * `IdeviceError::UnexpectedResponse` if server response is invalid
+    /// * Other communication or serialization errors
+    pub async fn launch(
+        &mut self,
+        bundle_id: &str,
+        args: Vec<String>,
+        env: HashMap<String, String>,
+        kill_existing: bool,
+    ) -> Result<u64, IdeviceError> {
+        let env = env
+            .into_iter()
+            .map(|(k, v)| (k, Value::String(v)))
+            .collect();
+        let args = Value::Array(args.into_iter().map(Value::String).collect());
+        self.launch_with(bundle_id.to_string(), env, args, false, kill_existing)
+            .await
+    }
+
+    async fn launch_with(
+        &mut self,
+        bundle_id: String,
+        env_vars: Dictionary,
+        arguments: Value,
+        start_suspended: bool,
+        kill_existing: bool,
     ) -> Result<u64, IdeviceError> {
         let method = Value::String(
             "launchSuspendedProcessWithDevicePath:bundleIdentifier:environment:arguments:options:"
@@ -104,21 +155,12 @@ impl<'a, R: ReadWrite> ProcessControlClient<'a, R> {
             "KillExisting": kill_existing
         });
 
-        let env_vars = match env_vars {
-            Some(e) => e,
-            None => Dictionary::new(),
-        };
-        let arguments = match arguments {
-            Some(a) => a,
-            None => Dictionary::new(),
-        };
-
         self.channel
             .call_method(
                 Some(method),
                 Some(vec![
                     AuxValue::archived_value("/private/"),
-                    AuxValue::archived_value(bundle_id.into()),
+                    AuxValue::archived_value(bundle_id),
                     AuxValue::archived_value(env_vars),
                     AuxValue::archived_value(arguments),
                     AuxValue::archived_value(options),
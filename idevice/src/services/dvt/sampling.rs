@@ -0,0 +1,46 @@
+//! Helpers shared by the instruments services that sample at a fixed interval.
+
+use std::{future::Future, pin::Pin};
+
+use futures::Stream;
+use plist::Value;
+use tokio_util::sync::CancellationToken;
+
+use crate::IdeviceError;
+
+/// A client that reads samples from its channel until its token is cancelled
+pub(crate) trait SampleSource: Send {
+    type Sample: Send;
+
+    /// The token that ends the sample stream
+    fn cancel_token(&self) -> &CancellationToken;
+
+    /// Reads messages until one contains a sample
+    fn next_sample(&mut self) -> impl Future<Output = Result<Self::Sample, IdeviceError>> + Send;
+}
+
+/// Streams samples from `source` until it fails or its token is cancelled
+pub(crate) fn sample_stream<S: SampleSource>(
+    source: &mut S,
+) -> Pin<Box<dyn Stream<Item = Result<S::Sample, IdeviceError>> + Send + '_>> {
+    Box::pin(futures::stream::try_unfold(source, |source| async move {
+        match source
+            .cancel_token()
+            .clone()
+            .run_until_cancelled(source.next_sample())
+            .await
+        {
+            Some(sample) => Ok(Some((sample?, source))),
+            None => Ok(None),
+        }
+    }))
+}
+
+/// Reads a number that the device may send as either a real or an integer
+pub(crate) fn as_f64(v: &Value) -> Option<f64> {
+    match v {
+        Value::Real(r) => Some(*r),
+        Value::Integer(i) => i.as_signed().map(|i| i as f64),
+        _ => None,
+    }
+}
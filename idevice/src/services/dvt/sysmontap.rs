@@ -0,0 +1,263 @@
+//! System monitor service client for iOS instruments protocol.
+//!
+//! This module provides a client for the sysmontap service on iOS devices
+//! through the instruments protocol. It samples CPU and memory usage of the whole
+//! system and of each running process at a fixed interval.
+
+use std::{pin::Pin, time::Duration};
+
+use futures::Stream;
+use plist::Value;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::{
+    IdeviceError, ReadWrite,
+    dvt::{
+        message::AuxValue,
+        remote_server::{Channel, RemoteServerClient},
+        sampling::{SampleSource, as_f64, sample_stream},
+    },
+    obf,
+};
+
+/// Per-process attributes requested from the device, in the order they're reported
+const PROCESS_ATTRIBUTES: [&str; 5] = [
+    "pid",
+    "name",
+    "cpuUsage",
+    "memResidentSize",
+    "physFootprint",
+];
+
+/// One sample of CPU and memory usage
+#[derive(Debug, Clone, Default)]
+pub struct SysmontapSample {
+    /// System-wide CPU usage
+    pub system: SystemCpuUsage,
+    /// Usage of every process running when the sample was taken
+    pub processes: Vec<ProcessSample>,
+}
+
+/// System-wide CPU usage, as a percentage of one core times the number of cores
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemCpuUsage {
+    /// Total CPU load
+    pub total_load: f64,
+    /// CPU load from user space
+    pub user_load: f64,
+    /// CPU load from the kernel
+    pub system_load: f64,
+    /// Number of enabled CPU cores
+    pub cpu_count: u64,
+}
+
+/// CPU and memory usage of a single process
+#[derive(Debug, Clone)]
+pub struct ProcessSample {
+    /// Process ID
+    pub pid: u64,
+    /// Executable name of the process
+    pub name: String,
+    /// CPU usage, as a percentage of one core
+    pub cpu_usage: f64,
+    /// Resident memory in bytes
+    pub mem_resident_size: u64,
+    /// Physical memory footprint in bytes
+    pub phys_footprint: u64,
+}
+
+/// Client for system monitoring on iOS devices
+///
+/// Each instance maintains its own communication channel.
+#[derive(Debug)]
+pub struct SysmontapClient<'a, R: ReadWrite> {
+    /// The underlying channel for communication
+    channel: Channel<'a, R>,
+    /// Ends the sample stream when cancelled
+    cancel: CancellationToken,
+}
+
+impl<'a, R: ReadWrite> SysmontapClient<'a, R> {
+    /// Creates a new SysmontapClient
+    ///
+    /// # Arguments
+    /// * `client` - The base RemoteServerClient to use
+    ///
+    /// # Returns
+    /// * `Ok(SysmontapClient)` - Connected client instance
+    /// * `Err(IdeviceError)` - If channel creation fails
+    pub async fn new(client: &'a mut RemoteServerClient<R>) -> Result<Self, IdeviceError> {
+        let channel = client
+            .make_channel(obf!("com.apple.instruments.server.services.sysmontap"))
+            .await?; // Drop `&mut client` before continuing
+
+        Ok(Self {
+            channel,
+            cancel: CancellationToken::new(),
+        })
+    }
+
+    /// Returns a token that ends the sample stream
+    ///
+    /// Cancelling aborts a pending read, so it can be done from another task or thread to
+    /// stop a stream waiting for the next sample. The aborted read may leave a message
+    /// partially received, so the `RemoteServerClient` shouldn't be reused afterwards.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Starts sampling and returns a stream of samples
+    ///
+    /// Drop the stream and call [`SysmontapClient::stop`] to stop sampling.
+    ///
+    /// # Arguments
+    /// * `interval` - Time between samples
+    ///
+    /// # Returns
+    /// * `Ok(Stream)` - A stream yielding one sample per interval
+    /// * `Err(IdeviceError)` - If sampling couldn't be started
+    pub async fn start(
+        &mut self,
+        interval: Duration,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<SysmontapSample, IdeviceError>> + Send + '_>>,
+        IdeviceError,
+    > {
+        let config = crate::plist!({
+            "ur": interval.as_millis() as u64,
+            "bm": 0,
+            "procAttrs": PROCESS_ATTRIBUTES.to_vec(),
+            "sysAttrs": Vec::<&str>::new(),
+            "cpuUsage": true,
+            "physFootprint": true,
+            "sampleInterval": interval.as_nanos() as u64,
+        });
+        self.channel
+            .call_method(
+                Some(Value::String("setConfig:".into())),
+                Some(vec![AuxValue::archived_value(config)]),
+                true,
+            )
+            .await?;
+        self.channel.read_message().await?;
+
+        self.channel
+            .call_method(Some(Value::String("start".into())), None, true)
+            .await?;
+        self.channel.read_message().await?;
+
+        Ok(sample_stream(self))
+    }
+
+    /// Stops sampling
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the stop request was sent
+    /// * `Err(IdeviceError)` - If communication fails
+    pub async fn stop(&mut self) -> Result<(), IdeviceError> {
+        self.channel
+            .call_method(Some(Value::String("stop".into())), None, false)
+            .await
+    }
+}
+
+impl<R: ReadWrite> SampleSource for SysmontapClient<'_, R> {
+    type Sample = SysmontapSample;
+
+    fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    async fn next_sample(&mut self) -> Result<SysmontapSample, IdeviceError> {
+        loop {
+            let msg = self.channel.read_message().await?;
+            let entries = match msg.data {
+                Some(Value::Array(a)) => a,
+                Some(v) => vec![v],
+                None => continue,
+            };
+            for entry in entries {
+                if let Some(sample) = parse_sample(entry) {
+                    return Ok(sample);
+                }
+            }
+            debug!("Skipping sysmontap message without process data");
+        }
+    }
+}
+
+fn parse_sample(entry: Value) -> Option<SysmontapSample> {
+    let entry = match entry {
+        Value::Dictionary(d) => d,
+        _ => return None,
+    };
+    let processes = entry.get("Processes")?.as_dictionary()?;
+
+    let mut sample = SysmontapSample::default();
+    if let Some(cpu) = entry.get("SystemCPUUsage").and_then(|c| c.as_dictionary()) {
+        let load = |key| cpu.get(key).and_then(as_f64).unwrap_or(0.0);
+        sample.system = SystemCpuUsage {
+            total_load: load("CPU_TotalLoad"),
+            user_load: load("CPU_UserLoad"),
+            system_load: load("CPU_SystemLoad"),
+            cpu_count: entry
+                .get("EnabledCPUs")
+                .and_then(|c| c.as_unsigned_integer())
+                .unwrap_or(0),
+        };
+    }
+
+    for values in processes.values() {
+        let values = match values.as_array() {
+            Some(v) if v.len() == PROCESS_ATTRIBUTES.len() => v,
+            _ => {
+                warn!("Unexpected sysmontap process entry: {values:?}");
+                continue;
+            }
+        };
+        let Some(pid) = values[0].as_unsigned_integer() else {
+            continue;
+        };
+        sample.processes.push(ProcessSample {
+            pid,
+            name: values[1].as_string().unwrap_or_default().to_string(),
+            cpu_usage: as_f64(&values[2]).unwrap_or(0.0),
+            mem_resident_size: values[3].as_unsigned_integer().unwrap_or(0),
+            phys_footprint: values[4].as_unsigned_integer().unwrap_or(0),
+        });
+    }
+    Some(sample)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_process_and_system_usage() {
+        let entry = crate::plist!({
+            "Processes": {
+                "1": [1, "launchd", 0.5, 4096, 8192],
+                "2": ["malformed"],
+            },
+            "SystemCPUUsage": {
+                "CPU_TotalLoad": 12.5,
+                "CPU_UserLoad": 10,
+            },
+            "EnabledCPUs": 6,
+        });
+        let sample = parse_sample(entry).unwrap();
+        assert_eq!(sample.processes.len(), 1);
+        assert_eq!(sample.processes[0].name, "launchd");
+        assert_eq!(sample.processes[0].mem_resident_size, 4096);
+        assert_eq!(sample.system.total_load, 12.5);
+        assert_eq!(sample.system.user_load, 10.0);
+        assert_eq!(sample.system.cpu_count, 6);
+    }
+
+    #[test]
+    fn skips_entries_without_processes() {
+        assert!(parse_sample(crate::plist!({ "CPUCount": 6 })).is_none());
+    }
+}
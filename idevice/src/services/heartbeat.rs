@@ -3,8 +3,18 @@
 //! iOS automatically closes service connections if there is no heartbeat client connected and
 //! responding.
 
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    sync::{broadcast, oneshot},
+    task::JoinHandle,
+};
+
 use crate::{Idevice, IdeviceError, IdeviceService, obf};
 
+/// The interval requested from the device before it has sent its first marco
+const INITIAL_INTERVAL: u64 = 15;
+
 /// Client for interacting with the iOS device heartbeat service
 ///
 /// The heartbeat service provides a keep-alive mechanism and can notify when
@@ -55,10 +65,39 @@ impl HeartbeatClient {
     /// - `HeartbeatSleepyTime` if device is going to sleep
     /// - `UnexpectedResponse` for malformed messages
     pub async fn get_marco(&mut self, interval: u64) -> Result<u64, IdeviceError> {
+        self.get_marco_with_timeout(interval, None).await
+    }
+
+    /// Waits for a "Marco" message from the device, giving up after a client-side deadline
+    ///
+    /// Behaves like [`HeartbeatClient::get_marco`], but additionally bounds the whole call by
+    /// `timeout` so a device that disappears mid-session can't block the caller indefinitely.
+    ///
+    /// # Arguments
+    /// * `interval` - Timeout in seconds to wait for message
+    /// * `timeout` - Client-side deadline for the call, or `None` to wait for the interval only
+    ///
+    /// # Errors
+    /// - `Timeout` if `timeout` elapses before a message is received
+    /// - Any error returned by [`HeartbeatClient::get_marco`]
+    pub async fn get_marco_with_timeout(
+        &mut self,
+        interval: u64,
+        timeout: Option<Duration>,
+    ) -> Result<u64, IdeviceError> {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.read_marco(interval))
+                .await
+                .map_err(|_| IdeviceError::Timeout)?,
+            None => self.read_marco(interval).await,
+        }
+    }
+
+    async fn read_marco(&mut self, interval: u64) -> Result<u64, IdeviceError> {
         // Get a plist or wait for the interval
         let rec = tokio::select! {
             rec = self.idevice.read_plist() => rec?,
-            _ = tokio::time::sleep(tokio::time::Duration::from_secs(interval)) => {
+            _ = tokio::time::sleep(Duration::from_secs(interval)) => {
                 return Err(IdeviceError::HeartbeatTimeout)
             }
         };
@@ -83,6 +122,48 @@ impl HeartbeatClient {
         }
     }
 
+    /// Runs the marco/polo loop in a background task until shut down or an error occurs
+    ///
+    /// The loop waits for a marco from the device, answers with a polo, and repeats.
+    /// The first error encountered ends the loop and is broadcast to all subscribers of the
+    /// returned handle.
+    ///
+    /// # Returns
+    /// A handle used to observe errors and stop the loop
+    pub fn run(mut self) -> HeartbeatHandle {
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+        let (error_tx, error_rx) = broadcast::channel(1);
+
+        let task = tokio::spawn(async move {
+            let mut interval = INITIAL_INTERVAL;
+            loop {
+                let res = tokio::select! {
+                    res = self.marco_polo(interval) => res,
+                    _ = &mut shutdown_rx => return,
+                };
+                match res {
+                    Ok(new_interval) => interval = new_interval + 5,
+                    Err(e) => {
+                        let _ = error_tx.send(Arc::new(e));
+                        return;
+                    }
+                }
+            }
+        });
+
+        HeartbeatHandle {
+            shutdown: shutdown_tx,
+            errors: error_rx,
+            task,
+        }
+    }
+
+    async fn marco_polo(&mut self, interval: u64) -> Result<u64, IdeviceError> {
+        let new_interval = self.get_marco(interval).await?;
+        self.send_polo().await?;
+        Ok(new_interval)
+    }
+
     /// Sends a "Polo" response to the device
     ///
     /// This acknowledges receipt of a "Marco" message and maintains
@@ -98,3 +179,61 @@ impl HeartbeatClient {
         Ok(())
     }
 }
+
+/// Handle to a heartbeat loop started with [`HeartbeatClient::run`]
+///
+/// Dropping the handle stops the loop without waiting for it to exit; use
+/// [`HeartbeatHandle::shutdown`] to wait for it.
+#[derive(Debug)]
+pub struct HeartbeatHandle {
+    shutdown: oneshot::Sender<()>,
+    errors: broadcast::Receiver<Arc<IdeviceError>>,
+    task: JoinHandle<()>,
+}
+
+impl HeartbeatHandle {
+    /// Waits for the loop to fail
+    ///
+    /// # Returns
+    /// The error that ended the loop, or `None` if it was shut down without an error
+    pub async fn error(&mut self) -> Option<Arc<IdeviceError>> {
+        self.errors.recv().await.ok()
+    }
+
+    /// Subscribes to errors raised by the loop after this call
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<IdeviceError>> {
+        self.errors.resubscribe()
+    }
+
+    /// Returns whether the loop has stopped
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Stops the loop and waits for it to exit
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn dropping_handle_stops_loop() {
+        let (mut device, host) = tokio::io::duplex(1024);
+        let handle = HeartbeatClient::new(Idevice::new(Box::new(host), "test")).run();
+        drop(handle);
+
+        // The loop drops the connection as it exits
+        let mut buf = [0u8; 1];
+        let read = tokio::time::timeout(Duration::from_secs(5), device.read(&mut buf))
+            .await
+            .expect("loop kept running after the handle was dropped");
+        assert_eq!(read.unwrap(), 0);
+    }
+}
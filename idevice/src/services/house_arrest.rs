@@ -4,9 +4,21 @@
 //! installed on an iOS device. This is typically used for file transfer and inspection of
 //! app-specific data during development or diagnostics.
 
-use crate::{Idevice, IdeviceError, IdeviceService, obf};
+use crate::{Idevice, IdeviceError, IdeviceService, obf, provider::IdeviceProvider};
 
-use super::afc::AfcClient;
+use super::{afc::AfcClient, installation_proxy::InstallationProxyClient};
+
+/// Error the device reports when no installed app matches the bundle id
+const LOOKUP_FAILED: &str = "InstallationLookupFailed";
+
+/// Part of an app's sandbox to expose over AFC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerType {
+    /// The full data container, only available for apps signed with `get-task-allow`
+    App,
+    /// The Documents directory, only available for apps that enable file sharing
+    Documents,
+}
 
 /// Client for interacting with the iOS HouseArrest service
 ///
@@ -38,6 +50,47 @@ impl HouseArrestClient {
         Self { idevice }
     }
 
+    /// Connects to HouseArrest and returns an AFC client rooted at an app's container
+    ///
+    /// # Arguments
+    /// * `provider` - The device provider used for connection and pairing info
+    /// * `bundle_id` - The bundle identifier of the target app (e.g., "com.example.MyApp")
+    /// * `container_type` - Which part of the app's sandbox to expose
+    ///
+    /// # Errors
+    /// * `IdeviceError::HouseArrestAppNotFound` if the app isn't installed, or if its full
+    ///   container was requested and it isn't signed with `get-task-allow`
+    /// * `IdeviceError::HouseArrestDocumentsNotShared` if the Documents directory was requested
+    ///   and installation_proxy reports the app installed, but it doesn't enable file sharing
+    pub async fn connect_container(
+        provider: &dyn IdeviceProvider,
+        bundle_id: impl Into<String>,
+        container_type: ContainerType,
+    ) -> Result<AfcClient, IdeviceError> {
+        let bundle_id = bundle_id.into();
+        let client = Self::connect(provider).await?;
+        match container_type {
+            ContainerType::App => client.vend_container(bundle_id).await,
+            ContainerType::Documents => match client.vend_documents(bundle_id.as_str()).await {
+                // The lookup also fails for installed apps that don't share their documents,
+                // so ask installation_proxy whether the app exists
+                Err(IdeviceError::HouseArrestAppNotFound(_)) => {
+                    let installed = InstallationProxyClient::connect(provider)
+                        .await?
+                        .lookup(&[&bundle_id], Some(&["CFBundleIdentifier"]))
+                        .await?
+                        .contains_key(&bundle_id);
+                    Err(if installed {
+                        IdeviceError::HouseArrestDocumentsNotShared(bundle_id)
+                    } else {
+                        IdeviceError::HouseArrestAppNotFound(bundle_id)
+                    })
+                }
+                res => res,
+            },
+        }
+    }
+
     /// Requests access to the app's full container (Documents, Library, etc.) over AFC
     ///
     /// # Arguments
@@ -88,16 +141,93 @@ impl HouseArrestClient {
     /// A connected `AfcClient` instance
     ///
     /// # Errors
-    /// Returns `IdeviceError` if the request or AFC setup fails
+    /// Returns `IdeviceError::HouseArrestAppNotFound` if the device can't find the app, or
+    /// another `IdeviceError` if the request or AFC setup fails
     async fn vend(mut self, bundle_id: String, cmd: String) -> Result<AfcClient, IdeviceError> {
         let req = crate::plist!({
             "Command": cmd,
-            "Identifier": bundle_id
+            "Identifier": bundle_id.clone()
         });
 
         self.idevice.send_plist(req).await?;
-        self.idevice.read_plist().await?;
+        match self.idevice.read_plist().await {
+            Ok(_) => Ok(AfcClient::new(self.idevice)),
+            Err(IdeviceError::UnknownErrorType(e)) if e.starts_with(LOOKUP_FAILED) => {
+                Err(IdeviceError::HouseArrestAppNotFound(bundle_id))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::{MockProvider, MockScript};
+
+    fn vend_documents_fails() -> MockScript {
+        MockScript::new()
+            .expect(crate::plist!({
+                "Command": "VendDocuments",
+                "Identifier": "com.example.app",
+            }))
+            .respond(crate::plist!({ "Error": LOOKUP_FAILED }))
+    }
+
+    fn lookup(result: plist::Value) -> MockScript {
+        MockScript::new()
+            .expect(crate::plist!({
+                "Command": "Lookup",
+                "ClientOptions": { "BundleIDs": ["com.example.app"] },
+            }))
+            .respond(crate::plist!({ "LookupResult": result, "Status": "Complete" }))
+    }
+
+    #[tokio::test]
+    async fn missing_app_is_distinct() {
+        let provider = MockProvider::new()
+            .with_service(HouseArrestClient::service_name(), vend_documents_fails())
+            .with_service(
+                InstallationProxyClient::service_name(),
+                lookup(crate::plist!({})),
+            );
+
+        match HouseArrestClient::connect_container(
+            &provider,
+            "com.example.app",
+            ContainerType::Documents,
+        )
+        .await
+        {
+            Err(IdeviceError::HouseArrestAppNotFound(id)) => assert_eq!(id, "com.example.app"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn installed_app_without_file_sharing() {
+        let provider = MockProvider::new()
+            .with_service(HouseArrestClient::service_name(), vend_documents_fails())
+            .with_service(
+                InstallationProxyClient::service_name(),
+                lookup(crate::plist!({
+                    "com.example.app": { "CFBundleIdentifier": "com.example.app" },
+                })),
+            );
 
-        Ok(AfcClient::new(self.idevice))
+        match HouseArrestClient::connect_container(
+            &provider,
+            "com.example.app",
+            ContainerType::Documents,
+        )
+        .await
+        {
+            Err(IdeviceError::HouseArrestDocumentsNotShared(id)) => {
+                assert_eq!(id, "com.example.app")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert!(provider.verify().await.is_empty());
     }
 }
@@ -31,6 +31,128 @@ impl IdeviceService for InstallationProxyClient {
     }
 }
 
+/// The kind of applications to return when browsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplicationType {
+    /// Applications installed by the user
+    User,
+    /// Applications shipped with the system
+    System,
+    /// Internal Apple applications
+    Internal,
+    /// Every application on the device
+    Any,
+}
+
+impl ApplicationType {
+    /// Returns the name of the type as expected by installation_proxy
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApplicationType::User => "User",
+            ApplicationType::System => "System",
+            ApplicationType::Internal => "Internal",
+            ApplicationType::Any => "Any",
+        }
+    }
+
+    /// Parses a type name as reported by installation_proxy
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "User" => Some(ApplicationType::User),
+            "System" => Some(ApplicationType::System),
+            "Internal" => Some(ApplicationType::Internal),
+            "Any" => Some(ApplicationType::Any),
+            _ => None,
+        }
+    }
+}
+
+/// An installed application, with the commonly used fields of its browse entry parsed
+#[derive(Debug, Clone)]
+pub struct InstalledApp {
+    /// The bundle identifier, from `CFBundleIdentifier`
+    pub bundle_id: String,
+    /// The display name, from `CFBundleDisplayName` or `CFBundleName`
+    pub name: Option<String>,
+    /// The marketing version, from `CFBundleShortVersionString`
+    pub version: Option<String>,
+    /// The build version, from `CFBundleVersion`
+    pub build_version: Option<String>,
+    /// The kind of application, from `ApplicationType`
+    pub application_type: Option<ApplicationType>,
+    /// The identity the application was signed with, from `SignerIdentity`
+    pub signer_identity: Option<String>,
+    /// Where the application bundle is installed, from `Path`
+    pub path: Option<String>,
+    /// The full browse entry, for every other attribute
+    pub raw: Dictionary,
+}
+
+impl InstalledApp {
+    /// Parses a browse entry, returning `None` if it has no bundle identifier
+    pub fn from_plist(raw: Dictionary) -> Option<Self> {
+        let string = |key: &str| raw.get(key).and_then(|v| v.as_string()).map(String::from);
+        Some(Self {
+            bundle_id: string("CFBundleIdentifier")?,
+            name: string("CFBundleDisplayName").or_else(|| string("CFBundleName")),
+            version: string("CFBundleShortVersionString"),
+            build_version: string("CFBundleVersion"),
+            application_type: raw
+                .get("ApplicationType")
+                .and_then(|v| v.as_string())
+                .and_then(ApplicationType::from_name),
+            signer_identity: string("SignerIdentity"),
+            path: string("Path"),
+            raw,
+        })
+    }
+}
+
+/// High-level builder for browse options so callers don't need to remember raw keys
+#[derive(Debug, Clone, Default)]
+pub struct BrowseOptions {
+    /// Only list apps of this type, or every type if `None`
+    pub application_type: Option<ApplicationType>,
+    /// Attributes to return for each app, or all of them if `None`
+    pub return_attributes: Option<Vec<String>>,
+    /// Only list these bundle identifiers, or every app if `None`
+    pub bundle_ids: Option<Vec<String>>,
+}
+
+impl BrowseOptions {
+    /// Creates options that list every app with all of its attributes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only lists apps of the given type
+    pub fn application_type(mut self, application_type: ApplicationType) -> Self {
+        self.application_type = Some(application_type);
+        self
+    }
+
+    /// Only returns the given attributes for each app, such as `CFBundleIdentifier`
+    pub fn return_attributes(mut self, attributes: &[&str]) -> Self {
+        self.return_attributes = Some(attributes.iter().map(|a| a.to_string()).collect());
+        self
+    }
+
+    /// Only lists apps with the given bundle identifiers
+    pub fn bundle_ids(mut self, bundle_ids: &[&str]) -> Self {
+        self.bundle_ids = Some(bundle_ids.iter().map(|b| b.to_string()).collect());
+        self
+    }
+
+    /// Builds the `ClientOptions` dictionary sent with a browse request
+    pub fn to_plist(&self) -> Dictionary {
+        crate::plist!(dict {
+            "ApplicationType":? self.application_type.map(|t| t.as_str()),
+            "ReturnAttributes":? self.return_attributes.clone(),
+            "BundleIDs":? self.bundle_ids.clone(),
+        })
+    }
+}
+
 impl InstallationProxyClient {
     /// Creates a new installation proxy client from an existing device connection
     ///
@@ -90,6 +212,47 @@ impl InstallationProxyClient {
         }
     }
 
+    /// Looks up specific applications by bundle identifier
+    ///
+    /// Unlike [`Self::browse`], only the requested applications are sent by the device, so this
+    /// is the cheap way to check whether an app is installed and at what version.
+    ///
+    /// # Arguments
+    /// * `bundle_ids` - Bundle identifiers to look up
+    /// * `return_attributes` - Attributes to return for each app, such as
+    ///   `CFBundleShortVersionString`, or `None` for every attribute
+    ///
+    /// # Returns
+    /// A map of bundle identifiers to application information. Apps that aren't installed are
+    /// left out.
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if:
+    /// - Communication fails
+    /// - The response is malformed
+    /// - The service returns an error
+    pub async fn lookup(
+        &mut self,
+        bundle_ids: &[&str],
+        return_attributes: Option<&[&str]>,
+    ) -> Result<HashMap<String, plist::Value>, IdeviceError> {
+        let req = crate::plist!({
+            "Command": "Lookup",
+            "ClientOptions": {
+                "ApplicationType": ApplicationType::Any.as_str(),
+                "BundleIDs": bundle_ids.to_vec(),
+                "ReturnAttributes":? return_attributes.map(|a| a.to_vec()),
+            }
+        });
+        self.idevice.send_plist(req).await?;
+
+        let mut res = self.idevice.read_plist().await?;
+        match res.remove("LookupResult") {
+            Some(plist::Value::Dictionary(res)) => Ok(res.into_iter().collect()),
+            _ => Err(IdeviceError::UnexpectedResponse),
+        }
+    }
+
     /// Installs an application package on the device
     ///
     /// # Arguments
@@ -157,7 +320,50 @@ impl InstallationProxyClient {
 
         self.idevice.send_plist(command).await?;
 
-        self.watch_completion(callback, state).await
+        self.watch_completion(|(c, _, state)| callback((c, state)), state)
+            .await
+    }
+
+    /// Installs an application package on the device, reporting each progress status
+    ///
+    /// # Arguments
+    /// * `package_path` - Path to the .ipa package in the AFC jail (device's installation directory)
+    /// * `options` - Optional installation options as a plist dictionary
+    /// * `callback` - Progress callback that receives (percent_complete, status, state). It is
+    ///   called once more with 100 and `"Complete"` when the installation finishes.
+    /// * `state` - State to pass to the callback
+    ///
+    /// # Returns
+    /// `Ok(())` on successful installation
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if:
+    /// - Communication fails
+    /// - The installation fails
+    /// - The service returns an error
+    pub async fn install_with_status_callback<Fut, S>(
+        &mut self,
+        package_path: impl Into<String>,
+        options: Option<plist::Value>,
+        callback: impl Fn((u64, String, S)) -> Fut,
+        state: S,
+    ) -> Result<(), IdeviceError>
+    where
+        Fut: std::future::Future<Output = ()>,
+        S: Clone,
+    {
+        let package_path = package_path.into();
+        let options = options.unwrap_or(plist::Value::Dictionary(Dictionary::new()));
+
+        let command = crate::plist!({
+            "Command": "Install",
+            "ClientOptions": options,
+            "PackagePath": package_path,
+        });
+
+        self.idevice.send_plist(command).await?;
+
+        self.watch_status(callback, state).await
     }
 
     /// Upgrades an existing application on the device
@@ -221,7 +427,8 @@ impl InstallationProxyClient {
 
         self.idevice.send_plist(command).await?;
 
-        self.watch_completion(callback, state).await
+        self.watch_completion(|(c, _, state)| callback((c, state)), state)
+            .await
     }
 
     /// Uninstalls an application from the device
@@ -285,7 +492,101 @@ impl InstallationProxyClient {
 
         self.idevice.send_plist(command).await?;
 
-        self.watch_completion(callback, state).await
+        self.watch_completion(|(c, _, state)| callback((c, state)), state)
+            .await
+    }
+
+    /// Uninstalls an application from the device, reporting each progress status
+    ///
+    /// # Arguments
+    /// * `bundle_id` - Bundle identifier of the application to uninstall
+    /// * `options` - Optional uninstall options as a plist dictionary
+    /// * `callback` - Progress callback that receives (percent_complete, status, state). It is
+    ///   called once more with 100 and `"Complete"` when the uninstallation finishes.
+    /// * `state` - State to pass to the callback
+    ///
+    /// # Returns
+    /// `Ok(())` on successful uninstallation
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if:
+    /// - Communication fails
+    /// - The uninstallation fails
+    /// - The service returns an error
+    pub async fn uninstall_with_status_callback<Fut, S>(
+        &mut self,
+        bundle_id: impl Into<String>,
+        options: Option<plist::Value>,
+        callback: impl Fn((u64, String, S)) -> Fut,
+        state: S,
+    ) -> Result<(), IdeviceError>
+    where
+        Fut: std::future::Future<Output = ()>,
+        S: Clone,
+    {
+        let bundle_id = bundle_id.into();
+        let options = options.unwrap_or(plist::Value::Dictionary(Dictionary::new()));
+
+        let command = crate::plist!({
+            "Command": "Uninstall",
+            "ApplicationIdentifier": bundle_id,
+            "ClientOptions": options,
+        });
+
+        self.idevice.send_plist(command).await?;
+
+        self.watch_status(callback, state).await
+    }
+
+    /// Uninstalls an application, then checks that it is gone
+    ///
+    /// See [`Self::uninstall_and_verify_with_callback`].
+    pub async fn uninstall_and_verify(
+        &mut self,
+        bundle_id: impl Into<String>,
+        options: Option<plist::Value>,
+    ) -> Result<(), IdeviceError> {
+        self.uninstall_and_verify_with_callback(bundle_id, options, |_| async {}, ())
+            .await
+    }
+
+    /// Uninstalls an application, then checks that it is gone
+    ///
+    /// The device can report a successful uninstall while the app is still registered, such
+    /// as when its data container couldn't be removed. The app is looked up afterwards to
+    /// catch that.
+    ///
+    /// # Arguments
+    /// * `bundle_id` - Bundle identifier of the application to uninstall
+    /// * `options` - Optional uninstall options as a plist dictionary
+    /// * `callback` - Progress callback that receives (percent_complete, state)
+    /// * `state` - State to pass to the callback
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::AppStillInstalled` if the app can still be looked up after the
+    /// device reported it uninstalled, or the error from uninstalling
+    pub async fn uninstall_and_verify_with_callback<Fut, S>(
+        &mut self,
+        bundle_id: impl Into<String>,
+        options: Option<plist::Value>,
+        callback: impl Fn((u64, S)) -> Fut,
+        state: S,
+    ) -> Result<(), IdeviceError>
+    where
+        Fut: std::future::Future<Output = ()>,
+        S: Clone,
+    {
+        let bundle_id = bundle_id.into();
+        self.uninstall_with_callback(bundle_id.as_str(), options, callback, state)
+            .await?;
+
+        let remaining = self
+            .lookup(&[&bundle_id], Some(&["CFBundleIdentifier"]))
+            .await?;
+        if remaining.contains_key(&bundle_id) {
+            return Err(IdeviceError::AppStillInstalled(bundle_id));
+        }
+        Ok(())
     }
 
     /// Checks if the device capabilities match the required capabilities
@@ -327,7 +628,56 @@ impl InstallationProxyClient {
     /// Browses installed applications on the device
     ///
     /// # Arguments
-    /// * `options` - Optional browse options as a plist dictionary
+    /// * `options` - Optional browse options, defaulting to every application
+    ///
+    /// # Returns
+    /// A vector of plist values representing application information
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if:
+    /// - Communication fails
+    /// - The service returns an error
+    pub async fn browse(
+        &mut self,
+        options: Option<BrowseOptions>,
+    ) -> Result<Vec<plist::Value>, IdeviceError> {
+        self.browse_raw(options.map(|o| plist::Value::Dictionary(o.to_plist())))
+            .await
+    }
+
+    /// Browses installed applications on the device, parsing the common fields of each
+    ///
+    /// If `options` limits the returned attributes, `CFBundleIdentifier` is always added to
+    /// them, since every entry is identified by it.
+    ///
+    /// # Arguments
+    /// * `options` - Browse options selecting the applications and attributes to return
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if:
+    /// - Communication fails
+    /// - The service returns an error
+    pub async fn list_apps(
+        &mut self,
+        mut options: BrowseOptions,
+    ) -> Result<Vec<InstalledApp>, IdeviceError> {
+        if let Some(attributes) = &mut options.return_attributes
+            && !attributes.iter().any(|a| a == "CFBundleIdentifier")
+        {
+            attributes.push("CFBundleIdentifier".to_string());
+        }
+        Ok(self
+            .browse(Some(options))
+            .await?
+            .into_iter()
+            .filter_map(|v| InstalledApp::from_plist(v.into_dictionary()?))
+            .collect())
+    }
+
+    /// Browses installed applications on the device
+    ///
+    /// # Arguments
+    /// * `options` - Optional browse options as a raw plist dictionary
     ///
     /// # Returns
     /// A vector of plist values representing application information
@@ -339,7 +689,7 @@ impl InstallationProxyClient {
     ///
     /// # Note
     /// This method streams application information in chunks and collects them into a single vector
-    pub async fn browse(
+    pub async fn browse_raw(
         &mut self,
         options: Option<plist::Value>,
     ) -> Result<Vec<plist::Value>, IdeviceError> {
@@ -377,8 +727,8 @@ impl InstallationProxyClient {
     /// Watches for operation completion and handles progress callbacks
     ///
     /// # Arguments
-    /// * `callback` - Optional progress callback that receives (percent_complete, state)
-    /// * `state` - Optional state to pass to the callback
+    /// * `callback` - Progress callback that receives (percent_complete, status, state)
+    /// * `state` - State to pass to the callback
     ///
     /// # Returns
     /// `Ok(())` when the operation completes successfully
@@ -390,7 +740,7 @@ impl InstallationProxyClient {
     /// - The service returns an error
     async fn watch_completion<Fut, S>(
         &mut self,
-        callback: impl Fn((u64, S)) -> Fut,
+        callback: impl Fn((u64, String, S)) -> Fut,
         state: S,
     ) -> Result<(), IdeviceError>
     where
@@ -401,24 +751,214 @@ impl InstallationProxyClient {
             let mut res = self.idevice.read_plist().await?;
 
             if let Some(e) = res.remove("ErrorDescription").and_then(|x| x.into_string()) {
-                return Err(IdeviceError::InstallationProxyOperationFailed(
-                    e.to_string(),
-                ));
+                return Err(IdeviceError::InstallationProxyOperationFailed(e));
             }
 
+            let status = res
+                .remove("Status")
+                .and_then(|x| x.into_string())
+                .unwrap_or_default();
+            let complete = status == "Complete";
+
             if let Some(c) = res
                 .remove("PercentComplete")
                 .and_then(|x| x.as_unsigned_integer())
             {
-                callback((c, state.clone())).await;
+                callback((c, status, state.clone())).await;
             }
 
-            if let Some(c) = res.remove("Status").and_then(|x| x.into_string())
-                && c == "Complete"
-            {
-                break;
+            if complete {
+                return Ok(());
             }
         }
+    }
+
+    /// Watches for operation completion, then reports 100% with a `"Complete"` status
+    async fn watch_status<Fut, S>(
+        &mut self,
+        callback: impl Fn((u64, String, S)) -> Fut,
+        state: S,
+    ) -> Result<(), IdeviceError>
+    where
+        Fut: std::future::Future<Output = ()>,
+        S: Clone,
+    {
+        self.watch_completion(&callback, state.clone()).await?;
+        callback((100, "Complete".to_string(), state)).await;
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::{MockProvider, MockScript};
+
+    #[tokio::test]
+    async fn lookup_requests_only_given_bundles() {
+        let provider = MockProvider::new().with_service(
+            InstallationProxyClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({
+                    "Command": "Lookup",
+                    "ClientOptions": {
+                        "BundleIDs": ["com.example.app", "com.example.missing"],
+                        "ReturnAttributes": ["CFBundleShortVersionString"],
+                    }
+                }))
+                .respond(crate::plist!({
+                    "LookupResult": {
+                        "com.example.app": { "CFBundleShortVersionString": "1.2" }
+                    },
+                    "Status": "Complete",
+                })),
+        );
+
+        let mut client = InstallationProxyClient::connect(&provider).await.unwrap();
+        let apps = client
+            .lookup(
+                &["com.example.app", "com.example.missing"],
+                Some(&["CFBundleShortVersionString"]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(
+            apps["com.example.app"]
+                .as_dictionary()
+                .and_then(|d| d.get("CFBundleShortVersionString"))
+                .and_then(|v| v.as_string()),
+            Some("1.2")
+        );
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_apps_parses_common_fields() {
+        let provider = MockProvider::new().with_service(
+            InstallationProxyClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({
+                    "Command": "Browse",
+                    "ClientOptions": {
+                        "ReturnAttributes": ["SignerIdentity", "CFBundleIdentifier"],
+                    }
+                }))
+                .respond(crate::plist!({
+                    "CurrentList": [
+                        {
+                            "CFBundleIdentifier": "com.example.app",
+                            "CFBundleName": "Example",
+                            "CFBundleShortVersionString": "1.2",
+                            "ApplicationType": "User",
+                            "SignerIdentity": "Apple Development: Example",
+                            "UIFileSharingEnabled": true,
+                        },
+                        { "CFBundleName": "No identifier" }
+                    ],
+                    "Status": "Complete",
+                })),
+        );
+
+        let mut client = InstallationProxyClient::connect(&provider).await.unwrap();
+        let apps = client
+            .list_apps(BrowseOptions::new().return_attributes(&["SignerIdentity"]))
+            .await
+            .unwrap();
+        assert_eq!(apps.len(), 1);
+        let app = &apps[0];
+        assert_eq!(app.bundle_id, "com.example.app");
+        assert_eq!(app.name.as_deref(), Some("Example"));
+        assert_eq!(app.version.as_deref(), Some("1.2"));
+        assert_eq!(app.application_type, Some(ApplicationType::User));
+        assert_eq!(
+            app.signer_identity.as_deref(),
+            Some("Apple Development: Example")
+        );
+        assert_eq!(
+            app.raw
+                .get("UIFileSharingEnabled")
+                .and_then(|v| v.as_boolean()),
+            Some(true)
+        );
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn status_callback_reports_progress_then_completion() {
+        let provider = MockProvider::new().with_service(
+            InstallationProxyClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({
+                    "Command": "Install",
+                    "PackagePath": "PublicStaging/app.ipa",
+                }))
+                .respond(crate::plist!({
+                    "PercentComplete": 40,
+                    "Status": "CopyingApplication",
+                }))
+                .respond(crate::plist!({ "Status": "Complete" })),
+        );
+
+        let calls = std::sync::Mutex::new(Vec::new());
+        let mut client = InstallationProxyClient::connect(&provider).await.unwrap();
+        client
+            .install_with_status_callback(
+                "PublicStaging/app.ipa",
+                None,
+                |(c, status, ())| {
+                    calls.lock().unwrap().push((c, status));
+                    async {}
+                },
+                (),
+            )
+            .await
+            .unwrap();
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+        assert_eq!(
+            calls.into_inner().unwrap(),
+            [
+                (40, "CopyingApplication".to_string()),
+                (100, "Complete".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn uninstall_and_verify_reports_lingering_app() {
+        let provider = MockProvider::new().with_service(
+            InstallationProxyClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({
+                    "Command": "Uninstall",
+                    "ApplicationIdentifier": "com.example.app",
+                }))
+                .respond(crate::plist!({ "Status": "Complete" }))
+                .expect(crate::plist!({
+                    "Command": "Lookup",
+                    "ClientOptions": { "BundleIDs": ["com.example.app"] }
+                }))
+                .respond(crate::plist!({
+                    "LookupResult": {
+                        "com.example.app": { "CFBundleIdentifier": "com.example.app" }
+                    },
+                    "Status": "Complete",
+                })),
+        );
+
+        let mut client = InstallationProxyClient::connect(&provider).await.unwrap();
+        assert!(matches!(
+            client.uninstall_and_verify("com.example.app", None).await,
+            Err(IdeviceError::AppStillInstalled(id)) if id == "com.example.app"
+        ));
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+}
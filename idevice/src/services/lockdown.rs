@@ -3,8 +3,13 @@
 //! Provides functionality for interacting with the lockdown service on iOS devices,
 //! which is the primary service for device management and service discovery.
 
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
 use plist::Value;
-use tracing::error;
+use tracing::{debug, error};
 
 use crate::{Idevice, IdeviceError, IdeviceService, obf, pairing_file};
 
@@ -14,10 +19,16 @@ use crate::{Idevice, IdeviceError, IdeviceService, obf, pairing_file};
 /// - Access to device information and settings
 /// - Service discovery and port allocation
 /// - Session management and security
+///
+/// A session started with [`LockdownClient::start_session`] isn't stopped when the client is
+/// dropped. Use [`LockdownClient::with_session`] to stop it explicitly, or
+/// [`LockdownClient::into_session`] for a guard that stops it when dropped.
 #[derive(Debug)]
 pub struct LockdownClient {
     /// The underlying device connection with established lockdown service
     pub idevice: crate::Idevice,
+    /// The ID of the session started by [`LockdownClient::start_session`], if any
+    session_id: Option<String>,
 }
 
 impl IdeviceService for LockdownClient {
@@ -39,6 +50,9 @@ impl IdeviceService for LockdownClient {
     async fn connect(
         provider: &dyn crate::provider::IdeviceProvider,
     ) -> Result<Self, IdeviceError> {
+        if let Some(idevice) = provider.connect_service(&Self::service_name()).await? {
+            return Ok(Self::new(idevice));
+        }
         let idevice = provider.connect(Self::LOCKDOWND_PORT).await?;
         Ok(Self::new(idevice))
     }
@@ -52,12 +66,65 @@ impl LockdownClient {
     /// The default TCP port for the lockdown service
     pub const LOCKDOWND_PORT: u16 = 62078;
 
+    /// Names of the services probed by [`LockdownClient::query_services`]
+    ///
+    /// Taken from the service clients enabled by this crate's features. Services that act as
+    /// soon as they are started are left out, such as crashreportmover, which flushes crash
+    /// reports, or pcapd, which starts capturing packets.
+    pub fn probed_services() -> Vec<std::borrow::Cow<'static, str>> {
+        Vec::from([
+            #[cfg(feature = "afc")]
+            crate::afc::AfcClient::service_name(),
+            #[cfg(feature = "amfi")]
+            crate::amfi::AmfiClient::service_name(),
+            #[cfg(feature = "companion_proxy")]
+            crate::companion_proxy::CompanionProxy::service_name(),
+            #[cfg(feature = "crashreportcopymobile")]
+            crate::crashreportcopymobile::CrashReportCopyMobileClient::service_name(),
+            #[cfg(feature = "diagnostics_relay")]
+            crate::diagnostics_relay::DiagnosticsRelayClient::service_name(),
+            #[cfg(feature = "house_arrest")]
+            crate::house_arrest::HouseArrestClient::service_name(),
+            #[cfg(feature = "installation_proxy")]
+            crate::installation_proxy::InstallationProxyClient::service_name(),
+            #[cfg(feature = "location_simulation")]
+            crate::simulate_location::LocationSimulationService::service_name(),
+            #[cfg(feature = "mcinstall")]
+            crate::mcinstall::McInstallClient::service_name(),
+            #[cfg(feature = "misagent")]
+            crate::misagent::MisagentClient::service_name(),
+            #[cfg(feature = "mobile_image_mounter")]
+            crate::mobile_image_mounter::ImageMounter::service_name(),
+            #[cfg(feature = "mobilebackup2")]
+            crate::mobilebackup2::MobileBackup2Client::service_name(),
+            #[cfg(feature = "notification_proxy")]
+            crate::notification_proxy::NotificationProxyClient::service_name(),
+            #[cfg(feature = "power_assertion")]
+            crate::power_assertion::PowerAssertionClient::service_name(),
+            #[cfg(feature = "preboard_service")]
+            crate::preboard_service::PreboardServiceClient::service_name(),
+            #[cfg(feature = "restored")]
+            crate::restored::RestoredClient::service_name(),
+            #[cfg(feature = "screenshotr")]
+            crate::screenshotr::ScreenshotService::service_name(),
+            #[cfg(feature = "springboardservices")]
+            crate::springboardservices::SpringBoardServicesClient::service_name(),
+            #[cfg(feature = "syslog_relay")]
+            crate::syslog_relay::SyslogRelayClient::service_name(),
+            #[cfg(feature = "syslog_relay")]
+            crate::os_trace_relay::OsTraceRelayClient::service_name(),
+        ])
+    }
+
     /// Creates a new lockdown client from an existing device connection
     ///
     /// # Arguments
     /// * `idevice` - Pre-established device connection
     pub fn new(idevice: Idevice) -> Self {
-        Self { idevice }
+        Self {
+            idevice,
+            session_id: None,
+        }
     }
 
     /// Retrieves a specific value from the device
@@ -136,23 +203,109 @@ impl LockdownClient {
         Ok(())
     }
 
-    /// Starts a secure TLS session with the device
+    /// Retrieves the user-assigned name of the device
+    pub async fn device_name(&mut self) -> Result<String, IdeviceError> {
+        self.get_string_value("DeviceName").await
+    }
+
+    /// Retrieves the iOS version of the device, such as `17.4.1`
+    pub async fn product_version(&mut self) -> Result<String, IdeviceError> {
+        self.get_string_value("ProductVersion").await
+    }
+
+    /// Checks that lockdown still answers, with the cheapest request it supports
+    ///
+    /// Useful as a health check for pooled connections, since it catches connections lost
+    /// without being closed, which [`Idevice::is_alive`] can't.
+    pub async fn ping(&mut self) -> Result<(), IdeviceError> {
+        self.product_version().await.map(|_| ())
+    }
+
+    /// Retrieves the UDID of the device
+    pub async fn unique_device_id(&mut self) -> Result<String, IdeviceError> {
+        self.get_string_value("UniqueDeviceID").await
+    }
+
+    /// Retrieves the MAC address of the device's Wi-Fi interface
+    pub async fn wifi_address(&mut self) -> Result<String, IdeviceError> {
+        self.get_string_value("WiFiAddress").await
+    }
+
+    /// Retrieves every value in a domain in a single request
+    ///
+    /// # Arguments
+    /// * `domain` - The domain to read, or `None` for the global domain
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::UnexpectedResponse` if the device doesn't return a dictionary
+    pub async fn get_all_values(
+        &mut self,
+        domain: Option<&str>,
+    ) -> Result<plist::Dictionary, IdeviceError> {
+        match self.get_value(None, domain).await? {
+            Value::Dictionary(d) => Ok(d),
+            _ => Err(IdeviceError::UnexpectedResponse),
+        }
+    }
+
+    /// Retrieves the most commonly needed device information in a single request
+    pub async fn device_info(&mut self) -> Result<DeviceInfo, IdeviceError> {
+        Ok(DeviceInfo::from_values(&self.get_all_values(None).await?))
+    }
+
+    /// Reads the device's clock and time zone
+    ///
+    /// These are lockdown values, diagnostics_relay doesn't report them. Neither service can
+    /// set the clock, so this is read-only. The host's clock is read when the values arrive,
+    /// so [`DeviceTime::clock_offset`] shows how far the device's clock is off.
+    pub async fn time_info(&mut self) -> Result<DeviceTime, IdeviceError> {
+        let values = self.get_all_values(None).await?;
+        DeviceTime::from_values(&values, SystemTime::now())
+    }
+
+    /// Checks whether the device is currently locked with a passcode
+    ///
+    /// Until a passcode-protected device has been unlocked once since boot, its user data stays
+    /// encrypted. Services that touch it, such as afc, house_arrest, installation_proxy,
+    /// crashreportcopymobile and mobilebackup2, refuse to start and their connects fail with
+    /// `IdeviceError::PasswordProtected`. Automation can poll this to wait for an unlock.
+    ///
+    /// # Returns
+    /// `false` if the device has no passcode or is unlocked
+    pub async fn is_passcode_locked(&mut self) -> Result<bool, IdeviceError> {
+        match self.get_value(Some("PasswordProtected"), None).await? {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(IdeviceError::UnexpectedResponse),
+        }
+    }
+
+    async fn get_string_value(&mut self, key: &str) -> Result<String, IdeviceError> {
+        match self.get_value(Some(key), None).await? {
+            Value::String(s) => Ok(s),
+            _ => Err(IdeviceError::UnexpectedResponse),
+        }
+    }
+
+    /// Starts a session with the device, upgrading the connection to TLS if the device asks for it
+    ///
+    /// Values in protected domains can only be read from within a session.
     ///
     /// # Arguments
     /// * `pairing_file` - Contains the device's identity and certificates
     ///
     /// # Returns
-    /// `Ok(())` on successful session establishment
+    /// Whether the device enabled SSL for the session
     ///
     /// # Errors
     /// Returns `IdeviceError` if:
     /// - No connection is established
-    /// - The session request is denied
+    /// - The device rejects the pairing record (`IdeviceError::InvalidHostID`)
+    /// - The session request is otherwise denied
     /// - TLS handshake fails
     pub async fn start_session(
         &mut self,
         pairing_file: &pairing_file::PairingFile,
-    ) -> Result<(), IdeviceError> {
+    ) -> Result<bool, IdeviceError> {
         if self.idevice.socket.is_none() {
             return Err(IdeviceError::NoEstablishedConnection);
         }
@@ -177,22 +330,114 @@ impl LockdownClient {
         });
         self.idevice.send_plist(request).await?;
 
-        let response = self.idevice.read_plist().await?;
-        match response.get("EnableSessionSSL") {
-            Some(plist::Value::Boolean(enable)) => {
-                if !enable {
-                    return Err(IdeviceError::UnexpectedResponse);
-                }
-            }
+        let mut response = self.idevice.read_plist().await?;
+        let enable_ssl = match response.get("EnableSessionSSL") {
+            Some(plist::Value::Boolean(enable)) => *enable,
             _ => {
                 return Err(IdeviceError::UnexpectedResponse);
             }
+        };
+        self.session_id = match response.remove("SessionID") {
+            Some(plist::Value::String(id)) => Some(id),
+            _ => None,
+        };
+
+        if enable_ssl {
+            self.idevice.start_session(pairing_file, legacy).await?;
         }
+        Ok(enable_ssl)
+    }
+
+    /// Stops the session started by [`LockdownClient::start_session`]
+    ///
+    /// Note that the connection stays encrypted if SSL was enabled for the session.
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::SessionInactive` if no session was started
+    pub async fn stop_session(&mut self) -> Result<(), IdeviceError> {
+        let session_id = match self.session_id.take() {
+            Some(id) => id,
+            None => return Err(IdeviceError::SessionInactive),
+        };
 
-        self.idevice.start_session(pairing_file, legacy).await?;
+        let req = crate::plist!({
+            "Label": self.idevice.label.clone(),
+            "Request": "StopSession",
+            "SessionID": session_id,
+        });
+        self.idevice.send_plist(req).await?;
+        self.idevice.read_plist().await?;
         Ok(())
     }
 
+    /// Runs `f` inside a session and stops the session afterwards
+    ///
+    /// The session is stopped whether or not `f` succeeds, so the device isn't left with a
+    /// stale session for the next connection. The error from `f` takes precedence over an error
+    /// stopping the session.
+    ///
+    /// # Arguments
+    /// * `pairing_file` - The pairing file used to start the session
+    /// * `f` - The work to run while the session is active
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if the session can't be started or stopped, or if `f` fails
+    pub async fn with_session<T>(
+        &mut self,
+        pairing_file: &pairing_file::PairingFile,
+        f: impl AsyncFnOnce(&mut Self) -> Result<T, IdeviceError>,
+    ) -> Result<T, IdeviceError> {
+        self.start_session(pairing_file).await?;
+        let res = f(self).await;
+        let stopped = self.stop_session().await;
+        let res = res?;
+        stopped?;
+        Ok(res)
+    }
+
+    /// Starts a session, returning a guard that stops it when dropped
+    ///
+    /// The guard derefs to the client. Dropping it sends StopSession on a best-effort basis
+    /// from a spawned task, so it must be dropped within a Tokio runtime for the session to be
+    /// stopped. Use [`LockdownSession::stop`] to stop it and see the result instead.
+    ///
+    /// # Arguments
+    /// * `pairing_file` - Contains the device's identity and certificates
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if the session can't be started, see
+    /// [`LockdownClient::start_session`]
+    pub async fn into_session(
+        mut self,
+        pairing_file: &pairing_file::PairingFile,
+    ) -> Result<LockdownSession, IdeviceError> {
+        let ssl_enabled = self.start_session(pairing_file).await?;
+        Ok(LockdownSession {
+            client: Some(self),
+            ssl_enabled,
+        })
+    }
+
+    /// Reboots the device into recovery mode
+    ///
+    /// The device drops the lockdown connection once it starts rebooting, so this client can't
+    /// be used afterwards. That drop is expected and isn't reported as an error.
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if the device refuses the request
+    pub async fn enter_recovery(&mut self) -> Result<(), IdeviceError> {
+        let req = crate::plist!({
+            "Label": self.idevice.label.clone(),
+            "Request": "EnterRecovery",
+        });
+        self.idevice.send_plist(req).await?;
+
+        match self.idevice.read_plist().await {
+            Ok(_) | Err(IdeviceError::Socket(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Requests to start a service on the device
     ///
     /// # Arguments
@@ -206,6 +451,7 @@ impl LockdownClient {
     /// # Errors
     /// Returns `IdeviceError` if:
     /// - The service cannot be started
+    /// - The device is locked with a passcode (`IdeviceError::PasswordProtected`)
     /// - The response is malformed
     /// - The requested service doesn't exist
     pub async fn start_service(
@@ -241,6 +487,51 @@ impl LockdownClient {
         }
     }
 
+    /// Finds which of the [`Self::probed_services`] the device exposes
+    ///
+    /// Lockdown has no request listing its services, so each one is probed with
+    /// `StartService`. The device opens a port for every service that starts, which it closes
+    /// again when nothing connects. Only services with a client enabled are probed. Developer
+    /// services, like debugserver, only exist once the developer disk image is mounted. A
+    /// session must be started first.
+    ///
+    /// # Returns
+    /// The services that exist on the device, by name
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::PasswordProtected` if the device must be unlocked to start
+    /// services, or the error if the connection fails. Services failing for other reasons
+    /// are left out.
+    pub async fn query_services(&mut self) -> Result<HashMap<String, ServiceInfo>, IdeviceError> {
+        let mut services = HashMap::new();
+        for name in Self::probed_services() {
+            let (ssl, requires_escrow) = match self.start_service(&*name).await {
+                Ok((_, ssl)) => (ssl, false),
+                // Needs the escrow bag from the pairing record while the device is locked
+                Err(IdeviceError::UnknownErrorType(e)) if e.starts_with("EscrowLocked") => {
+                    (false, true)
+                }
+                Err(e) if e.is_connection_error() => return Err(e),
+                Err(IdeviceError::PasswordProtected) => {
+                    return Err(IdeviceError::PasswordProtected);
+                }
+                Err(e) => {
+                    debug!("{name} is unavailable: {e}");
+                    continue;
+                }
+            };
+            services.insert(
+                name.to_string(),
+                ServiceInfo {
+                    name: name.to_string(),
+                    ssl,
+                    requires_escrow,
+                },
+            );
+        }
+        Ok(services)
+    }
+
     /// Generates a pairing file and sends it to the device for trusting.
     /// Note that this does NOT save the file to usbmuxd's cache. That's a responsibility of the
     /// caller.
@@ -326,6 +617,172 @@ impl LockdownClient {
             }
         }
     }
+
+    /// Checks that the device still trusts a pairing record
+    ///
+    /// # Arguments
+    /// * `pairing_file` - The pairing record to validate
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::InvalidHostID` if the device doesn't recognize the record
+    pub async fn validate_pair(
+        &mut self,
+        pairing_file: &pairing_file::PairingFile,
+    ) -> Result<(), IdeviceError> {
+        let req = crate::plist!({
+            "Label": self.idevice.label.clone(),
+            "Request": "ValidatePair",
+            "PairRecord": public_pair_record(pairing_file)?,
+            "ProtocolVersion": "2",
+        });
+        self.idevice.send_plist(req).await?;
+        self.idevice.read_plist().await?;
+        Ok(())
+    }
+
+    /// Removes a pairing record from the device, so the host must pair again to connect
+    ///
+    /// Note that this does NOT remove the record from usbmuxd's cache.
+    ///
+    /// # Arguments
+    /// * `pairing_file` - The pairing record to remove
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::InvalidHostID` if the device doesn't recognize the record
+    pub async fn unpair(
+        &mut self,
+        pairing_file: &pairing_file::PairingFile,
+    ) -> Result<(), IdeviceError> {
+        let req = crate::plist!({
+            "Label": self.idevice.label.clone(),
+            "Request": "Unpair",
+            "PairRecord": public_pair_record(pairing_file)?,
+            "ProtocolVersion": "2",
+        });
+        self.idevice.send_plist(req).await?;
+        self.idevice.read_plist().await?;
+        Ok(())
+    }
+}
+
+/// A service found by [`LockdownClient::query_services`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceInfo {
+    /// The service name, such as `com.apple.afc`
+    pub name: String,
+    /// Whether the connection to the service must be wrapped in TLS
+    ///
+    /// Only known if the service started, so false when `requires_escrow` is set.
+    pub ssl: bool,
+    /// Whether the device refused to start the service until it's unlocked, or the escrow bag
+    /// is sent along
+    pub requires_escrow: bool,
+}
+
+/// Commonly needed device information, read from the global lockdown domain
+///
+/// Fields the device didn't report are empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// The user-assigned name, such as `John's iPhone`
+    pub device_name: String,
+    /// The model identifier, such as `iPhone15,2`
+    pub product_type: String,
+    /// The iOS version, such as `17.4.1`
+    pub product_version: String,
+    /// The OS build, such as `21E236`
+    pub build_version: String,
+    /// The serial number
+    pub serial_number: String,
+    /// The board identifier, such as `D73AP`
+    pub hardware_model: String,
+    /// The CPU architecture, such as `arm64e`
+    pub cpu_architecture: String,
+    /// The UDID
+    pub unique_device_id: String,
+}
+
+/// The device's clock and time zone, as returned by [`LockdownClient::time_info`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceTime {
+    /// The time zone, such as `Europe/Berlin`
+    pub time_zone: Option<String>,
+    /// The time zone's current offset from UTC, in seconds
+    pub utc_offset: Option<f64>,
+    /// Whether the device shows times with a 24-hour clock
+    pub uses_24_hour_clock: Option<bool>,
+    /// The time on the device's clock
+    pub device_time: SystemTime,
+    /// The time on the host's clock when the device's time was received
+    pub host_time: SystemTime,
+}
+
+impl DeviceTime {
+    /// Picks the time values out of the values returned by [`LockdownClient::get_all_values`]
+    ///
+    /// # Arguments
+    /// * `values` - The values of the global domain
+    /// * `host_time` - When the values were received
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::MissingKey` if the device didn't report its time
+    pub fn from_values(
+        values: &plist::Dictionary,
+        host_time: SystemTime,
+    ) -> Result<Self, IdeviceError> {
+        let since_epoch = values
+            .get("TimeIntervalSince1970")
+            .and_then(|v| v.as_real())
+            .and_then(|v| Duration::try_from_secs_f64(v).ok())
+            .ok_or_else(|| IdeviceError::MissingKey {
+                key: "TimeIntervalSince1970".to_string(),
+            })?;
+        Ok(Self {
+            time_zone: values
+                .get("TimeZone")
+                .and_then(|v| v.as_string())
+                .map(str::to_string),
+            utc_offset: values
+                .get("TimeZoneOffsetFromUTC")
+                .and_then(|v| v.as_real()),
+            uses_24_hour_clock: values.get("Uses24HourClock").and_then(|v| v.as_boolean()),
+            device_time: SystemTime::UNIX_EPOCH + since_epoch,
+            host_time,
+        })
+    }
+
+    /// How far the device's clock is ahead of the host's, in seconds
+    ///
+    /// Negative if the device is behind. Includes the time the values took to arrive.
+    pub fn clock_offset(&self) -> f64 {
+        match self.device_time.duration_since(self.host_time) {
+            Ok(ahead) => ahead.as_secs_f64(),
+            Err(e) => -e.duration().as_secs_f64(),
+        }
+    }
+}
+
+impl DeviceInfo {
+    /// Picks the known fields out of the values returned by [`LockdownClient::get_all_values`]
+    pub fn from_values(values: &plist::Dictionary) -> Self {
+        let get = |key| {
+            values
+                .get(key)
+                .and_then(|v| v.as_string())
+                .unwrap_or_default()
+                .to_string()
+        };
+        Self {
+            device_name: get("DeviceName"),
+            product_type: get("ProductType"),
+            product_version: get("ProductVersion"),
+            build_version: get("BuildVersion"),
+            serial_number: get("SerialNumber"),
+            hardware_model: get("HardwareModel"),
+            cpu_architecture: get("CPUArchitecture"),
+            unique_device_id: get("UniqueDeviceID"),
+        }
+    }
 }
 
 impl From<Idevice> for LockdownClient {
@@ -334,3 +791,260 @@ impl From<Idevice> for LockdownClient {
         Self::new(value)
     }
 }
+
+/// Builds the parts of a pairing record that are sent to the device, leaving out the private keys
+fn public_pair_record(
+    pairing_file: &pairing_file::PairingFile,
+) -> Result<plist::Dictionary, IdeviceError> {
+    let mut record: plist::Dictionary = plist::from_bytes(&pairing_file.clone().serialize()?)?;
+    record.retain(|k, _| {
+        matches!(
+            k.as_str(),
+            "DeviceCertificate" | "HostCertificate" | "RootCertificate" | "HostID" | "SystemBUID"
+        )
+    });
+    Ok(record)
+}
+
+/// A lockdown client with an active session, stopped when dropped
+///
+/// Created by [`LockdownClient::into_session`].
+#[derive(Debug)]
+pub struct LockdownSession {
+    /// Only taken by `stop` and `drop`
+    client: Option<LockdownClient>,
+    ssl_enabled: bool,
+}
+
+impl LockdownSession {
+    /// Whether the device enabled SSL for the session
+    pub fn ssl_enabled(&self) -> bool {
+        self.ssl_enabled
+    }
+
+    /// Stops the session, returning the client
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if the device doesn't acknowledge stopping the session
+    pub async fn stop(mut self) -> Result<LockdownClient, IdeviceError> {
+        let mut client = self
+            .client
+            .take()
+            .expect("client is only taken on stop or drop");
+        if client.session_id.is_some() {
+            client.stop_session().await?;
+        }
+        Ok(client)
+    }
+}
+
+impl std::ops::Deref for LockdownSession {
+    type Target = LockdownClient;
+
+    fn deref(&self) -> &LockdownClient {
+        self.client
+            .as_ref()
+            .expect("client is only taken on stop or drop")
+    }
+}
+
+impl std::ops::DerefMut for LockdownSession {
+    fn deref_mut(&mut self) -> &mut LockdownClient {
+        self.client
+            .as_mut()
+            .expect("client is only taken on stop or drop")
+    }
+}
+
+impl Drop for LockdownSession {
+    fn drop(&mut self) {
+        let Some(mut client) = self.client.take() else {
+            return;
+        };
+        if client.session_id.is_none() {
+            return;
+        }
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    if let Err(e) = client.stop_session().await {
+                        debug!("Failed to stop dropped lockdown session: {e:?}");
+                    }
+                });
+            }
+            Err(_) => debug!("Dropped lockdown session outside a runtime, not stopping it"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_info_from_values() {
+        let values = crate::plist!(dict {
+            "ProductType": "iPhone15,2",
+            "ProductVersion": "17.4.1",
+            "CPUArchitecture": "arm64e",
+            "BuildVersion": 21,
+        });
+        let info = DeviceInfo::from_values(&values);
+        assert_eq!(info.product_type, "iPhone15,2");
+        assert_eq!(info.cpu_architecture, "arm64e");
+        assert_eq!(info.build_version, "");
+        assert_eq!(info.serial_number, "");
+    }
+
+    #[test]
+    fn device_time_from_values() {
+        let values = crate::plist!(dict {
+            "TimeIntervalSince1970": 1_700_000_010.5,
+            "TimeZone": "Europe/Berlin",
+            "TimeZoneOffsetFromUTC": 3600.0,
+        });
+        let host_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let time = DeviceTime::from_values(&values, host_time).unwrap();
+        assert_eq!(time.time_zone.as_deref(), Some("Europe/Berlin"));
+        assert_eq!(time.utc_offset, Some(3600.0));
+        assert_eq!(time.uses_24_hour_clock, None);
+        assert_eq!(time.clock_offset(), 10.5);
+
+        let behind = DeviceTime::from_values(&values, host_time + Duration::from_secs(20));
+        assert_eq!(behind.unwrap().clock_offset(), -9.5);
+        assert!(DeviceTime::from_values(&plist::Dictionary::new(), host_time).is_err());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn locked_device_refuses_service() {
+        use crate::testing::{MockProvider, MockScript};
+
+        let provider = MockProvider::new().with_port(
+            LockdownClient::LOCKDOWND_PORT,
+            MockScript::new()
+                .expect(crate::plist!({
+                    "Request": "StartService",
+                    "Service": "com.apple.afc",
+                }))
+                .respond(crate::plist!({
+                    "Request": "StartService",
+                    "Error": "PasswordProtected",
+                })),
+        );
+
+        let mut client = LockdownClient::connect(&provider).await.unwrap();
+        assert!(matches!(
+            client.start_service("com.apple.afc").await,
+            Err(IdeviceError::PasswordProtected)
+        ));
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[cfg(all(
+        feature = "testing",
+        feature = "afc",
+        feature = "amfi",
+        feature = "installation_proxy"
+    ))]
+    #[tokio::test]
+    async fn query_services_skips_missing_services() {
+        use crate::testing::{MockProvider, MockScript};
+
+        let names = LockdownClient::probed_services();
+        let mut script = MockScript::new();
+        for (i, name) in names.iter().enumerate() {
+            script = script
+                .expect(crate::plist!({
+                    "Request": "StartService",
+                    "Service": name.to_string(),
+                }))
+                .respond(match i {
+                    0 => crate::plist!({ "Port": 50000, "EnableServiceSSL": true }),
+                    1 => crate::plist!({ "Error": "EscrowLocked" }),
+                    2 => crate::plist!({ "Port": 50001 }),
+                    _ => crate::plist!({ "Error": "InvalidService" }),
+                });
+        }
+        let provider = MockProvider::new().with_port(LockdownClient::LOCKDOWND_PORT, script);
+
+        let mut client = LockdownClient::connect(&provider).await.unwrap();
+        let services = client.query_services().await.unwrap();
+        drop(client);
+        assert!(provider.verify().await.is_empty());
+
+        assert_eq!(services.len(), 3);
+        let first = &services[&*names[0]];
+        assert!(first.ssl && !first.requires_escrow);
+        assert!(services[&*names[1]].requires_escrow);
+        assert!(!services[&*names[2]].ssl);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn stop_session_ends_active_session() {
+        use crate::testing::{MockProvider, MockScript};
+
+        let provider = MockProvider::new().with_port(
+            LockdownClient::LOCKDOWND_PORT,
+            MockScript::new()
+                .expect(crate::plist!({
+                    "Request": "StopSession",
+                    "SessionID": "session",
+                }))
+                .respond(crate::plist!({ "Request": "StopSession" })),
+        );
+
+        let mut client = LockdownClient::connect(&provider).await.unwrap();
+        client.session_id = Some("session".to_string());
+        client.stop_session().await.unwrap();
+        assert!(matches!(
+            client.stop_session().await,
+            Err(IdeviceError::SessionInactive)
+        ));
+
+        drop(client);
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[cfg(all(feature = "testing", feature = "rustls"))]
+    #[tokio::test]
+    async fn dropping_session_guard_stops_session() {
+        use crate::testing::{MockProvider, MockScript};
+
+        let provider = MockProvider::new().with_port(
+            LockdownClient::LOCKDOWND_PORT,
+            MockScript::new()
+                .expect(crate::plist!({ "Request": "GetValue", "Key": "ProductVersion" }))
+                .respond(crate::plist!({ "Value": "17.4" }))
+                .expect(crate::plist!({ "Request": "StartSession", "HostID": "host" }))
+                .respond(crate::plist!({ "EnableSessionSSL": false, "SessionID": "session" }))
+                .expect(crate::plist!({
+                    "Request": "StopSession",
+                    "SessionID": "session",
+                }))
+                .respond(crate::plist!({ "Request": "StopSession" })),
+        );
+        let pairing_file = pairing_file::PairingFile {
+            device_certificate: Vec::new().into(),
+            host_private_key: Vec::new(),
+            host_certificate: Vec::new().into(),
+            root_private_key: Vec::new(),
+            root_certificate: Vec::new().into(),
+            system_buid: "buid".into(),
+            host_id: "host".into(),
+            escrow_bag: None,
+            wifi_mac_address: String::new(),
+            udid: None,
+        };
+
+        let client = LockdownClient::connect(&provider).await.unwrap();
+        let session = client.into_session(&pairing_file).await.unwrap();
+        assert!(!session.ssl_enabled());
+        drop(session);
+
+        assert!(provider.verify().await.is_empty());
+    }
+}
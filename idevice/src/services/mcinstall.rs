@@ -0,0 +1,215 @@
+//! iOS Configuration Profile Service Client
+//!
+//! Provides functionality for listing and removing the configuration profiles installed on
+//! the device, such as MDM enrollment and enterprise profiles, through the MCInstall service.
+//! Provisioning profiles are managed by misagent instead.
+
+use tracing::warn;
+
+use crate::{Idevice, IdeviceError, IdeviceService, PlistExt, obf};
+
+/// Client for interacting with the iOS MCInstall service
+#[derive(Debug)]
+pub struct McInstallClient {
+    /// The underlying device connection with established MCInstall service
+    pub idevice: Idevice,
+}
+
+/// A configuration profile installed on the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProfile {
+    /// The profile's `PayloadIdentifier`, used to remove it
+    pub identifier: String,
+    /// The name shown in Settings
+    pub display_name: Option<String>,
+    /// The profile's description
+    pub description: Option<String>,
+    /// The organization that issued the profile
+    pub organization: Option<String>,
+    /// The profile's `PayloadUUID`
+    pub uuid: Option<String>,
+    /// The profile's `PayloadVersion`
+    pub version: Option<u64>,
+    /// Whether the profile forbids removing it
+    pub removal_disallowed: bool,
+}
+
+impl IdeviceService for McInstallClient {
+    /// Returns the MCInstall service name as registered with lockdownd
+    fn service_name() -> std::borrow::Cow<'static, str> {
+        obf!("com.apple.mobile.MCInstall")
+    }
+
+    async fn from_stream(idevice: Idevice) -> Result<Self, crate::IdeviceError> {
+        Ok(Self::new(idevice))
+    }
+}
+
+impl McInstallClient {
+    /// Creates a new MCInstall client from an existing device connection
+    ///
+    /// # Arguments
+    /// * `idevice` - Pre-established device connection
+    pub fn new(idevice: Idevice) -> Self {
+        Self { idevice }
+    }
+
+    /// Lists the configuration profiles installed on the device
+    ///
+    /// The device only reports each profile's metadata, not the payloads it contains.
+    ///
+    /// # Returns
+    /// The profiles in the order the device lists them
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::McInstallFailed` if the device refuses the request
+    pub async fn get_profile_list(&mut self) -> Result<Vec<ConfigProfile>, IdeviceError> {
+        let res = self
+            .request(crate::plist!({ "RequestType": "GetProfileList" }))
+            .await?;
+
+        let metadata = res.get_dict("ProfileMetadata")?;
+        let identifiers = match res.get("OrderedIdentifiers").and_then(|i| i.as_array()) {
+            Some(i) => i.iter().filter_map(|i| i.as_string()).collect(),
+            None => metadata.keys().map(String::as_str).collect::<Vec<_>>(),
+        };
+
+        Ok(identifiers
+            .into_iter()
+            .map(|identifier| {
+                let meta = metadata.get(identifier).and_then(|m| m.as_dictionary());
+                let string = |key| {
+                    meta.and_then(|m| m.get(key))
+                        .and_then(|v| v.as_string())
+                        .map(str::to_string)
+                };
+                ConfigProfile {
+                    identifier: identifier.to_string(),
+                    display_name: string("PayloadDisplayName"),
+                    description: string("PayloadDescription"),
+                    organization: string("PayloadOrganization"),
+                    uuid: string("PayloadUUID"),
+                    version: meta
+                        .and_then(|m| m.get("PayloadVersion"))
+                        .and_then(|v| v.as_unsigned_integer()),
+                    removal_disallowed: meta
+                        .and_then(|m| m.get("PayloadRemovalDisallowed"))
+                        .and_then(|v| v.as_boolean())
+                        .unwrap_or(false),
+                }
+            })
+            .collect())
+    }
+
+    /// Removes a configuration profile from the device
+    ///
+    /// # Arguments
+    /// * `identifier` - The profile's identifier, as listed by [`Self::get_profile_list`]
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::MissingKey` if no installed profile has that identifier, and
+    /// `IdeviceError::McInstallFailed` if the device refuses to remove it
+    pub async fn remove_profile(&mut self, identifier: &str) -> Result<(), IdeviceError> {
+        let profile = self
+            .get_profile_list()
+            .await?
+            .into_iter()
+            .find(|p| p.identifier == identifier)
+            .ok_or_else(|| IdeviceError::MissingKey {
+                key: identifier.to_string(),
+            })?;
+
+        // The profile is identified by a serialized plist rather than by its identifier alone
+        let mut data = Vec::new();
+        crate::plist!({
+            "PayloadType": "Configuration",
+            "PayloadIdentifier": identifier,
+            "PayloadUUID":? profile.uuid,
+            "PayloadVersion":? profile.version,
+        })
+        .to_writer_xml(&mut data)?;
+
+        self.request(crate::plist!({
+            "RequestType": "RemoveProfile",
+            "ProfileIdentifier": data,
+        }))
+        .await?;
+        Ok(())
+    }
+
+    /// Sends a request, returning the response if the device acknowledged it
+    async fn request(&mut self, req: plist::Value) -> Result<plist::Dictionary, IdeviceError> {
+        self.idevice.send_plist(req).await?;
+        let res = self.idevice.read_plist().await?;
+
+        match res.get("Status").and_then(|s| s.as_string()) {
+            Some("Acknowledged") => Ok(res),
+            status => {
+                let reason = res
+                    .get("ErrorChain")
+                    .and_then(|c| c.as_array())
+                    .and_then(|c| c.first())
+                    .and_then(|e| e.as_dictionary())
+                    .and_then(|e| e.get("LocalizedDescription"))
+                    .and_then(|d| d.as_string())
+                    .or(status)
+                    .unwrap_or("no status")
+                    .to_string();
+                warn!("MCInstall request failed: {reason}");
+                Err(IdeviceError::McInstallFailed(reason))
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::{MockProvider, MockScript};
+
+    #[tokio::test]
+    async fn removes_listed_profile() {
+        let mut identifier = Vec::new();
+        crate::plist!({
+            "PayloadType": "Configuration",
+            "PayloadIdentifier": "com.example.wifi",
+            "PayloadUUID": "1234",
+            "PayloadVersion": 1,
+        })
+        .to_writer_xml(&mut identifier)
+        .unwrap();
+        let provider = MockProvider::new().with_service(
+            McInstallClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({ "RequestType": "GetProfileList" }))
+                .respond(crate::plist!({
+                    "Status": "Acknowledged",
+                    "OrderedIdentifiers": ["com.example.wifi"],
+                    "ProfileMetadata": {
+                        "com.example.wifi": {
+                            "PayloadDisplayName": "Office Wi-Fi",
+                            "PayloadUUID": "1234",
+                            "PayloadVersion": 1,
+                        },
+                    },
+                }))
+                .expect(crate::plist!({
+                    "RequestType": "RemoveProfile",
+                    "ProfileIdentifier": identifier,
+                }))
+                .respond(crate::plist!({
+                    "Status": "Error",
+                    "ErrorChain": [{ "LocalizedDescription": "Removal is not allowed" }],
+                })),
+        );
+
+        let mut client = McInstallClient::connect(&provider).await.unwrap();
+        assert!(matches!(
+            client.remove_profile("com.example.wifi").await,
+            Err(IdeviceError::McInstallFailed(reason)) if reason == "Removal is not allowed"
+        ));
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+}
@@ -9,7 +9,7 @@
 
 use tracing::debug;
 
-use crate::{Idevice, IdeviceError, IdeviceService, obf};
+use crate::{Idevice, IdeviceError, IdeviceService, PlistExt, obf};
 use sha2::{Digest, Sha384};
 
 #[cfg(feature = "tss")]
@@ -28,6 +28,20 @@ pub struct ImageMounter {
     idevice: Idevice,
 }
 
+/// Alias for [`ImageMounter`]
+pub type ImageMounterClient = ImageMounter;
+
+/// An image currently mounted on the device
+#[derive(Debug, Clone)]
+pub struct MountedImage {
+    /// The type of the image, such as `Developer` or `Personalized`
+    pub image_type: String,
+    /// The signature the image was mounted with
+    pub signature: Vec<u8>,
+    /// Where the image is mounted on the device
+    pub mount_path: String,
+}
+
 impl IdeviceService for ImageMounter {
     /// Returns the image mounter service name as registered with lockdownd
     fn service_name() -> std::borrow::Cow<'static, str> {
@@ -48,6 +62,11 @@ impl ImageMounter {
         Self { idevice }
     }
 
+    /// Consumes the client and returns the underlying device connection
+    pub fn into_inner(self) -> Idevice {
+        self.idevice
+    }
+
     /// Retrieves a list of currently mounted devices
     ///
     /// # Returns
@@ -68,6 +87,41 @@ impl ImageMounter {
         }
     }
 
+    /// Retrieves the images currently mounted on the device
+    ///
+    /// # Returns
+    /// The type, signature, and mount path of each mounted image
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails or response is malformed
+    pub async fn query_mounted(&mut self) -> Result<Vec<MountedImage>, IdeviceError> {
+        Ok(self
+            .copy_devices()
+            .await?
+            .into_iter()
+            .filter_map(|entry| {
+                let mut entry = entry.into_dictionary()?;
+                if entry.get("IsMounted").and_then(|m| m.as_boolean()) == Some(false) {
+                    return None;
+                }
+                Some(MountedImage {
+                    image_type: entry
+                        .remove("DiskImageType")
+                        .and_then(|t| t.into_string())
+                        .unwrap_or_default(),
+                    signature: entry
+                        .remove("ImageSignature")
+                        .and_then(|s| s.into_data())
+                        .unwrap_or_default(),
+                    mount_path: entry
+                        .remove("MountPath")
+                        .and_then(|p| p.into_string())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect())
+    }
+
     /// Looks up an image by type and returns its signature
     ///
     /// # Arguments
@@ -77,23 +131,72 @@ impl ImageMounter {
     /// The image signature if found
     ///
     /// # Errors
-    /// Returns `IdeviceError::NotFound` if image doesn't exist
+    /// Returns `IdeviceError::ImageNotMounted` if no image of that type is mounted, or
+    /// `IdeviceError::NotFound` if the device doesn't report a signature
     pub async fn lookup_image(
         &mut self,
         image_type: impl Into<&str>,
     ) -> Result<Vec<u8>, IdeviceError> {
-        let image_type = image_type.into();
+        self.lookup_signature(image_type.into())
+            .await?
+            .ok_or(IdeviceError::NotFound)
+    }
+
+    /// Returns the signature of the mounted image of a type, if one is mounted
+    ///
+    /// Comparing this with the signature of a locally cached image tells whether mounting
+    /// it again can be skipped.
+    ///
+    /// # Arguments
+    /// * `image_type` - The type of image to lookup (e.g., "Developer")
+    ///
+    /// # Returns
+    /// The signature of the mounted image, or `None` if no image of that type is mounted
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails
+    pub async fn mounted_signature(
+        &mut self,
+        image_type: &str,
+    ) -> Result<Option<Vec<u8>>, IdeviceError> {
+        match self.lookup_signature(image_type).await {
+            Err(IdeviceError::ImageNotMounted) => Ok(None),
+            res => res,
+        }
+    }
+
+    /// Sends `LookupImage`, failing with `IdeviceError::ImageNotMounted` if the device says
+    /// nothing of that type is mounted
+    async fn lookup_signature(
+        &mut self,
+        image_type: &str,
+    ) -> Result<Option<Vec<u8>>, IdeviceError> {
         let req = crate::plist!({
             "Command": "LookupImage",
             "ImageType": image_type
         });
         self.idevice.send_plist(req).await?;
 
-        let res = self.idevice.read_plist().await?;
-        match res.get("ImageSignature") {
-            Some(plist::Value::Data(signature)) => Ok(signature.clone()),
-            _ => Err(IdeviceError::NotFound),
-        }
+        let mut res = self.idevice.read_plist().await?;
+        // Newer versions send an array of signatures, one per mounted image
+        Ok(match res.remove("ImageSignature") {
+            Some(plist::Value::Data(signature)) => Some(signature),
+            Some(plist::Value::Array(signatures)) => {
+                signatures.into_iter().next().and_then(|s| s.into_data())
+            }
+            _ => None,
+        })
+    }
+
+    /// Checks whether an image of a type is mounted
+    ///
+    /// # Arguments
+    /// * `image_type` - The type of image to check (e.g., "Developer")
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails
+    pub async fn is_mounted(&mut self, image_type: &str) -> Result<bool, IdeviceError> {
+        Ok(self.mounted_signature(image_type).await?.is_some())
     }
 
     /// Uploads an image to the device
@@ -309,10 +412,7 @@ impl ImageMounter {
         self.idevice.send_plist(req).await?;
 
         let res = self.idevice.read_plist().await?;
-        match res.get("DeveloperModeStatus") {
-            Some(plist::Value::Boolean(status)) => Ok(*status),
-            _ => Err(IdeviceError::UnexpectedResponse),
-        }
+        res.get_bool("DeveloperModeStatus")
     }
 
     /// Queries the nonce value from the device
@@ -336,10 +436,7 @@ impl ImageMounter {
         self.idevice.send_plist(req).await?;
 
         let res = self.idevice.read_plist().await?;
-        match res.get("PersonalizationNonce") {
-            Some(plist::Value::Data(nonce)) => Ok(nonce.clone()),
-            _ => Err(IdeviceError::UnexpectedResponse),
-        }
+        Ok(res.get_data("PersonalizationNonce")?.to_vec())
     }
 
     /// Queries personalization identifiers from the device
@@ -363,10 +460,7 @@ impl ImageMounter {
         self.idevice.send_plist(req).await?;
 
         let res = self.idevice.read_plist().await?;
-        match res.get("PersonalizationIdentifiers") {
-            Some(plist::Value::Dictionary(identifiers)) => Ok(identifiers.clone()),
-            _ => Err(IdeviceError::UnexpectedResponse),
-        }
+        Ok(res.get_dict("PersonalizationIdentifiers")?.clone())
     }
 
     /// Rolls the personalization nonce on the device
@@ -450,6 +544,47 @@ impl ImageMounter {
         .await
     }
 
+    #[cfg(feature = "tss")]
+    /// Mounts a personalized image, looking up the device's unique chip ID through lockdown
+    ///
+    /// # Arguments
+    /// * `provider` - Device connection provider
+    /// * `image` - The image data
+    /// * `trust_cache` - Trust cache data
+    /// * `build_manifest` - Build manifest data
+    /// * `info_plist` - Optional info plist for the image
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if the chip ID can't be read or mounting fails
+    pub async fn mount_personalized_for_device(
+        &mut self,
+        provider: &dyn crate::provider::IdeviceProvider,
+        image: Vec<u8>,
+        trust_cache: Vec<u8>,
+        build_manifest: &[u8],
+        info_plist: Option<plist::Value>,
+    ) -> Result<(), IdeviceError> {
+        let mut lockdown = crate::lockdown::LockdownClient::connect(provider).await?;
+        lockdown
+            .start_session(&provider.get_pairing_file().await?)
+            .await?;
+        let unique_chip_id = lockdown
+            .get_value(Some("UniqueChipID"), None)
+            .await?
+            .as_unsigned_integer()
+            .ok_or(IdeviceError::UnexpectedResponse)?;
+
+        self.mount_personalized(
+            provider,
+            image,
+            trust_cache,
+            build_manifest,
+            info_plist,
+            unique_chip_id,
+        )
+        .await
+    }
+
     #[cfg(feature = "tss")]
     /// Mounts a personalized image with progress callbacks
     ///
@@ -722,3 +857,169 @@ impl ImageMounter {
         }
     }
 }
+
+/// File name of a developer disk image in an image directory
+const DEVELOPER_IMAGE_NAME: &str = "DeveloperDiskImage.dmg";
+
+/// File name of a developer disk image's signature in an image directory
+const DEVELOPER_IMAGE_SIGNATURE_NAME: &str = "DeveloperDiskImage.dmg.signature";
+
+/// Finds the developer disk image for an iOS version in a local image repository
+///
+/// The repository holds one directory per version, named like Xcode's DeviceSupport
+/// directories, `16.4 (20E247)` or `16.4`, each containing `DeveloperDiskImage.dmg` and
+/// `DeveloperDiskImage.dmg.signature`. Directories are tried in the same order Xcode uses:
+/// - the exact version and build
+/// - the exact version
+/// - the major and minor version, so `16.4.1` uses `16.4`
+/// - the newest earlier version with the same major version
+///
+/// Only devices before iOS 17 use developer disk images, later ones use personalized images.
+///
+/// # Arguments
+/// * `versions_dir` - The repository directory
+/// * `product_version` - The device's `ProductVersion`, such as `16.4.1`
+/// * `build` - The device's `BuildVersion`, such as `20E252`
+///
+/// # Returns
+/// The paths of the image and its signature, or `None` if no directory matches
+pub fn find_image(
+    versions_dir: &std::path::Path,
+    product_version: &str,
+    build: &str,
+) -> Option<(std::path::PathBuf, std::path::PathBuf)> {
+    let image_in = |dir: std::path::PathBuf| {
+        let dmg = dir.join(DEVELOPER_IMAGE_NAME);
+        let sig = dir.join(DEVELOPER_IMAGE_SIGNATURE_NAME);
+        (dmg.is_file() && sig.is_file()).then_some((dmg, sig))
+    };
+
+    let wanted = parse_version(product_version)?;
+    let major_minor = wanted
+        .iter()
+        .take(2)
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(".");
+    let candidates = [
+        format!("{product_version} ({build})"),
+        product_version.to_string(),
+        major_minor,
+    ];
+    if let Some(found) = candidates
+        .into_iter()
+        .find_map(|name| image_in(versions_dir.join(name)))
+    {
+        return Some(found);
+    }
+
+    let mut earlier = std::fs::read_dir(versions_dir)
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let version = parse_version(name.to_str()?.split(' ').next()?)?;
+            (version[0] == wanted[0] && version <= wanted).then_some((version, entry.path()))
+        })
+        .collect::<Vec<_>>();
+    earlier.sort();
+    earlier.into_iter().rev().find_map(|(_, dir)| image_in(dir))
+}
+
+/// Parses a dotted version such as `16.4.1`
+fn parse_version(version: &str) -> Option<Vec<u32>> {
+    version.split('.').map(|p| p.parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "testing")]
+    use crate::testing::{MockProvider, MockScript};
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn reads_mounted_signature() {
+        let provider = MockProvider::new().with_service(
+            ImageMounterClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({
+                    "Command": "LookupImage",
+                    "ImageType": "Developer",
+                }))
+                .respond(crate::plist!({
+                    "ImageSignature": [plist::Value::Data(vec![1, 2, 3])]
+                }))
+                .expect(crate::plist!({
+                    "Command": "LookupImage",
+                    "ImageType": "Personalized",
+                }))
+                .respond(crate::plist!({ "ImageSignature": [] })),
+        );
+
+        let mut client = ImageMounterClient::connect(&provider).await.unwrap();
+        assert_eq!(
+            client.mounted_signature("Developer").await.unwrap(),
+            Some(vec![1, 2, 3])
+        );
+        assert!(!client.is_mounted("Personalized").await.unwrap());
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn lookup_image_reports_unmounted_image() {
+        let not_mounted = crate::plist!({
+            "Error": "InternalError",
+            "DetailedError": "There is no matching entry in the device map for Developer",
+        });
+        let lookup = crate::plist!({
+            "Command": "LookupImage",
+            "ImageType": "Developer",
+        });
+        let provider = MockProvider::new().with_service(
+            ImageMounterClient::service_name(),
+            MockScript::new()
+                .expect(lookup.clone())
+                .respond(not_mounted.clone())
+                .expect(lookup)
+                .respond(not_mounted),
+        );
+
+        let mut client = ImageMounterClient::connect(&provider).await.unwrap();
+        assert!(matches!(
+            client.lookup_image("Developer").await,
+            Err(IdeviceError::ImageNotMounted)
+        ));
+        assert!(!client.is_mounted("Developer").await.unwrap());
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[test]
+    fn finds_image_with_version_fallback() {
+        let root = std::env::temp_dir().join(format!("idevice-ddi-{}", std::process::id()));
+        for dir in ["15.7", "16.2", "16.4 (20E247)"] {
+            let dir = root.join(dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(DEVELOPER_IMAGE_NAME), b"dmg").unwrap();
+            std::fs::write(dir.join(DEVELOPER_IMAGE_SIGNATURE_NAME), b"sig").unwrap();
+        }
+        let found = |version, build| {
+            find_image(&root, version, build).map(|(dmg, _)| {
+                let dir = dmg.parent().unwrap().file_name().unwrap();
+                dir.to_string_lossy().into_owned()
+            })
+        };
+
+        assert_eq!(found("16.4", "20E247").as_deref(), Some("16.4 (20E247)"));
+        assert_eq!(found("16.3.1", "20D67").as_deref(), Some("16.2"));
+        assert_eq!(found("16.1", "20B82"), None);
+        assert_eq!(found("15.8", "19H370").as_deref(), Some("15.7"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
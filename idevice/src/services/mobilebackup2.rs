@@ -7,7 +7,6 @@ use plist::Dictionary;
 use std::io::{Read, Write};
 use std::path::Path;
 use tokio::fs;
-use tokio::io::AsyncReadExt;
 use tracing::{debug, warn};
 
 use crate::{Idevice, IdeviceError, IdeviceService, obf};
@@ -248,23 +247,17 @@ impl MobileBackup2Client {
 
     /// Receives any DL* message and returns (message_tag, full_array_value)
     pub async fn receive_dl_message(&mut self) -> Result<(String, plist::Value), IdeviceError> {
-        if let Some(socket) = &mut self.idevice.socket {
-            let mut buf = [0u8; 4];
-            socket.read_exact(&mut buf).await?;
-            let len = u32::from_be_bytes(buf);
-            let mut body = vec![0; len as usize];
-            socket.read_exact(&mut body).await?;
-            let value: plist::Value = plist::from_bytes(&body)?;
-            if let plist::Value::Array(arr) = &value
-                && let Some(plist::Value::String(tag)) = arr.first()
-            {
-                return Ok((tag.clone(), value));
-            }
-            warn!("Invalid DL message format");
-            Err(IdeviceError::UnexpectedResponse)
-        } else {
-            Err(IdeviceError::NoEstablishedConnection)
+        let len = self.idevice.read_raw(4).await?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]);
+        let body = self.idevice.read_raw(len as usize).await?;
+        let value: plist::Value = plist::from_bytes(&body)?;
+        if let plist::Value::Array(arr) = &value
+            && let Some(plist::Value::String(tag)) = arr.first()
+        {
+            return Ok((tag.clone(), value));
         }
+        warn!("Invalid DL message format");
+        Err(IdeviceError::UnexpectedResponse)
     }
 
     /// Performs version exchange with the device
@@ -359,43 +352,36 @@ impl MobileBackup2Client {
         expected_message: &str,
     ) -> Result<Dictionary, IdeviceError> {
         // Read raw bytes and parse as plist::Value to handle array format
-        if let Some(socket) = &mut self.idevice.socket {
-            debug!("Reading response size");
-            let mut buf = [0u8; 4];
-            socket.read_exact(&mut buf).await?;
-            let len = u32::from_be_bytes(buf);
-            let mut buf = vec![0; len as usize];
-            socket.read_exact(&mut buf).await?;
-            let response_value: plist::Value = plist::from_bytes(&buf)?;
-
-            // Parse DLMessageProcessMessage format
-            if let plist::Value::Array(array) = response_value
-                && array.len() >= 2
-                && let Some(plist::Value::String(dl_message)) = array.first()
-                && let Some(plist::Value::Dictionary(dict)) = array.get(1)
-                && dl_message == "DLMessageProcessMessage"
-            {
-                // Check MessageName if expected
-                if !expected_message.is_empty() {
-                    if let Some(message_name) = dict.get("MessageName").and_then(|v| v.as_string())
-                    {
-                        if message_name != expected_message {
-                            warn!("Expected message '{expected_message}', got '{message_name}'");
-                            return Err(IdeviceError::UnexpectedResponse);
-                        }
-                    } else {
-                        warn!("No MessageName in response");
+        debug!("Reading response size");
+        let len = self.idevice.read_raw(4).await?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]);
+        let buf = self.idevice.read_raw(len as usize).await?;
+        let response_value: plist::Value = plist::from_bytes(&buf)?;
+
+        // Parse DLMessageProcessMessage format
+        if let plist::Value::Array(array) = response_value
+            && array.len() >= 2
+            && let Some(plist::Value::String(dl_message)) = array.first()
+            && let Some(plist::Value::Dictionary(dict)) = array.get(1)
+            && dl_message == "DLMessageProcessMessage"
+        {
+            // Check MessageName if expected
+            if !expected_message.is_empty() {
+                if let Some(message_name) = dict.get("MessageName").and_then(|v| v.as_string()) {
+                    if message_name != expected_message {
+                        warn!("Expected message '{expected_message}', got '{message_name}'");
                         return Err(IdeviceError::UnexpectedResponse);
                     }
+                } else {
+                    warn!("No MessageName in response");
+                    return Err(IdeviceError::UnexpectedResponse);
                 }
-                return Ok(dict.clone());
             }
-
-            warn!("Invalid device link message format");
-            Err(IdeviceError::UnexpectedResponse)
-        } else {
-            Err(IdeviceError::NoEstablishedConnection)
+            return Ok(dict.clone());
         }
+
+        warn!("Invalid device link message format");
+        Err(IdeviceError::UnexpectedResponse)
     }
 
     /// Sends a backup message to the device
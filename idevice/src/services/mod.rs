@@ -27,6 +27,8 @@ pub mod installation_proxy;
 #[cfg(feature = "installcoordination_proxy")]
 pub mod installcoordination_proxy;
 pub mod lockdown;
+#[cfg(feature = "mcinstall")]
+pub mod mcinstall;
 #[cfg(feature = "misagent")]
 pub mod misagent;
 #[cfg(feature = "mobile_image_mounter")]
@@ -35,14 +37,20 @@ pub mod mobile_image_mounter;
 pub mod mobileactivationd;
 #[cfg(feature = "mobilebackup2")]
 pub mod mobilebackup2;
+#[cfg(feature = "notification_proxy")]
+pub mod notification_proxy;
 #[cfg(feature = "syslog_relay")]
 pub mod os_trace_relay;
 #[cfg(feature = "pcapd")]
 pub mod pcapd;
+#[cfg(feature = "power_assertion")]
+pub mod power_assertion;
 #[cfg(feature = "preboard_service")]
 pub mod preboard_service;
 #[cfg(feature = "restore_service")]
 pub mod restore_service;
+#[cfg(feature = "restored")]
+pub mod restored;
 #[cfg(feature = "rsd")]
 pub mod rsd;
 #[cfg(feature = "screenshotr")]
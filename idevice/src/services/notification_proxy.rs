@@ -0,0 +1,186 @@
+//! Notification Proxy
+//!
+//! Posts and observes Darwin notifications on the device, such as
+//! `com.apple.mobile.application_installed`.
+
+use std::pin::Pin;
+
+use futures::Stream;
+use tracing::warn;
+
+use crate::{Idevice, IdeviceError, IdeviceService, obf};
+
+/// Client for interacting with the notification proxy service on the device
+#[derive(Debug)]
+pub struct NotificationProxyClient {
+    /// The underlying device connection with established notification proxy service
+    pub idevice: Idevice,
+}
+
+impl IdeviceService for NotificationProxyClient {
+    /// Returns the notification proxy service name as registered with lockdownd
+    fn service_name() -> std::borrow::Cow<'static, str> {
+        obf!("com.apple.mobile.notification_proxy")
+    }
+
+    async fn from_stream(idevice: Idevice) -> Result<Self, crate::IdeviceError> {
+        Ok(Self::new(idevice))
+    }
+}
+
+impl NotificationProxyClient {
+    /// Creates a new notification proxy client from an existing device connection
+    ///
+    /// # Arguments
+    /// * `idevice` - Pre-established device connection
+    pub fn new(idevice: Idevice) -> Self {
+        Self { idevice }
+    }
+
+    /// Posts a notification on the device
+    ///
+    /// # Arguments
+    /// * `name` - The notification to post
+    pub async fn post(&mut self, name: &str) -> Result<(), IdeviceError> {
+        let req = crate::plist!({
+            "Command": "PostNotification",
+            "Name": name,
+        });
+        self.idevice.send_plist(req).await
+    }
+
+    /// Starts observing notifications, and returns a stream of the observed notification names
+    ///
+    /// The stream ends when the device closes the proxy.
+    ///
+    /// # Arguments
+    /// * `names` - The notifications to observe
+    pub async fn observe(
+        &mut self,
+        names: &[&str],
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<String, IdeviceError>> + Send + '_>>, IdeviceError>
+    {
+        for name in names {
+            let req = crate::plist!({
+                "Command": "ObserveNotification",
+                "Name": *name,
+            });
+            self.idevice.send_plist(req).await?;
+        }
+
+        Ok(Box::pin(futures::stream::try_unfold(
+            self,
+            |client| async move {
+                let name = client.next_notification().await?;
+                Ok(name.map(|n| (n, client)))
+            },
+        )))
+    }
+
+    /// Waits for the next observed notification
+    ///
+    /// # Returns
+    /// The notification's name, or `None` if the device closed the proxy
+    pub async fn next_notification(&mut self) -> Result<Option<String>, IdeviceError> {
+        loop {
+            let mut res = self.idevice.read_plist().await?;
+            match res.get("Command").and_then(|x| x.as_string()) {
+                Some("RelayNotification") => match res.remove("Name") {
+                    Some(plist::Value::String(name)) => return Ok(Some(name)),
+                    _ => return Err(IdeviceError::UnexpectedResponse),
+                },
+                Some("ProxyDeath") => return Ok(None),
+                _ => warn!("Unexpected notification proxy message: {res:?}"),
+            }
+        }
+    }
+
+    /// Asks the device to close the proxy
+    pub async fn shutdown(&mut self) -> Result<(), IdeviceError> {
+        let req = crate::plist!({
+            "Command": "Shutdown",
+        });
+        self.idevice.send_plist(req).await?;
+
+        let res = self.idevice.read_plist().await?;
+        match res.get("Command").and_then(|x| x.as_string()) {
+            Some("ProxyDeath") => Ok(()),
+            _ => Err(IdeviceError::UnexpectedResponse),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::testing::{MockProvider, MockScript};
+
+    #[tokio::test]
+    async fn posts_notification() {
+        let provider = MockProvider::new().with_service(
+            NotificationProxyClient::service_name(),
+            MockScript::new().expect(crate::plist!({
+                "Command": "PostNotification",
+                "Name": "com.example.posted",
+            })),
+        );
+
+        let mut client = NotificationProxyClient::connect(&provider).await.unwrap();
+        client.post("com.example.posted").await.unwrap();
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn observe_stream_ends_on_proxy_death() {
+        let provider = MockProvider::new().with_service(
+            NotificationProxyClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({
+                    "Command": "ObserveNotification",
+                    "Name": "com.example.first",
+                }))
+                .expect(crate::plist!({
+                    "Command": "ObserveNotification",
+                    "Name": "com.example.second",
+                }))
+                .respond(crate::plist!({
+                    "Command": "RelayNotification",
+                    "Name": "com.example.second",
+                }))
+                .respond(crate::plist!({ "Command": "ProxyDeath" })),
+        );
+
+        let mut client = NotificationProxyClient::connect(&provider).await.unwrap();
+        let names: Vec<_> = client
+            .observe(&["com.example.first", "com.example.second"])
+            .await
+            .unwrap()
+            .map(|n| n.unwrap())
+            .collect()
+            .await;
+        assert_eq!(names, ["com.example.second"]);
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_proxy_death() {
+        let provider = MockProvider::new().with_service(
+            NotificationProxyClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({ "Command": "Shutdown" }))
+                .respond(crate::plist!({ "Command": "ProxyDeath" })),
+        );
+
+        let mut client = NotificationProxyClient::connect(&provider).await.unwrap();
+        client.shutdown().await.unwrap();
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+}
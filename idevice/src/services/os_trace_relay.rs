@@ -3,11 +3,17 @@
 //! Huge thanks to pymobiledevice3 for the struct implementation
 //! https://github.com/doronz88/pymobiledevice3/blob/master/pymobiledevice3/services/os_trace.py
 
+use std::{path::Path, pin::Pin, time::Duration};
+
 use chrono::{DateTime, NaiveDateTime};
+use futures::{FutureExt, Stream};
 use serde::{Deserialize, Serialize};
 use tokio::io::AsyncWriteExt;
 
-use crate::{Idevice, IdeviceError, IdeviceService, obf};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::{Idevice, IdeviceError, IdeviceService, RetryPolicy, obf, provider::IdeviceProvider};
 
 /// Client for interacting with the iOS device OsTraceRelay service
 #[derive(Debug)]
@@ -27,15 +33,68 @@ impl IdeviceService for OsTraceRelayClient {
     }
 }
 
+/// Maximum number of bytes read from the socket at once
+const READ_CHUNK_SIZE: u32 = 16 * 1024;
+
 /// An initialized client for receiving logs
 #[derive(Debug)]
 pub struct OsTraceRelayReceiver {
     inner: OsTraceRelayClient,
+    filter: OsTraceFilter,
+    /// Bytes received from the relay that don't form a complete packet yet
+    buffer: Vec<u8>,
+    /// Cancels pending and future reads, closing the relay
+    cancel: CancellationToken,
+}
+
+/// Log stream that re-establishes the relay when the connection to the device is lost
+///
+/// The relay is connected on the first read. After a connection error, such as the device
+/// being unplugged or going to sleep, a new relay is started with the same filter, retrying
+/// as described by the [`RetryPolicy`]. Logs emitted while the relay was down are lost, which
+/// is marked by a [`StreamItem::Reconnected`] item.
+#[derive(Debug)]
+pub struct ReconnectingOsTraceRelay<'a> {
+    provider: &'a dyn IdeviceProvider,
+    filter: OsTraceFilter,
+    policy: RetryPolicy,
+    receiver: Option<OsTraceRelayReceiver>,
+    /// Whether a relay was started before, so the next one is a reconnection
+    connected_before: bool,
+    cancel: CancellationToken,
+}
+
+/// An item yielded by [`ReconnectingOsTraceRelay`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamItem {
+    /// A log matching the filter
+    Log(OsTraceLog),
+    /// The relay was re-established after losing the connection, so logs may be missing
+    Reconnected,
+}
+
+/// Filter applied when starting a trace
+///
+/// The PID is sent to the device, while the name and level filters are applied locally
+/// against every received log. When both a PID and a name are set, a log must match both.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OsTraceFilter {
+    /// Only stream logs from this PID
+    pub pid: Option<u32>,
+    /// Only yield logs whose process or image file name matches exactly (e.g. `backboardd`)
+    pub process_name: Option<String>,
+    /// Only yield logs at or above this severity
+    pub min_level: Option<LogLevel>,
 }
 
+/// A parsed log from the relay
+///
+/// Serializes with the timestamp as an RFC 3339 string in UTC, so logs can be forwarded as JSON.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OsTraceLog {
     pub pid: u32,
+    /// When the log was emitted, in UTC
+    #[serde(with = "rfc3339")]
     pub timestamp: NaiveDateTime,
     pub level: LogLevel,
     pub image_name: String,
@@ -44,6 +103,7 @@ pub struct OsTraceLog {
     pub label: Option<SyslogLabel>,
 }
 
+/// The subsystem and category a log was emitted under
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SyslogLabel {
     pub subsystem: String,
@@ -59,16 +119,49 @@ pub enum LogLevel {
     Fault = 11,
 }
 
+/// (De)serializes a UTC timestamp as an RFC 3339 string
+mod rfc3339 {
+    use chrono::{DateTime, NaiveDateTime, SecondsFormat};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(t: &NaiveDateTime, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&t.and_utc().to_rfc3339_opts(SecondsFormat::Micros, true))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveDateTime, D::Error> {
+        let s = String::deserialize(d)?;
+        DateTime::parse_from_rfc3339(&s)
+            .map(|t| t.naive_utc())
+            .map_err(D::Error::custom)
+    }
+}
+
 impl OsTraceRelayClient {
     /// Starts the stream of logs from the relay
     ///
+    /// The PID list isn't fetched first, the only round trip is the device confirming the
+    /// trace started. Call [`Self::get_pid_list`] before starting if the PIDs are needed,
+    /// since the relay only streams logs once the trace is running.
+    ///
     /// # Arguments
     /// * `pid` - An optional pid to stream logs from
-    pub async fn start_trace(
+    pub async fn start_trace(self, pid: Option<u32>) -> Result<OsTraceRelayReceiver, IdeviceError> {
+        self.start_trace_filtered(OsTraceFilter {
+            pid,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Starts the stream of logs from the relay, only yielding logs matching the filter
+    ///
+    /// # Arguments
+    /// * `filter` - The PID, process name and minimum level to filter logs by
+    pub async fn start_trace_filtered(
         mut self,
-        pid: Option<u32>,
+        filter: OsTraceFilter,
     ) -> Result<OsTraceRelayReceiver, IdeviceError> {
-        let pid = match pid {
+        let pid = match filter.pid {
             Some(p) => p as i64,
             None => -1,
         };
@@ -90,7 +183,12 @@ impl OsTraceRelayClient {
         match res.get("Status").and_then(|x| x.as_string()) {
             Some(r) => {
                 if r == "RequestSuccessful" {
-                    Ok(OsTraceRelayReceiver { inner: self })
+                    Ok(OsTraceRelayReceiver {
+                        inner: self,
+                        filter,
+                        buffer: Vec::new(),
+                        cancel: CancellationToken::new(),
+                    })
                 } else {
                     Err(IdeviceError::UnexpectedResponse)
                 }
@@ -100,6 +198,8 @@ impl OsTraceRelayClient {
     }
 
     /// Get the list of available PIDs
+    ///
+    /// Only requested when called, starting a trace doesn't need it.
     pub async fn get_pid_list(&mut self) -> Result<Vec<u64>, IdeviceError> {
         let req = crate::plist!({
             "Request": "PidList"
@@ -178,125 +278,411 @@ impl OsTraceRelayClient {
 }
 
 impl OsTraceRelayReceiver {
-    /// Get the next log from the relay
+    /// Returns a token that cancels this receiver
+    ///
+    /// Cancelling aborts a pending read and closes the relay, so it can be done from another
+    /// task or thread to stop a receiver blocked waiting for logs. Reads then fail with
+    /// `IdeviceError::Canceled`, and the stream from [`Self::into_stream`] ends.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Closes the relay gracefully, see [`crate::Idevice::shutdown`]
+    pub async fn shutdown(&mut self) -> Result<(), IdeviceError> {
+        self.inner.idevice.shutdown().await
+    }
+
+    /// Get the next log from the relay that matches the trace's filter
     ///
     /// # Returns
     /// A string containing the log
     ///
     /// # Errors
-    /// UnexpectedResponse if the service sends an EOF
+    /// UnexpectedResponse if the service sends an EOF, or Canceled if the receiver was canceled
     pub async fn next(&mut self) -> Result<OsTraceLog, IdeviceError> {
-        // Read 0x02, at the beginning of each packet
-        if self.inner.idevice.read_raw(1).await?[0] != 0x02 {
+        loop {
+            let log = self.read_log().await?;
+            if self.filter.matches(&log) {
+                return Ok(log);
+            }
+        }
+    }
+
+    /// Get the next log from the relay without waiting for more data to arrive
+    ///
+    /// # Returns
+    /// `None` if no complete log matching the trace's filter is currently buffered
+    pub async fn try_next(&mut self) -> Result<Option<OsTraceLog>, IdeviceError> {
+        loop {
+            if let Some(packet) = self.take_packet()? {
+                let log = parse_log(&packet)?;
+                if self.filter.matches(&log) {
+                    return Ok(Some(log));
+                }
+                continue;
+            }
+
+            if self.cancel.is_cancelled() {
+                self.inner.idevice.close();
+                return Err(IdeviceError::Canceled);
+            }
+            match self.inner.idevice.read_any(READ_CHUNK_SIZE).now_or_never() {
+                Some(data) => self.extend_buffer(data?)?,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Get up to `max` logs matching the trace's filter in one call
+    ///
+    /// Waits up to `timeout` for the first log, then takes any further logs that are already
+    /// buffered without waiting again. Useful to cut per-log overhead under heavy logging.
+    ///
+    /// # Returns
+    /// Between 0 and `max` logs. Empty if no log arrived before the timeout.
+    pub async fn next_batch(
+        &mut self,
+        max: usize,
+        timeout: Duration,
+    ) -> Result<Vec<OsTraceLog>, IdeviceError> {
+        let mut logs = Vec::new();
+        if max == 0 {
+            return Ok(logs);
+        }
+
+        match tokio::time::timeout(timeout, self.next()).await {
+            Ok(log) => logs.push(log?),
+            Err(_) => return Ok(logs),
+        }
+        while logs.len() < max {
+            match self.try_next().await? {
+                Some(log) => logs.push(log),
+                None => break,
+            }
+        }
+        Ok(logs)
+    }
+
+    async fn read_log(&mut self) -> Result<OsTraceLog, IdeviceError> {
+        loop {
+            if let Some(packet) = self.take_packet()? {
+                return parse_log(&packet);
+            }
+            let data = self
+                .cancel
+                .run_until_cancelled(self.inner.idevice.read_any(READ_CHUNK_SIZE))
+                .await;
+            match data {
+                Some(data) => self.extend_buffer(data?)?,
+                None => {
+                    self.inner.idevice.close();
+                    return Err(IdeviceError::Canceled);
+                }
+            }
+        }
+    }
+
+    /// Appends freshly read bytes to the buffer, treating an empty read as EOF
+    fn extend_buffer(&mut self, data: Vec<u8>) -> Result<(), IdeviceError> {
+        if data.is_empty() {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        self.buffer.extend_from_slice(&data);
+        Ok(())
+    }
+
+    /// Removes a complete packet from the buffer, if one has been received
+    fn take_packet(&mut self) -> Result<Option<Vec<u8>>, IdeviceError> {
+        if self.buffer.len() < 5 {
+            return Ok(None);
+        }
+
+        // 0x02 is at the beginning of each packet, followed by the length
+        if self.buffer[0] != 0x02 {
             return Err(IdeviceError::UnexpectedResponse);
         }
+        let packet_length = u32::from_le_bytes([
+            self.buffer[1],
+            self.buffer[2],
+            self.buffer[3],
+            self.buffer[4],
+        ]) as usize;
+
+        if self.buffer.len() < 5 + packet_length {
+            return Ok(None);
+        }
+        let packet = self.buffer[5..5 + packet_length].to_vec();
+        self.buffer.drain(..5 + packet_length);
+        Ok(Some(packet))
+    }
+
+    /// Converts the receiver into a continuous stream of logs.
+    ///
+    /// Transport and parsing errors are yielded as `Err` items, after which the stream ends.
+    /// The stream also ends, without an error, when the receiver is canceled. Dropping the
+    /// stream closes the relay connection.
+    pub fn into_stream(
+        mut self,
+    ) -> Pin<Box<dyn Stream<Item = Result<OsTraceLog, IdeviceError>> + Send>> {
+        Box::pin(async_stream::try_stream! {
+            loop {
+                match self.next().await {
+                    Err(IdeviceError::Canceled) => break,
+                    res => yield res?,
+                }
+            }
+        })
+    }
+}
+
+impl<'a> ReconnectingOsTraceRelay<'a> {
+    /// Creates a stream for the logs matching a filter, without connecting yet
+    ///
+    /// By default connecting is retried 5 times with an exponential backoff, for any
+    /// connection or transient error.
+    ///
+    /// # Arguments
+    /// * `provider` - The device provider used to connect, and to reconnect
+    /// * `filter` - The PID, process name and minimum level to filter logs by
+    pub fn new(provider: &'a dyn IdeviceProvider, filter: OsTraceFilter) -> Self {
+        Self {
+            provider,
+            filter,
+            policy: RetryPolicy::default()
+                .with_retryable(|e| e.is_transient() || e.is_connection_error()),
+            receiver: None,
+            connected_before: false,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Replaces the policy deciding how often and for which errors connecting is retried
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-        // Read the len of the packet
-        let pl = self.inner.idevice.read_raw(4).await?;
-        let packet_length = u32::from_le_bytes([pl[0], pl[1], pl[2], pl[3]]);
-
-        let packet = self.inner.idevice.read_raw(packet_length as usize).await?;
-
-        // 9 bytes of padding
-        let packet = &packet[9..];
-
-        // Parse PID (4 bytes)
-        let pid = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
-        let packet = &packet[4..];
-
-        // Skip 42 unknown bytes
-        let packet = &packet[42..];
-
-        // Parse timestamp (seconds + microseconds)
-        let seconds = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
-        let packet = &packet[8..]; // skip 4 bytes padding after seconds
-        let microseconds = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
-        let packet = &packet[4..];
-
-        // Skip 1 byte padding
-        let packet = &packet[1..];
-
-        // Parse log level
-        let log_level = packet[0];
-        let log_level: LogLevel = log_level.try_into()?;
-        let packet = &packet[1..];
-
-        // Skip 38 unknown bytes
-        let packet = &packet[38..];
-
-        // Parse string sizes
-        let image_name_size = u16::from_le_bytes([packet[0], packet[1]]) as usize;
-        let packet = &packet[2..];
-        let message_size = u16::from_le_bytes([packet[0], packet[1]]) as usize;
-        let packet = &packet[2..];
-
-        // Skip 6 bytes
-        let packet = &packet[6..];
-
-        // Parse subsystem and category sizes
-        let subsystem_size =
-            u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]) as usize;
-        let packet = &packet[4..];
-        let category_size =
-            u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]) as usize;
-        let packet = &packet[4..];
-
-        // Skip 4 bytes
-        let packet = &packet[4..];
-
-        // Parse filename (null-terminated string)
-        let filename_end = packet
-            .iter()
-            .position(|&b| b == 0)
-            .ok_or(IdeviceError::UnexpectedResponse)?;
-        let filename = String::from_utf8_lossy(&packet[..filename_end]).into_owned();
-        let packet = &packet[filename_end + 1..];
-
-        // Parse image name
-        let image_name_bytes = &packet[..image_name_size];
-        let image_name =
-            String::from_utf8_lossy(&image_name_bytes[..image_name_bytes.len() - 1]).into_owned();
-        let packet = &packet[image_name_size..];
-
-        // Parse message
-        let message_bytes = &packet[..message_size];
-        let message =
-            String::from_utf8_lossy(&message_bytes[..message_bytes.len() - 1]).into_owned();
-        let packet = &packet[message_size..];
-
-        // Parse label if subsystem and category exist
-        let label = if subsystem_size > 0 && category_size > 0 && !packet.is_empty() {
-            let subsystem_bytes = &packet[..subsystem_size];
-            let subsystem =
-                String::from_utf8_lossy(&subsystem_bytes[..subsystem_bytes.len() - 1]).into_owned();
-            let packet = &packet[subsystem_size..];
-
-            let category_bytes = &packet[..category_size];
-            let category =
-                String::from_utf8_lossy(&category_bytes[..category_bytes.len() - 1]).into_owned();
-
-            Some(SyslogLabel {
-                subsystem,
-                category,
+    /// Returns a token that cancels this stream
+    ///
+    /// Cancelling aborts a pending read or reconnection and closes the relay. Reads then fail
+    /// with `IdeviceError::Canceled`, and the stream from [`Self::into_stream`] ends.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Gets the next log, reconnecting first if the connection was lost
+    ///
+    /// # Returns
+    /// The next log, or [`StreamItem::Reconnected`] right after a reconnection
+    ///
+    /// # Errors
+    /// Returns the last error if reconnecting fails, any error that isn't a connection error,
+    /// or `IdeviceError::Canceled` if the stream was canceled
+    pub async fn next(&mut self) -> Result<StreamItem, IdeviceError> {
+        let cancel = self.cancel.clone();
+        match cancel.run_until_cancelled(self.next_inner()).await {
+            Some(res) => res,
+            None => {
+                self.receiver = None;
+                Err(IdeviceError::Canceled)
+            }
+        }
+    }
+
+    async fn next_inner(&mut self) -> Result<StreamItem, IdeviceError> {
+        loop {
+            let receiver = match &mut self.receiver {
+                Some(r) => r,
+                None => {
+                    let receiver = self.connect().await?;
+                    let reconnected = std::mem::replace(&mut self.connected_before, true);
+                    let receiver = self.receiver.insert(receiver);
+                    if reconnected {
+                        return Ok(StreamItem::Reconnected);
+                    }
+                    receiver
+                }
+            };
+
+            match receiver.next().await {
+                Ok(log) => return Ok(StreamItem::Log(log)),
+                Err(e) => {
+                    self.receiver = None;
+                    if !e.is_connection_error() {
+                        return Err(e);
+                    }
+                    debug!("Lost the os_trace_relay connection, reconnecting: {e:?}");
+                }
+            }
+        }
+    }
+
+    /// Starts a relay with the filter, retrying as the policy allows
+    async fn connect(&self) -> Result<OsTraceRelayReceiver, IdeviceError> {
+        self.policy
+            .run(|| async {
+                OsTraceRelayClient::connect(self.provider)
+                    .await?
+                    .start_trace_filtered(self.filter.clone())
+                    .await
             })
-        } else {
-            None
-        };
+            .await
+    }
+
+    /// Converts the relay into a continuous stream of logs and reconnection markers
+    ///
+    /// An error that can't be recovered from is yielded as an `Err` item, after which the
+    /// stream ends. The stream also ends, without an error, when it is canceled.
+    pub fn into_stream(
+        mut self,
+    ) -> Pin<Box<dyn Stream<Item = Result<StreamItem, IdeviceError>> + Send + 'a>> {
+        Box::pin(async_stream::try_stream! {
+            loop {
+                match self.next().await {
+                    Err(IdeviceError::Canceled) => break,
+                    res => yield res?,
+                }
+            }
+        })
+    }
+}
 
-        let timestamp = match DateTime::from_timestamp(seconds as i64, microseconds) {
+fn parse_log(packet: &[u8]) -> Result<OsTraceLog, IdeviceError> {
+    // 9 bytes of padding
+    let packet = &packet[9..];
+
+    // Parse PID (4 bytes)
+    let pid = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+    let packet = &packet[4..];
+
+    // Skip 42 unknown bytes
+    let packet = &packet[42..];
+
+    // Parse timestamp (seconds + microseconds)
+    let seconds = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+    let packet = &packet[8..]; // skip 4 bytes padding after seconds
+    let microseconds = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+    let packet = &packet[4..];
+
+    // Skip 1 byte padding
+    let packet = &packet[1..];
+
+    // Parse log level
+    let log_level = packet[0];
+    let log_level: LogLevel = log_level.try_into()?;
+    let packet = &packet[1..];
+
+    // Skip 38 unknown bytes
+    let packet = &packet[38..];
+
+    // Parse string sizes
+    let image_name_size = u16::from_le_bytes([packet[0], packet[1]]) as usize;
+    let packet = &packet[2..];
+    let message_size = u16::from_le_bytes([packet[0], packet[1]]) as usize;
+    let packet = &packet[2..];
+
+    // Skip 6 bytes
+    let packet = &packet[6..];
+
+    // Parse subsystem and category sizes
+    let subsystem_size = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]) as usize;
+    let packet = &packet[4..];
+    let category_size = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]) as usize;
+    let packet = &packet[4..];
+
+    // Skip 4 bytes
+    let packet = &packet[4..];
+
+    // Parse filename (null-terminated string)
+    let filename_end = packet
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(IdeviceError::UnexpectedResponse)?;
+    let filename = String::from_utf8_lossy(&packet[..filename_end]).into_owned();
+    let packet = &packet[filename_end + 1..];
+
+    // Parse image name
+    let image_name_bytes = &packet[..image_name_size];
+    let image_name =
+        String::from_utf8_lossy(&image_name_bytes[..image_name_bytes.len() - 1]).into_owned();
+    let packet = &packet[image_name_size..];
+
+    // Parse message
+    let message_bytes = &packet[..message_size];
+    let message = String::from_utf8_lossy(&message_bytes[..message_bytes.len() - 1]).into_owned();
+    let packet = &packet[message_size..];
+
+    // Parse label if subsystem and category exist
+    let label = if subsystem_size > 0 && category_size > 0 && !packet.is_empty() {
+        let subsystem_bytes = &packet[..subsystem_size];
+        let subsystem =
+            String::from_utf8_lossy(&subsystem_bytes[..subsystem_bytes.len() - 1]).into_owned();
+        let packet = &packet[subsystem_size..];
+
+        let category_bytes = &packet[..category_size];
+        let category =
+            String::from_utf8_lossy(&category_bytes[..category_bytes.len() - 1]).into_owned();
+
+        Some(SyslogLabel {
+            subsystem,
+            category,
+        })
+    } else {
+        None
+    };
+
+    let timestamp =
+        match DateTime::from_timestamp(seconds as i64, microseconds.saturating_mul(1000)) {
             Some(t) => t.naive_local(),
             None => return Err(IdeviceError::UnexpectedResponse),
         };
 
-        Ok(OsTraceLog {
-            pid,
-            timestamp,
-            level: log_level,
-            image_name,
-            filename,
-            message,
-            label,
-        })
+    Ok(OsTraceLog {
+        pid,
+        timestamp,
+        level: log_level,
+        image_name,
+        filename,
+        message,
+        label,
+    })
+}
+
+impl OsTraceFilter {
+    /// Checks whether a log passes the filter
+    pub fn matches(&self, log: &OsTraceLog) -> bool {
+        if let Some(pid) = self.pid
+            && log.pid != pid
+        {
+            return false;
+        }
+        if let Some(name) = &self.process_name {
+            let name = Some(std::ffi::OsStr::new(name));
+            if Path::new(&log.filename).file_name() != name
+                && Path::new(&log.image_name).file_name() != name
+            {
+                return false;
+            }
+        }
+        if let Some(min_level) = self.min_level
+            && log.level.severity() < min_level.severity()
+        {
+            return false;
+        }
+        true
+    }
+}
+
+impl LogLevel {
+    /// Orders the levels from least to most severe
+    fn severity(self) -> u8 {
+        match self {
+            Self::Debug => 0,
+            Self::Info => 1,
+            Self::Notice => 2,
+            Self::Error => 3,
+            Self::Fault => 4,
+        }
     }
 }
 
@@ -314,3 +700,130 @@ impl TryFrom<u8> for LogLevel {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_timestamp_as_rfc3339() {
+        let log = OsTraceLog {
+            pid: 1,
+            timestamp: DateTime::from_timestamp(1_700_000_000, 250_000_000)
+                .unwrap()
+                .naive_utc(),
+            level: LogLevel::Info,
+            image_name: "/usr/libexec/backboardd".into(),
+            filename: "/usr/libexec/backboardd".into(),
+            message: "hello".into(),
+            label: Some(SyslogLabel {
+                subsystem: "com.apple.backboard".into(),
+                category: "default".into(),
+            }),
+        };
+
+        let value = plist::to_value(&log).unwrap();
+        assert_eq!(
+            value
+                .as_dictionary()
+                .and_then(|d| d.get("timestamp"))
+                .and_then(|t| t.as_string()),
+            Some("2023-11-14T22:13:20.250000Z")
+        );
+        assert_eq!(plist::from_value::<OsTraceLog>(&value).unwrap(), log);
+    }
+
+    /// Frames a log the way the relay sends it
+    fn encode_log(message: &str) -> Vec<u8> {
+        let image_name = b"backboardd\0";
+        let message = [message.as_bytes(), b"\0"].concat();
+
+        let mut packet = vec![0; 9];
+        packet.extend(42u32.to_le_bytes()); // pid
+        packet.extend([0; 42]);
+        packet.extend(1_700_000_000u32.to_le_bytes());
+        packet.extend([0; 4]);
+        packet.extend(0u32.to_le_bytes()); // microseconds
+        packet.push(0);
+        packet.push(LogLevel::Info as u8);
+        packet.extend([0; 38]);
+        packet.extend((image_name.len() as u16).to_le_bytes());
+        packet.extend((message.len() as u16).to_le_bytes());
+        packet.extend([0; 6]);
+        packet.extend(0u32.to_le_bytes()); // subsystem size
+        packet.extend(0u32.to_le_bytes()); // category size
+        packet.extend([0; 4]);
+        packet.extend(b"/usr/libexec/backboardd\0");
+        packet.extend(image_name);
+        packet.extend(message);
+
+        let mut framed = vec![0x02];
+        framed.extend((packet.len() as u32).to_le_bytes());
+        framed.extend(packet);
+        framed
+    }
+
+    #[tokio::test]
+    async fn batches_buffered_logs() {
+        let (mut device, host) = tokio::io::duplex(4096);
+        let mut receiver = OsTraceRelayReceiver {
+            inner: OsTraceRelayClient {
+                idevice: Idevice::new(Box::new(host), "test"),
+            },
+            filter: OsTraceFilter::default(),
+            buffer: Vec::new(),
+            cancel: CancellationToken::new(),
+        };
+
+        let logs = ["one", "two", "three"].map(encode_log).concat();
+        device.write_all(&logs).await.unwrap();
+
+        let timeout = Duration::from_millis(50);
+        let batch = receiver.next_batch(2, timeout).await.unwrap();
+        assert_eq!(
+            batch.iter().map(|l| l.message.as_str()).collect::<Vec<_>>(),
+            ["one", "two"]
+        );
+        let batch = receiver.next_batch(10, timeout).await.unwrap();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].message, "three");
+        assert!(receiver.next_batch(10, timeout).await.unwrap().is_empty());
+    }
+
+    /// Accepts a trace request, then sends the given logs and hangs up
+    #[cfg(feature = "testing")]
+    fn trace_script(messages: &[&str]) -> crate::testing::MockScript {
+        let mut script = crate::testing::MockScript::new()
+            .expect(crate::plist!({ "Request": "StartActivity" }))
+            .respond_raw([0])
+            .respond(crate::plist!({ "Status": "RequestSuccessful" }));
+        for message in messages {
+            script = script.respond_raw(encode_log(message));
+        }
+        script
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn reconnects_after_connection_loss() {
+        let provider = crate::testing::MockProvider::new()
+            .with_service(
+                OsTraceRelayClient::service_name(),
+                trace_script(&["before"]),
+            )
+            .with_service(OsTraceRelayClient::service_name(), trace_script(&["after"]));
+
+        let mut relay = ReconnectingOsTraceRelay::new(&provider, OsTraceFilter::default());
+        let mut messages = Vec::new();
+        for _ in 0..3 {
+            messages.push(match relay.next().await.unwrap() {
+                StreamItem::Log(log) => log.message,
+                StreamItem::Reconnected => "reconnected".to_string(),
+            });
+        }
+        assert_eq!(messages, ["before", "reconnected", "after"]);
+        drop(relay);
+
+        assert!(provider.verify().await.is_empty());
+    }
+}
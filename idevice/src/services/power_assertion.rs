@@ -0,0 +1,173 @@
+//! iOS Power Assertion Service Client
+//!
+//! Provides functionality for keeping the device awake through the assertion_agent service,
+//! such as during long transfers or installs.
+
+use std::time::Duration;
+
+use crate::{Idevice, IdeviceError, IdeviceService, obf};
+
+/// Client for interacting with the iOS power assertion service
+///
+/// Assertions are tied to the service connection and end when it closes, see
+/// [`AssertionGuard`].
+#[derive(Debug)]
+pub struct PowerAssertionClient {
+    /// The underlying device connection with established assertion_agent service
+    pub idevice: Idevice,
+}
+
+/// The kind of sleep a power assertion prevents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerAssertionType {
+    /// Keeps the device from sleeping because the user is idle
+    PreventUserIdleSystemSleep,
+    /// Keeps the device from sleeping at all
+    PreventSystemSleep,
+    /// The assertion taken during wireless syncs
+    WirelessSync,
+}
+
+impl PowerAssertionType {
+    /// Returns the name of the type as expected by assertion_agent
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PowerAssertionType::PreventUserIdleSystemSleep => "PreventUserIdleSystemSleep",
+            PowerAssertionType::PreventSystemSleep => "PreventSystemSleep",
+            PowerAssertionType::WirelessSync => "AMDPowerAssertionTypeWirelessSync",
+        }
+    }
+}
+
+/// A power assertion held on the device
+///
+/// The device releases the assertion when its connection closes, so dropping the guard
+/// closes the client's connection. Connect a new client to create another assertion.
+#[derive(Debug)]
+pub struct AssertionGuard<'a> {
+    client: &'a mut PowerAssertionClient,
+}
+
+impl AssertionGuard<'_> {
+    /// Releases the assertion, the same as dropping the guard
+    pub fn release(self) {}
+}
+
+impl Drop for AssertionGuard<'_> {
+    fn drop(&mut self) {
+        self.client.idevice.close();
+    }
+}
+
+impl IdeviceService for PowerAssertionClient {
+    /// Returns the power assertion service name as registered with lockdownd
+    fn service_name() -> std::borrow::Cow<'static, str> {
+        obf!("com.apple.mobile.assertion_agent")
+    }
+
+    async fn from_stream(idevice: Idevice) -> Result<Self, crate::IdeviceError> {
+        Ok(Self::new(idevice))
+    }
+}
+
+impl PowerAssertionClient {
+    /// Creates a new power assertion client from an existing device connection
+    ///
+    /// # Arguments
+    /// * `idevice` - Pre-established device connection
+    pub fn new(idevice: Idevice) -> Self {
+        Self { idevice }
+    }
+
+    /// Keeps the device from idle sleeping until the returned guard is dropped
+    ///
+    /// # Arguments
+    /// * `name` - Name of the assertion, shown in the device's power logs
+    /// * `details` - Description of why the assertion is held
+    /// * `timeout` - How long the device keeps the assertion at most, or `None` to keep it
+    ///   until the guard is dropped
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails or the device refuses the assertion
+    pub async fn create(
+        &mut self,
+        name: &str,
+        details: &str,
+        timeout: Option<Duration>,
+    ) -> Result<AssertionGuard<'_>, IdeviceError> {
+        self.create_with_type(
+            PowerAssertionType::PreventUserIdleSystemSleep,
+            name,
+            details,
+            timeout,
+        )
+        .await
+    }
+
+    /// Creates a power assertion of a given type, held until the returned guard is dropped
+    ///
+    /// # Arguments
+    /// * `assertion_type` - The kind of sleep to prevent
+    /// * `name` - Name of the assertion, shown in the device's power logs
+    /// * `details` - Description of why the assertion is held
+    /// * `timeout` - How long the device keeps the assertion at most, or `None` to keep it
+    ///   until the guard is dropped
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails or the device refuses the assertion
+    pub async fn create_with_type(
+        &mut self,
+        assertion_type: PowerAssertionType,
+        name: &str,
+        details: &str,
+        timeout: Option<Duration>,
+    ) -> Result<AssertionGuard<'_>, IdeviceError> {
+        let req = crate::plist!({
+            "CommandKey": "CommandCreateAssertion",
+            "AssertionTypeKey": assertion_type.as_str(),
+            "AssertionNameKey": name,
+            "AssertionDetailKey": details,
+            "AssertionTimeoutKey":? timeout.map(|t| t.as_secs_f64()),
+        });
+        self.idevice.send_plist(req).await?;
+        self.idevice.read_plist().await?;
+
+        Ok(AssertionGuard { client: self })
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::{MockProvider, MockScript};
+
+    #[tokio::test]
+    async fn dropping_guard_closes_connection() {
+        let provider = MockProvider::new().with_service(
+            PowerAssertionClient::service_name(),
+            MockScript::new()
+                .expect(crate::plist!({
+                    "CommandKey": "CommandCreateAssertion",
+                    "AssertionTypeKey": "PreventUserIdleSystemSleep",
+                    "AssertionNameKey": "transfer",
+                    "AssertionTimeoutKey": 60.0,
+                }))
+                .respond(crate::plist!({ "CommandKey": "CommandCreateAssertion" })),
+        );
+
+        let mut client = PowerAssertionClient::connect(&provider).await.unwrap();
+        let guard = client
+            .create("transfer", "copying photos", Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        // The connection closing is what releases the assertion
+        drop(guard);
+        assert!(matches!(
+            client.create("again", "", None).await,
+            Err(IdeviceError::NoEstablishedConnection)
+        ));
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+//! iOS Restore Mode Service Client
+//!
+//! Provides read-only access to restored, the daemon a device runs in place of lockdownd
+//! while it is in restore mode. It listens on the lockdownd port and speaks the same framed
+//! plists, without sessions or pairing.
+//!
+//! Only the handshake is implemented: querying the type and values, and saying goodbye.
+//! Driving an actual restore isn't supported.
+
+use crate::{Idevice, IdeviceError, IdeviceService, PlistExt, obf};
+
+/// Client for interacting with restored on a device in restore mode
+#[derive(Debug)]
+pub struct RestoredClient {
+    /// The underlying device connection to restored
+    pub idevice: Idevice,
+}
+
+/// What restored reports about itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoredType {
+    /// The service type, `com.apple.mobile.restored` for restored
+    pub service_type: String,
+    /// The version of the restore protocol the device speaks, if reported
+    pub protocol_version: Option<u64>,
+}
+
+impl IdeviceService for RestoredClient {
+    /// Returns the type restored reports, as it isn't started through lockdown
+    fn service_name() -> std::borrow::Cow<'static, str> {
+        obf!("com.apple.mobile.restored")
+    }
+
+    /// Connects to restored on the lockdownd port
+    ///
+    /// # Arguments
+    /// * `provider` - Device connection provider
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if connection fails
+    async fn connect(
+        provider: &dyn crate::provider::IdeviceProvider,
+    ) -> Result<Self, IdeviceError> {
+        if let Some(idevice) = provider.connect_service(&Self::service_name()).await? {
+            return Ok(Self::new(idevice));
+        }
+        let idevice = provider.connect(Self::RESTORED_PORT).await?;
+        Ok(Self::new(idevice))
+    }
+
+    async fn from_stream(idevice: Idevice) -> Result<Self, IdeviceError> {
+        Ok(Self::new(idevice))
+    }
+}
+
+impl RestoredClient {
+    /// The port restored listens on, shared with lockdownd outside of restore mode
+    pub const RESTORED_PORT: u16 = 62078;
+
+    /// Creates a new restored client from an existing device connection
+    ///
+    /// # Arguments
+    /// * `idevice` - Pre-established device connection
+    pub fn new(idevice: Idevice) -> Self {
+        Self { idevice }
+    }
+
+    /// Queries the type of the service on the other end
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::UnexpectedResponse` if the device isn't in restore mode, such as
+    /// when lockdownd answers instead of restored
+    pub async fn query_type(&mut self) -> Result<RestoredType, IdeviceError> {
+        let req = crate::plist!({
+            "Label": self.idevice.label.clone(),
+            "Request": "QueryType",
+        });
+        self.idevice.send_plist(req).await?;
+
+        let res = self.idevice.read_plist().await?;
+        let service_type = res.get_str("Type")?;
+        if service_type != Self::service_name() {
+            tracing::warn!("Expected restored, got {service_type}");
+            return Err(IdeviceError::UnexpectedResponse);
+        }
+        Ok(RestoredType {
+            service_type: service_type.to_string(),
+            protocol_version: res
+                .get("RestoreProtocolVersion")
+                .and_then(|v| v.as_unsigned_integer()),
+        })
+    }
+
+    /// Queries a value from restored
+    ///
+    /// # Arguments
+    /// * `key` - The key to query, such as `SavedDebugInfo` or `HardwareInfo`
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::MissingKey` if restored has no value for the key
+    pub async fn query_value(&mut self, key: &str) -> Result<plist::Value, IdeviceError> {
+        let req = crate::plist!({
+            "Label": self.idevice.label.clone(),
+            "Request": "QueryValue",
+            "QueryKey": key,
+        });
+        self.idevice.send_plist(req).await?;
+
+        let res = self.idevice.read_plist().await?;
+        res.get_required(key).cloned()
+    }
+
+    /// Ends the conversation with restored
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::UnexpectedResponse` if restored doesn't acknowledge it
+    pub async fn goodbye(&mut self) -> Result<(), IdeviceError> {
+        let req = crate::plist!({
+            "Label": self.idevice.label.clone(),
+            "Request": "Goodbye",
+        });
+        self.idevice.send_plist(req).await?;
+
+        let res = self.idevice.read_plist().await?;
+        match res.get_str("Result")? {
+            "Success" => Ok(()),
+            r => {
+                tracing::warn!("restored refused goodbye: {r}");
+                Err(IdeviceError::UnexpectedResponse)
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::{MockProvider, MockScript};
+
+    #[tokio::test]
+    async fn performs_handshake() {
+        let provider = MockProvider::new().with_port(
+            RestoredClient::RESTORED_PORT,
+            MockScript::new()
+                .expect(crate::plist!({ "Request": "QueryType" }))
+                .respond(crate::plist!({
+                    "Type": "com.apple.mobile.restored",
+                    "RestoreProtocolVersion": 15,
+                }))
+                .expect(crate::plist!({
+                    "Request": "QueryValue",
+                    "QueryKey": "SavedDebugInfo",
+                }))
+                .respond(crate::plist!({ "SavedDebugInfo": { "Panics": [] } }))
+                .expect(crate::plist!({ "Request": "Goodbye" }))
+                .respond(crate::plist!({ "Result": "Success" })),
+        );
+
+        let mut client = RestoredClient::connect(&provider).await.unwrap();
+        assert_eq!(
+            client.query_type().await.unwrap(),
+            RestoredType {
+                service_type: "com.apple.mobile.restored".into(),
+                protocol_version: Some(15),
+            }
+        );
+        assert!(
+            client
+                .query_value("SavedDebugInfo")
+                .await
+                .unwrap()
+                .as_dictionary()
+                .is_some()
+        );
+        client.goodbye().await.unwrap();
+        drop(client);
+
+        assert!(provider.verify().await.is_empty());
+    }
+}
@@ -1,5 +1,12 @@
 //! Remote Service Discovery
 //! Communicates via XPC and returns advertised services
+//!
+//! On iOS 17+, RSD is reached through the CoreDeviceProxy tunnel. Once the tunnel is up,
+//! connect to `server_rsd_port` on the device's tunnel address (see
+//! `CoreDeviceProxy::tunnel_info`) and pass the stream to [`RsdHandshake::new`]. The
+//! returned handshake lists every service the device advertises along with its port, which
+//! can be opened over the same tunnel, either by hand with [`RsdHandshake::service_port`] or
+//! with [`RsdHandshake::connect`] for services implemented by this crate.
 
 use std::collections::HashMap;
 
@@ -23,15 +30,30 @@ pub struct RsdService {
     pub service_version: Option<i64>,
 }
 
+/// The result of a handshake with RemoteServiceDiscovery
 #[derive(Debug, Clone)]
 pub struct RsdHandshake {
+    /// Advertised services, keyed by service name
     pub services: HashMap<String, RsdService>,
+    /// RSD protocol version reported by the device
     pub protocol_version: usize,
+    /// Device properties sent along with the service list
     pub properties: HashMap<String, plist::Value>,
+    /// UUID of the device
     pub uuid: String,
 }
 
 impl RsdHandshake {
+    /// Performs the RSD handshake over a stream connected to the RSD port
+    ///
+    /// # Arguments
+    /// * `socket` - A stream connected to the device's RSD port
+    ///
+    /// # Returns
+    /// The advertised services and device properties
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if the XPC handshake fails or the response is malformed
     pub async fn new(socket: impl ReadWrite) -> Result<Self, IdeviceError> {
         let mut xpc_client = RemoteXpcClient::new(socket).await?;
         xpc_client.do_handshake().await?;
@@ -157,6 +179,24 @@ impl RsdHandshake {
         })
     }
 
+    /// Looks up the port a service is advertised on
+    ///
+    /// # Arguments
+    /// * `name` - The service name, such as `com.apple.internal.dt.remote.debugproxy`
+    ///
+    /// # Returns
+    /// The port, or `None` if the device doesn't advertise the service
+    pub fn service_port(&self, name: &str) -> Option<u16> {
+        self.services.get(name).map(|s| s.port)
+    }
+
+    /// Connects to a service advertised in this handshake
+    ///
+    /// # Arguments
+    /// * `provider` - A provider that can open ports over the tunnel
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::ServiceNotFound` if the device doesn't advertise the service
     pub async fn connect<T>(&mut self, provider: &mut impl RsdProvider) -> Result<T, IdeviceError>
     where
         T: crate::RsdService,
@@ -5,9 +5,9 @@
 
 use crate::{Idevice, IdeviceError, IdeviceService, obf};
 use std::borrow::Cow;
-use tokio::io::AsyncReadExt;
 use tracing::{debug, warn};
 
+/// Client for the screenshotr service
 #[derive(Debug)]
 pub struct ScreenshotService {
     /// Underlying device connection
@@ -28,6 +28,12 @@ impl IdeviceService for ScreenshotService {
 }
 
 impl ScreenshotService {
+    /// Creates a new client from an existing device connection
+    ///
+    /// The DeviceLink version exchange is not performed; use `connect` for a ready client.
+    ///
+    /// # Arguments
+    /// * `idevice` - Pre-established device connection
     pub fn new(idevice: Idevice) -> Self {
         Self { idevice }
     }
@@ -65,25 +71,26 @@ impl ScreenshotService {
 
     /// Receives any DL* message and returns (message_tag, full_array_value)
     pub async fn receive_dl_message(&mut self) -> Result<(String, plist::Value), IdeviceError> {
-        if let Some(socket) = &mut self.idevice.socket {
-            let mut buf = [0u8; 4];
-            socket.read_exact(&mut buf).await?;
-            let len = u32::from_be_bytes(buf);
-            let mut body = vec![0; len as usize];
-            socket.read_exact(&mut body).await?;
-            let value: plist::Value = plist::from_bytes(&body)?;
-            if let plist::Value::Array(arr) = &value
-                && let Some(plist::Value::String(tag)) = arr.first()
-            {
-                return Ok((tag.clone(), value));
-            }
-            warn!("Invalid DL message format");
-            Err(IdeviceError::UnexpectedResponse)
-        } else {
-            Err(IdeviceError::NoEstablishedConnection)
+        let len = self.idevice.read_raw(4).await?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]);
+        let body = self.idevice.read_raw(len as usize).await?;
+        let value: plist::Value = plist::from_bytes(&body)?;
+        if let plist::Value::Array(arr) = &value
+            && let Some(plist::Value::String(tag)) = arr.first()
+        {
+            return Ok((tag.clone(), value));
         }
+        warn!("Invalid DL message format");
+        Err(IdeviceError::UnexpectedResponse)
     }
 
+    /// Takes a screenshot of the device's screen
+    ///
+    /// # Returns
+    /// The raw image bytes sent by the device, PNG or TIFF depending on the iOS version
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::UnexpectedResponse` if the device's reply is malformed
     pub async fn take_screenshot(&mut self) -> Result<Vec<u8>, IdeviceError> {
         // Send DLMessageTakeScreenshot
 
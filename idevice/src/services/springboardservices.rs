@@ -5,6 +5,118 @@
 
 use crate::{Idevice, IdeviceError, IdeviceService, obf};
 
+/// Orientation of the device's user interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterfaceOrientation {
+    /// Upright, home button or indicator at the bottom
+    Portrait,
+    /// Upside down
+    PortraitUpsideDown,
+    /// Rotated with the top of the interface on the left
+    LandscapeRight,
+    /// Rotated with the top of the interface on the right
+    LandscapeLeft,
+    /// Any value not known to this library
+    Unknown(u64),
+}
+
+impl From<u64> for InterfaceOrientation {
+    fn from(value: u64) -> Self {
+        match value {
+            1 => Self::Portrait,
+            2 => Self::PortraitUpsideDown,
+            3 => Self::LandscapeRight,
+            4 => Self::LandscapeLeft,
+            v => Self::Unknown(v),
+        }
+    }
+}
+
+/// An icon on the home screen, as listed in the icon state
+#[derive(Debug, Clone, PartialEq)]
+pub enum IconEntry {
+    /// An app
+    App {
+        /// Bundle identifier of the app
+        bundle_id: String,
+        /// Name shown under the icon
+        display_name: Option<String>,
+    },
+    /// A folder of icons
+    Folder {
+        /// Name of the folder
+        name: Option<String>,
+        /// The folder's pages, each a list of icons
+        pages: Vec<Vec<IconEntry>>,
+    },
+    /// Any other entry, such as a web clip or a widget
+    Other(plist::Value),
+}
+
+/// The home screen layout, as returned by [`SpringBoardServicesClient::get_icon_state`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct IconState {
+    /// Icons in the dock
+    pub dock: Vec<IconEntry>,
+    /// The home screen pages, each a list of icons
+    pub pages: Vec<Vec<IconEntry>>,
+}
+
+impl IconState {
+    /// Parses the raw icon state
+    ///
+    /// The first list is the dock, the rest are the home screen pages.
+    ///
+    /// # Errors
+    /// Returns `IdeviceError::UnexpectedResponse` if the value isn't a list of icon lists
+    pub fn from_value(value: &plist::Value) -> Result<Self, IdeviceError> {
+        let mut lists = parse_icon_lists(value)?.into_iter();
+        Ok(Self {
+            dock: lists.next().unwrap_or_default(),
+            pages: lists.collect(),
+        })
+    }
+}
+
+fn parse_icon_lists(value: &plist::Value) -> Result<Vec<Vec<IconEntry>>, IdeviceError> {
+    value
+        .as_array()
+        .ok_or(IdeviceError::UnexpectedResponse)?
+        .iter()
+        .map(|list| {
+            list.as_array()
+                .ok_or(IdeviceError::UnexpectedResponse)?
+                .iter()
+                .map(parse_icon_entry)
+                .collect()
+        })
+        .collect()
+}
+
+fn parse_icon_entry(value: &plist::Value) -> Result<IconEntry, IdeviceError> {
+    let Some(entry) = value.as_dictionary() else {
+        return Ok(IconEntry::Other(value.clone()));
+    };
+    let display_name = entry
+        .get("displayName")
+        .and_then(|n| n.as_string())
+        .map(str::to_string);
+
+    if let Some(lists) = entry.get("iconLists") {
+        return Ok(IconEntry::Folder {
+            name: display_name,
+            pages: parse_icon_lists(lists)?,
+        });
+    }
+    match entry.get("bundleIdentifier").and_then(|b| b.as_string()) {
+        Some(bundle_id) => Ok(IconEntry::App {
+            bundle_id: bundle_id.to_string(),
+            display_name,
+        }),
+        None => Ok(IconEntry::Other(value.clone())),
+    }
+}
+
 /// Client for interacting with the iOS SpringBoard services
 ///
 /// This service provides access to home screen and app icon functionality,
@@ -70,4 +182,125 @@ impl SpringBoardServicesClient {
             _ => Err(IdeviceError::UnexpectedResponse),
         }
     }
+
+    /// Retrieves the home screen wallpaper as PNG data
+    ///
+    /// The service can only read the wallpaper. It has no command to set one, so wallpapers
+    /// can't be changed through this crate.
+    ///
+    /// # Returns
+    /// The raw PNG data of the wallpaper
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails or the response is malformed
+    pub async fn get_wallpaper_pngdata(&mut self) -> Result<Vec<u8>, IdeviceError> {
+        let req = crate::plist!({
+            "command": "getHomeScreenWallpaperPNGData",
+        });
+        self.idevice.send_plist(req).await?;
+
+        let mut res = self.idevice.read_plist().await?;
+        match res.remove("pngData") {
+            Some(plist::Value::Data(res)) => Ok(res),
+            _ => Err(IdeviceError::UnexpectedResponse),
+        }
+    }
+
+    /// Retrieves the home screen layout
+    ///
+    /// Use [`IconState::from_value`] for a typed view of it. The raw value keeps every field,
+    /// so it can be modified and passed back to [`Self::set_icon_state`].
+    ///
+    /// # Returns
+    /// A list of icon lists, the dock followed by each home screen page
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails
+    pub async fn get_icon_state(&mut self) -> Result<plist::Value, IdeviceError> {
+        let req = crate::plist!({
+            "command": "getIconState",
+            "formatVersion": "2",
+        });
+        self.idevice.send_plist(req).await?;
+        self.idevice.read_plist_value().await
+    }
+
+    /// Rearranges the home screen
+    ///
+    /// SpringBoard doesn't answer, and ignores layouts it can't apply, so read the state
+    /// back to check the result.
+    ///
+    /// # Arguments
+    /// * `icon_state` - The layout, in the format returned by [`Self::get_icon_state`]
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails
+    pub async fn set_icon_state(&mut self, icon_state: plist::Value) -> Result<(), IdeviceError> {
+        let req = crate::plist!({
+            "command": "setIconState",
+            "iconState": icon_state,
+        });
+        self.idevice.send_plist(req).await
+    }
+
+    /// Gets the current orientation of the device's user interface
+    ///
+    /// # Returns
+    /// The interface orientation
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if communication fails or the response is malformed
+    pub async fn get_interface_orientation(
+        &mut self,
+    ) -> Result<InterfaceOrientation, IdeviceError> {
+        let req = crate::plist!({
+            "command": "getInterfaceOrientation",
+        });
+        self.idevice.send_plist(req).await?;
+
+        let res = self.idevice.read_plist().await?;
+        match res
+            .get("interfaceOrientation")
+            .and_then(|x| x.as_unsigned_integer())
+        {
+            Some(o) => Ok(o.into()),
+            None => Err(IdeviceError::UnexpectedResponse),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_icon_state() {
+        let raw = crate::plist!([
+            [{ "bundleIdentifier": "com.apple.mobilephone", "displayName": "Phone" }],
+            [
+                { "bundleIdentifier": "com.apple.Maps" },
+                {
+                    "displayName": "Utilities",
+                    "listType": "folder",
+                    "iconLists": [[{ "bundleIdentifier": "com.apple.calculator" }]],
+                },
+                { "webClipURL": "https://example.com" },
+            ],
+        ]);
+
+        let state = IconState::from_value(&raw).unwrap();
+        assert_eq!(
+            state.dock,
+            [IconEntry::App {
+                bundle_id: "com.apple.mobilephone".into(),
+                display_name: Some("Phone".into()),
+            }]
+        );
+        assert_eq!(state.pages.len(), 1);
+        assert!(matches!(
+            &state.pages[0][1],
+            IconEntry::Folder { name: Some(n), pages } if n == "Utilities" && pages[0].len() == 1
+        ));
+        assert!(matches!(state.pages[0][2], IconEntry::Other(_)));
+    }
 }
@@ -1,4 +1,11 @@
 //! iOS Device SyslogRelay Service Abstraction
+//!
+//! Streams the device's plaintext syslog, one line at a time, like `idevicesyslog`.
+
+use std::pin::Pin;
+
+use futures::Stream;
+use tokio_util::sync::CancellationToken;
 
 use crate::{Idevice, IdeviceError, IdeviceService, obf};
 
@@ -7,6 +14,10 @@ use crate::{Idevice, IdeviceError, IdeviceService, obf};
 pub struct SyslogRelayClient {
     /// The underlying device connection with established SyslogRelay service
     pub idevice: Idevice,
+    /// Data read past the end of the last returned line
+    buffer: bytes::BytesMut,
+    /// Cancels pending and future reads, closing the relay
+    cancel: CancellationToken,
 }
 
 impl IdeviceService for SyslogRelayClient {
@@ -26,7 +37,42 @@ impl SyslogRelayClient {
     /// # Arguments
     /// * `idevice` - Pre-established device connection
     pub fn new(idevice: Idevice) -> Self {
-        Self { idevice }
+        Self {
+            idevice,
+            buffer: bytes::BytesMut::new(),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Returns a token that cancels this client
+    ///
+    /// Cancelling aborts a pending read and closes the relay, so it can be done from another
+    /// task or thread to stop a client blocked waiting for logs. Reads then fail with
+    /// `IdeviceError::Canceled`, and the stream from [`Self::stream`] ends.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Reads the next line, or `None` on EOF
+    ///
+    /// Lines end with `\n`, `\0` or both, so the empty lines between the two are skipped.
+    async fn read_line(&mut self) -> Result<Option<String>, IdeviceError> {
+        loop {
+            let res = self
+                .cancel
+                .run_until_cancelled(self.idevice.read_until_delim(b"\n\x00", &mut self.buffer))
+                .await;
+            match res {
+                Some(line) => match line? {
+                    Some(l) if l.is_empty() => continue,
+                    l => return Ok(l.map(|l| String::from_utf8_lossy(&l).to_string())),
+                },
+                None => {
+                    self.idevice.close();
+                    return Err(IdeviceError::Canceled);
+                }
+            }
+        }
     }
 
     /// Get the next log from the relay
@@ -35,12 +81,75 @@ impl SyslogRelayClient {
     /// A string containing the log
     ///
     /// # Errors
-    /// UnexpectedResponse if the service sends an EOF
+    /// UnexpectedResponse if the service sends an EOF, or Canceled if the client was canceled
     pub async fn next(&mut self) -> Result<String, IdeviceError> {
-        let res = self.idevice.read_until_delim(b"\n\x00").await?;
-        match res {
-            Some(res) => Ok(String::from_utf8_lossy(&res).to_string()),
-            None => Err(IdeviceError::UnexpectedResponse),
-        }
+        self.read_line()
+            .await?
+            .ok_or(IdeviceError::UnexpectedResponse)
+    }
+
+    /// Streams log lines from the relay
+    ///
+    /// The stream ends when the device closes the relay or the client is canceled.
+    pub fn stream(
+        &mut self,
+    ) -> Pin<Box<dyn Stream<Item = Result<String, IdeviceError>> + Send + '_>> {
+        Box::pin(futures::stream::try_unfold(self, |client| async move {
+            match client.read_line().await {
+                Ok(Some(line)) => Ok(Some((line, client))),
+                Ok(None) | Err(IdeviceError::Canceled) => Ok(None),
+                Err(e) => Err(e),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn splits_lines_within_one_read() {
+        let (mut device, host) = tokio::io::duplex(1024);
+        let mut client = SyslogRelayClient::new(Idevice::new(Box::new(host), "test"));
+
+        device
+            .write_all(b"first line\n\x00second line\n\x00")
+            .await
+            .unwrap();
+        drop(device);
+
+        let lines: Vec<_> = client.stream().map(|l| l.unwrap()).collect().await;
+        assert_eq!(lines, ["first line", "second line"]);
+    }
+
+    #[tokio::test]
+    async fn splits_lines_on_either_terminator() {
+        let (mut device, host) = tokio::io::duplex(1024);
+        let mut client = SyslogRelayClient::new(Idevice::new(Box::new(host), "test"));
+
+        device
+            .write_all(b"newline\nnul\x00both\n\x00last")
+            .await
+            .unwrap();
+        drop(device);
+
+        let lines: Vec<_> = client.stream().map(|l| l.unwrap()).collect().await;
+        assert_eq!(lines, ["newline", "nul", "both", "last"]);
+    }
+
+    #[tokio::test]
+    async fn cancel_aborts_pending_read() {
+        let (_device, host) = tokio::io::duplex(1024);
+        let mut client = SyslogRelayClient::new(Idevice::new(Box::new(host), "test"));
+
+        let token = client.cancellation_token();
+        tokio::spawn(async move { token.cancel() });
+
+        assert!(matches!(client.next().await, Err(IdeviceError::Canceled)));
+        assert!(client.stream().next().await.is_none());
     }
 }
@@ -0,0 +1,353 @@
+//! Scripted device connections for testing
+//!
+//! [`MockProvider`] hands out in-memory connections that play back a [`MockScript`] instead
+//! of talking to a device. Each script is a list of plists the client is expected to send and
+//! the responses the device sends back, so code built on this crate can be tested
+//! deterministically without hardware.
+//!
+//! ```rust,no_run
+//! # async fn example() -> Result<(), idevice::IdeviceError> {
+//! use idevice::{IdeviceService, notification_proxy::NotificationProxyClient};
+//! use idevice::testing::{MockProvider, MockScript};
+//!
+//! let provider = MockProvider::new().with_service(
+//!     "com.apple.mobile.notification_proxy",
+//!     MockScript::new().expect(plist::Value::Dictionary(plist::Dictionary::from_iter([
+//!         ("Command".to_string(), "PostNotification"),
+//!         ("Name".to_string(), "com.example.hello"),
+//!     ]))),
+//! );
+//!
+//! let mut client = NotificationProxyClient::connect(&provider).await?;
+//! client.post("com.example.hello").await?;
+//! drop(client);
+//!
+//! assert_eq!(provider.verify().await, Vec::<String>::new());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream},
+    task::JoinHandle,
+};
+
+use crate::{Idevice, IdeviceError, pairing_file::PairingFile, provider::IdeviceProvider};
+
+/// Size of the in-memory buffer between the client and the script
+const DUPLEX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// One step of a scripted exchange
+#[derive(Debug, Clone)]
+enum MockStep {
+    /// Read a plist from the client and check it against the expected one
+    Expect(plist::Value),
+    /// Send a plist to the client
+    Respond(plist::Value),
+    /// Read exactly these bytes from the client
+    ExpectRaw(Vec<u8>),
+    /// Send these bytes to the client
+    RespondRaw(Vec<u8>),
+}
+
+/// A scripted exchange played back on one connection
+///
+/// Plists are framed the way lockdown services frame them, with a 4-byte big-endian length.
+#[derive(Debug, Clone, Default)]
+pub struct MockScript {
+    steps: Vec<MockStep>,
+}
+
+impl MockScript {
+    /// Creates an empty script
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expects the client to send a plist
+    ///
+    /// A received dictionary matches if it contains every key of `request` with an equal value,
+    /// so fields like `Label` can be left out. Other values must be equal.
+    pub fn expect(mut self, request: plist::Value) -> Self {
+        self.steps.push(MockStep::Expect(request));
+        self
+    }
+
+    /// Sends a plist to the client
+    pub fn respond(mut self, response: plist::Value) -> Self {
+        self.steps.push(MockStep::Respond(response));
+        self
+    }
+
+    /// Expects the client to send exactly these bytes
+    pub fn expect_raw(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.steps.push(MockStep::ExpectRaw(data.into()));
+        self
+    }
+
+    /// Sends these bytes to the client
+    pub fn respond_raw(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.steps.push(MockStep::RespondRaw(data.into()));
+        self
+    }
+
+    /// Plays the script against the device end of a connection
+    async fn play(self, mut device: DuplexStream) -> Result<(), String> {
+        for (i, step) in self.steps.into_iter().enumerate() {
+            match step {
+                MockStep::Expect(expected) => {
+                    let received = read_plist(&mut device)
+                        .await
+                        .map_err(|e| format!("step {i}: expected {expected:?}, got {e}"))?;
+                    if !plist_matches(&expected, &received) {
+                        return Err(format!("step {i}: expected {expected:?}, got {received:?}"));
+                    }
+                }
+                MockStep::Respond(response) => {
+                    let mut buf = Vec::new();
+                    response
+                        .to_writer_xml(&mut buf)
+                        .map_err(|e| format!("step {i}: {e}"))?;
+                    let mut framed = (buf.len() as u32).to_be_bytes().to_vec();
+                    framed.extend(buf);
+                    device
+                        .write_all(&framed)
+                        .await
+                        .map_err(|e| format!("step {i}: {e}"))?;
+                }
+                MockStep::ExpectRaw(expected) => {
+                    let mut received = vec![0; expected.len()];
+                    device
+                        .read_exact(&mut received)
+                        .await
+                        .map_err(|e| format!("step {i}: expected {expected:02X?}, got {e}"))?;
+                    if received != expected {
+                        return Err(format!(
+                            "step {i}: expected {expected:02X?}, got {received:02X?}"
+                        ));
+                    }
+                }
+                MockStep::RespondRaw(data) => {
+                    device
+                        .write_all(&data)
+                        .await
+                        .map_err(|e| format!("step {i}: {e}"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn read_plist(device: &mut DuplexStream) -> Result<plist::Value, String> {
+    let len = device.read_u32().await.map_err(|e| e.to_string())?;
+    let mut buf = vec![0; len as usize];
+    device
+        .read_exact(&mut buf)
+        .await
+        .map_err(|e| e.to_string())?;
+    plist::from_bytes(&buf).map_err(|e| e.to_string())
+}
+
+/// Checks a received plist against an expected one, only requiring the expected dictionary keys
+fn plist_matches(expected: &plist::Value, received: &plist::Value) -> bool {
+    match (expected, received) {
+        (plist::Value::Dictionary(e), plist::Value::Dictionary(r)) => e
+            .iter()
+            .all(|(k, v)| r.get(k).is_some_and(|r| plist_matches(v, r))),
+        _ => expected == received,
+    }
+}
+
+/// Provider whose connections play back scripts instead of talking to a device
+///
+/// Scripts are registered per service name, used by [`crate::IdeviceService::connect`], or
+/// per port, used by [`IdeviceProvider::connect`]. Each connection takes the next script
+/// queued for it, and fails with `IdeviceError::ServiceNotFound` if none is left.
+///
+/// Connections must be made from within a Tokio runtime, since every script runs in a task.
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    services: Mutex<HashMap<String, VecDeque<MockScript>>>,
+    ports: Mutex<HashMap<u16, VecDeque<MockScript>>>,
+    tasks: Mutex<Vec<JoinHandle<Result<(), String>>>>,
+    /// Pairing file returned by [`IdeviceProvider::get_pairing_file`]
+    pub pairing_file: Option<PairingFile>,
+    /// Connection label
+    pub label: String,
+}
+
+impl MockProvider {
+    /// Creates a provider with no scripts
+    pub fn new() -> Self {
+        Self {
+            label: "mock".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Queues a script for the next connection to a service
+    pub fn with_service(self, service_name: impl Into<String>, script: MockScript) -> Self {
+        self.services
+            .lock()
+            .unwrap()
+            .entry(service_name.into())
+            .or_default()
+            .push_back(script);
+        self
+    }
+
+    /// Queues a script for the next connection to a port
+    pub fn with_port(self, port: u16, script: MockScript) -> Self {
+        self.ports
+            .lock()
+            .unwrap()
+            .entry(port)
+            .or_default()
+            .push_back(script);
+        self
+    }
+
+    /// Sets the pairing file returned by [`IdeviceProvider::get_pairing_file`]
+    pub fn with_pairing_file(mut self, pairing_file: PairingFile) -> Self {
+        self.pairing_file = Some(pairing_file);
+        self
+    }
+
+    /// Waits for every started script to finish, and returns what went wrong
+    ///
+    /// Drop the clients first, or scripts still waiting for a request will never finish.
+    ///
+    /// # Returns
+    /// A description of every failed expectation and every script that was never used.
+    /// Empty if the exchange went exactly as scripted.
+    pub async fn verify(&self) -> Vec<String> {
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        let mut failures = Vec::new();
+        for task in tasks {
+            match task.await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => failures.push(e),
+                Err(e) => failures.push(format!("script panicked: {e}")),
+            }
+        }
+        for (name, scripts) in self.services.lock().unwrap().iter() {
+            if !scripts.is_empty() {
+                failures.push(format!("{} unused script(s) for {name}", scripts.len()));
+            }
+        }
+        for (port, scripts) in self.ports.lock().unwrap().iter() {
+            if !scripts.is_empty() {
+                failures.push(format!(
+                    "{} unused script(s) for port {port}",
+                    scripts.len()
+                ));
+            }
+        }
+        failures
+    }
+
+    /// Starts playing a script, returning the client end of the connection
+    fn start(&self, script: MockScript) -> Idevice {
+        let (client, device) = tokio::io::duplex(DUPLEX_BUFFER_SIZE);
+        let task = tokio::spawn(script.play(device));
+        self.tasks.lock().unwrap().push(task);
+        Idevice::new(Box::new(client), self.label.clone())
+    }
+}
+
+impl IdeviceProvider for MockProvider {
+    fn connect(
+        &self,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = Result<Idevice, IdeviceError>> + Send>> {
+        let script = self
+            .ports
+            .lock()
+            .unwrap()
+            .get_mut(&port)
+            .and_then(|s| s.pop_front());
+        let res = script
+            .map(|s| self.start(s))
+            .ok_or(IdeviceError::ServiceNotFound);
+        Box::pin(async move { res })
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn get_pairing_file(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<PairingFile, IdeviceError>> + Send>> {
+        let pairing_file = self.pairing_file.clone();
+        Box::pin(async move { pairing_file.ok_or(IdeviceError::InvalidHostID) })
+    }
+
+    /// Connects to a scripted service, or falls back to lockdown if none is registered
+    fn connect_service(
+        &self,
+        service_name: &str,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Idevice>, IdeviceError>> + Send>> {
+        let script = self
+            .services
+            .lock()
+            .unwrap()
+            .get_mut(service_name)
+            .and_then(|s| s.pop_front());
+        let res = script.map(|s| self.start(s));
+        Box::pin(async move { Ok(res) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IdeviceService, lockdown::LockdownClient};
+
+    #[tokio::test]
+    async fn plays_scripted_exchange() {
+        let provider = MockProvider::new().with_service(
+            "com.apple.mobile.lockdown",
+            MockScript::new()
+                .expect(crate::plist!({
+                    "Request": "GetValue",
+                    "Key": "DeviceName",
+                }))
+                .respond(crate::plist!({
+                    "Request": "GetValue",
+                    "Value": "Test iPhone",
+                })),
+        );
+
+        let mut lockdown = LockdownClient::connect(&provider).await.unwrap();
+        assert_eq!(lockdown.device_name().await.unwrap(), "Test iPhone");
+        drop(lockdown);
+
+        assert!(provider.verify().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_mismatched_request() {
+        let provider = MockProvider::new().with_port(
+            1,
+            MockScript::new().expect(crate::plist!({ "Request": "Expected" })),
+        );
+
+        let mut idevice = provider.connect(1).await.unwrap();
+        idevice
+            .send_plist(crate::plist!({ "Request": "Other" }))
+            .await
+            .unwrap();
+        drop(idevice);
+
+        assert_eq!(provider.verify().await.len(), 1);
+    }
+}
@@ -40,10 +40,28 @@ impl DeviceListResponse {
     }
 }
 
+/// Reads the address of a `sockaddr_in6`, which starts at offset 8
+fn ipv6_from_sockaddr(addr: &[u8]) -> Ipv6Addr {
+    let octets: [u8; 16] = addr[8..24].try_into().unwrap();
+    Ipv6Addr::from(octets)
+}
+
+/// Reads the zone index of a `sockaddr_in6`, or 0 if it was cut off
+///
+/// The index follows the address, in the byte order of the host running usbmuxd. Link-local
+/// addresses can't be reached without it.
+fn scope_id_from_sockaddr(addr: &[u8]) -> u32 {
+    match addr.get(24..28) {
+        Some(scope_id) => u32::from_le_bytes(scope_id.try_into().unwrap()),
+        None => 0,
+    }
+}
+
 impl TryFrom<DeviceListResponse> for UsbmuxdDevice {
     type Error = IdeviceError;
 
     fn try_from(dev: DeviceListResponse) -> Result<Self, Self::Error> {
+        let mut scope_id = 0;
         let connection_type = match dev.properties.connection_type.as_str() {
             "Network" => {
                 if let Some(addr) = dev.properties.network_address {
@@ -67,16 +85,8 @@ impl TryFrom<DeviceListResponse> for UsbmuxdDevice {
                                 return Err(IdeviceError::UnexpectedResponse);
                             }
 
-                            Connection::Network(IpAddr::V6(Ipv6Addr::new(
-                                u16::from_be_bytes([addr[8], addr[9]]),
-                                u16::from_be_bytes([addr[10], addr[11]]),
-                                u16::from_be_bytes([addr[12], addr[13]]),
-                                u16::from_be_bytes([addr[14], addr[15]]),
-                                u16::from_be_bytes([addr[16], addr[17]]),
-                                u16::from_be_bytes([addr[18], addr[19]]),
-                                u16::from_be_bytes([addr[20], addr[21]]),
-                                u16::from_be_bytes([addr[22], addr[23]]),
-                            )))
+                            scope_id = scope_id_from_sockaddr(addr);
+                            Connection::Network(IpAddr::V6(ipv6_from_sockaddr(addr)))
                         }
                         0x1C => {
                             if addr.len() < 28 {
@@ -84,17 +94,8 @@ impl TryFrom<DeviceListResponse> for UsbmuxdDevice {
                                 return Err(IdeviceError::UnexpectedResponse);
                             }
                             if addr[1] == 0x1E {
-                                // IPv6 address starts at offset 8 in sockaddr_in6
-                                Connection::Network(IpAddr::V6(Ipv6Addr::new(
-                                    u16::from_be_bytes([addr[8], addr[9]]),
-                                    u16::from_be_bytes([addr[10], addr[11]]),
-                                    u16::from_be_bytes([addr[12], addr[13]]),
-                                    u16::from_be_bytes([addr[14], addr[15]]),
-                                    u16::from_be_bytes([addr[16], addr[17]]),
-                                    u16::from_be_bytes([addr[18], addr[19]]),
-                                    u16::from_be_bytes([addr[20], addr[21]]),
-                                    u16::from_be_bytes([addr[22], addr[23]]),
-                                )))
+                                scope_id = scope_id_from_sockaddr(addr);
+                                Connection::Network(IpAddr::V6(ipv6_from_sockaddr(addr)))
                             } else {
                                 warn!(
                                     "Expected IPv6 family (0x1E) but got {:02X} for length 0x1C",
@@ -121,6 +122,37 @@ impl TryFrom<DeviceListResponse> for UsbmuxdDevice {
             connection_type,
             udid: dev.properties.serial_number,
             device_id: dev.device_id,
+            scope_id,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_scope_id_of_link_local_address() {
+        // sockaddr_in6 for fe80::1c2b:3cff:fe4d:5e6f%4
+        let mut sockaddr = vec![0x1C, 0x1E, 0, 0, 0, 0, 0, 0];
+        sockaddr.extend([
+            0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0x1c, 0x2b, 0x3c, 0xff, 0xfe, 0x4d, 0x5e, 0x6f,
+        ]);
+        sockaddr.extend(4u32.to_le_bytes());
+        let response = DeviceListResponse {
+            device_id: 7,
+            properties: DevicePropertiesResponse {
+                connection_type: "Network".to_string(),
+                network_address: Some(sockaddr.into()),
+                serial_number: "00008030-001A".to_string(),
+            },
+        };
+
+        let device = response.into_usbmuxd_dev().unwrap();
+        assert_eq!(
+            device.connection_type,
+            Connection::Network("fe80::1c2b:3cff:fe4d:5e6f".parse().unwrap())
+        );
+        assert_eq!(device.scope_id, 4);
+    }
+}
@@ -7,18 +7,19 @@ use std::{
     net::{AddrParseError, IpAddr, SocketAddr},
     pin::Pin,
     str::FromStr,
+    time::Duration,
 };
 
 #[cfg(not(unix))]
 use std::net::{Ipv4Addr, SocketAddrV4};
 
-use futures::Stream;
+use futures::{Stream, StreamExt};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{debug, warn};
 
 use crate::{
-    Idevice, IdeviceError, ReadWrite, pairing_file::PairingFile, provider::UsbmuxdProvider,
-    usbmuxd::des::DeviceListResponse,
+    Idevice, IdeviceError, IdeviceService, ReadWrite, lockdown::LockdownClient,
+    pairing_file::PairingFile, provider::UsbmuxdProvider, usbmuxd::des::DeviceListResponse,
 };
 
 mod des;
@@ -44,6 +45,11 @@ pub struct UsbmuxdDevice {
     pub udid: String,
     /// usbmuxd-assigned device ID
     pub device_id: u32,
+    /// Zone index of the interface a network device was found on, or 0 for none
+    ///
+    /// Link-local IPv6 addresses (`fe80::/10`) are only reachable through the interface they
+    /// belong to, like `fe80::1%en0`. Always 0 for devices attached over USB.
+    pub scope_id: u32,
 }
 
 /// Listen events from the socket
@@ -231,15 +237,29 @@ impl UsbmuxdConnection {
     /// # Returns
     /// The pairing file or error
     pub async fn get_pair_record(&mut self, udid: &str) -> Result<PairingFile, IdeviceError> {
+        let raw = self.get_pair_record_bytes(udid).await?;
+        PairingFile::from_bytes(&raw)
+    }
+
+    /// Gets the pairing record for a device as the raw plist stored by usbmuxd
+    ///
+    /// Useful when the record needs to be handed off unparsed, such as to another process.
+    ///
+    /// # Arguments
+    /// * `udid` - The device UDID
+    ///
+    /// # Returns
+    /// The plist bytes of the pairing record or error
+    pub async fn get_pair_record_bytes(&mut self, udid: &str) -> Result<Vec<u8>, IdeviceError> {
         debug!("Getting pair record for {udid}");
         let mut req = plist::Dictionary::new();
         req.insert("MessageType".into(), "ReadPairRecord".into());
         req.insert("PairRecordID".into(), udid.into());
         self.write_plist(req).await?;
-        let res = self.read_plist().await?;
+        let mut res = self.read_plist().await?;
 
-        match res.get("PairRecordData") {
-            Some(plist::Value::Data(d)) => PairingFile::from_bytes(d),
+        match res.remove("PairRecordData") {
+            Some(plist::Value::Data(d)) => Ok(d),
             _ => Err(IdeviceError::UnexpectedResponse),
         }
     }
@@ -320,10 +340,19 @@ impl UsbmuxdConnection {
         }
     }
 
+    /// Subscribes to device attach and detach events
+    ///
+    /// The connection cannot be used for other requests while the stream is alive.
+    ///
+    /// # Returns
+    /// A stream yielding an event each time a device is attached or detached
+    ///
+    /// # Errors
+    /// Returns `IdeviceError` if usbmuxd rejects the listen request
     pub async fn listen<'a>(
         &'a mut self,
     ) -> Result<
-        Pin<Box<dyn Stream<Item = Result<UsbmuxdListenEvent, IdeviceError>> + 'a>>,
+        Pin<Box<dyn Stream<Item = Result<UsbmuxdListenEvent, IdeviceError>> + Send + 'a>>,
         IdeviceError,
     > {
         let req = crate::plist!(dict {
@@ -438,6 +467,221 @@ impl UsbmuxdConnection {
     }
 }
 
+/// How long [`list_devices`] waits for each device to report its name
+const DEVICE_NAME_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A device attached to usbmuxd, as returned by [`list_devices`]
+#[derive(Debug, Clone)]
+pub struct DeviceEntry {
+    /// Unique Device Identifier
+    pub udid: String,
+    /// How the device is connected
+    pub connection_type: Connection,
+    /// usbmuxd-assigned device ID
+    pub device_id: u32,
+    /// User-assigned name of the device, if lockdown reported it in time
+    pub device_name: Option<String>,
+}
+
+/// Lists the devices attached to the local usbmuxd
+///
+/// usbmuxd is located the same way as [`connect_usbmuxd`]. Each device is asked for its name
+/// through lockdown, which doesn't require pairing. Devices that don't answer within a second
+/// are still listed, without a name. A device attached over both USB and the network is
+/// listed once per connection.
+pub async fn list_devices() -> Result<Vec<DeviceEntry>, IdeviceError> {
+    let addr = UsbmuxdAddr::from_env_var().unwrap_or_default();
+    let devices = addr.connect(0).await?.get_devices().await?;
+
+    let mut entries = Vec::with_capacity(devices.len());
+    for device in devices {
+        let provider = device.to_provider(addr.clone(), "idevice-rs");
+        let device_name = tokio::time::timeout(DEVICE_NAME_TIMEOUT, async {
+            LockdownClient::connect(&provider)
+                .await?
+                .device_name()
+                .await
+        })
+        .await;
+        let device_name = match device_name {
+            Ok(Ok(name)) => Some(name),
+            Ok(Err(e)) => {
+                debug!("Failed to get the name of {}: {e:?}", device.udid);
+                None
+            }
+            Err(_) => {
+                debug!("Timed out getting the name of {}", device.udid);
+                None
+            }
+        };
+
+        entries.push(DeviceEntry {
+            udid: device.udid,
+            connection_type: device.connection_type,
+            device_id: device.device_id,
+            device_name,
+        });
+    }
+    Ok(entries)
+}
+
+/// Creates a provider for a device attached to the local usbmuxd
+///
+/// usbmuxd is located through `USBMUXD_SOCKET_ADDRESS`, or the platform default if it's
+/// unset or invalid. A device attached over USB is preferred to the same device attached
+/// over the network. The device's pair record is read up front, so an unpaired device
+/// fails here instead of on the first connection.
+///
+/// # Arguments
+/// * `udid` - UDID of the device to connect to
+///
+/// # Errors
+/// Returns `IdeviceError::DeviceNotFound` if no device with that UDID is attached, or the
+/// error from reading the pair record
+pub async fn connect_usbmuxd(udid: &str) -> Result<UsbmuxdProvider, IdeviceError> {
+    let addr = UsbmuxdAddr::from_env_var().unwrap_or_default();
+    let mut conn = addr.connect(0).await?;
+
+    let device = conn
+        .get_devices()
+        .await?
+        .into_iter()
+        .filter(|d| d.udid == udid)
+        .min_by_key(|d| d.connection_type != Connection::Usb)
+        .ok_or(IdeviceError::DeviceNotFound)?;
+
+    conn.get_pair_record(udid).await?;
+
+    Ok(device.to_provider(addr, "idevice-rs"))
+}
+
+/// How often [`wait_for_device`] retries lockdown while an attached device boots
+const DEVICE_READY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Waits for a device to attach to the local usbmuxd and become ready
+///
+/// usbmuxd is located the same way as [`connect_usbmuxd`]. Attach events are watched instead
+/// of polling the device list, and a device that is already attached returns right away.
+/// Right after a reboot a device attaches before lockdown is up, so the device is only
+/// returned once lockdown answers.
+///
+/// # Arguments
+/// * `udid` - UDID of the device to wait for
+/// * `timeout` - How long to wait in total
+///
+/// # Errors
+/// Returns `IdeviceError::Timeout` if the device isn't ready in time
+pub async fn wait_for_device(
+    udid: &str,
+    timeout: Duration,
+) -> Result<UsbmuxdProvider, IdeviceError> {
+    let addr = UsbmuxdAddr::from_env_var().unwrap_or_default();
+
+    let wait = async {
+        // Listen before listing, so an attach between the two isn't missed
+        let mut listen_conn = addr.connect(0).await?;
+        let mut events = listen_conn.listen().await?;
+
+        let attached = addr
+            .connect(0)
+            .await?
+            .get_devices()
+            .await?
+            .into_iter()
+            .filter(|d| d.udid == udid)
+            .min_by_key(|d| d.connection_type != Connection::Usb);
+        let device = match attached {
+            Some(d) => d,
+            None => loop {
+                match events.next().await {
+                    Some(Ok(UsbmuxdListenEvent::Connected(d))) if d.udid == udid => break d,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e),
+                    None => return Err(IdeviceError::NoEstablishedConnection),
+                }
+            },
+        };
+        debug!("{udid} attached, waiting for lockdown");
+
+        let provider = device.to_provider(addr.clone(), "idevice-rs");
+        loop {
+            let ready = async {
+                LockdownClient::connect(&provider)
+                    .await?
+                    .device_name()
+                    .await
+            };
+            match ready.await {
+                Ok(_) => return Ok(provider),
+                Err(e) => debug!("Lockdown on {udid} isn't ready: {e:?}"),
+            }
+            tokio::time::sleep(DEVICE_READY_POLL_INTERVAL).await;
+        }
+    };
+
+    tokio::time::timeout(timeout, wait)
+        .await
+        .unwrap_or(Err(IdeviceError::Timeout))
+}
+
+/// Runs an operation against every device attached to the local usbmuxd
+///
+/// usbmuxd is located the same way as [`connect_usbmuxd`]. A device attached over both USB
+/// and the network is only visited once, through USB. At most `concurrency` operations run
+/// at a time, and a failing device doesn't stop the others.
+///
+/// # Arguments
+/// * `concurrency` - How many devices to work on at once, at least 1
+/// * `f` - The operation, given a provider for the device
+///
+/// # Returns
+/// Each device's UDID and result, in the order the operations finished
+///
+/// # Errors
+/// Returns `IdeviceError` if the devices can't be listed
+pub async fn for_each_device<F, Fut, T>(
+    concurrency: usize,
+    f: F,
+) -> Result<Vec<(String, Result<T, IdeviceError>)>, IdeviceError>
+where
+    F: Fn(UsbmuxdProvider) -> Fut,
+    Fut: Future<Output = Result<T, IdeviceError>>,
+{
+    let addr = UsbmuxdAddr::from_env_var().unwrap_or_default();
+    let mut devices = addr.connect(0).await?.get_devices().await?;
+    devices.sort_by(|a, b| {
+        a.udid.cmp(&b.udid).then_with(|| {
+            (a.connection_type != Connection::Usb).cmp(&(b.connection_type != Connection::Usb))
+        })
+    });
+    devices.dedup_by(|a, b| a.udid == b.udid);
+
+    let providers = devices
+        .into_iter()
+        .map(|d| (d.udid.clone(), d.to_provider(addr.clone(), "idevice-rs")))
+        .collect();
+    Ok(run_bounded(providers, concurrency, f).await)
+}
+
+/// Runs `f` on each item, with at most `concurrency` running at a time
+async fn run_bounded<P, F, Fut, T>(
+    items: Vec<(String, P)>,
+    concurrency: usize,
+    f: F,
+) -> Vec<(String, Result<T, IdeviceError>)>
+where
+    F: Fn(P) -> Fut,
+    Fut: Future<Output = Result<T, IdeviceError>>,
+{
+    futures::stream::iter(items.into_iter().map(|(udid, item)| {
+        let fut = f(item);
+        async move { (udid, fut.await) }
+    }))
+    .buffer_unordered(concurrency.max(1))
+    .collect()
+    .await
+}
+
 impl UsbmuxdDevice {
     /// Creates a provider for this device
     ///
@@ -449,14 +693,69 @@ impl UsbmuxdDevice {
     /// # Returns
     /// Configured `UsbmuxdProvider`
     pub fn to_provider(&self, addr: UsbmuxdAddr, label: impl Into<String>) -> UsbmuxdProvider {
-        let label = label.into();
-
-        UsbmuxdProvider {
-            addr,
-            tag: self.device_id,
-            udid: self.udid.clone(),
-            device_id: self.device_id,
-            label,
+        UsbmuxdProvider::new(addr, self.device_id, &self.udid, self.device_id, label)
+    }
+
+    /// Creates a provider connecting straight to a network attached device
+    ///
+    /// # Arguments
+    /// * `pairing_file` - Pairing file for the device
+    /// * `label` - Connection label
+    ///
+    /// # Returns
+    /// A `TcpProvider` for the device's address and zone index, or `None` if the device
+    /// isn't attached over the network
+    #[cfg(feature = "tcp")]
+    pub fn to_network_provider(
+        &self,
+        pairing_file: PairingFile,
+        label: impl Into<String>,
+    ) -> Option<crate::provider::TcpProvider> {
+        match self.connection_type {
+            Connection::Network(addr) => Some(
+                crate::provider::TcpProvider::new(addr, pairing_file, label)
+                    .with_scope_id(self.scope_id),
+            ),
+            _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn run_bounded_limits_concurrency() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let items = (0..8).map(|i| (format!("udid-{i}"), i)).collect();
+
+        let results = run_bounded(items, 3, |i: u32| {
+            let (running, peak) = (&running, &peak);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                if i == 5 {
+                    Err(IdeviceError::DeviceNotFound)
+                } else {
+                    Ok(i)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert_eq!(results.len(), 8);
+        assert!(
+            results
+                .iter()
+                .any(|(udid, r)| udid == "udid-5" && r.is_err())
+        );
+        assert_eq!(results.iter().filter(|(_, r)| r.is_ok()).count(), 7);
+    }
+}
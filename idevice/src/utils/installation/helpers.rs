@@ -16,6 +16,9 @@ pub const IPCC_REMOTE_FILE: &str = "idevice.ipcc";
 
 pub const IPA_REMOTE_FILE: &str = "idevice.ipa";
 
+/// Size of each write while uploading a package, so progress can be reported
+const UPLOAD_CHUNK_SIZE: usize = 1024 * 1024;
+
 /// Result of a prepared upload, containing the remote path to use in Install/Upgrade
 pub struct InstallPackage {
     /// Path inside the AFC jail for InstallationProxy `PackagePath`
@@ -135,30 +138,44 @@ pub async fn determine_package_type<P: AsRef<[u8]>>(
 }
 
 /// Upload a single file to a destination path on device using AFC
+///
+/// `on_written` is called with the number of bytes sent after each chunk.
 pub async fn afc_upload_file<F: AsRef<[u8]>>(
     afc: &mut AfcClient,
     file: F,
     remote_path: &str,
+    mut on_written: impl FnMut(u64),
 ) -> Result<(), IdeviceError> {
     let mut fd = afc.open(remote_path, AfcFopenMode::WrOnly).await?;
-    fd.write_entire(file.as_ref()).await?;
+    for chunk in file.as_ref().chunks(UPLOAD_CHUNK_SIZE) {
+        fd.write_entire(chunk).await?;
+        on_written(chunk.len() as u64);
+    }
     fd.close().await
 }
 
 /// Recursively upload a directory to device via AFC (mirror contents)
+///
+/// `on_progress` is called with the bytes uploaded so far and the size of all files, before
+/// the first file and after each chunk. Nothing is uploaded if the device lacks the space for
+/// all of them.
 pub async fn afc_upload_dir(
     afc: &mut AfcClient,
     local_dir: &Path,
     remote_dir: &str,
+    mut on_progress: impl FnMut(u64, u64),
 ) -> Result<(), IdeviceError> {
     use std::collections::VecDeque;
-    afc.mk_dir(remote_dir).await.ok();
 
+    // Walk the tree first, so the total size is known before anything is uploaded
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
     let mut queue: VecDeque<(std::path::PathBuf, String)> = VecDeque::new();
     queue.push_back((local_dir.to_path_buf(), remote_dir.to_string()));
 
     while let Some((cur_local, cur_remote)) = queue.pop_front() {
         let mut rd = tokio::fs::read_dir(&cur_local).await?;
+        dirs.push(cur_remote.clone());
         while let Some(entry) = rd.next_entry().await? {
             let meta = entry.metadata().await?;
             let name = entry.file_name();
@@ -169,13 +186,27 @@ pub async fn afc_upload_dir(
             let child_local = entry.path();
             let child_remote = format!("{cur_remote}/{name}");
             if meta.is_dir() {
-                afc.mk_dir(&child_remote).await.ok();
                 queue.push_back((child_local, child_remote));
             } else if meta.is_file() {
-                afc_upload_file(afc, tokio::fs::read(&child_local).await?, &child_remote).await?;
+                files.push((child_local, child_remote, meta.len()));
             }
         }
     }
+
+    let total = files.iter().map(|(_, _, len)| len).sum();
+    afc.ensure_free_space(total).await?;
+    let mut sent = 0;
+    on_progress(sent, total);
+    for dir in dirs {
+        afc.mk_dir(&dir).await.ok();
+    }
+    for (local, remote, _) in files {
+        afc_upload_file(afc, tokio::fs::read(&local).await?, &remote, |n| {
+            sent += n;
+            on_progress(sent, total);
+        })
+        .await?;
+    }
     Ok(())
 }
 
@@ -183,6 +214,7 @@ pub async fn afc_upload_dir(
 async fn upload_file_to_public_staging<P: AsRef<[u8]>>(
     provider: &dyn IdeviceProvider,
     file: P,
+    mut on_progress: impl FnMut(u64, u64),
 ) -> Result<InstallPackage, IdeviceError> {
     // Connect to AFC via the generic service connector
     let mut afc = AfcClient::connect(provider).await?;
@@ -195,7 +227,15 @@ async fn upload_file_to_public_staging<P: AsRef<[u8]>>(
 
     let remote_path = format!("{PUBLIC_STAGING}/{}", package_type.get_remote_file()?);
 
-    afc_upload_file(&mut afc, file, &remote_path).await?;
+    let total = file.len() as u64;
+    afc.ensure_free_space(total).await?;
+    let mut sent = 0;
+    on_progress(sent, total);
+    afc_upload_file(&mut afc, file, &remote_path, |n| {
+        sent += n;
+        on_progress(sent, total);
+    })
+    .await?;
 
     let options = match package_type {
         PackageType::Ipcc => plist!({"PackageType": "CarrierBundle"}),
@@ -213,6 +253,7 @@ async fn upload_file_to_public_staging<P: AsRef<[u8]>>(
 async fn upload_dir_to_public_staging<P: AsRef<Path>>(
     provider: &dyn IdeviceProvider,
     file: P,
+    on_progress: impl FnMut(u64, u64),
 ) -> Result<InstallPackage, IdeviceError> {
     let mut afc = AfcClient::connect(provider).await?;
 
@@ -222,7 +263,7 @@ async fn upload_dir_to_public_staging<P: AsRef<Path>>(
 
     let remote_path = format!("{PUBLIC_STAGING}/{IPA_REMOTE_FILE}");
 
-    afc_upload_dir(&mut afc, file, &remote_path).await?;
+    afc_upload_dir(&mut afc, file, &remote_path, on_progress).await?;
 
     Ok(InstallPackage {
         remote_package_path: remote_path,
@@ -234,11 +275,12 @@ pub async fn prepare_file_upload(
     provider: &dyn IdeviceProvider,
     data: impl AsRef<[u8]>,
     caller_options: Option<plist::Value>,
+    on_progress: impl FnMut(u64, u64),
 ) -> Result<InstallPackage, IdeviceError> {
     let InstallPackage {
         remote_package_path,
         options,
-    } = upload_file_to_public_staging(provider, data).await?;
+    } = upload_file_to_public_staging(provider, data, on_progress).await?;
     let full_options = plist!({
         :<? caller_options,
         :< options,
@@ -254,11 +296,12 @@ pub async fn prepare_dir_upload(
     provider: &dyn IdeviceProvider,
     local_path: impl AsRef<Path>,
     caller_options: Option<plist::Value>,
+    on_progress: impl FnMut(u64, u64),
 ) -> Result<InstallPackage, IdeviceError> {
     let InstallPackage {
         remote_package_path,
         options,
-    } = upload_dir_to_public_staging(provider, &local_path).await?;
+    } = upload_dir_to_public_staging(provider, &local_path, on_progress).await?;
 
     let full_options = plist!({
         :<? caller_options,
@@ -270,3 +313,124 @@ pub async fn prepare_dir_upload(
         options: full_options,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::{
+        Idevice,
+        afc::{
+            MAGIC,
+            errors::AfcError,
+            opcode::AfcOpcode,
+            packet::{AfcPacket, AfcPacketHeader},
+        },
+    };
+
+    fn packet(operation: AfcOpcode, header_payload: Vec<u8>) -> AfcPacket {
+        let len = AfcPacketHeader::LEN + header_payload.len() as u64;
+        AfcPacket {
+            header: AfcPacketHeader {
+                magic: MAGIC,
+                entire_len: len,
+                header_payload_len: len,
+                packet_num: 0,
+                operation,
+            },
+            header_payload,
+            payload: Vec::new(),
+        }
+    }
+
+    fn device_info(free_bytes: u64) -> AfcPacket {
+        let payload = [
+            "Model",
+            "iPhone14,2",
+            "FSTotalBytes",
+            "128000000000",
+            "FSFreeBytes",
+            &free_bytes.to_string(),
+            "FSBlockSize",
+            "4096",
+        ]
+        .join("\0")
+        .into_bytes();
+        AfcPacket {
+            header: AfcPacketHeader {
+                magic: MAGIC,
+                entire_len: AfcPacketHeader::LEN + payload.len() as u64,
+                header_payload_len: AfcPacketHeader::LEN,
+                packet_num: 0,
+                operation: AfcOpcode::Data,
+            },
+            header_payload: Vec::new(),
+            payload,
+        }
+    }
+
+    /// Uploads a directory holding 7 bytes to a device with `free_bytes` free
+    async fn upload_test_dir(free_bytes: u64) -> (Result<(), IdeviceError>, Vec<(u64, u64)>) {
+        let local = std::env::temp_dir().join(format!(
+            "idevice-upload-dir-{}-{free_bytes}",
+            std::process::id()
+        ));
+        tokio::fs::create_dir_all(local.join("sub")).await.unwrap();
+        tokio::fs::write(local.join("a"), b"abc").await.unwrap();
+        tokio::fs::write(local.join("sub/b"), b"defg")
+            .await
+            .unwrap();
+
+        let success = || {
+            packet(
+                AfcOpcode::Status,
+                (AfcError::Success as u64).to_le_bytes().to_vec(),
+            )
+        };
+        let opened = || packet(AfcOpcode::FileOpenRes, 3u64.to_le_bytes().to_vec());
+        let (client, mut device) = tokio::io::duplex(4096);
+        for response in [
+            device_info(free_bytes),
+            success(),
+            success(),
+            opened(),
+            success(),
+            success(),
+            opened(),
+            success(),
+            success(),
+        ] {
+            device.write_all(&response.serialize()).await.unwrap();
+        }
+        let mut afc = AfcClient::new(Idevice::new(Box::new(client), "test"));
+
+        let mut reports = Vec::new();
+        let res = afc_upload_dir(&mut afc, &local, "PublicStaging/app", |sent, total| {
+            reports.push((sent, total))
+        })
+        .await;
+        tokio::fs::remove_dir_all(&local).await.unwrap();
+        (res, reports)
+    }
+
+    #[tokio::test]
+    async fn upload_dir_reports_progress() {
+        let (res, reports) = upload_test_dir(7).await;
+        res.unwrap();
+        assert_eq!(reports, [(0, 7), (3, 7), (7, 7)]);
+    }
+
+    #[tokio::test]
+    async fn upload_dir_checks_free_space() {
+        let (res, reports) = upload_test_dir(6).await;
+        assert!(matches!(
+            res,
+            Err(IdeviceError::NotEnoughSpace {
+                needed: 7,
+                available: 6,
+            })
+        ));
+        assert!(reports.is_empty());
+    }
+}
@@ -16,8 +16,12 @@ use std::path::Path;
 
 use helpers::{InstallPackage, prepare_dir_upload, prepare_file_upload};
 
+use tracing::warn;
+
 use crate::{
-    IdeviceError, IdeviceService, provider::IdeviceProvider,
+    IdeviceError, IdeviceService,
+    afc::{AfcClient, errors::AfcError},
+    provider::IdeviceProvider,
     services::installation_proxy::InstallationProxyClient,
 };
 
@@ -46,20 +50,69 @@ where
     Fut: std::future::Future<Output = ()>,
     S: Clone,
 {
-    let metadata = tokio::fs::metadata(&local_path).await?;
+    install_package_with_progress(provider, local_path, options, |_, _| {}, callback, state).await
+}
 
-    if metadata.is_dir() {
-        let InstallPackage {
-            remote_package_path,
-            options,
-        } = prepare_dir_upload(provider, local_path, options).await?;
-        let mut inst = InstallationProxyClient::connect(provider).await?;
+/// Same as `install_package_with_callback` but also reporting the upload of the package.
+///
+/// - `on_upload` receives `(bytes_sent, total_bytes)` before the upload starts and after each
+///   chunk.
+/// - The staged package is removed from `PublicStaging` once InstallationProxy is done,
+///   whether the install succeeded or not.
+/// - Fails with `IdeviceError::NotEnoughSpace` before anything is uploaded if the device
+///   can't hold the package.
+pub async fn install_package_with_progress<P: AsRef<Path>, Fut, S>(
+    provider: &dyn IdeviceProvider,
+    local_path: P,
+    options: Option<plist::Value>,
+    on_upload: impl FnMut(u64, u64),
+    callback: impl Fn((u64, S)) -> Fut,
+    state: S,
+) -> Result<(), IdeviceError>
+where
+    Fut: std::future::Future<Output = ()>,
+    S: Clone,
+{
+    let is_dir = tokio::fs::metadata(&local_path).await?.is_dir();
 
-        inst.upgrade_with_callback(remote_package_path, Some(options), callback, state)
-            .await
+    let InstallPackage {
+        remote_package_path,
+        options,
+    } = if is_dir {
+        prepare_dir_upload(provider, local_path, options, on_upload).await?
     } else {
         let data = tokio::fs::read(&local_path).await?;
-        install_bytes_with_callback(provider, data, options, callback, state).await
+        prepare_file_upload(provider, data, options, on_upload).await?
+    };
+
+    let res = async {
+        let mut inst = InstallationProxyClient::connect(provider).await?;
+        if is_dir {
+            inst.upgrade_with_callback(&remote_package_path, Some(options), callback, state)
+                .await
+        } else {
+            inst.install_with_callback(&remote_package_path, Some(options), callback, state)
+                .await
+        }
+    }
+    .await;
+
+    remove_staged_package(provider, &remote_package_path).await;
+    res
+}
+
+/// Removes a package from `PublicStaging`, which installd may already have done
+async fn remove_staged_package(provider: &dyn IdeviceProvider, remote_package_path: &str) {
+    let res = async {
+        AfcClient::connect(provider)
+            .await?
+            .remove_all(remote_package_path)
+            .await
+    }
+    .await;
+    match res {
+        Ok(()) | Err(IdeviceError::Afc(AfcError::ObjectNotFound)) => {}
+        Err(e) => warn!("Failed to remove staged package {remote_package_path}: {e}"),
     }
 }
 
@@ -94,7 +147,7 @@ where
         let InstallPackage {
             remote_package_path,
             options,
-        } = prepare_dir_upload(provider, local_path, options).await?;
+        } = prepare_dir_upload(provider, local_path, options, |_, _| {}).await?;
         let mut inst = InstallationProxyClient::connect(provider).await?;
 
         inst.upgrade_with_callback(remote_package_path, Some(options), callback, state)
@@ -138,7 +191,7 @@ where
     let InstallPackage {
         remote_package_path,
         options,
-    } = prepare_file_upload(provider, data, options).await?;
+    } = prepare_file_upload(provider, data, options, |_, _| {}).await?;
     let mut inst = InstallationProxyClient::connect(provider).await?;
 
     inst.install_with_callback(remote_package_path, Some(options), callback, state)
@@ -178,7 +231,7 @@ where
     let InstallPackage {
         remote_package_path,
         options,
-    } = prepare_file_upload(provider, data, options).await?;
+    } = prepare_file_upload(provider, data, options, |_, _| {}).await?;
     let mut inst = InstallationProxyClient::connect(provider).await?;
 
     inst.upgrade_with_callback(remote_package_path, Some(options), callback, state)
@@ -4,8 +4,8 @@ use std::path::PathBuf;
 
 use idevice::{
     IdeviceService,
-    afc::{AfcClient, opcode::AfcFopenMode},
-    house_arrest::HouseArrestClient,
+    afc::AfcClient,
+    house_arrest::{ContainerType, HouseArrestClient},
     provider::IdeviceProvider,
 };
 use jkcli::{CollectedArguments, JkArgument, JkCommand, JkFlag};
@@ -108,17 +108,11 @@ pub fn register() -> JkCommand {
 
 pub async fn main(arguments: &CollectedArguments, provider: Box<dyn IdeviceProvider>) {
     let mut afc_client = if let Some(bundle_id) = arguments.get_flag::<String>("container") {
-        let h = HouseArrestClient::connect(&*provider)
-            .await
-            .expect("Failed to connect to house arrest");
-        h.vend_container(bundle_id)
+        HouseArrestClient::connect_container(&*provider, bundle_id, ContainerType::App)
             .await
             .expect("Failed to vend container")
     } else if let Some(bundle_id) = arguments.get_flag::<String>("documents") {
-        let h = HouseArrestClient::connect(&*provider)
-            .await
-            .expect("Failed to connect to house arrest");
-        h.vend_documents(bundle_id)
+        HouseArrestClient::connect_container(&*provider, bundle_id, ContainerType::Documents)
             .await
             .expect("Failed to vend documents")
     } else {
@@ -146,27 +140,25 @@ pub async fn main(arguments: &CollectedArguments, provider: Box<dyn IdeviceProvi
             let path = sub_args.next_argument::<String>().expect("No path passed");
             let save = sub_args.next_argument::<String>().expect("No path passed");
 
-            let mut file = afc_client
-                .open(path, AfcFopenMode::RdOnly)
-                .await
-                .expect("Failed to open");
+            let mut file = afc_client.open_read(path).await.expect("Failed to open");
 
-            let res = file.read_entire().await.expect("Failed to read");
-            tokio::fs::write(save, res)
+            let mut out = tokio::fs::File::create(save)
+                .await
+                .expect("Failed to create file");
+            tokio::io::copy(&mut file, &mut out)
                 .await
-                .expect("Failed to write to file");
+                .expect("Failed to download file");
         }
         "upload" => {
             let file = sub_args.next_argument::<PathBuf>().expect("No path passed");
             let path = sub_args.next_argument::<String>().expect("No path passed");
 
-            let bytes = tokio::fs::read(file).await.expect("Failed to read file");
-            let mut file = afc_client
-                .open(path, AfcFopenMode::WrOnly)
+            let mut local = tokio::fs::File::open(file)
                 .await
-                .expect("Failed to open");
+                .expect("Failed to open file");
+            let mut file = afc_client.open_write(path).await.expect("Failed to open");
 
-            file.write_entire(&bytes)
+            tokio::io::copy(&mut local, &mut file)
                 .await
                 .expect("Failed to upload bytes");
         }
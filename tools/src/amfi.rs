@@ -53,7 +53,7 @@ pub async fn main(arguments: &CollectedArguments, provider: Box<dyn IdeviceProvi
         }
         "status" => {
             let status = amfi_client
-                .get_developer_mode_status()
+                .developer_mode_status()
                 .await
                 .expect("Failed to get status");
             println!("Enabled: {status}");
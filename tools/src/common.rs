@@ -54,11 +54,7 @@ pub async fn get_provider(
             }
         };
 
-        Box::new(TcpProvider {
-            addr: host,
-            pairing_file,
-            label: "ideviceinfo-jkcoxson".to_string(),
-        })
+        Box::new(TcpProvider::new(host, pairing_file, "ideviceinfo-jkcoxson"))
     } else {
         let mut usbmuxd = if let Ok(var) = std::env::var("USBMUXD_SOCKET_ADDRESS") {
             let socket = SocketAddr::from_str(&var).expect("Bad USBMUXD_SOCKET_ADDRESS");
@@ -79,7 +79,10 @@ pub async fn main(arguments: &CollectedArguments, provider: Box<dyn IdeviceProvi
 
     match sub_name.as_str() {
         "list" => {
-            proxy.get_device_registry().await.expect("Failed to show");
+            let devices = proxy.get_device_registry().await.expect("Failed to show");
+            for udid in devices {
+                println!("{udid}");
+            }
         }
         "listen" => {
             let mut stream = proxy.listen_for_devices().await.expect("Failed to show");
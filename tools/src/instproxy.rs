@@ -2,7 +2,9 @@
 // Just lists apps for now
 
 use idevice::{
-    IdeviceService, installation_proxy::InstallationProxyClient, provider::IdeviceProvider,
+    IdeviceService,
+    installation_proxy::{BrowseOptions, InstallationProxyClient},
+    provider::IdeviceProvider,
 };
 use jkcli::{CollectedArguments, JkArgument, JkCommand};
 
@@ -50,7 +52,18 @@ pub async fn main(arguments: &CollectedArguments, provider: Box<dyn IdeviceProvi
             }
         }
         "browse" => {
-            instproxy_client.browse(None).await.expect("browse failed");
+            let apps = instproxy_client
+                .list_apps(BrowseOptions::new())
+                .await
+                .expect("browse failed");
+            for app in apps {
+                println!(
+                    "{} {} ({})",
+                    app.bundle_id,
+                    app.version.unwrap_or_default(),
+                    app.signer_identity.unwrap_or_default()
+                );
+            }
         }
         "check_capabilities" => {
             instproxy_client
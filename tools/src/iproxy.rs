@@ -1,7 +1,7 @@
 // iproxy - Proxy tool to forward local TCP ports to specified ports on iOS devices
 // Based on libusbmuxd/tools/iproxy.c implementation
 
-use std::net::{IpAddr, SocketAddr};
+use std::net::{IpAddr, SocketAddr, SocketAddrV6};
 use std::sync::Arc;
 
 use clap::{Arg, Command};
@@ -178,11 +178,14 @@ async fn connect_to_device(
 ) -> Result<Box<dyn ReadWrite>, Box<dyn std::error::Error + Send + Sync>> {
     match &device.connection_type {
         Connection::Network(ip_addr) => {
+            let socket_addr = match *ip_addr {
+                IpAddr::V4(addr) => SocketAddr::new(IpAddr::V4(addr), port),
+                IpAddr::V6(addr) => SocketAddrV6::new(addr, port, 0, device.scope_id).into(),
+            };
             info!(
-                "Requesting connection to NETWORK device {} (serial: {}), port {}",
-                ip_addr, device.udid, port
+                "Requesting connection to NETWORK device {} (serial: {})",
+                socket_addr, device.udid
             );
-            let socket_addr = SocketAddr::new(*ip_addr, port);
             let stream = TcpStream::connect(socket_addr).await?;
             Ok(Box::new(stream) as Box<dyn ReadWrite>)
         }
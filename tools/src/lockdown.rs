@@ -31,6 +31,10 @@ pub fn register() -> JkCommand {
                 )
                 .with_argument(JkArgument::new().with_help("The domain to set in")),
         )
+        .with_subcommand(
+            "services",
+            JkCommand::new().help("Lists the known services the device exposes"),
+        )
         .subcommand_required(true)
 }
 
@@ -95,6 +99,23 @@ pub async fn main(arguments: &CollectedArguments, provider: Box<dyn IdeviceProvi
                 Err(e) => eprintln!("Error setting value: {e}"),
             }
         }
+        "services" => match lockdown_client.query_services().await {
+            Ok(services) => {
+                let mut services = services.into_values().collect::<Vec<_>>();
+                services.sort_by(|a, b| a.name.cmp(&b.name));
+                for service in services {
+                    let mut flags = Vec::new();
+                    if service.ssl {
+                        flags.push("ssl");
+                    }
+                    if service.requires_escrow {
+                        flags.push("requires escrow");
+                    }
+                    println!("{} {}", service.name, flags.join(", "));
+                }
+            }
+            Err(e) => eprintln!("Error querying services: {e}"),
+        },
         _ => unreachable!(),
     }
 }
@@ -80,7 +80,7 @@ async fn main() {
         .with_flag(
             JkFlag::new("host")
                 .with_argument(JkArgument::new().required(true))
-                .with_help("The host to connect to"),
+                .with_help("The host to connect to, with the scope id of link-local addresses like fe80::1%en0"),
         )
         .with_flag(
             JkFlag::new("udid")
@@ -243,6 +243,24 @@ async fn main() {
     }
 }
 
+/// Parses an IP address, with the scope of IPv6 addresses given as an interface name or index
+/// like `fe80::1%en0` or `fe80::1%4`
+async fn parse_host(host: &str) -> Result<(IpAddr, u32), String> {
+    if !host.contains('%') {
+        return IpAddr::from_str(host)
+            .map(|h| (h, 0))
+            .map_err(|e| format!("Invalid host: {e:?}"));
+    }
+    // getaddrinfo resolves interface names in the zone to their index
+    let mut addrs = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("Invalid host: {e:?}"))?;
+    match addrs.next() {
+        Some(SocketAddr::V6(addr)) => Ok((IpAddr::V6(*addr.ip()), addr.scope_id())),
+        _ => Err(format!("Invalid host: {host}")),
+    }
+}
+
 async fn get_provider(
     udid: Option<String>,
     host: Option<String>,
@@ -272,12 +290,7 @@ async fn get_provider(
     } else if let Some(host) = host
         && let Some(pairing_file) = pairing_file
     {
-        let host = match IpAddr::from_str(host.as_str()) {
-            Ok(h) => h,
-            Err(e) => {
-                return Err(format!("Invalid host: {e:?}"));
-            }
-        };
+        let (host, scope_id) = parse_host(host.as_str()).await?;
         let pairing_file = match PairingFile::read_from_file(pairing_file) {
             Ok(p) => p,
             Err(e) => {
@@ -285,11 +298,7 @@ async fn get_provider(
             }
         };
 
-        Box::new(TcpProvider {
-            addr: host,
-            pairing_file,
-            label: label.to_string(),
-        })
+        Box::new(TcpProvider::new(host, pairing_file, label).with_scope_id(scope_id))
     } else {
         let mut usbmuxd = if let Ok(var) = std::env::var("USBMUXD_SOCKET_ADDRESS") {
             let socket = SocketAddr::from_str(&var).expect("Bad USBMUXD_SOCKET_ADDRESS");
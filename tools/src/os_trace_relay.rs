@@ -1,5 +1,6 @@
 // Jackson Coxson
 
+use futures_util::StreamExt;
 use idevice::{IdeviceService, os_trace_relay::OsTraceRelayClient, provider::IdeviceProvider};
 use jkcli::{CollectedArguments, JkCommand};
 
@@ -12,12 +13,10 @@ pub async fn main(_arguments: &CollectedArguments, provider: Box<dyn IdeviceProv
         .await
         .expect("Unable to connect to misagent");
 
-    let mut relay = log_client.start_trace(None).await.expect("Start failed");
+    let relay = log_client.start_trace(None).await.expect("Start failed");
+    let mut logs = relay.into_stream();
 
-    loop {
-        println!(
-            "{:#?}",
-            relay.next().await.expect("Failed to read next log")
-        );
+    while let Some(log) = logs.next().await {
+        println!("{:#?}", log.expect("Failed to read next log"));
     }
 }